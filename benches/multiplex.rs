@@ -0,0 +1,200 @@
+// throughput of the stream pipeline with synthetic blocks of mainnet size
+//
+// compare against a saved baseline:
+//   cargo bench --bench multiplex -- --save-baseline main
+//   cargo bench --bench multiplex -- --baseline main
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use futures::{stream, StreamExt};
+use geyser_grpc_connector::block_analytics::{
+    compute_unit_summary, priority_fee_summary, BlockExtractor,
+};
+use geyser_grpc_connector::block_pruning::BlockPruning;
+use geyser_grpc_connector::grpc_stream_utils::channelize_stream_arc;
+use geyser_grpc_connector::grpcmultiplex_fastestwins::{
+    create_multiplexed_stream, FromYellowstoneExtractor,
+};
+use geyser_grpc_connector::Message;
+use tokio::runtime::Runtime;
+use yellowstone_grpc_proto::geyser::subscribe_update::UpdateOneof;
+use yellowstone_grpc_proto::geyser::{
+    SubscribeUpdate, SubscribeUpdateBlock, SubscribeUpdateTransactionInfo,
+};
+use yellowstone_grpc_proto::prelude::{
+    CompiledInstruction, Message as TransactionMessage, MessageHeader, Transaction,
+    TransactionStatusMeta,
+};
+
+// busy mainnet block: ~1500 transactions, a few MB encoded
+const TRANSACTIONS_PER_BLOCK: usize = 1500;
+const SLOTS: u64 = 50;
+const SOURCES: usize = 3;
+const RECEIVERS: usize = 4;
+
+fn synthetic_transaction(index: usize) -> SubscribeUpdateTransactionInfo {
+    let compute_budget_program = 0;
+    SubscribeUpdateTransactionInfo {
+        signature: vec![index as u8; 64],
+        is_vote: index % 3 == 0,
+        transaction: Some(Transaction {
+            signatures: vec![vec![index as u8; 64]],
+            message: Some(TransactionMessage {
+                header: Some(MessageHeader {
+                    num_required_signatures: 1,
+                    num_readonly_signed_accounts: 0,
+                    num_readonly_unsigned_accounts: 2,
+                }),
+                account_keys: (0..12).map(|key| vec![key as u8; 32]).collect(),
+                recent_blockhash: vec![7; 32],
+                instructions: vec![
+                    CompiledInstruction {
+                        program_id_index: compute_budget_program,
+                        accounts: vec![],
+                        data: [vec![3], 10_000u64.to_le_bytes().to_vec()].concat(),
+                    },
+                    CompiledInstruction {
+                        program_id_index: 5,
+                        accounts: (0..8).collect(),
+                        data: vec![1; 120],
+                    },
+                ],
+                ..Default::default()
+            }),
+        }),
+        meta: Some(TransactionStatusMeta {
+            fee: 5000,
+            pre_balances: vec![1_000_000; 12],
+            post_balances: vec![995_000; 12],
+            log_messages: (0..20)
+                .map(|line| {
+                    format!(
+                        "Program log: synthetic log line {} of transaction {}",
+                        line, index
+                    )
+                })
+                .collect(),
+            compute_units_consumed: Some(150_000),
+            ..Default::default()
+        }),
+        index: index as u64,
+    }
+}
+
+fn synthetic_block(slot: u64) -> SubscribeUpdateBlock {
+    SubscribeUpdateBlock {
+        slot,
+        blockhash: format!("blockhash-{}", slot),
+        parent_slot: slot - 1,
+        parent_blockhash: format!("blockhash-{}", slot - 1),
+        executed_transaction_count: TRANSACTIONS_PER_BLOCK as u64,
+        transactions: (0..TRANSACTIONS_PER_BLOCK)
+            .map(synthetic_transaction)
+            .collect(),
+        ..Default::default()
+    }
+}
+
+fn block_message(block: &SubscribeUpdateBlock) -> Message {
+    Message::GeyserSubscribeUpdate(Box::new(SubscribeUpdate {
+        filters: vec!["multiplexed".to_string()],
+        update_oneof: Some(UpdateOneof::Block(block.clone())),
+    }))
+}
+
+fn source_messages(blocks: &[SubscribeUpdateBlock]) -> Vec<Vec<Message>> {
+    (0..SOURCES)
+        .map(|_| blocks.iter().map(block_message).collect())
+        .collect()
+}
+
+fn bench_dedup(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let blocks: Vec<_> = (1..=SLOTS).map(synthetic_block).collect();
+
+    let mut group = c.benchmark_group("dedup");
+    group.throughput(Throughput::Elements(SLOTS * SOURCES as u64));
+    group.bench_function("fastest_wins", |b| {
+        b.to_async(&runtime).iter_batched(
+            || source_messages(&blocks),
+            |sources| async move {
+                let streams = sources.into_iter().map(stream::iter).collect();
+                let yielded = create_multiplexed_stream(streams, BlockExtractor)
+                    .count()
+                    .await;
+                assert_eq!(yielded, SLOTS as usize);
+            },
+            BatchSize::LargeInput,
+        );
+    });
+    group.finish();
+}
+
+fn bench_extraction(c: &mut Criterion) {
+    let block = synthetic_block(1);
+
+    let mut group = c.benchmark_group("extraction");
+    group.throughput(Throughput::Elements(TRANSACTIONS_PER_BLOCK as u64));
+    group.bench_function("block_extractor", |b| {
+        b.iter_batched(
+            || match block_message(&block) {
+                Message::GeyserSubscribeUpdate(update) => *update,
+                Message::Connecting(_) => unreachable!(),
+            },
+            |update| BlockExtractor.map_yellowstone_update(update),
+            BatchSize::LargeInput,
+        );
+    });
+    group.bench_function("priority_fee_summary", |b| {
+        b.iter(|| priority_fee_summary(&block));
+    });
+    group.bench_function("compute_unit_summary", |b| {
+        b.iter(|| compute_unit_summary(&block, 10));
+    });
+    group.bench_function("pruning", |b| {
+        let pruning = BlockPruning::balances_and_signatures();
+        b.iter_batched(
+            || block.clone(),
+            |mut block| pruning.apply(&mut block),
+            BatchSize::LargeInput,
+        );
+    });
+    group.finish();
+}
+
+fn bench_fan_out(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let blocks: Vec<_> = (1..=SLOTS).map(synthetic_block).collect();
+
+    let mut group = c.benchmark_group("fan_out");
+    group.throughput(Throughput::Elements(SLOTS * RECEIVERS as u64));
+    group.bench_function("channelize_arc", |b| {
+        b.to_async(&runtime).iter_batched(
+            || blocks.clone(),
+            |blocks| async move {
+                let (rx, jh_channelizer) = channelize_stream_arc(stream::iter(blocks));
+                let receivers: Vec<_> = (0..RECEIVERS).map(|_| rx.resubscribe()).collect();
+                drop(rx);
+                let consumers: Vec<_> = receivers
+                    .into_iter()
+                    .map(|mut rx| {
+                        tokio::spawn(async move {
+                            let mut received = 0;
+                            while rx.recv().await.is_ok() {
+                                received += 1;
+                            }
+                            received
+                        })
+                    })
+                    .collect();
+                jh_channelizer.await.unwrap();
+                for consumer in consumers {
+                    consumer.await.unwrap();
+                }
+            },
+            BatchSize::LargeInput,
+        );
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_dedup, bench_extraction, bench_fan_out);
+criterion_main!(benches);
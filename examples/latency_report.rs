@@ -0,0 +1,144 @@
+use futures::StreamExt;
+use log::info;
+use serde::Serialize;
+use solana_sdk::clock::Slot;
+use solana_sdk::commitment_config::CommitmentConfig;
+use std::env;
+use std::pin::pin;
+
+use geyser_grpc_connector::grpc_subscription_autoreconnect_streams::create_geyser_reconnecting_stream;
+use geyser_grpc_connector::grpcmultiplex_fastestwins::{
+    create_multiplexed_stream_with_diagnostics, FromYellowstoneExtractor,
+};
+use geyser_grpc_connector::multiplex_diagnostics::LateArrivalDiagnostics;
+use geyser_grpc_connector::{GeyserFilter, GrpcSourceConfig};
+use tokio::time::{timeout, Duration};
+use yellowstone_grpc_proto::geyser::subscribe_update::UpdateOneof;
+use yellowstone_grpc_proto::geyser::SubscribeUpdate;
+
+struct SlotFromBlockMetaExtractor;
+
+impl FromYellowstoneExtractor for SlotFromBlockMetaExtractor {
+    type Target = Slot;
+    fn map_yellowstone_update(&self, update: SubscribeUpdate) -> Option<(Slot, Self::Target)> {
+        match update.update_oneof {
+            Some(UpdateOneof::BlockMeta(update_blockmeta_message)) => {
+                Some((update_blockmeta_message.slot, update_blockmeta_message.slot))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct SourceReport {
+    source: String,
+    slots: u64,
+    wins: u64,
+    win_rate: f64,
+    lost_by_mean_ms: f64,
+    lost_by_p50_ms: f64,
+    lost_by_p90_ms: f64,
+    lost_by_p99_ms: f64,
+}
+
+fn build_report(
+    labels: &[String],
+    total_slots: u64,
+    diagnostics: &LateArrivalDiagnostics,
+) -> Vec<SourceReport> {
+    labels
+        .iter()
+        .enumerate()
+        .map(|(idx, label)| {
+            let wins = diagnostics.source_wins(idx);
+            let lost = diagnostics.source_stats(idx);
+            let ms = |d: Option<Duration>| d.map(|d| d.as_secs_f64() * 1000.0).unwrap_or(0.0);
+            SourceReport {
+                source: label.clone(),
+                slots: total_slots,
+                wins,
+                win_rate: if total_slots > 0 {
+                    wins as f64 / total_slots as f64
+                } else {
+                    0.0
+                },
+                lost_by_mean_ms: ms(lost.as_ref().map(|l| l.mean)),
+                lost_by_p50_ms: ms(lost.as_ref().map(|l| l.p50)),
+                lost_by_p90_ms: ms(lost.as_ref().map(|l| l.p90)),
+                lost_by_p99_ms: ms(lost.as_ref().map(|l| l.p99)),
+            }
+        })
+        .collect()
+}
+
+fn print_csv(report: &[SourceReport]) {
+    println!(
+        "source,slots,wins,win_rate,lost_by_mean_ms,lost_by_p50_ms,lost_by_p90_ms,lost_by_p99_ms"
+    );
+    for row in report {
+        println!(
+            "{},{},{},{:.4},{:.2},{:.2},{:.2},{:.2}",
+            row.source,
+            row.slots,
+            row.wins,
+            row.win_rate,
+            row.lost_by_mean_ms,
+            row.lost_by_p50_ms,
+            row.lost_by_p90_ms,
+            row.lost_by_p99_ms
+        );
+    }
+}
+
+#[tokio::main]
+pub async fn main() {
+    // RUST_LOG=info,latency_report=debug,geyser_grpc_connector=info
+    tracing_subscriber::fmt::init();
+
+    // GRPC_ADDR, GRPC_ADDR_2, ... with optional GRPC_X_TOKEN, GRPC_X_TOKEN_2, ...
+    let sources = GrpcSourceConfig::all_from_env("GRPC").expect("need grpc sources");
+    assert!(sources.len() >= 2, "need at least 2 sources to compare");
+    let duration_secs: u64 = env::var("REPORT_DURATION_SECS")
+        .map(|secs| secs.parse().expect("REPORT_DURATION_SECS must be a number"))
+        .unwrap_or(60);
+    let format = env::var("REPORT_FORMAT").unwrap_or_else(|_| "csv".to_string());
+
+    let labels: Vec<String> = sources.iter().map(|source| source.label()).collect();
+    info!(
+        "Comparing {} sources for {}s: {:?}",
+        labels.len(),
+        duration_secs,
+        labels
+    );
+
+    let streams = sources
+        .into_iter()
+        .map(|source| {
+            create_geyser_reconnecting_stream(
+                source,
+                GeyserFilter(CommitmentConfig::confirmed()).blocks_meta(),
+            )
+        })
+        .collect::<Vec<_>>();
+    let (multiplex_stream, diagnostics) =
+        create_multiplexed_stream_with_diagnostics(streams, SlotFromBlockMetaExtractor);
+
+    let mut total_slots = 0;
+    let _elapsed = timeout(Duration::from_secs(duration_secs), async {
+        let mut multiplex_stream = pin!(multiplex_stream);
+        while let Some(_slot) = multiplex_stream.next().await {
+            total_slots += 1;
+        }
+    })
+    .await;
+
+    let report = build_report(&labels, total_slots, &diagnostics);
+    match format.as_str() {
+        "json" => println!(
+            "{}",
+            serde_json::to_string_pretty(&report).expect("report serializable")
+        ),
+        _ => print_csv(&report),
+    }
+}
@@ -0,0 +1,331 @@
+use crate::account_stream::{dedup_account_updates, AccountUpdate};
+use crate::commitment::CommitmentLevel;
+use crate::grpc_subscription_autoreconnect_streams::create_geyser_reconnecting_stream;
+use crate::{GeyserFilter, GeyserFilterKind, GrpcSourceConfig, Message};
+use anyhow::{anyhow, bail};
+use async_stream::stream;
+use bytes::Bytes;
+use futures::future::{self, Either};
+use futures::{Future, Stream, StreamExt};
+use log::debug;
+use solana_account_decoder::{UiAccount, UiAccountEncoding};
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_rpc_client_api::config::{RpcAccountInfoConfig, RpcProgramAccountsConfig};
+use solana_rpc_client_api::filter::RpcFilterType;
+use solana_rpc_client_api::request::RpcRequest;
+use solana_rpc_client_api::response::{OptionalContext, RpcKeyedAccount};
+use solana_sdk::account::Account;
+use solana_sdk::clock::Slot;
+use solana_sdk::pubkey::Pubkey;
+use std::pin::pin;
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::sync::watch;
+
+/// state of all accounts of a program at ``slot``
+#[derive(Clone, Debug)]
+pub struct AccountSnapshot {
+    // context slot of getProgramAccounts
+    pub slot: Slot,
+    // slot is the snapshot slot, write_version is 0
+    pub accounts: Vec<AccountUpdate>,
+}
+
+#[derive(Clone, Debug)]
+pub enum AccountFeedItem {
+    // always the first item
+    Snapshot(AccountSnapshot),
+    // changes after the snapshot slot
+    Update(AccountUpdate),
+}
+
+/// getProgramAccounts with context, so the snapshot knows its slot
+pub async fn fetch_program_accounts(
+    rpc_client: &RpcClient,
+    program_id: &Pubkey,
+    filters: Vec<RpcFilterType>,
+    commitment: CommitmentLevel,
+) -> anyhow::Result<AccountSnapshot> {
+    let config = RpcProgramAccountsConfig {
+        filters: (!filters.is_empty()).then_some(filters),
+        account_config: RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            commitment: Some(commitment.commitment_config()),
+            ..Default::default()
+        },
+        with_context: Some(true),
+    };
+    let response: OptionalContext<Vec<RpcKeyedAccount>> = rpc_client
+        .send(
+            RpcRequest::GetProgramAccounts,
+            serde_json::json!([program_id.to_string(), config]),
+        )
+        .await?;
+    let OptionalContext::Context(response) = response else {
+        bail!("getProgramAccounts response without context");
+    };
+
+    let slot = response.context.slot;
+    let accounts = response
+        .value
+        .into_iter()
+        .map(|keyed_account| account_update_of(slot, keyed_account))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    debug!(
+        "snapshot of {} accounts of program {} at slot {}",
+        accounts.len(),
+        program_id,
+        slot
+    );
+    Ok(AccountSnapshot { slot, accounts })
+}
+
+fn account_update_of(slot: Slot, keyed_account: RpcKeyedAccount) -> anyhow::Result<AccountUpdate> {
+    let pubkey = Pubkey::from_str(&keyed_account.pubkey)?;
+    let account: Account = UiAccount::decode(&keyed_account.account)
+        .ok_or_else(|| anyhow!("cannot decode account {}", pubkey))?;
+    Ok(AccountUpdate {
+        slot,
+        pubkey,
+        owner: account.owner,
+        lamports: account.lamports,
+        executable: account.executable,
+        rent_epoch: account.rent_epoch,
+        write_version: 0,
+        data: Bytes::from(account.data),
+    })
+}
+
+/// snapshot followed by the updates after its slot
+///
+/// ``account_updates`` is read (and buffered) while the snapshot is fetched, so no update between
+/// subscribing and the snapshot slot is lost; updates up to the snapshot slot are dropped as they
+/// are contained in the snapshot
+pub async fn join_snapshot(
+    snapshot: impl Future<Output = anyhow::Result<AccountSnapshot>>,
+    account_updates: impl Stream<Item = AccountUpdate>,
+) -> anyhow::Result<impl Stream<Item = AccountFeedItem>> {
+    let mut account_updates = Box::pin(account_updates);
+    let mut snapshot = pin!(snapshot);
+    let mut buffered = vec![];
+    let snapshot = loop {
+        match future::select(snapshot.as_mut(), account_updates.next()).await {
+            Either::Left((snapshot, _)) => break snapshot?,
+            Either::Right((Some(account_update), _)) => buffered.push(account_update),
+            Either::Right((None, _)) => bail!("account updates ended before the snapshot"),
+        }
+    };
+    let cutover_slot = snapshot.slot;
+    debug!(
+        "snapshot at slot {} with {} buffered updates",
+        cutover_slot,
+        buffered.len()
+    );
+
+    Ok(stream! {
+        yield AccountFeedItem::Snapshot(snapshot);
+        for account_update in buffered {
+            if account_update.slot > cutover_slot {
+                yield AccountFeedItem::Update(account_update);
+            }
+        }
+        while let Some(account_update) = account_updates.next().await {
+            if account_update.slot > cutover_slot {
+                yield AccountFeedItem::Update(account_update);
+            }
+        }
+    })
+}
+
+/// accounts owned by ``program_id``: snapshot via rpc, then the multiplexed updates from all sources
+///
+/// the snapshot is only requested once a subscription is live, otherwise updates between the
+/// snapshot slot and the start of the subscription would be lost; the subscription includes slots
+/// so the first update arrives within a slot
+pub async fn create_program_account_feed(
+    grpc_sources: Vec<GrpcSourceConfig>,
+    rpc_url: String,
+    program_id: Pubkey,
+    commitment: CommitmentLevel,
+) -> anyhow::Result<impl Stream<Item = AccountFeedItem>> {
+    let subscribe_request = GeyserFilter(commitment.into()).subscribe_request(&[
+        GeyserFilterKind::Accounts {
+            account: vec![],
+            owner: vec![program_id.to_string()],
+        },
+        GeyserFilterKind::Slots,
+    ]);
+    let source_streams = grpc_sources
+        .into_iter()
+        .map(|grpc_source| {
+            create_geyser_reconnecting_stream(grpc_source, subscribe_request.clone())
+        })
+        .collect();
+    let (source_streams, subscribed) = signal_subscribed(source_streams);
+    let rpc_client = RpcClient::new(rpc_url);
+    let snapshot = async move {
+        wait_subscribed(subscribed).await?;
+        fetch_program_accounts(&rpc_client, &program_id, vec![], commitment).await
+    };
+    join_snapshot(snapshot, dedup_account_updates(source_streams)).await
+}
+
+// set on the first update of any source
+fn signal_subscribed(
+    source_streams: Vec<impl Stream<Item = Message>>,
+) -> (Vec<impl Stream<Item = Message>>, watch::Receiver<bool>) {
+    let (subscribed_tx, subscribed_rx) = watch::channel(false);
+    let subscribed_tx = Arc::new(subscribed_tx);
+    let source_streams = source_streams
+        .into_iter()
+        .map(|source_stream| {
+            let subscribed_tx = subscribed_tx.clone();
+            source_stream.inspect(move |message| {
+                if matches!(message, Message::GeyserSubscribeUpdate(_)) {
+                    subscribed_tx
+                        .send_if_modified(|subscribed| !std::mem::replace(subscribed, true));
+                }
+            })
+        })
+        .collect();
+    (source_streams, subscribed_rx)
+}
+
+async fn wait_subscribed(mut subscribed: watch::Receiver<bool>) -> anyhow::Result<()> {
+    subscribed
+        .wait_for(|subscribed| *subscribed)
+        .await
+        .map_err(|_| anyhow!("account streams dropped before subscribing"))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::slot_update;
+    use futures::stream;
+    use std::time::Duration;
+    use tokio::time::Instant;
+    use yellowstone_grpc_proto::geyser::subscribe_update::UpdateOneof;
+    use yellowstone_grpc_proto::geyser::{
+        SubscribeUpdate, SubscribeUpdateAccount, SubscribeUpdateAccountInfo,
+    };
+
+    fn account_update(pubkey: Pubkey, slot: Slot, lamports: u64) -> AccountUpdate {
+        AccountUpdate {
+            slot,
+            pubkey,
+            owner: Pubkey::default(),
+            lamports,
+            executable: false,
+            rent_epoch: 0,
+            write_version: 0,
+            data: Bytes::new(),
+        }
+    }
+
+    fn account_message(pubkey: Pubkey, slot: Slot) -> Message {
+        Message::GeyserSubscribeUpdate(Box::new(SubscribeUpdate {
+            filters: vec![],
+            update_oneof: Some(UpdateOneof::Account(SubscribeUpdateAccount {
+                slot,
+                is_startup: false,
+                account: Some(SubscribeUpdateAccountInfo {
+                    pubkey: pubkey.to_bytes().to_vec(),
+                    owner: Pubkey::default().to_bytes().to_vec(),
+                    lamports: 1,
+                    ..Default::default()
+                }),
+            })),
+        }))
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_snapshot_waits_for_subscription() {
+        let pubkey = Pubkey::new_unique();
+        // subscription becomes live after 100ms
+        let source = stream::iter(vec![
+            (0, Message::Connecting(1)),
+            (
+                100,
+                Message::GeyserSubscribeUpdate(Box::new(slot_update(11))),
+            ),
+            (0, account_message(pubkey, 11)),
+        ])
+        .then(|(delay_ms, message)| async move {
+            tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+            message
+        });
+        let (source_streams, subscribed) = signal_subscribed(vec![source]);
+        let started_at = Instant::now();
+        // answers right away, i.e. before the first streamed update if it was not waiting
+        let snapshot = async move {
+            wait_subscribed(subscribed).await?;
+            Ok(AccountSnapshot {
+                slot: 10,
+                accounts: vec![],
+            })
+        };
+        let snapshot = async move {
+            let snapshot = snapshot.await;
+            assert!(started_at.elapsed() >= Duration::from_millis(100));
+            snapshot
+        };
+
+        let items: Vec<AccountFeedItem> =
+            join_snapshot(snapshot, dedup_account_updates(source_streams))
+                .await
+                .unwrap()
+                .collect()
+                .await;
+
+        assert_eq!(items.len(), 2);
+        let AccountFeedItem::Update(account_update) = &items[1] else {
+            panic!("update after the snapshot");
+        };
+        assert_eq!(account_update.slot, 11);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_join_snapshot_cutover() {
+        let pubkey = Pubkey::new_unique();
+        // the first three arrive while the snapshot is fetched
+        let account_updates = stream::iter(vec![
+            (1, account_update(pubkey, 9, 1)),
+            (1, account_update(pubkey, 10, 2)),
+            (1, account_update(pubkey, 11, 3)),
+            (100, account_update(pubkey, 10, 4)),
+            (0, account_update(pubkey, 12, 5)),
+        ])
+        .then(|(delay_ms, account_update)| async move {
+            tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+            account_update
+        });
+        let snapshot = async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            Ok(AccountSnapshot {
+                slot: 10,
+                accounts: vec![account_update(pubkey, 10, 2)],
+            })
+        };
+
+        let items: Vec<AccountFeedItem> = join_snapshot(snapshot, account_updates)
+            .await
+            .unwrap()
+            .collect()
+            .await;
+
+        let AccountFeedItem::Snapshot(snapshot) = &items[0] else {
+            panic!("snapshot must come first");
+        };
+        assert_eq!(snapshot.slot, 10);
+        let lamports: Vec<u64> = items[1..]
+            .iter()
+            .map(|item| match item {
+                AccountFeedItem::Update(account_update) => account_update.lamports,
+                AccountFeedItem::Snapshot(_) => panic!("only one snapshot"),
+            })
+            .collect();
+        assert_eq!(lamports, vec![3, 5]);
+    }
+}
@@ -0,0 +1,195 @@
+use crate::grpc_subscription_autoreconnect_streams::create_geyser_reconnecting_stream;
+use crate::{GeyserFilter, GrpcSourceConfig, Message};
+use anyhow::anyhow;
+use async_stream::stream;
+use bytes::Bytes;
+use futures::{Stream, StreamExt};
+use log::warn;
+use merge_streams::MergeStreams;
+use solana_sdk::clock::Slot;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::HashMap;
+use std::pin::pin;
+use yellowstone_grpc_proto::geyser::subscribe_update::UpdateOneof;
+use yellowstone_grpc_proto::geyser::SubscribeUpdateAccount;
+
+// accounts are remembered for that many slots behind the highest slot seen
+const DEDUP_SLOTS: Slot = 1000;
+
+/// account update with data as ``Bytes``: cloning (e.g. for each receiver of ``channelize_stream``)
+/// only bumps a reference count
+/// note: prost decodes into a ``Vec`` - the conversion moves that buffer, it does not copy it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AccountUpdate {
+    pub slot: Slot,
+    pub pubkey: Pubkey,
+    pub owner: Pubkey,
+    pub lamports: u64,
+    pub executable: bool,
+    pub rent_epoch: u64,
+    // validator specific, not comparable across sources
+    pub write_version: u64,
+    pub data: Bytes,
+}
+
+impl TryFrom<SubscribeUpdateAccount> for AccountUpdate {
+    type Error = anyhow::Error;
+    fn try_from(update: SubscribeUpdateAccount) -> anyhow::Result<Self> {
+        let account = update
+            .account
+            .ok_or_else(|| anyhow!("account update without account"))?;
+        Ok(AccountUpdate {
+            slot: update.slot,
+            pubkey: Pubkey::try_from(account.pubkey.as_slice())
+                .map_err(|_| anyhow!("invalid pubkey"))?,
+            owner: Pubkey::try_from(account.owner.as_slice())
+                .map_err(|_| anyhow!("invalid owner"))?,
+            lamports: account.lamports,
+            executable: account.executable,
+            rent_epoch: account.rent_epoch,
+            write_version: account.write_version,
+            data: Bytes::from(account.data),
+        })
+    }
+}
+
+/// account updates for the given accounts/owners from all sources
+///
+/// per account and slot the fastest source wins; later updates of the same account in that slot
+/// are taken from the winning source only
+pub fn create_account_multiplex(
+    grpc_sources: Vec<GrpcSourceConfig>,
+    geyser_filter: GeyserFilter,
+    account: Vec<String>,
+    owner: Vec<String>,
+) -> impl Stream<Item = AccountUpdate> {
+    let streams: Vec<_> = grpc_sources
+        .into_iter()
+        .map(|grpc_source| {
+            create_geyser_reconnecting_stream(
+                grpc_source,
+                geyser_filter.accounts(account.clone(), owner.clone()),
+            )
+        })
+        .collect();
+    dedup_account_updates(streams)
+}
+
+/// per account the first source delivering a newer slot wins; updates more than ``DEDUP_SLOTS``
+/// behind the highest slot are dropped as the account could be forgotten already
+pub fn dedup_account_updates(
+    grpc_source_streams: Vec<impl Stream<Item = Message>>,
+) -> impl Stream<Item = AccountUpdate> {
+    let tagged_streams: Vec<_> = grpc_source_streams
+        .into_iter()
+        .enumerate()
+        .map(|(stream_idx, geyser_stream)| geyser_stream.map(move |message| (stream_idx, message)))
+        .collect();
+    stream! {
+        let mut merged = pin!(tagged_streams.merge());
+        // per account: slot and source of the last yielded update
+        let mut latest: HashMap<Pubkey, (Slot, usize)> = HashMap::new();
+        // entries below this slot are pruned
+        let mut horizon: Slot = 0;
+        while let Some((stream_idx, message)) = merged.next().await {
+            let Message::GeyserSubscribeUpdate(update) = message else {
+                continue;
+            };
+            let Some(UpdateOneof::Account(account_update)) = update.update_oneof else {
+                continue;
+            };
+            let account_update = match AccountUpdate::try_from(account_update) {
+                Ok(account_update) => account_update,
+                Err(err) => {
+                    warn!("skip account update from Stream-{}: {}", stream_idx, err);
+                    continue;
+                }
+            };
+            if account_update.slot < horizon {
+                continue;
+            }
+            // only every hundredth slot to avoid a scan per update
+            if account_update.slot.saturating_sub(DEDUP_SLOTS) >= horizon + 100 {
+                horizon = account_update.slot - DEDUP_SLOTS;
+                latest.retain(|_, (slot, _)| *slot >= horizon);
+            }
+            let accept = match latest.get(&account_update.pubkey) {
+                None => true,
+                Some((slot, winner_idx)) => {
+                    account_update.slot > *slot
+                        || (account_update.slot == *slot && stream_idx == *winner_idx)
+                }
+            };
+            if accept {
+                latest.insert(account_update.pubkey, (account_update.slot, stream_idx));
+                yield account_update;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::stream;
+    use yellowstone_grpc_proto::geyser::{SubscribeUpdate, SubscribeUpdateAccountInfo};
+
+    fn account_message(pubkey: &Pubkey, slot: Slot, lamports: u64) -> Message {
+        Message::GeyserSubscribeUpdate(Box::new(SubscribeUpdate {
+            filters: vec!["client".to_string()],
+            update_oneof: Some(UpdateOneof::Account(SubscribeUpdateAccount {
+                slot,
+                is_startup: false,
+                account: Some(SubscribeUpdateAccountInfo {
+                    pubkey: pubkey.to_bytes().to_vec(),
+                    owner: Pubkey::default().to_bytes().to_vec(),
+                    lamports,
+                    data: vec![1, 2, 3],
+                    ..Default::default()
+                }),
+            })),
+        }))
+    }
+
+    #[tokio::test]
+    async fn test_dedup_account_updates() {
+        let pubkey = Pubkey::new_unique();
+        let fast = stream::iter(vec![
+            account_message(&pubkey, 10, 1),
+            // second write to the same account in slot 10
+            account_message(&pubkey, 10, 2),
+            account_message(&pubkey, 11, 3),
+        ]);
+        let slow = stream::iter(vec![account_message(&pubkey, 10, 1)]).then(|message| async {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            message
+        });
+
+        let updates: Vec<AccountUpdate> = dedup_account_updates(vec![fast.boxed(), slow.boxed()])
+            .collect()
+            .await;
+
+        let lamports: Vec<u64> = updates.iter().map(|update| update.lamports).collect();
+        assert_eq!(lamports, vec![1, 2, 3]);
+        assert_eq!(updates[0].data, Bytes::from_static(&[1, 2, 3]));
+    }
+
+    #[tokio::test]
+    async fn test_late_updates_behind_horizon_dropped() {
+        let quiet = Pubkey::new_unique();
+        let busy = Pubkey::new_unique();
+        let source = stream::iter(vec![
+            account_message(&quiet, 10, 1),
+            // forgets the quiet account
+            account_message(&busy, 10 + DEDUP_SLOTS + 100, 2),
+            // late duplicate from another source
+            account_message(&quiet, 10, 1),
+            account_message(&quiet, 20 + DEDUP_SLOTS, 3),
+        ]);
+
+        let updates: Vec<AccountUpdate> = dedup_account_updates(vec![source]).collect().await;
+
+        let lamports: Vec<u64> = updates.iter().map(|update| update.lamports).collect();
+        assert_eq!(lamports, vec![1, 2, 3]);
+    }
+}
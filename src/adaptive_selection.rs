@@ -0,0 +1,503 @@
+use crate::grpc_subscription_autoreconnect_streams::create_geyser_reconnecting_stream;
+use crate::grpcmultiplex_fastestwins::FromYellowstoneExtractor;
+use crate::shared_connection::receiver_stream;
+use crate::{GeyserFilter, GrpcSourceConfig, Message, Slot};
+use async_stream::stream;
+use futures::{Stream, StreamExt};
+use log::{debug, info};
+use merge_streams::MergeStreams;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::pin::pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use yellowstone_grpc_proto::geyser::subscribe_update::UpdateOneof;
+use yellowstone_grpc_proto::geyser::SubscribeRequest;
+
+// first arrivals of that many slots are kept to compute the lag of slower sources
+const SLOT_ARRIVAL_WINDOW: usize = 64;
+
+#[derive(Clone, Debug)]
+pub struct AdaptiveSelectionConfig {
+    // number of sources with a full subscription
+    pub active_sources: usize,
+    pub evaluation_interval: Duration,
+    // weight of the latest sample in the rolling latency score
+    pub smoothing: f64,
+    // a source without slot update for longer than this is penalized by the excess
+    pub stall_threshold: Duration,
+    // a standby source replaces an active one only if its score is lower by this margin
+    pub switch_margin_ms: f64,
+    // minimum time between two changes of the selection
+    pub hold_down: Duration,
+}
+
+impl Default for AdaptiveSelectionConfig {
+    fn default() -> Self {
+        AdaptiveSelectionConfig {
+            active_sources: 2,
+            evaluation_interval: Duration::from_secs(10),
+            smoothing: 0.1,
+            stall_threshold: Duration::from_secs(2),
+            switch_margin_ms: 20.0,
+            hold_down: Duration::from_secs(30),
+        }
+    }
+}
+
+/// rolling score per source: how many milliseconds a source is behind the fastest one on slot updates
+pub struct SourceScores {
+    smoothing: f64,
+    stall_threshold: Duration,
+    first_arrivals: BTreeMap<Slot, Instant>,
+    lag_ms: Vec<Option<f64>>,
+    last_update_at: Vec<Instant>,
+}
+
+impl SourceScores {
+    pub fn new(num_sources: usize, config: &AdaptiveSelectionConfig) -> Self {
+        SourceScores {
+            smoothing: config.smoothing,
+            stall_threshold: config.stall_threshold,
+            first_arrivals: BTreeMap::new(),
+            lag_ms: vec![None; num_sources],
+            last_update_at: vec![Instant::now(); num_sources],
+        }
+    }
+
+    pub fn record(&mut self, source_idx: usize, slot: Slot, received_at: Instant) {
+        let first_arrival = *self.first_arrivals.entry(slot).or_insert(received_at);
+        while self.first_arrivals.len() > SLOT_ARRIVAL_WINDOW {
+            self.first_arrivals.pop_first();
+        }
+        let lag_ms = received_at.duration_since(first_arrival).as_secs_f64() * 1000.0;
+        let score = &mut self.lag_ms[source_idx];
+        *score = Some(match *score {
+            Some(previous) => previous + self.smoothing * (lag_ms - previous),
+            None => lag_ms,
+        });
+        self.last_update_at[source_idx] = received_at;
+    }
+
+    /// lower is better; sources without samples rank last
+    pub fn score(&self, source_idx: usize, now: Instant) -> f64 {
+        let stalled_ms = now
+            .duration_since(self.last_update_at[source_idx])
+            .saturating_sub(self.stall_threshold)
+            .as_secs_f64()
+            * 1000.0;
+        self.lag_ms[source_idx].map_or(f64::MAX, |lag_ms| lag_ms + stalled_ms)
+    }
+
+    /// source indices, best first
+    pub fn ranking(&self, now: Instant) -> Vec<usize> {
+        let mut ranking: Vec<usize> = (0..self.lag_ms.len()).collect();
+        ranking.sort_by(|a, b| self.score(*a, now).total_cmp(&self.score(*b, now)));
+        ranking
+    }
+}
+
+// keeps the current selection unless a standby source is better than the worst active one by the
+// margin; missing active sources are filled up in ranking order
+fn select_sources(
+    ranking: &[usize],
+    score: impl Fn(usize) -> f64,
+    current: &HashSet<usize>,
+    active_sources: usize,
+    switch_margin_ms: f64,
+) -> HashSet<usize> {
+    let mut selected = current.clone();
+    for source_idx in ranking {
+        if selected.len() >= active_sources {
+            break;
+        }
+        selected.insert(*source_idx);
+    }
+    let standby: Vec<usize> = ranking
+        .iter()
+        .copied()
+        .filter(|source_idx| !selected.contains(source_idx))
+        .collect();
+    for standby_idx in standby {
+        let Some(worst_idx) = selected
+            .iter()
+            .copied()
+            .max_by(|a, b| score(*a).total_cmp(&score(*b)))
+        else {
+            break;
+        };
+        if score(standby_idx) + switch_margin_ms < score(worst_idx) {
+            selected.remove(&worst_idx);
+            selected.insert(standby_idx);
+        } else {
+            // ranking is sorted, no later standby source can be better
+            break;
+        }
+    }
+    selected
+}
+
+/// observe the selection made by ``create_adaptive_multiplex``
+#[derive(Clone)]
+pub struct AdaptiveSelection {
+    scores: Arc<Mutex<SourceScores>>,
+    active: Arc<Mutex<HashSet<usize>>>,
+}
+
+impl AdaptiveSelection {
+    pub fn active_sources(&self) -> Vec<usize> {
+        let mut active: Vec<usize> = self.active.lock().unwrap().iter().copied().collect();
+        active.sort();
+        active
+    }
+
+    pub fn scores(&self) -> Vec<f64> {
+        let scores = self.scores.lock().unwrap();
+        let now = Instant::now();
+        (0..scores.lag_ms.len())
+            .map(|idx| scores.score(idx, now))
+            .collect()
+    }
+}
+
+/// subscribe ``subscribe_filter`` only on the ``active_sources`` best sources; all sources keep a cheap
+/// slots subscription to score them, a standby source gets promoted once it scores better than an active one
+/// by ``switch_margin_ms``, at most once per ``hold_down``
+///
+/// a demoted source keeps its subscription until all promoted sources delivered their first update
+///
+/// note: the sources are started immediately, scoring starts with no knowledge so the first sources get picked
+pub fn create_adaptive_multiplex<E>(
+    grpc_sources: Vec<GrpcSourceConfig>,
+    health_filter: GeyserFilter,
+    subscribe_filter: SubscribeRequest,
+    extractor: E,
+    config: AdaptiveSelectionConfig,
+) -> (impl Stream<Item = E::Target>, AdaptiveSelection)
+where
+    E: FromYellowstoneExtractor + Send + 'static,
+    E::Target: Send,
+{
+    let source_names = grpc_sources.iter().map(ToString::to_string).collect();
+    let health_streams = grpc_sources
+        .iter()
+        .map(|grpc_source| {
+            create_geyser_reconnecting_stream(grpc_source.clone(), health_filter.slots())
+        })
+        .collect();
+    adaptive_multiplex(
+        source_names,
+        health_streams,
+        move |source_idx| {
+            create_geyser_reconnecting_stream(
+                grpc_sources[source_idx].clone(),
+                subscribe_filter.clone(),
+            )
+        },
+        extractor,
+        config,
+    )
+}
+
+fn adaptive_multiplex<E, H, S>(
+    source_names: Vec<String>,
+    health_streams: Vec<H>,
+    subscribe: impl Fn(usize) -> S + Send + 'static,
+    extractor: E,
+    config: AdaptiveSelectionConfig,
+) -> (impl Stream<Item = E::Target>, AdaptiveSelection)
+where
+    E: FromYellowstoneExtractor + Send + 'static,
+    E::Target: Send,
+    H: Stream<Item = Message> + Send + 'static,
+    S: Stream<Item = Message> + Send + 'static,
+{
+    assert!(config.active_sources > 0, "need at least one active source");
+    let num_sources = source_names.len();
+    let scores = Arc::new(Mutex::new(SourceScores::new(num_sources, &config)));
+    let active = Arc::new(Mutex::new(HashSet::new()));
+    let selection = AdaptiveSelection {
+        scores: scores.clone(),
+        active: active.clone(),
+    };
+
+    let health_streams: Vec<_> = health_streams
+        .into_iter()
+        .enumerate()
+        .map(|(source_idx, health_stream)| health_stream.map(move |message| (source_idx, message)))
+        .collect();
+    let jh_scoring = tokio::spawn({
+        let scores = scores.clone();
+        async move {
+            let mut health_streams = pin!(health_streams.merge());
+            while let Some((source_idx, message)) = health_streams.next().await {
+                let Message::GeyserSubscribeUpdate(update) = message else {
+                    continue;
+                };
+                if let Some(UpdateOneof::Slot(update_slot)) = update.update_oneof {
+                    scores
+                        .lock()
+                        .unwrap()
+                        .record(source_idx, update_slot.slot, Instant::now());
+                }
+            }
+        }
+    });
+
+    let (payload_tx, payload_rx) = mpsc::channel::<(usize, Message)>(1000);
+    // promoted sources report their first update
+    let (first_update_tx, mut first_update_rx) = mpsc::unbounded_channel::<usize>();
+    tokio::spawn(async move {
+        let mut subscriptions: Vec<Option<JoinHandle<()>>> =
+            (0..num_sources).map(|_| None).collect();
+        // demoted subscriptions, aborted once no promoted source waits for its first update
+        let mut retiring: HashMap<usize, JoinHandle<()>> = HashMap::new();
+        let mut awaiting_first_update: HashSet<usize> = HashSet::new();
+        let mut interval = tokio::time::interval(config.evaluation_interval);
+        let mut current: HashSet<usize> = HashSet::new();
+        let mut changed_at: Option<Instant> = None;
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                Some(source_idx) = first_update_rx.recv() => {
+                    awaiting_first_update.remove(&source_idx);
+                    if awaiting_first_update.is_empty() {
+                        retire(&source_names, &mut retiring);
+                    }
+                    continue;
+                }
+            }
+            if payload_tx.is_closed() {
+                break;
+            }
+            let now = Instant::now();
+            if changed_at.map_or(false, |changed_at| now - changed_at < config.hold_down)
+                && current.len() >= config.active_sources.min(num_sources)
+            {
+                continue;
+            }
+            let selected = {
+                let scores = scores.lock().unwrap();
+                select_sources(
+                    &scores.ranking(now),
+                    |source_idx| scores.score(source_idx, now),
+                    &current,
+                    config.active_sources,
+                    config.switch_margin_ms,
+                )
+            };
+            if selected != current {
+                changed_at = Some(now);
+            }
+            for (source_idx, subscription) in subscriptions.iter_mut().enumerate() {
+                match (selected.contains(&source_idx), subscription.is_some()) {
+                    (true, false) => {
+                        info!(
+                            "promote source {} to full subscription",
+                            source_names[source_idx]
+                        );
+                        if let Some(jh) = retiring.remove(&source_idx) {
+                            *subscription = Some(jh);
+                            continue;
+                        }
+                        let geyser_stream = subscribe(source_idx);
+                        let payload_tx = payload_tx.clone();
+                        let first_update_tx = first_update_tx.clone();
+                        awaiting_first_update.insert(source_idx);
+                        *subscription = Some(tokio::spawn(async move {
+                            let mut geyser_stream = pin!(geyser_stream);
+                            let mut first_update = true;
+                            while let Some(message) = geyser_stream.next().await {
+                                if first_update
+                                    && matches!(message, Message::GeyserSubscribeUpdate(_))
+                                {
+                                    first_update = false;
+                                    let _ = first_update_tx.send(source_idx);
+                                }
+                                if payload_tx.send((source_idx, message)).await.is_err() {
+                                    return;
+                                }
+                            }
+                        }));
+                    }
+                    (false, true) => {
+                        info!("demote source {} to standby", source_names[source_idx]);
+                        let jh = subscription.take().expect("subscription");
+                        if awaiting_first_update.remove(&source_idx) {
+                            // delivered nothing yet, nothing to take over
+                            jh.abort();
+                        } else {
+                            retiring.insert(source_idx, jh);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            if awaiting_first_update.is_empty() {
+                retire(&source_names, &mut retiring);
+            }
+            *active.lock().unwrap() = selected.clone();
+            current = selected;
+        }
+        debug!("adaptive multiplex dropped - stopping subscriptions");
+        subscriptions
+            .into_iter()
+            .flatten()
+            .chain(retiring.into_values())
+            .for_each(|jh| jh.abort());
+        jh_scoring.abort();
+    });
+
+    let multiplexed = stream! {
+        let mut tip: Slot = 0;
+        let mut payloads = pin!(receiver_stream(payload_rx));
+        while let Some((_source_idx, message)) = payloads.next().await {
+            let Message::GeyserSubscribeUpdate(update) = message else {
+                continue;
+            };
+            let Some((proposed_slot, block)) = extractor.map_yellowstone_update(*update) else {
+                continue;
+            };
+            if proposed_slot > tip {
+                tip = proposed_slot;
+                yield block;
+            }
+        }
+    };
+
+    (multiplexed, selection)
+}
+
+fn retire(source_names: &[String], retiring: &mut HashMap<usize, JoinHandle<()>>) {
+    for (source_idx, jh) in retiring.drain() {
+        debug!(
+            "stop subscription of demoted source {}",
+            source_names[source_idx]
+        );
+        jh.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reconnect_policy::FixedDelay;
+    use crate::simulation::{slot_update, SimConnection, SimStep, SimulatedSource};
+    use crate::tip_slot::SlotExtractor;
+
+    #[test]
+    fn test_source_scores_ranking() {
+        let config = AdaptiveSelectionConfig {
+            smoothing: 0.5,
+            ..Default::default()
+        };
+        let mut scores = SourceScores::new(3, &config);
+        let start = Instant::now();
+        for slot in 0..10 {
+            let at = start + Duration::from_millis(400 * slot);
+            scores.record(1, slot, at);
+            scores.record(0, slot, at + Duration::from_millis(20));
+            scores.record(2, slot, at + Duration::from_millis(100));
+        }
+
+        let now = start + Duration::from_secs(4);
+        assert_eq!(scores.ranking(now), vec![1, 0, 2]);
+        assert!(scores.score(0, now) > 19.0 && scores.score(0, now) < 21.0);
+
+        // fastest source stalls
+        let later = now + Duration::from_secs(3);
+        for slot in 10..15 {
+            let at = now + Duration::from_millis(400 * (slot - 9));
+            scores.record(0, slot, at);
+            scores.record(2, slot, at + Duration::from_millis(100));
+        }
+        assert_eq!(scores.ranking(later)[0], 0);
+        assert_eq!(scores.ranking(later)[2], 1);
+    }
+
+    #[test]
+    fn test_select_sources_hysteresis() {
+        let mut current = HashSet::new();
+        // sources 0 and 1 alternate within the margin, source 2 is far behind
+        for round in 0..6 {
+            let scores: [f64; 3] = if round % 2 == 0 {
+                [10.0, 15.0, 100.0]
+            } else {
+                [15.0, 10.0, 100.0]
+            };
+            let mut ranking = vec![0, 1, 2];
+            ranking.sort_by(|a, b| scores[*a].total_cmp(&scores[*b]));
+            current = select_sources(&ranking, |idx| scores[idx], &current, 1, 20.0);
+            assert_eq!(current, HashSet::from([0]));
+        }
+
+        // clearly better
+        let scores = [80.0, 10.0, 100.0];
+        current = select_sources(&[1, 0, 2], |idx| scores[idx], &current, 1, 20.0);
+        assert_eq!(current, HashSet::from([1]));
+    }
+
+    fn reconnect_policy() -> FixedDelay {
+        FixedDelay {
+            wait: Duration::from_secs(1),
+            max_attempts: None,
+            rotate_endpoint: false,
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_switch_keeps_demoted_until_promoted_delivers() {
+        // source 1 sees every slot 50ms earlier than source 0
+        let health_steps = |offset_ms: u64| {
+            let mut steps = vec![SimStep::Sleep(Duration::from_millis(offset_ms))];
+            for slot in 1..=30 {
+                steps.push(SimStep::Update(slot_update(slot)));
+                steps.push(SimStep::Sleep(Duration::from_millis(100)));
+            }
+            steps
+        };
+        let health_streams = vec![
+            SimulatedSource::new("slow")
+                .accept(health_steps(150))
+                .stream(reconnect_policy()),
+            SimulatedSource::new("fast")
+                .accept(health_steps(100))
+                .stream(reconnect_policy()),
+        ];
+        // the promoted source needs 400ms to deliver its first slot (14 at 1.4s)
+        let full_sources = [
+            SimulatedSource::new("slow").accept(health_steps(150)),
+            SimulatedSource::new("fast").connection(SimConnection::Accept {
+                delay: Duration::from_millis(400),
+                steps: (14..=30)
+                    .flat_map(|slot| {
+                        [
+                            SimStep::Update(slot_update(slot)),
+                            SimStep::Sleep(Duration::from_millis(100)),
+                        ]
+                    })
+                    .collect(),
+            }),
+        ];
+        let config = AdaptiveSelectionConfig {
+            active_sources: 1,
+            evaluation_interval: Duration::from_secs(1),
+            hold_down: Duration::from_secs(1),
+            ..Default::default()
+        };
+        let (multiplexed, selection) = adaptive_multiplex(
+            vec!["slow".to_string(), "fast".to_string()],
+            health_streams,
+            move |source_idx| full_sources[source_idx].stream(reconnect_policy()),
+            SlotExtractor,
+            config,
+        );
+
+        let slots: Vec<Slot> = multiplexed.take(20).collect().await;
+        // no gap while the promoted source connects
+        assert_eq!(slots, (1..=20).collect::<Vec<_>>());
+        assert_eq!(selection.active_sources(), vec![1]);
+    }
+}
@@ -0,0 +1,178 @@
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use yellowstone_grpc_client::GeyserGrpcClientResult;
+use yellowstone_grpc_proto::tonic::metadata::AsciiMetadataValue;
+use yellowstone_grpc_proto::tonic::service::Interceptor;
+use yellowstone_grpc_proto::tonic::{Request, Status};
+
+/// applied to every request of a source after the auth header, e.g. for request signing or
+/// provider-specific headers; see ``GrpcSourceConfig::with_interceptor``
+pub type RequestInterceptor = Arc<dyn Fn(Request<()>) -> Result<Request<()>, Status> + Send + Sync>;
+
+/// how the secret of a source (``grpc_x_token`` or token provider) is sent
+///
+/// ```toml
+/// [[sources]]
+/// grpc_addr = "https://gateway.example.com"
+/// grpc_x_token = "password"
+/// auth = { scheme = "basic", username = "me" }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "scheme", rename_all = "snake_case")]
+pub enum AuthScheme {
+    // yellowstone default
+    #[default]
+    XToken,
+    // authorization: Bearer <token>
+    Bearer,
+    // authorization: Basic base64(<username>:<token>)
+    Basic {
+        username: String,
+    },
+}
+
+impl AuthScheme {
+    pub(crate) fn interceptor(
+        &self,
+        token: Option<String>,
+    ) -> GeyserGrpcClientResult<AuthInterceptor> {
+        let Some(token) = token else {
+            return Ok(AuthInterceptor {
+                header: None,
+                request_interceptors: vec![],
+            });
+        };
+        let (key, value) = match self {
+            AuthScheme::XToken => ("x-token", token),
+            AuthScheme::Bearer => ("authorization", format!("Bearer {token}")),
+            AuthScheme::Basic { username } => {
+                let credentials =
+                    base64::engine::general_purpose::STANDARD.encode(format!("{username}:{token}"));
+                ("authorization", format!("Basic {credentials}"))
+            }
+        };
+        let value: AsciiMetadataValue = value.try_into()?;
+        Ok(AuthInterceptor {
+            header: Some((key, value)),
+            request_interceptors: vec![],
+        })
+    }
+}
+
+/// adds the auth header of the source to every request, then runs the custom interceptors
+#[derive(Clone)]
+pub struct AuthInterceptor {
+    header: Option<(&'static str, AsciiMetadataValue)>,
+    request_interceptors: Vec<RequestInterceptor>,
+}
+
+impl AuthInterceptor {
+    pub(crate) fn with_request_interceptors(
+        mut self,
+        request_interceptors: Vec<RequestInterceptor>,
+    ) -> Self {
+        self.request_interceptors = request_interceptors;
+        self
+    }
+}
+
+impl Interceptor for AuthInterceptor {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        if let Some((key, value)) = &self.header {
+            request.metadata_mut().insert(*key, value.clone());
+        }
+        for request_interceptor in &self.request_interceptors {
+            request = request_interceptor(request)?;
+        }
+        Ok(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header_of(scheme: AuthScheme, token: &str) -> Option<(String, String)> {
+        let mut interceptor = scheme.interceptor(Some(token.to_string())).unwrap();
+        let request = interceptor.call(Request::new(())).unwrap();
+        request.metadata().iter().find_map(|entry| match entry {
+            yellowstone_grpc_proto::tonic::metadata::KeyAndValueRef::Ascii(key, value) => {
+                Some((key.to_string(), value.to_str().unwrap().to_string()))
+            }
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn test_auth_headers() {
+        assert_eq!(
+            header_of(AuthScheme::XToken, "secret"),
+            Some(("x-token".to_string(), "secret".to_string()))
+        );
+        assert_eq!(
+            header_of(AuthScheme::Bearer, "secret"),
+            Some(("authorization".to_string(), "Bearer secret".to_string()))
+        );
+        assert_eq!(
+            header_of(
+                AuthScheme::Basic {
+                    username: "me".to_string()
+                },
+                "secret"
+            ),
+            Some((
+                "authorization".to_string(),
+                "Basic bWU6c2VjcmV0".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_no_token_no_header() {
+        let mut interceptor = AuthScheme::Bearer.interceptor(None).unwrap();
+        let request = interceptor.call(Request::new(())).unwrap();
+        assert!(request.metadata().is_empty());
+    }
+
+    #[test]
+    fn test_request_interceptors() {
+        let add_header: RequestInterceptor = Arc::new(|mut request: Request<()>| {
+            request
+                .metadata_mut()
+                .insert("x-provider-key", AsciiMetadataValue::from_static("abc"));
+            Ok(request)
+        });
+        let reject: RequestInterceptor =
+            Arc::new(|_request| Err(Status::permission_denied("not signed")));
+
+        let mut interceptor = AuthScheme::XToken
+            .interceptor(Some("secret".to_string()))
+            .unwrap()
+            .with_request_interceptors(vec![add_header.clone()]);
+        let request = interceptor.call(Request::new(())).unwrap();
+        assert_eq!(request.metadata().get("x-token").unwrap(), "secret");
+        assert_eq!(request.metadata().get("x-provider-key").unwrap(), "abc");
+
+        let mut interceptor = AuthScheme::XToken
+            .interceptor(None)
+            .unwrap()
+            .with_request_interceptors(vec![add_header, reject]);
+        assert!(interceptor.call(Request::new(())).is_err());
+    }
+
+    #[test]
+    fn test_auth_scheme_deserialize() {
+        let scheme: AuthScheme = toml::from_str(
+            r#"scheme = "basic"
+username = "me""#,
+        )
+        .unwrap();
+        assert_eq!(
+            scheme,
+            AuthScheme::Basic {
+                username: "me".to_string()
+            }
+        );
+    }
+}
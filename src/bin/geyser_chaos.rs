@@ -0,0 +1,73 @@
+/// Chaos run against one geyser source behind toxiproxy
+///
+/// toxiproxy-server &
+/// cargo run --features cli --bin geyser-chaos -- --config sources.toml --upstream host:10000
+///
+/// the first configured source is replaced by the proxy; exits non-zero on gaps in the chain
+use clap::Parser;
+use log::info;
+
+use geyser_grpc_connector::chaos::{default_scenario, run_chaos, ToxiproxyClient};
+use geyser_grpc_connector::commitment::CommitmentLevel;
+use geyser_grpc_connector::config_file::GrpcSourcesConfig;
+use geyser_grpc_connector::grpcmultiplex_fastestwins::create_multiplex_blockmeta;
+
+#[derive(Parser, Debug)]
+#[command(
+    about = "Program toxiproxy against one source and check the multiplexed chain is gap-free"
+)]
+struct Args {
+    /// source list (.toml or .yaml); the first source is routed through the proxy
+    #[arg(long)]
+    config: String,
+    /// grpc host:port of the first source as seen by toxiproxy
+    #[arg(long)]
+    upstream: String,
+    #[arg(long, default_value = "127.0.0.1:8474")]
+    toxiproxy_api: String,
+    #[arg(long, default_value = "127.0.0.1:10001")]
+    listen: String,
+    #[arg(long, default_value = "geyser-chaos")]
+    proxy_name: String,
+}
+
+#[tokio::main]
+pub async fn main() -> anyhow::Result<()> {
+    tracing_subscriber::fmt::init();
+    let args = Args::parse();
+
+    let mut sources = GrpcSourcesConfig::from_file(&args.config)?.sources;
+    anyhow::ensure!(
+        sources.len() >= 2,
+        "need at least two sources - one behind the proxy and one to keep the chain alive"
+    );
+
+    let client = ToxiproxyClient::new(args.toxiproxy_api);
+    // leftover from an aborted run
+    let _ = client.delete_proxy(&args.proxy_name).await;
+    client
+        .create_proxy(&args.proxy_name, &args.listen, &args.upstream)
+        .await?;
+
+    // keeps token and tls settings of the source, only the address changes
+    let upstream_label = sources[0].label();
+    sources[0].grpc_addr = format!("http://{}", args.listen);
+    info!("routing {} through {}", upstream_label, args.listen);
+
+    let blocks = create_multiplex_blockmeta(sources, CommitmentLevel::Confirmed);
+    let result = run_chaos(&client, &args.proxy_name, default_scenario(), blocks).await;
+    client.delete_proxy(&args.proxy_name).await?;
+    let report = result?;
+
+    println!(
+        "blocks {} discontinuities {} max block interval {}ms",
+        report.blocks,
+        report.discontinuities.len(),
+        report.max_block_interval.as_millis()
+    );
+    for discontinuity in &report.discontinuities {
+        println!("{:?}", discontinuity);
+    }
+    anyhow::ensure!(report.is_gap_free(), "multiplexed chain has gaps");
+    Ok(())
+}
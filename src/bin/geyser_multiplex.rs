@@ -0,0 +1,161 @@
+/// Smoke-test tool for operators evaluating geyser providers
+///
+/// cargo run --features cli --bin geyser-multiplex -- --config sources.toml
+use clap::Parser;
+use futures::StreamExt;
+use geyser_grpc_connector::commitment::CommitmentConfig;
+use geyser_grpc_connector::Slot;
+use log::info;
+use std::pin::pin;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use geyser_grpc_connector::config_file::GrpcSourcesConfig;
+use geyser_grpc_connector::grpc_subscription_autoreconnect_streams::create_geyser_reconnecting_stream;
+use geyser_grpc_connector::grpcmultiplex_fastestwins::{
+    create_multiplexed_stream_with_diagnostics, FromYellowstoneExtractor,
+};
+use geyser_grpc_connector::multiplex_diagnostics::LateArrivalDiagnostics;
+use geyser_grpc_connector::GeyserFilter;
+use yellowstone_grpc_proto::geyser::subscribe_update::UpdateOneof;
+use yellowstone_grpc_proto::geyser::SubscribeUpdate;
+
+#[derive(Parser, Debug)]
+#[command(about = "Multiplex geyser sources and print block summaries and per-source statistics")]
+struct Args {
+    /// source list (.toml or .yaml)
+    #[arg(long)]
+    config: String,
+    /// confirmed or finalized
+    #[arg(long, default_value = "confirmed")]
+    commitment: String,
+    /// subscribe full blocks instead of blocks meta
+    #[arg(long)]
+    full_blocks: bool,
+    /// print per-source statistics every n seconds
+    #[arg(long, default_value_t = 10)]
+    stats_interval_secs: u64,
+}
+
+struct BlockSummary {
+    slot: Slot,
+    blockhash: String,
+    transaction_count: u64,
+    block_time: Option<i64>,
+}
+
+struct BlockSummaryExtractor;
+
+impl FromYellowstoneExtractor for BlockSummaryExtractor {
+    type Target = BlockSummary;
+    fn map_yellowstone_update(&self, update: SubscribeUpdate) -> Option<(Slot, Self::Target)> {
+        match update.update_oneof {
+            Some(UpdateOneof::Block(block)) => Some((
+                block.slot,
+                BlockSummary {
+                    slot: block.slot,
+                    blockhash: block.blockhash,
+                    transaction_count: block.executed_transaction_count,
+                    block_time: block.block_time.map(|t| t.timestamp),
+                },
+            )),
+            Some(UpdateOneof::BlockMeta(block_meta)) => Some((
+                block_meta.slot,
+                BlockSummary {
+                    slot: block_meta.slot,
+                    blockhash: block_meta.blockhash,
+                    transaction_count: block_meta.executed_transaction_count,
+                    block_time: block_meta.block_time.map(|t| t.timestamp),
+                },
+            )),
+            _ => None,
+        }
+    }
+}
+
+fn print_source_stats(labels: &[String], diagnostics: &LateArrivalDiagnostics) {
+    for (idx, label) in labels.iter().enumerate() {
+        match diagnostics.source_stats(idx) {
+            Some(lost) => println!(
+                "source {} ({}): wins={} losses={} lost_by p50={}ms p90={}ms max={}ms",
+                idx,
+                label,
+                diagnostics.source_wins(idx),
+                lost.count,
+                lost.p50.as_millis(),
+                lost.p90.as_millis(),
+                lost.max.as_millis()
+            ),
+            None => println!(
+                "source {} ({}): wins={} losses=0",
+                idx,
+                label,
+                diagnostics.source_wins(idx)
+            ),
+        }
+    }
+}
+
+#[tokio::main]
+pub async fn main() -> anyhow::Result<()> {
+    tracing_subscriber::fmt::init();
+    let args = Args::parse();
+
+    let commitment_config = match args.commitment.as_str() {
+        "confirmed" => CommitmentConfig::confirmed(),
+        "finalized" => CommitmentConfig::finalized(),
+        other => anyhow::bail!("unsupported commitment {other}"),
+    };
+
+    let sources = GrpcSourcesConfig::from_file(&args.config)?.sources;
+    anyhow::ensure!(!sources.is_empty(), "no sources configured");
+    let labels: Vec<String> = sources.iter().map(|source| source.label()).collect();
+    for (idx, label) in labels.iter().enumerate() {
+        info!("Using source {} on {}", idx, label);
+    }
+
+    let filter = GeyserFilter(commitment_config);
+    let streams = sources
+        .into_iter()
+        .map(|source| {
+            let subscribe_filter = if args.full_blocks {
+                filter.blocks_and_txs()
+            } else {
+                filter.blocks_meta()
+            };
+            create_geyser_reconnecting_stream(source, subscribe_filter)
+        })
+        .collect::<Vec<_>>();
+
+    let (multiplex_stream, diagnostics) =
+        create_multiplexed_stream_with_diagnostics(streams, BlockSummaryExtractor);
+
+    let stats_interval = Duration::from_secs(args.stats_interval_secs);
+    tokio::spawn({
+        let diagnostics = diagnostics.clone();
+        async move {
+            let mut interval = tokio::time::interval(stats_interval);
+            loop {
+                interval.tick().await;
+                print_source_stats(&labels, &diagnostics);
+            }
+        }
+    });
+
+    let mut multiplex_stream = pin!(multiplex_stream);
+    while let Some(block) = multiplex_stream.next().await {
+        let now_secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time after epoch")
+            .as_secs() as i64;
+        let lag = block
+            .block_time
+            .map(|block_time| format!("{}s", now_secs - block_time))
+            .unwrap_or_else(|| "n/a".to_string());
+        println!(
+            "slot {} blockhash {} txs {} lag {}",
+            block.slot, block.blockhash, block.transaction_count, lag
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,382 @@
+// moved to grpcmultiplex_fastestwins so it is available without solana-sdk
+pub use crate::grpcmultiplex_fastestwins::BlockExtractor;
+use crate::multiplex_diagnostics::percentile;
+use futures::{Stream, StreamExt};
+use solana_sdk::clock::Slot;
+use solana_sdk::compute_budget;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+use yellowstone_grpc_proto::geyser::{SubscribeUpdateBlock, SubscribeUpdateTransactionInfo};
+
+// block limit of the cost model (MAX_BLOCK_UNITS)
+pub const MAX_BLOCK_COMPUTE_UNITS: u64 = 48_000_000;
+
+// instruction discriminants of ComputeBudgetInstruction (borsh enum index)
+const SET_COMPUTE_UNIT_LIMIT: u8 = 2;
+const SET_COMPUTE_UNIT_PRICE: u8 = 3;
+
+/// compute unit price in micro-lamports per CU over the non-vote transactions of one block
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PriorityFeeSummary {
+    pub slot: Slot,
+    pub transaction_count: usize,
+    // transactions which set a compute unit price
+    pub prioritized_count: usize,
+    pub min: u64,
+    pub median: u64,
+    pub p75: u64,
+    pub p95: u64,
+    pub max: u64,
+}
+
+/// None if the block has no non-vote transactions
+pub fn priority_fee_summary(block: &SubscribeUpdateBlock) -> Option<PriorityFeeSummary> {
+    let mut prices: Vec<u64> = block
+        .transactions
+        .iter()
+        .filter(|tx| !tx.is_vote)
+        .map(|tx| compute_budget_of(tx).unit_price.unwrap_or_default())
+        .collect();
+    if prices.is_empty() {
+        return None;
+    }
+    prices.sort_unstable();
+
+    Some(PriorityFeeSummary {
+        slot: block.slot,
+        transaction_count: prices.len(),
+        prioritized_count: prices.iter().filter(|price| **price > 0).count(),
+        min: prices[0],
+        median: percentile(&prices, 50),
+        p75: percentile(&prices, 75),
+        p95: percentile(&prices, 95),
+        max: prices[prices.len() - 1],
+    })
+}
+
+/// one summary per multiplexed block, see ``BlockExtractor``
+pub fn priority_fee_stream(
+    blocks: impl Stream<Item = SubscribeUpdateBlock>,
+) -> impl Stream<Item = PriorityFeeSummary> {
+    blocks.filter_map(|block| futures::future::ready(priority_fee_summary(&block)))
+}
+
+/// compute units consumed by one block
+#[derive(Clone, Debug, PartialEq)]
+pub struct ComputeUnitSummary {
+    pub slot: Slot,
+    // including votes
+    pub total_consumed: u64,
+    // total_consumed relative to MAX_BLOCK_COMPUTE_UNITS
+    pub utilization: f64,
+    // highest first; each transaction counts for the first program it invokes other than compute budget
+    pub top_programs: Vec<(Pubkey, u64)>,
+}
+
+pub fn compute_unit_summary(block: &SubscribeUpdateBlock, top_n: usize) -> ComputeUnitSummary {
+    let mut total_consumed = 0;
+    let mut per_program: HashMap<Pubkey, u64> = HashMap::new();
+    for tx in &block.transactions {
+        let consumed = tx
+            .meta
+            .as_ref()
+            .and_then(|meta| meta.compute_units_consumed)
+            .unwrap_or_default();
+        total_consumed += consumed;
+        if let Some(program_id) = main_program_of(tx) {
+            *per_program.entry(program_id).or_default() += consumed;
+        }
+    }
+
+    let mut top_programs: Vec<(Pubkey, u64)> = per_program.into_iter().collect();
+    // tie-break on pubkey to be deterministic
+    top_programs
+        .sort_unstable_by(|(a_key, a_cu), (b_key, b_cu)| b_cu.cmp(a_cu).then(a_key.cmp(b_key)));
+    top_programs.truncate(top_n);
+
+    ComputeUnitSummary {
+        slot: block.slot,
+        total_consumed,
+        utilization: total_consumed as f64 / MAX_BLOCK_COMPUTE_UNITS as f64,
+        top_programs,
+    }
+}
+
+/// one summary per multiplexed block, see ``BlockExtractor``
+pub fn compute_unit_stream(
+    blocks: impl Stream<Item = SubscribeUpdateBlock>,
+    top_n: usize,
+) -> impl Stream<Item = ComputeUnitSummary> {
+    blocks.map(move |block| compute_unit_summary(&block, top_n))
+}
+
+fn main_program_of(tx: &SubscribeUpdateTransactionInfo) -> Option<Pubkey> {
+    let message = tx.transaction.as_ref()?.message.as_ref()?;
+    let compute_budget_program = compute_budget::id().to_bytes();
+    message
+        .instructions
+        .iter()
+        .filter_map(|ix| message.account_keys.get(ix.program_id_index as usize))
+        .find(|program_id| program_id.as_slice() != compute_budget_program)
+        .and_then(|program_id| <[u8; 32]>::try_from(program_id.as_slice()).ok())
+        .map(Pubkey::new_from_array)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TpsSample {
+    pub window: Duration,
+    pub tps: f64,
+    pub non_vote_tps: f64,
+}
+
+/// rolling throughput after one block, one sample per configured window
+#[derive(Clone, Debug, PartialEq)]
+pub struct TpsReport {
+    pub slot: Slot,
+    pub samples: Vec<TpsSample>,
+}
+
+/// windows are measured on the block time (second granularity); until a full window was observed
+/// the rate is underestimated; blocks without block time are ignored
+pub fn tps_stream(
+    blocks: impl Stream<Item = SubscribeUpdateBlock>,
+    windows: Vec<Duration>,
+) -> impl Stream<Item = TpsReport> {
+    let max_window = windows.iter().max().copied().unwrap_or_default().as_secs() as i64;
+    // (block_time, transactions, non-vote transactions)
+    let mut history: VecDeque<(i64, u64, u64)> = VecDeque::new();
+    blocks.filter_map(move |block| {
+        let report = block.block_time.as_ref().map(|block_time| {
+            let non_vote = block.transactions.iter().filter(|tx| !tx.is_vote).count() as u64;
+            history.push_back((
+                block_time.timestamp,
+                block.transactions.len() as u64,
+                non_vote,
+            ));
+            while history.front().map_or(false, |(oldest, _, _)| {
+                *oldest <= block_time.timestamp - max_window
+            }) {
+                history.pop_front();
+            }
+
+            let samples = windows
+                .iter()
+                .map(|window| {
+                    let since = block_time.timestamp - window.as_secs() as i64;
+                    let (transactions, non_vote) = history
+                        .iter()
+                        .filter(|(time, _, _)| *time > since)
+                        .fold((0, 0), |(transactions, non_vote), (_, tx, nv)| {
+                            (transactions + tx, non_vote + nv)
+                        });
+                    let secs = window.as_secs_f64().max(1.0);
+                    TpsSample {
+                        window: *window,
+                        tps: transactions as f64 / secs,
+                        non_vote_tps: non_vote as f64 / secs,
+                    }
+                })
+                .collect();
+            TpsReport {
+                slot: block.slot,
+                samples,
+            }
+        });
+        futures::future::ready(report)
+    })
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct ComputeBudget {
+    pub unit_limit: Option<u32>,
+    pub unit_price: Option<u64>,
+}
+
+// reads the compute budget instructions of a transaction
+pub(crate) fn compute_budget_of(tx: &SubscribeUpdateTransactionInfo) -> ComputeBudget {
+    let mut compute_budget = ComputeBudget::default();
+    let Some(message) = tx.transaction.as_ref().and_then(|tx| tx.message.as_ref()) else {
+        return compute_budget;
+    };
+    let compute_budget_program = compute_budget::id().to_bytes();
+
+    for ix in &message.instructions {
+        let is_compute_budget = message
+            .account_keys
+            .get(ix.program_id_index as usize)
+            .map_or(false, |program_id| {
+                program_id.as_slice() == compute_budget_program
+            });
+        if !is_compute_budget {
+            continue;
+        }
+        match ix.data.split_first() {
+            Some((&SET_COMPUTE_UNIT_LIMIT, units)) => {
+                if let Ok(units) = <[u8; 4]>::try_from(units) {
+                    compute_budget.unit_limit = Some(u32::from_le_bytes(units));
+                }
+            }
+            Some((&SET_COMPUTE_UNIT_PRICE, micro_lamports)) => {
+                if let Ok(micro_lamports) = <[u8; 8]>::try_from(micro_lamports) {
+                    compute_budget.unit_price = Some(u64::from_le_bytes(micro_lamports));
+                }
+            }
+            _ => {}
+        }
+    }
+    compute_budget
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::stream;
+    use yellowstone_grpc_proto::prelude::{
+        CompiledInstruction, Message, Transaction, TransactionStatusMeta, UnixTimestamp,
+    };
+
+    fn transaction(
+        is_vote: bool,
+        unit_price: Option<u64>,
+        compute_units_consumed: u64,
+    ) -> SubscribeUpdateTransactionInfo {
+        let mut instructions = vec![CompiledInstruction {
+            program_id_index: 2,
+            accounts: vec![0],
+            data: vec![],
+        }];
+        if let Some(unit_price) = unit_price {
+            let mut data = vec![SET_COMPUTE_UNIT_PRICE];
+            data.extend_from_slice(&unit_price.to_le_bytes());
+            instructions.push(CompiledInstruction {
+                program_id_index: 1,
+                accounts: vec![],
+                data,
+            });
+        }
+        let mut data = vec![SET_COMPUTE_UNIT_LIMIT];
+        data.extend_from_slice(&200_000u32.to_le_bytes());
+        instructions.push(CompiledInstruction {
+            program_id_index: 1,
+            accounts: vec![],
+            data,
+        });
+
+        SubscribeUpdateTransactionInfo {
+            is_vote,
+            transaction: Some(Transaction {
+                signatures: vec![vec![1; 64]],
+                message: Some(Message {
+                    account_keys: vec![
+                        vec![7; 32],
+                        compute_budget::id().to_bytes().to_vec(),
+                        vec![9; 32],
+                    ],
+                    instructions,
+                    ..Default::default()
+                }),
+            }),
+            meta: Some(TransactionStatusMeta {
+                compute_units_consumed: Some(compute_units_consumed),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_compute_budget_of() {
+        let compute_budget = compute_budget_of(&transaction(false, Some(5000), 1000));
+        assert_eq!(compute_budget.unit_price, Some(5000));
+        assert_eq!(compute_budget.unit_limit, Some(200_000));
+    }
+
+    #[test]
+    fn test_priority_fee_summary() {
+        let mut transactions: Vec<SubscribeUpdateTransactionInfo> = (1..=100)
+            .map(|price| transaction(false, Some(price * 10), 1000))
+            .collect();
+        transactions.push(transaction(false, None, 1000));
+        transactions.push(transaction(true, Some(1_000_000), 1000));
+        let block = SubscribeUpdateBlock {
+            slot: 42,
+            transactions,
+            ..Default::default()
+        };
+
+        let summary = priority_fee_summary(&block).unwrap();
+        assert_eq!(summary.slot, 42);
+        // vote is not counted
+        assert_eq!(summary.transaction_count, 101);
+        assert_eq!(summary.prioritized_count, 100);
+        assert_eq!(summary.min, 0);
+        assert_eq!(summary.median, 500);
+        assert_eq!(summary.p95, 950);
+        assert_eq!(summary.max, 1000);
+
+        assert!(priority_fee_summary(&SubscribeUpdateBlock::default()).is_none());
+    }
+
+    #[test]
+    fn test_compute_unit_summary() {
+        let mut vote = transaction(true, None, 2100);
+        vote.transaction
+            .as_mut()
+            .unwrap()
+            .message
+            .as_mut()
+            .unwrap()
+            .account_keys[2] = vec![8; 32];
+        let block = SubscribeUpdateBlock {
+            slot: 42,
+            transactions: vec![
+                transaction(false, Some(1), 24_000_000),
+                transaction(false, None, 1000),
+                vote,
+            ],
+            ..Default::default()
+        };
+
+        let summary = compute_unit_summary(&block, 1);
+        assert_eq!(summary.total_consumed, 24_003_100);
+        assert!(summary.utilization > 0.5 && summary.utilization < 0.51);
+        assert_eq!(
+            summary.top_programs,
+            vec![(Pubkey::new_from_array([9; 32]), 24_001_000)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tps_stream() {
+        let block = |slot: Slot, timestamp: i64| SubscribeUpdateBlock {
+            slot,
+            block_time: Some(UnixTimestamp { timestamp }),
+            transactions: vec![
+                transaction(false, None, 1000),
+                transaction(true, None, 1000),
+                transaction(true, None, 1000),
+                transaction(true, None, 1000),
+            ],
+            ..Default::default()
+        };
+        let blocks = stream::iter(vec![block(1, 100), block(2, 101), block(3, 110)]);
+
+        let reports: Vec<TpsReport> = tps_stream(
+            blocks,
+            vec![Duration::from_secs(2), Duration::from_secs(10)],
+        )
+        .collect()
+        .await;
+
+        assert_eq!(reports.len(), 3);
+        let after_second = &reports[1].samples;
+        assert_eq!(after_second[0].tps, 4.0);
+        assert_eq!(after_second[0].non_vote_tps, 1.0);
+        assert_eq!(after_second[1].tps, 0.8);
+        // block at 100 left the 10s window
+        let last = &reports[2].samples;
+        assert_eq!(last[0].tps, 2.0);
+        assert_eq!(last[1].tps, 0.8);
+    }
+}
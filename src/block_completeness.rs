@@ -0,0 +1,119 @@
+use crate::Slot;
+use futures::{future, Stream, StreamExt};
+use log::warn;
+use tokio::sync::broadcast;
+use yellowstone_grpc_proto::geyser::{SubscribeUpdateBlock, SubscribeUpdateBlockMeta};
+
+/// a block carries fewer transactions or entries than its header announces
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IncompleteBlock {
+    pub slot: Slot,
+    pub expected_transactions: u64,
+    pub actual_transactions: u64,
+    pub expected_entries: u64,
+    pub actual_entries: u64,
+}
+
+/// compare the counts announced by the block against its content
+///
+/// only meaningful for blocks subscribed without account filters; transactions and entries are
+/// each checked only if the block contains any (``include_transactions``, ``include_entries``),
+/// so a block truncated to no transactions at all is not detected
+pub fn check_completeness(block: &SubscribeUpdateBlock) -> Option<IncompleteBlock> {
+    incomplete(block, block.executed_transaction_count, block.entries_count)
+}
+
+/// like ``check_completeness`` but with the counts from the blocks meta of the same slot
+pub fn check_against_meta(
+    block: &SubscribeUpdateBlock,
+    block_meta: &SubscribeUpdateBlockMeta,
+) -> Option<IncompleteBlock> {
+    if block.slot != block_meta.slot {
+        return None;
+    }
+    incomplete(
+        block,
+        block_meta.executed_transaction_count,
+        block_meta.entries_count,
+    )
+}
+
+fn incomplete(
+    block: &SubscribeUpdateBlock,
+    expected_transactions: u64,
+    expected_entries: u64,
+) -> Option<IncompleteBlock> {
+    let actual_transactions = block.transactions.len() as u64;
+    let actual_entries = block.entries.len() as u64;
+    let transactions_missing =
+        actual_transactions > 0 && actual_transactions < expected_transactions;
+    let entries_missing = actual_entries > 0 && actual_entries < expected_entries;
+    (transactions_missing || entries_missing).then_some(IncompleteBlock {
+        slot: block.slot,
+        expected_transactions,
+        actual_transactions,
+        expected_entries,
+        actual_entries,
+    })
+}
+
+/// drop truncated blocks from the stream and report them on the returned channel
+pub fn filter_incomplete_blocks(
+    block_stream: impl Stream<Item = SubscribeUpdateBlock>,
+) -> (
+    impl Stream<Item = SubscribeUpdateBlock>,
+    broadcast::Receiver<IncompleteBlock>,
+) {
+    let (incomplete_tx, incomplete_rx) = broadcast::channel(100);
+    let complete = block_stream.filter(move |block| {
+        let incomplete_block = check_completeness(block);
+        if let Some(incomplete_block) = &incomplete_block {
+            warn!(
+                "dropping incomplete block {}: {}/{} transactions, {}/{} entries",
+                incomplete_block.slot,
+                incomplete_block.actual_transactions,
+                incomplete_block.expected_transactions,
+                incomplete_block.actual_entries,
+                incomplete_block.expected_entries
+            );
+            // no subscribers is fine
+            let _ = incomplete_tx.send(incomplete_block.clone());
+        }
+        future::ready(incomplete_block.is_none())
+    });
+    (complete, incomplete_rx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::stream;
+    use yellowstone_grpc_proto::geyser::SubscribeUpdateTransactionInfo;
+
+    fn block(slot: Slot, announced: u64, present: usize) -> SubscribeUpdateBlock {
+        SubscribeUpdateBlock {
+            slot,
+            executed_transaction_count: announced,
+            transactions: (0..present)
+                .map(|_| SubscribeUpdateTransactionInfo::default())
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_filter_incomplete_blocks() {
+        // slot 3 is subscribed without transactions
+        let (complete, mut incomplete) = filter_incomplete_blocks(stream::iter(vec![
+            block(1, 3, 3),
+            block(2, 3, 1),
+            block(3, 3, 0),
+        ]));
+
+        let slots: Vec<Slot> = complete.map(|block| block.slot).collect().await;
+        assert_eq!(slots, vec![1, 3]);
+        let truncated = incomplete.recv().await.unwrap();
+        assert_eq!(truncated.slot, 2);
+        assert_eq!(truncated.actual_transactions, 1);
+    }
+}
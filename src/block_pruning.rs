@@ -0,0 +1,129 @@
+use crate::Message;
+use futures::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use yellowstone_grpc_proto::geyser::subscribe_update::UpdateOneof;
+use yellowstone_grpc_proto::geyser::SubscribeUpdateBlock;
+
+/// heavy fields to strip from blocks before they are broadcast, see ``prune_blocks``
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct BlockPruning {
+    #[serde(default)]
+    pub strip_logs: bool,
+    #[serde(default)]
+    pub strip_inner_instructions: bool,
+    #[serde(default)]
+    pub strip_return_data: bool,
+    #[serde(default)]
+    pub drop_vote_transactions: bool,
+}
+
+impl BlockPruning {
+    /// keep only what is needed for balances and signatures
+    pub fn balances_and_signatures() -> Self {
+        BlockPruning {
+            strip_logs: true,
+            strip_inner_instructions: true,
+            strip_return_data: true,
+            drop_vote_transactions: true,
+        }
+    }
+
+    pub fn apply(&self, block: &mut SubscribeUpdateBlock) {
+        if self.drop_vote_transactions {
+            block
+                .transactions
+                .retain(|transaction| !transaction.is_vote);
+        }
+        for meta in block
+            .transactions
+            .iter_mut()
+            .filter_map(|transaction| transaction.meta.as_mut())
+        {
+            if self.strip_logs {
+                meta.log_messages = vec![];
+                meta.log_messages_none = true;
+            }
+            if self.strip_inner_instructions {
+                meta.inner_instructions = vec![];
+                meta.inner_instructions_none = true;
+            }
+            if self.strip_return_data {
+                meta.return_data = None;
+                meta.return_data_none = true;
+            }
+        }
+    }
+}
+
+/// apply the pruning to all block updates; use before ``channelize_stream`` so the pruned blocks are shared
+/// note: executed_transaction_count of the block still includes dropped vote transactions
+pub fn prune_blocks(
+    geyser_stream: impl Stream<Item = Message>,
+    pruning: BlockPruning,
+) -> impl Stream<Item = Message> {
+    geyser_stream.map(move |mut message| {
+        if let Message::GeyserSubscribeUpdate(update) = &mut message {
+            if let Some(UpdateOneof::Block(block)) = &mut update.update_oneof {
+                pruning.apply(block);
+            }
+        }
+        message
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yellowstone_grpc_proto::geyser::SubscribeUpdateTransactionInfo;
+    use yellowstone_grpc_proto::prelude::{ReturnData, TransactionStatusMeta};
+
+    fn transaction(is_vote: bool) -> SubscribeUpdateTransactionInfo {
+        SubscribeUpdateTransactionInfo {
+            is_vote,
+            meta: Some(TransactionStatusMeta {
+                log_messages: vec!["Program log: hello".to_string()],
+                return_data: Some(ReturnData::default()),
+                pre_balances: vec![10],
+                post_balances: vec![5],
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_prune_block() {
+        let mut block = SubscribeUpdateBlock {
+            slot: 42,
+            transactions: vec![transaction(true), transaction(false)],
+            ..Default::default()
+        };
+
+        BlockPruning::balances_and_signatures().apply(&mut block);
+
+        assert_eq!(block.transactions.len(), 1);
+        let meta = block.transactions[0].meta.as_ref().unwrap();
+        assert!(meta.log_messages.is_empty());
+        assert!(meta.log_messages_none);
+        assert!(meta.return_data.is_none());
+        assert_eq!(meta.post_balances, vec![5]);
+    }
+
+    #[test]
+    fn test_default_keeps_everything() {
+        let mut block = SubscribeUpdateBlock {
+            transactions: vec![transaction(true)],
+            ..Default::default()
+        };
+        BlockPruning::default().apply(&mut block);
+        assert_eq!(
+            block.transactions[0]
+                .meta
+                .as_ref()
+                .unwrap()
+                .log_messages
+                .len(),
+            1
+        );
+    }
+}
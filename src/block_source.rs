@@ -0,0 +1,124 @@
+use crate::grpc_subscription_autoreconnect_streams::{
+    create_geyser_reconnecting_stream, create_geyser_reconnecting_stream_with_client_factory,
+};
+use crate::reconnect_policy::{ExponentialBackoff, ReconnectPolicy};
+use crate::{GrpcSourceConfig, Message};
+use futures::future::BoxFuture;
+use futures::stream::BoxStream;
+use futures::{Stream, StreamExt};
+use yellowstone_grpc_client::{GeyserGrpcClient, GeyserGrpcClientResult};
+use yellowstone_grpc_proto::geyser::SubscribeRequest;
+use yellowstone_grpc_proto::tonic::service::Interceptor;
+
+/// anything that can feed the fastest-wins multiplexer: geyser grpc, websocket pubsub, ledger replay, test fixtures
+///
+/// sources must emit ``Message::GeyserSubscribeUpdate``; non-grpc sources convert their payload
+pub trait BlockSource {
+    /// safe for logging
+    fn label(&self) -> String;
+    fn into_stream(self: Box<Self>) -> BoxStream<'static, Message>;
+}
+
+/// geyser grpc source backed by ``create_geyser_reconnecting_stream``
+pub struct GeyserGrpcSource {
+    grpc_source: GrpcSourceConfig,
+    subscribe_filter: SubscribeRequest,
+}
+
+impl GeyserGrpcSource {
+    pub fn new(grpc_source: GrpcSourceConfig, subscribe_filter: SubscribeRequest) -> Self {
+        Self {
+            grpc_source,
+            subscribe_filter,
+        }
+    }
+}
+
+impl BlockSource for GeyserGrpcSource {
+    fn label(&self) -> String {
+        self.grpc_source.label()
+    }
+
+    fn into_stream(self: Box<Self>) -> BoxStream<'static, Message> {
+        create_geyser_reconnecting_stream(self.grpc_source, self.subscribe_filter).boxed()
+    }
+}
+
+/// geyser grpc source with a client from a custom factory, see
+/// ``create_geyser_reconnecting_stream_with_client_factory``
+pub struct ClientFactorySource<I, P = ExponentialBackoff> {
+    grpc_source: GrpcSourceConfig,
+    subscribe_filter: SubscribeRequest,
+    reconnect_policy: P,
+    client_factory: ClientFactory<I>,
+}
+
+/// builds a connected client for each (re)connect attempt
+pub type ClientFactory<I> = Box<
+    dyn Fn(GrpcSourceConfig) -> BoxFuture<'static, GeyserGrpcClientResult<GeyserGrpcClient<I>>>
+        + Send,
+>;
+
+impl<I: Interceptor + Send + 'static, P: ReconnectPolicy> ClientFactorySource<I, P> {
+    pub fn new(
+        grpc_source: GrpcSourceConfig,
+        subscribe_filter: SubscribeRequest,
+        reconnect_policy: P,
+        client_factory: impl Fn(GrpcSourceConfig) -> BoxFuture<'static, GeyserGrpcClientResult<GeyserGrpcClient<I>>>
+            + Send
+            + 'static,
+    ) -> Self {
+        Self {
+            grpc_source,
+            subscribe_filter,
+            reconnect_policy,
+            client_factory: Box::new(client_factory),
+        }
+    }
+}
+
+impl<I: Interceptor + Send + 'static, P: ReconnectPolicy> BlockSource
+    for ClientFactorySource<I, P>
+{
+    fn label(&self) -> String {
+        self.grpc_source.label()
+    }
+
+    fn into_stream(self: Box<Self>) -> BoxStream<'static, Message> {
+        create_geyser_reconnecting_stream_with_client_factory(
+            self.grpc_source,
+            self.subscribe_filter,
+            self.reconnect_policy,
+            self.client_factory,
+        )
+        .boxed()
+    }
+}
+
+/// wraps any message stream, e.g. a replay or a test fixture
+pub struct StreamSource {
+    label: String,
+    stream: BoxStream<'static, Message>,
+}
+
+impl StreamSource {
+    pub fn new(
+        label: impl Into<String>,
+        stream: impl Stream<Item = Message> + Send + 'static,
+    ) -> Self {
+        Self {
+            label: label.into(),
+            stream: stream.boxed(),
+        }
+    }
+}
+
+impl BlockSource for StreamSource {
+    fn label(&self) -> String {
+        self.label.clone()
+    }
+
+    fn into_stream(self: Box<Self>) -> BoxStream<'static, Message> {
+        self.stream
+    }
+}
@@ -0,0 +1,138 @@
+use crate::Slot;
+use futures::{Stream, StreamExt};
+use log::{info, warn};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BlockTimeDriftEvent {
+    // drift exceeded the threshold, e.g. a source serving stale replayed blocks
+    Drifting { slot: Slot, drift_secs: i64 },
+    // back within the threshold
+    Recovered { slot: Slot, drift_secs: i64 },
+}
+
+#[derive(Default)]
+struct WatchdogState {
+    drifting: bool,
+    last_drift_secs: Option<i64>,
+}
+
+/// compares block_time with the local clock; events are only sent on transitions
+///
+/// drift is wall clock minus block time: positive if blocks are behind; block time has second
+/// granularity and is usually within a few seconds of the clock, so keep the threshold above that
+/// cheap to clone
+#[derive(Clone)]
+pub struct BlockTimeWatchdog {
+    threshold: Duration,
+    sender: broadcast::Sender<BlockTimeDriftEvent>,
+    state: Arc<Mutex<WatchdogState>>,
+}
+
+impl BlockTimeWatchdog {
+    pub fn new(threshold: Duration) -> Self {
+        let (sender, _) = broadcast::channel(100);
+        Self {
+            threshold,
+            sender,
+            state: Arc::new(Mutex::new(WatchdogState::default())),
+        }
+    }
+
+    /// lagging receivers will miss events
+    pub fn subscribe(&self) -> broadcast::Receiver<BlockTimeDriftEvent> {
+        self.sender.subscribe()
+    }
+
+    pub fn is_drifting(&self) -> bool {
+        self.state.lock().unwrap().drifting
+    }
+
+    pub fn last_drift_secs(&self) -> Option<i64> {
+        self.state.lock().unwrap().last_drift_secs
+    }
+
+    /// ``block_time`` as unix timestamp like in ``SubscribeUpdateBlock::block_time``
+    pub fn check(&self, slot: Slot, block_time: i64) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time after epoch")
+            .as_secs() as i64;
+        self.check_at(slot, block_time, now);
+    }
+
+    fn check_at(&self, slot: Slot, block_time: i64, now: i64) {
+        let drift_secs = now - block_time;
+        let exceeded = drift_secs.unsigned_abs() > self.threshold.as_secs();
+        let mut state = self.state.lock().unwrap();
+        state.last_drift_secs = Some(drift_secs);
+        if exceeded == state.drifting {
+            return;
+        }
+        state.drifting = exceeded;
+        let event = if exceeded {
+            warn!(
+                "block time of slot {} drifts {}s from the local clock",
+                slot, drift_secs
+            );
+            BlockTimeDriftEvent::Drifting { slot, drift_secs }
+        } else {
+            info!("block time drift recovered at slot {}", slot);
+            BlockTimeDriftEvent::Recovered { slot, drift_secs }
+        };
+        // no subscribers is fine
+        let _ = self.sender.send(event);
+    }
+
+    /// passes the stream through and checks every item with a block time, e.g.
+    /// ``watchdog.watch(blocks, |block| block.block_time.as_ref().map(|t| (block.slot, t.timestamp)))``
+    pub fn watch<T>(
+        &self,
+        stream: impl Stream<Item = T>,
+        block_time_of: impl Fn(&T) -> Option<(Slot, i64)>,
+    ) -> impl Stream<Item = T> {
+        let watchdog = self.clone();
+        stream.inspect(move |item| {
+            if let Some((slot, block_time)) = block_time_of(item) {
+                watchdog.check(slot, block_time);
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drift_transitions() {
+        let watchdog = BlockTimeWatchdog::new(Duration::from_secs(10));
+        let mut events = watchdog.subscribe();
+
+        watchdog.check_at(1, 1000, 1002);
+        // stale replay, reported once
+        watchdog.check_at(2, 1000, 1060);
+        watchdog.check_at(3, 1001, 1061);
+        watchdog.check_at(4, 1060, 1062);
+
+        assert_eq!(
+            events.try_recv().unwrap(),
+            BlockTimeDriftEvent::Drifting {
+                slot: 2,
+                drift_secs: 60
+            }
+        );
+        assert_eq!(
+            events.try_recv().unwrap(),
+            BlockTimeDriftEvent::Recovered {
+                slot: 4,
+                drift_secs: 2
+            }
+        );
+        assert!(events.try_recv().is_err());
+        assert!(!watchdog.is_drifting());
+        assert_eq!(watchdog.last_drift_secs(), Some(2));
+    }
+}
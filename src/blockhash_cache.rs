@@ -0,0 +1,146 @@
+use crate::grpcmultiplex_fastestwins::create_multiplex_blockmeta;
+use crate::GrpcSourceConfig;
+use futures::StreamExt;
+use log::warn;
+use solana_sdk::clock::{Slot, MAX_PROCESSING_AGE};
+use solana_sdk::commitment_config::{CommitmentConfig, CommitmentLevel};
+use std::collections::{HashMap, VecDeque};
+use std::pin::pin;
+use std::sync::{Arc, RwLock};
+use tokio::task::JoinHandle;
+use yellowstone_grpc_proto::geyser::SubscribeUpdateBlockMeta;
+
+// comfortably more than the 150 blocks a blockhash is valid
+const CACHED_BLOCKHASHES: usize = 300;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LatestBlockhash {
+    pub blockhash: String,
+    pub slot: Slot,
+    pub block_height: u64,
+    // same semantics as in the getLatestBlockhash rpc response
+    pub last_valid_block_height: u64,
+}
+
+/// recent blockhashes per commitment level; cheap to clone
+#[derive(Clone, Default)]
+pub struct BlockhashCache {
+    blockhashes: Arc<RwLock<HashMap<CommitmentLevel, VecDeque<LatestBlockhash>>>>,
+}
+
+impl BlockhashCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get_latest_blockhash(&self, commitment: CommitmentConfig) -> Option<LatestBlockhash> {
+        self.blockhashes
+            .read()
+            .unwrap()
+            .get(&commitment.commitment)
+            .and_then(|blockhashes| blockhashes.back().cloned())
+    }
+
+    /// false if the blockhash is unknown or expired relative to the latest block height
+    pub fn is_blockhash_valid(&self, blockhash: &str, commitment: CommitmentConfig) -> bool {
+        let blockhashes = self.blockhashes.read().unwrap();
+        let Some(blockhashes) = blockhashes.get(&commitment.commitment) else {
+            return false;
+        };
+        let Some(latest) = blockhashes.back() else {
+            return false;
+        };
+        blockhashes
+            .iter()
+            .rev()
+            .find(|cached| cached.blockhash == blockhash)
+            .map_or(false, |cached| {
+                cached.last_valid_block_height >= latest.block_height
+            })
+    }
+
+    /// blocks meta must arrive in increasing order like the multiplexer yields them
+    pub fn update(&self, commitment: CommitmentConfig, block_meta: &SubscribeUpdateBlockMeta) {
+        let Some(block_height) = block_meta.block_height.as_ref().map(|h| h.block_height) else {
+            warn!(
+                "block meta for slot {} without block height - skipping",
+                block_meta.slot
+            );
+            return;
+        };
+        let mut blockhashes = self.blockhashes.write().unwrap();
+        let blockhashes = blockhashes.entry(commitment.commitment).or_default();
+        if blockhashes.len() >= CACHED_BLOCKHASHES {
+            blockhashes.pop_front();
+        }
+        blockhashes.push_back(LatestBlockhash {
+            blockhash: block_meta.blockhash.clone(),
+            slot: block_meta.slot,
+            block_height,
+            last_valid_block_height: block_height + MAX_PROCESSING_AGE as u64,
+        });
+    }
+}
+
+/// spawn one blocks meta multiplexer per commitment level feeding a shared cache
+pub fn spawn_blockhash_cache(
+    grpc_sources: Vec<GrpcSourceConfig>,
+    commitments: Vec<crate::commitment::CommitmentLevel>,
+) -> (BlockhashCache, Vec<JoinHandle<()>>) {
+    let cache = BlockhashCache::new();
+    let jh_updaters = commitments
+        .into_iter()
+        .map(|commitment_level| {
+            let blocks_meta = create_multiplex_blockmeta(grpc_sources.clone(), commitment_level);
+            let commitment = commitment_level.commitment_config();
+            let cache = cache.clone();
+            tokio::spawn(async move {
+                let mut blocks_meta = pin!(blocks_meta);
+                while let Some(block_meta) = blocks_meta.next().await {
+                    cache.update(commitment, &block_meta);
+                }
+                warn!("blocks meta stream for {:?} ended", commitment.commitment);
+            })
+        })
+        .collect();
+    (cache, jh_updaters)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yellowstone_grpc_proto::prelude::BlockHeight;
+
+    fn block_meta(slot: Slot, block_height: u64) -> SubscribeUpdateBlockMeta {
+        SubscribeUpdateBlockMeta {
+            slot,
+            blockhash: format!("hash-{}", slot),
+            block_height: Some(BlockHeight { block_height }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_blockhash_validity() {
+        let cache = BlockhashCache::new();
+        let confirmed = CommitmentConfig::confirmed();
+        assert!(cache.get_latest_blockhash(confirmed).is_none());
+
+        for height in 0..200 {
+            cache.update(confirmed, &block_meta(1000 + height, height));
+        }
+
+        let latest = cache.get_latest_blockhash(confirmed).unwrap();
+        assert_eq!(latest.blockhash, "hash-1199");
+        assert_eq!(
+            latest.last_valid_block_height,
+            199 + MAX_PROCESSING_AGE as u64
+        );
+        assert!(cache.is_blockhash_valid("hash-1199", confirmed));
+        assert!(cache.is_blockhash_valid("hash-1049", confirmed));
+        assert!(!cache.is_blockhash_valid("hash-1048", confirmed));
+        assert!(!cache.is_blockhash_valid("unknown", confirmed));
+        // other commitment levels are tracked separately
+        assert!(!cache.is_blockhash_valid("hash-1199", CommitmentConfig::finalized()));
+    }
+}
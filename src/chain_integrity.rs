@@ -0,0 +1,153 @@
+use crate::Slot;
+use futures::{Stream, StreamExt};
+use log::warn;
+use tokio::sync::broadcast;
+use yellowstone_grpc_proto::geyser::{SubscribeUpdateBlock, SubscribeUpdateBlockMeta};
+
+/// what ``verify_chain`` needs from a block
+pub trait ChainLink {
+    fn slot(&self) -> Slot;
+    fn blockhash(&self) -> &str;
+    fn parent_slot(&self) -> Slot;
+    fn parent_blockhash(&self) -> &str;
+}
+
+impl ChainLink for SubscribeUpdateBlock {
+    fn slot(&self) -> Slot {
+        self.slot
+    }
+    fn blockhash(&self) -> &str {
+        &self.blockhash
+    }
+    fn parent_slot(&self) -> Slot {
+        self.parent_slot
+    }
+    fn parent_blockhash(&self) -> &str {
+        &self.parent_blockhash
+    }
+}
+
+impl ChainLink for SubscribeUpdateBlockMeta {
+    fn slot(&self) -> Slot {
+        self.slot
+    }
+    fn blockhash(&self) -> &str {
+        &self.blockhash
+    }
+    fn parent_slot(&self) -> Slot {
+        self.parent_slot
+    }
+    fn parent_blockhash(&self) -> &str {
+        &self.parent_blockhash
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiscontinuityKind {
+    // the parent was never yielded, e.g. a block got lost
+    MissingParent,
+    // the parent is older than the previous block: the previous block was on another fork
+    ForkSwitch,
+    // parent slot matches but the blockhash differs
+    BlockhashMismatch,
+}
+
+/// a yielded block does not link to the block yielded before it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChainDiscontinuity {
+    pub kind: DiscontinuityKind,
+    pub slot: Slot,
+    pub parent_slot: Slot,
+    pub parent_blockhash: String,
+    pub previous_slot: Slot,
+    pub previous_blockhash: String,
+}
+
+/// pass blocks through and report blocks whose parent is not the previous block
+/// note: use with confirmed or finalized blocks; processed blocks form forks
+pub fn verify_chain<B: ChainLink>(
+    block_stream: impl Stream<Item = B>,
+) -> (
+    impl Stream<Item = B>,
+    broadcast::Receiver<ChainDiscontinuity>,
+) {
+    let (discontinuities_tx, discontinuities_rx) = broadcast::channel(100);
+    let mut previous: Option<(Slot, String)> = None;
+    let verified = block_stream.inspect(move |block| {
+        if let Some((previous_slot, previous_blockhash)) = &previous {
+            let kind = if block.parent_slot() > *previous_slot {
+                Some(DiscontinuityKind::MissingParent)
+            } else if block.parent_slot() < *previous_slot {
+                Some(DiscontinuityKind::ForkSwitch)
+            } else if block.parent_blockhash() != previous_blockhash {
+                Some(DiscontinuityKind::BlockhashMismatch)
+            } else {
+                None
+            };
+            if let Some(kind) = kind {
+                warn!(
+                    "chain discontinuity ({:?}) at slot {}: parent {} but previous block was {}",
+                    kind,
+                    block.slot(),
+                    block.parent_slot(),
+                    previous_slot
+                );
+                // no subscribers is fine
+                let _ = discontinuities_tx.send(ChainDiscontinuity {
+                    kind,
+                    slot: block.slot(),
+                    parent_slot: block.parent_slot(),
+                    parent_blockhash: block.parent_blockhash().to_string(),
+                    previous_slot: *previous_slot,
+                    previous_blockhash: previous_blockhash.clone(),
+                });
+            }
+        }
+        previous = Some((block.slot(), block.blockhash().to_string()));
+    });
+    (verified, discontinuities_rx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::stream;
+
+    fn block_meta(
+        slot: Slot,
+        parent_slot: Slot,
+        parent_blockhash: &str,
+    ) -> SubscribeUpdateBlockMeta {
+        SubscribeUpdateBlockMeta {
+            slot,
+            blockhash: format!("hash-{}", slot),
+            parent_slot,
+            parent_blockhash: parent_blockhash.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_verify_chain() {
+        let blocks = vec![
+            block_meta(10, 9, "hash-9"),
+            // slot 11 skipped
+            block_meta(12, 10, "hash-10"),
+            // block 13 lost
+            block_meta(14, 13, "hash-13"),
+            block_meta(15, 14, "other-hash"),
+        ];
+
+        let (verified, mut discontinuities) = verify_chain(stream::iter(blocks));
+        let slots: Vec<Slot> = verified.map(|block| block.slot).collect().await;
+        assert_eq!(slots, vec![10, 12, 14, 15]);
+
+        let missing = discontinuities.recv().await.unwrap();
+        assert_eq!(missing.kind, DiscontinuityKind::MissingParent);
+        assert_eq!((missing.slot, missing.previous_slot), (14, 12));
+        let mismatch = discontinuities.recv().await.unwrap();
+        assert_eq!(mismatch.kind, DiscontinuityKind::BlockhashMismatch);
+        assert_eq!(mismatch.slot, 15);
+        assert!(discontinuities.try_recv().is_err());
+    }
+}
@@ -0,0 +1,305 @@
+//! chaos testing against a toxiproxy instance in front of one geyser source
+//!
+//! program toxics (latency, resets, bandwidth caps) while consuming the multiplexed blocks and
+//! check that the chain stays gap-free, see ``run_chaos``
+
+use crate::chain_integrity::{verify_chain, ChainDiscontinuity, ChainLink};
+use anyhow::{bail, Context};
+use futures::{Stream, StreamExt};
+use log::info;
+use std::pin::pin;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::broadcast::error::TryRecvError;
+use tokio::time::{sleep, Instant};
+
+#[derive(Clone, Debug)]
+pub enum Toxic {
+    // delay every packet of the downstream direction
+    Latency { latency_ms: u64, jitter_ms: u64 },
+    // close connections with RST after the timeout
+    ResetPeer { timeout_ms: u64 },
+    // limit downstream bandwidth
+    Bandwidth { rate_kb_per_sec: u64 },
+    // stop all data and close the connection after the timeout (0 = never close)
+    Timeout { timeout_ms: u64 },
+}
+
+impl Toxic {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Toxic::Latency { .. } => "chaos_latency",
+            Toxic::ResetPeer { .. } => "chaos_reset_peer",
+            Toxic::Bandwidth { .. } => "chaos_bandwidth",
+            Toxic::Timeout { .. } => "chaos_timeout",
+        }
+    }
+
+    fn to_json(&self) -> String {
+        let (toxic_type, attributes) = match self {
+            Toxic::Latency {
+                latency_ms,
+                jitter_ms,
+            } => (
+                "latency",
+                format!(r#"{{"latency":{},"jitter":{}}}"#, latency_ms, jitter_ms),
+            ),
+            Toxic::ResetPeer { timeout_ms } => {
+                ("reset_peer", format!(r#"{{"timeout":{}}}"#, timeout_ms))
+            }
+            Toxic::Bandwidth { rate_kb_per_sec } => {
+                ("bandwidth", format!(r#"{{"rate":{}}}"#, rate_kb_per_sec))
+            }
+            Toxic::Timeout { timeout_ms } => {
+                ("timeout", format!(r#"{{"timeout":{}}}"#, timeout_ms))
+            }
+        };
+        format!(
+            r#"{{"name":"{}","type":"{}","stream":"downstream","toxicity":1.0,"attributes":{}}}"#,
+            self.name(),
+            toxic_type,
+            attributes
+        )
+    }
+}
+
+/// minimal client for the toxiproxy http api (default port 8474)
+#[derive(Clone, Debug)]
+pub struct ToxiproxyClient {
+    // host:port
+    api_addr: String,
+}
+
+impl ToxiproxyClient {
+    pub fn new(api_addr: impl Into<String>) -> Self {
+        ToxiproxyClient {
+            api_addr: api_addr.into(),
+        }
+    }
+
+    /// ``listen`` and ``upstream`` are host:port
+    pub async fn create_proxy(
+        &self,
+        name: &str,
+        listen: &str,
+        upstream: &str,
+    ) -> anyhow::Result<()> {
+        let body = format!(
+            r#"{{"name":"{}","listen":"{}","upstream":"{}","enabled":true}}"#,
+            name, listen, upstream
+        );
+        self.request("POST", "/proxies", Some(body)).await
+    }
+
+    pub async fn delete_proxy(&self, name: &str) -> anyhow::Result<()> {
+        self.request("DELETE", &format!("/proxies/{}", name), None)
+            .await
+    }
+
+    /// a disabled proxy closes all connections and refuses new ones
+    pub async fn set_enabled(&self, name: &str, enabled: bool) -> anyhow::Result<()> {
+        let body = format!(r#"{{"enabled":{}}}"#, enabled);
+        self.request("POST", &format!("/proxies/{}", name), Some(body))
+            .await
+    }
+
+    pub async fn add_toxic(&self, proxy: &str, toxic: &Toxic) -> anyhow::Result<()> {
+        self.request(
+            "POST",
+            &format!("/proxies/{}/toxics", proxy),
+            Some(toxic.to_json()),
+        )
+        .await
+    }
+
+    pub async fn remove_toxic(&self, proxy: &str, toxic: &Toxic) -> anyhow::Result<()> {
+        self.request(
+            "DELETE",
+            &format!("/proxies/{}/toxics/{}", proxy, toxic.name()),
+            None,
+        )
+        .await
+    }
+
+    async fn request(&self, method: &str, path: &str, body: Option<String>) -> anyhow::Result<()> {
+        let mut stream = TcpStream::connect(&self.api_addr)
+            .await
+            .with_context(|| format!("connect to toxiproxy api on {}", self.api_addr))?;
+        let body = body.unwrap_or_default();
+        let request = format!(
+            "{} {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            method,
+            path,
+            self.api_addr,
+            body.len(),
+            body
+        );
+        stream.write_all(request.as_bytes()).await?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await?;
+        let status: u16 = response
+            .split_whitespace()
+            .nth(1)
+            .and_then(|code| code.parse().ok())
+            .context("invalid toxiproxy response")?;
+        if !(200..300).contains(&status) {
+            bail!(
+                "toxiproxy {} {} failed with {}: {}",
+                method,
+                path,
+                status,
+                response
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum ChaosStep {
+    // apply the toxic for the duration, then remove it
+    Toxic(Toxic, Duration),
+    // disable the proxy for the duration (connection loss)
+    Outage(Duration),
+    // no chaos, e.g. to let the source recover
+    Pause(Duration),
+}
+
+/// steps with realistic provider failures
+pub fn default_scenario() -> Vec<ChaosStep> {
+    vec![
+        ChaosStep::Pause(Duration::from_secs(10)),
+        ChaosStep::Toxic(
+            Toxic::Latency {
+                latency_ms: 800,
+                jitter_ms: 200,
+            },
+            Duration::from_secs(30),
+        ),
+        ChaosStep::Toxic(
+            Toxic::Bandwidth {
+                rate_kb_per_sec: 64,
+            },
+            Duration::from_secs(30),
+        ),
+        ChaosStep::Toxic(
+            Toxic::ResetPeer { timeout_ms: 2000 },
+            Duration::from_secs(20),
+        ),
+        ChaosStep::Outage(Duration::from_secs(20)),
+        ChaosStep::Toxic(Toxic::Timeout { timeout_ms: 0 }, Duration::from_secs(20)),
+        ChaosStep::Pause(Duration::from_secs(10)),
+    ]
+}
+
+#[derive(Clone, Debug)]
+pub struct ChaosReport {
+    pub blocks: u64,
+    pub discontinuities: Vec<ChainDiscontinuity>,
+    // longest time without a block
+    pub max_block_interval: Duration,
+}
+
+impl ChaosReport {
+    pub fn is_gap_free(&self) -> bool {
+        self.discontinuities.is_empty()
+    }
+}
+
+/// run the scenario against the proxy while consuming the multiplexed blocks; the block stream
+/// should include at least one source which does not go through the proxy
+pub async fn run_chaos<B: ChainLink>(
+    client: &ToxiproxyClient,
+    proxy: &str,
+    scenario: Vec<ChaosStep>,
+    block_stream: impl Stream<Item = B>,
+) -> anyhow::Result<ChaosReport> {
+    let (verified, mut discontinuities_rx) = verify_chain(block_stream);
+    let mut verified = pin!(verified);
+    let mut report = ChaosReport {
+        blocks: 0,
+        discontinuities: vec![],
+        max_block_interval: Duration::ZERO,
+    };
+    let mut last_block_at = Instant::now();
+
+    let consume = async {
+        while verified.next().await.is_some() {
+            report.blocks += 1;
+            report.max_block_interval = report.max_block_interval.max(last_block_at.elapsed());
+            last_block_at = Instant::now();
+            loop {
+                match discontinuities_rx.try_recv() {
+                    Ok(discontinuity) => report.discontinuities.push(discontinuity),
+                    Err(TryRecvError::Lagged(_)) => continue,
+                    Err(TryRecvError::Empty) | Err(TryRecvError::Closed) => break,
+                }
+            }
+        }
+    };
+    let chaos = run_scenario(client, proxy, scenario);
+
+    let chaos_result = match futures::future::select(pin!(chaos), pin!(consume)).await {
+        futures::future::Either::Left((chaos_result, _)) => chaos_result,
+        futures::future::Either::Right(_) => bail!("block stream ended during the chaos run"),
+    };
+    chaos_result?;
+    Ok(report)
+}
+
+async fn run_scenario(
+    client: &ToxiproxyClient,
+    proxy: &str,
+    scenario: Vec<ChaosStep>,
+) -> anyhow::Result<()> {
+    for step in scenario {
+        info!("chaos step {:?}", step);
+        match step {
+            ChaosStep::Toxic(toxic, duration) => {
+                client.add_toxic(proxy, &toxic).await?;
+                sleep(duration).await;
+                client.remove_toxic(proxy, &toxic).await?;
+            }
+            ChaosStep::Outage(duration) => {
+                client.set_enabled(proxy, false).await?;
+                sleep(duration).await;
+                client.set_enabled(proxy, true).await?;
+            }
+            ChaosStep::Pause(duration) => sleep(duration).await,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_toxiproxy_request() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let client = ToxiproxyClient::new(listener.local_addr().unwrap().to_string());
+        let api = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0u8; 4096];
+            let n = stream.read(&mut buf).await.unwrap();
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                .await
+                .unwrap();
+            String::from_utf8_lossy(&buf[..n]).to_string()
+        });
+
+        client
+            .add_toxic("green", &Toxic::ResetPeer { timeout_ms: 100 })
+            .await
+            .unwrap();
+
+        let request = api.await.unwrap();
+        assert!(request.starts_with("POST /proxies/green/toxics HTTP/1.1\r\n"));
+        assert!(request.contains(r#""type":"reset_peer""#));
+        assert!(request.contains(r#""attributes":{"timeout":100}"#));
+    }
+}
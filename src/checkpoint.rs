@@ -0,0 +1,183 @@
+use crate::commitment::CommitmentLevel;
+use crate::grpcmultiplex_fastestwins::FromYellowstoneExtractor;
+use crate::Slot;
+use anyhow::Context;
+use futures::{future, Stream, StreamExt};
+use log::{info, warn};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use yellowstone_grpc_proto::geyser::SubscribeUpdate;
+
+/// last processed slot per commitment level, e.g. to resume an indexer after a restart
+///
+/// note: implementations may block, call them from ``tokio::task::spawn_blocking`` in async code
+pub trait CheckpointStore: Send + Sync {
+    fn load(&self, commitment_level: CommitmentLevel) -> anyhow::Result<Option<Slot>>;
+    fn store(&self, commitment_level: CommitmentLevel, slot: Slot) -> anyhow::Result<()>;
+}
+
+/// checkpoints in a small toml file, written atomically via rename and synced to disk
+/// note: ``store`` and ``load`` do synchronous file io
+pub struct FileCheckpointStore {
+    path: PathBuf,
+    // serializes read-modify-write of the file
+    lock: Mutex<()>,
+}
+
+impl FileCheckpointStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        FileCheckpointStore {
+            path: path.into(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    fn read_all(&self) -> anyhow::Result<BTreeMap<String, Slot>> {
+        if !self.path.exists() {
+            return Ok(BTreeMap::new());
+        }
+        let content = std::fs::read_to_string(&self.path)
+            .with_context(|| format!("read checkpoint file {}", self.path.display()))?;
+        toml::from_str(&content)
+            .with_context(|| format!("parse checkpoint file {}", self.path.display()))
+    }
+}
+
+impl CheckpointStore for FileCheckpointStore {
+    fn load(&self, commitment_level: CommitmentLevel) -> anyhow::Result<Option<Slot>> {
+        let _guard = self.lock.lock().unwrap();
+        Ok(self.read_all()?.get(&key(commitment_level)).copied())
+    }
+
+    fn store(&self, commitment_level: CommitmentLevel, slot: Slot) -> anyhow::Result<()> {
+        let _guard = self.lock.lock().unwrap();
+        let mut checkpoints = self.read_all()?;
+        checkpoints.insert(key(commitment_level), slot);
+        let temp_path = self.path.with_extension("tmp");
+        let mut temp_file = File::create(&temp_path)
+            .with_context(|| format!("create checkpoint file {}", temp_path.display()))?;
+        temp_file
+            .write_all(toml::to_string(&checkpoints)?.as_bytes())
+            .and_then(|_| temp_file.sync_all())
+            .with_context(|| format!("write checkpoint file {}", temp_path.display()))?;
+        std::fs::rename(&temp_path, &self.path)
+            .with_context(|| format!("replace checkpoint file {}", self.path.display()))?;
+        // the rename is durable only once the directory entry is synced
+        sync_parent_dir(&self.path)
+    }
+}
+
+#[cfg(unix)]
+fn sync_parent_dir(path: &Path) -> anyhow::Result<()> {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    File::open(parent)
+        .and_then(|dir| dir.sync_all())
+        .with_context(|| format!("sync checkpoint directory {}", parent.display()))
+}
+
+// directories cannot be opened for syncing on windows
+#[cfg(not(unix))]
+fn sync_parent_dir(_path: &Path) -> anyhow::Result<()> {
+    Ok(())
+}
+
+fn key(commitment_level: CommitmentLevel) -> String {
+    match commitment_level {
+        CommitmentLevel::Confirmed => "confirmed",
+        CommitmentLevel::Finalized => "finalized",
+        CommitmentLevel::ProcessedUnchecked => "processed",
+    }
+    .to_string()
+}
+
+/// wraps an extractor so the multiplexer yields the slot along with the payload
+pub struct SlotTagged<E>(pub E);
+
+impl<E: FromYellowstoneExtractor> FromYellowstoneExtractor for SlotTagged<E> {
+    type Target = (Slot, E::Target);
+    fn map_yellowstone_update(&self, update: SubscribeUpdate) -> Option<(Slot, Self::Target)> {
+        self.0
+            .map_yellowstone_update(update)
+            .map(|(slot, payload)| (slot, (slot, payload)))
+    }
+
+    fn try_map_yellowstone_update(
+        &self,
+        update: SubscribeUpdate,
+    ) -> Result<(Slot, Self::Target), SubscribeUpdate> {
+        self.0
+            .try_map_yellowstone_update(update)
+            .map(|(slot, payload)| (slot, (slot, payload)))
+    }
+}
+
+/// filter which skips slots up to the stored checkpoint, e.g. multiplexed with ``SlotTagged``; the
+/// consumer stores the checkpoint once a slot is fully processed
+///
+/// note: this does not backfill - slots between the checkpoint and the first received slot are
+/// missing (only logged), fetch them via rpc if the consumer must not have gaps
+pub fn skip_to_checkpoint<T>(
+    slot_stream: impl Stream<Item = (Slot, T)>,
+    store: Arc<dyn CheckpointStore>,
+    commitment_level: CommitmentLevel,
+) -> anyhow::Result<impl Stream<Item = (Slot, T)>> {
+    let checkpoint = store.load(commitment_level)?;
+    info!(
+        "skipping {} slots up to checkpoint slot {:?}",
+        commitment_level, checkpoint
+    );
+    let mut gap_checked = false;
+    Ok(slot_stream.filter(move |(slot, _)| {
+        let Some(checkpoint) = checkpoint else {
+            return future::ready(true);
+        };
+        if *slot <= checkpoint {
+            return future::ready(false);
+        }
+        if !gap_checked {
+            gap_checked = true;
+            if *slot > checkpoint + 1 {
+                warn!(
+                    "slots {}..{} after checkpoint not available",
+                    checkpoint + 1,
+                    slot
+                );
+            }
+        }
+        future::ready(true)
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::stream;
+
+    #[tokio::test]
+    async fn test_skip_to_file_checkpoint() {
+        let path = std::env::temp_dir().join(format!("checkpoint-{}.toml", std::process::id()));
+        let store = Arc::new(FileCheckpointStore::new(&path));
+        assert_eq!(store.load(CommitmentLevel::Confirmed).unwrap(), None);
+        store.store(CommitmentLevel::Confirmed, 12).unwrap();
+        store.store(CommitmentLevel::Finalized, 3).unwrap();
+
+        let reopened = Arc::new(FileCheckpointStore::new(&path));
+        assert_eq!(reopened.load(CommitmentLevel::Confirmed).unwrap(), Some(12));
+
+        let slots = stream::iter((10..=14).map(|slot| (slot, ())));
+        let resumed: Vec<Slot> = skip_to_checkpoint(slots, reopened, CommitmentLevel::Confirmed)
+            .unwrap()
+            .map(|(slot, _)| slot)
+            .collect()
+            .await;
+        assert_eq!(resumed, vec![13, 14]);
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
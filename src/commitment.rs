@@ -0,0 +1,150 @@
+use anyhow::bail;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "solana-sdk")]
+pub use solana_sdk::commitment_config::CommitmentConfig;
+use std::fmt::{Display, Formatter};
+#[cfg(not(feature = "solana-sdk"))]
+use yellowstone_grpc_proto::geyser::CommitmentLevel as GeyserCommitmentLevel;
+
+/// stand-in for solana_sdk's CommitmentConfig if built without the solana-sdk feature;
+/// same constructors, but ``commitment`` is the yellowstone proto level
+#[cfg(not(feature = "solana-sdk"))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CommitmentConfig {
+    pub commitment: GeyserCommitmentLevel,
+}
+
+#[cfg(not(feature = "solana-sdk"))]
+impl CommitmentConfig {
+    pub fn processed() -> Self {
+        CommitmentConfig {
+            commitment: GeyserCommitmentLevel::Processed,
+        }
+    }
+
+    pub fn confirmed() -> Self {
+        CommitmentConfig {
+            commitment: GeyserCommitmentLevel::Confirmed,
+        }
+    }
+
+    pub fn finalized() -> Self {
+        CommitmentConfig {
+            commitment: GeyserCommitmentLevel::Finalized,
+        }
+    }
+}
+
+// finalized like in solana_sdk
+#[cfg(not(feature = "solana-sdk"))]
+impl Default for CommitmentConfig {
+    fn default() -> Self {
+        CommitmentConfig::finalized()
+    }
+}
+
+/// commitment levels the multiplexer can work with
+///
+/// processed slots form forks, not a sequence - fastest-wins dedup by slot will then drop or mix
+/// blocks from different forks; ``ProcessedUnchecked`` is an explicit opt-in for that
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CommitmentLevel {
+    Confirmed,
+    Finalized,
+    ProcessedUnchecked,
+}
+
+impl CommitmentLevel {
+    pub fn commitment_config(&self) -> CommitmentConfig {
+        match self {
+            CommitmentLevel::Confirmed => CommitmentConfig::confirmed(),
+            CommitmentLevel::Finalized => CommitmentConfig::finalized(),
+            CommitmentLevel::ProcessedUnchecked => CommitmentConfig::processed(),
+        }
+    }
+}
+
+impl From<CommitmentLevel> for CommitmentConfig {
+    fn from(commitment_level: CommitmentLevel) -> Self {
+        commitment_level.commitment_config()
+    }
+}
+
+impl TryFrom<CommitmentConfig> for CommitmentLevel {
+    type Error = anyhow::Error;
+
+    // deprecated levels map to their replacement like in ``map_commitment_level``
+    #[cfg(feature = "solana-sdk")]
+    #[allow(deprecated)]
+    fn try_from(commitment_config: CommitmentConfig) -> anyhow::Result<Self> {
+        use solana_sdk::commitment_config::CommitmentLevel as SolanaCommitmentLevel;
+        match commitment_config.commitment {
+            SolanaCommitmentLevel::Confirmed
+            | SolanaCommitmentLevel::Single
+            | SolanaCommitmentLevel::SingleGossip => Ok(CommitmentLevel::Confirmed),
+            SolanaCommitmentLevel::Finalized
+            | SolanaCommitmentLevel::Max
+            | SolanaCommitmentLevel::Root => Ok(CommitmentLevel::Finalized),
+            SolanaCommitmentLevel::Processed | SolanaCommitmentLevel::Recent => {
+                bail!("processed is not supported by the multiplexer - use ProcessedUnchecked to opt in")
+            }
+        }
+    }
+
+    #[cfg(not(feature = "solana-sdk"))]
+    fn try_from(commitment_config: CommitmentConfig) -> anyhow::Result<Self> {
+        match commitment_config.commitment {
+            GeyserCommitmentLevel::Confirmed => Ok(CommitmentLevel::Confirmed),
+            GeyserCommitmentLevel::Finalized => Ok(CommitmentLevel::Finalized),
+            GeyserCommitmentLevel::Processed => {
+                bail!("processed is not supported by the multiplexer - use ProcessedUnchecked to opt in")
+            }
+        }
+    }
+}
+
+impl Display for CommitmentLevel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CommitmentLevel::Confirmed => write!(f, "confirmed"),
+            CommitmentLevel::Finalized => write!(f, "finalized"),
+            CommitmentLevel::ProcessedUnchecked => write!(f, "processed (unchecked)"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_commitment_config() {
+        assert_eq!(
+            CommitmentLevel::try_from(CommitmentConfig::finalized()).unwrap(),
+            CommitmentLevel::Finalized
+        );
+        assert!(CommitmentLevel::try_from(CommitmentConfig::processed()).is_err());
+        assert_eq!(
+            CommitmentConfig::from(CommitmentLevel::ProcessedUnchecked),
+            CommitmentConfig::processed()
+        );
+    }
+
+    #[cfg(feature = "solana-sdk")]
+    #[test]
+    #[allow(deprecated)]
+    fn test_from_deprecated_commitment_config() {
+        use solana_sdk::commitment_config::CommitmentLevel as SolanaCommitmentLevel;
+        let config = |commitment| CommitmentConfig { commitment };
+        assert_eq!(
+            CommitmentLevel::try_from(config(SolanaCommitmentLevel::Max)).unwrap(),
+            CommitmentLevel::Finalized
+        );
+        assert_eq!(
+            CommitmentLevel::try_from(config(SolanaCommitmentLevel::SingleGossip)).unwrap(),
+            CommitmentLevel::Confirmed
+        );
+        assert!(CommitmentLevel::try_from(config(SolanaCommitmentLevel::Recent)).is_err());
+    }
+}
@@ -0,0 +1,115 @@
+use crate::commitment::CommitmentLevel;
+use crate::grpc_subscription_autoreconnect_streams::create_geyser_reconnecting_stream;
+use crate::grpcmultiplex_fastestwins::{FromGeyserUpdate, GeyserUpdateExtractor};
+use crate::{GeyserFilter, GeyserFilterKind, GrpcSourceConfig, Message, Slot};
+use async_stream::stream;
+use futures::{Stream, StreamExt};
+use merge_streams::MergeStreams;
+use std::collections::{BTreeMap, BTreeSet};
+use std::pin::pin;
+use yellowstone_grpc_proto::geyser::subscribe_update::UpdateOneof;
+use yellowstone_grpc_proto::geyser::{SubscribeUpdate, SubscribeUpdateEntry};
+
+// entries of slots that far below the tip are dropped as late
+const ENTRY_SLOT_WINDOW: u64 = 32;
+
+pub struct EntryExtractor;
+
+impl FromGeyserUpdate<SubscribeUpdateEntry> for EntryExtractor {
+    fn from_geyser_update(&self, update: SubscribeUpdate) -> Option<(Slot, SubscribeUpdateEntry)> {
+        match update.update_oneof {
+            Some(UpdateOneof::Entry(entry)) => Some((entry.slot, entry)),
+            _ => None,
+        }
+    }
+
+    fn filter_kinds(&self) -> Vec<GeyserFilterKind> {
+        vec![GeyserFilterKind::Entries]
+    }
+}
+
+/// fastest-wins multiplexing per entry: each ``(slot, index)`` is yielded once, from the first source
+///
+/// unlike blocks there are many entries per slot, so the slot tip alone cannot be used for dedup
+pub fn create_entries_multiplex(
+    grpc_sources: Vec<GrpcSourceConfig>,
+    commitment_level: CommitmentLevel,
+) -> impl Stream<Item = SubscribeUpdateEntry> {
+    let extractor = GeyserUpdateExtractor::new(EntryExtractor);
+    let streams: Vec<_> = grpc_sources
+        .into_iter()
+        .map(|grpc_source| {
+            create_geyser_reconnecting_stream(
+                grpc_source,
+                extractor.subscribe_request(&GeyserFilter(commitment_level.into())),
+            )
+        })
+        .collect();
+    dedup_entries(streams.merge())
+}
+
+pub fn dedup_entries(
+    geyser_stream: impl Stream<Item = Message>,
+) -> impl Stream<Item = SubscribeUpdateEntry> {
+    stream! {
+        let mut geyser_stream = pin!(geyser_stream);
+        let mut seen: BTreeMap<Slot, BTreeSet<u64>> = BTreeMap::new();
+        let mut tip: Slot = 0;
+        while let Some(message) = geyser_stream.next().await {
+            let Message::GeyserSubscribeUpdate(update) = message else {
+                continue;
+            };
+            let Some((slot, entry)) = EntryExtractor.from_geyser_update(*update) else {
+                continue;
+            };
+            if slot + ENTRY_SLOT_WINDOW < tip {
+                continue;
+            }
+            if slot > tip {
+                tip = slot;
+                seen = seen.split_off(&tip.saturating_sub(ENTRY_SLOT_WINDOW));
+            }
+            if seen.entry(slot).or_default().insert(entry.index) {
+                yield entry;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::stream;
+
+    fn entry(slot: Slot, index: u64) -> Message {
+        Message::GeyserSubscribeUpdate(Box::new(SubscribeUpdate {
+            filters: vec!["client".to_string()],
+            update_oneof: Some(UpdateOneof::Entry(SubscribeUpdateEntry {
+                slot,
+                index,
+                ..Default::default()
+            })),
+        }))
+    }
+
+    #[tokio::test]
+    async fn test_dedup_entries() {
+        let source = stream::iter(vec![
+            entry(100, 0),
+            entry(100, 1),
+            // slower source
+            entry(100, 0),
+            entry(101, 0),
+            entry(100, 2),
+            // too late
+            entry(100 - ENTRY_SLOT_WINDOW, 0),
+        ]);
+
+        let entries: Vec<(Slot, u64)> = dedup_entries(source)
+            .map(|entry| (entry.slot, entry.index))
+            .collect()
+            .await;
+
+        assert_eq!(entries, vec![(100, 0), (100, 1), (101, 0), (100, 2)]);
+    }
+}
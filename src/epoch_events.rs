@@ -0,0 +1,75 @@
+use async_stream::stream;
+use futures::Stream;
+use solana_sdk::clock::{Epoch, Slot};
+use solana_sdk::epoch_schedule::EpochSchedule;
+
+/// the slot progression entered a new epoch
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EpochChange {
+    pub epoch: Epoch,
+    pub first_slot: Slot,
+    // slot which revealed the change; differs from first_slot if the first slots were skipped
+    pub observed_slot: Slot,
+}
+
+/// derive epoch rollovers from increasing slots, e.g. from ``tip_slot::SlotExtractor`` or blocks
+///
+/// mainnet uses ``EpochSchedule::without_warmup()``; the epoch of the first slot is not reported
+pub fn epoch_changes(
+    slots: impl Stream<Item = Slot>,
+    epoch_schedule: EpochSchedule,
+) -> impl Stream<Item = EpochChange> {
+    stream! {
+        let mut current_epoch: Option<Epoch> = None;
+        for await slot in slots {
+            let epoch = epoch_schedule.get_epoch(slot);
+            match current_epoch {
+                Some(current) if epoch > current => {
+                    current_epoch = Some(epoch);
+                    yield EpochChange {
+                        epoch,
+                        first_slot: epoch_schedule.get_first_slot_in_epoch(epoch),
+                        observed_slot: slot,
+                    };
+                }
+                Some(_) => {}
+                None => current_epoch = Some(epoch),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{stream, StreamExt};
+
+    #[tokio::test]
+    async fn test_epoch_changes() {
+        let epoch_schedule = EpochSchedule::custom(32, 32, false);
+        let slots = stream::iter(vec![30, 31, 33, 34, 63, 70, 100]);
+
+        let changes: Vec<EpochChange> = epoch_changes(slots, epoch_schedule).collect().await;
+
+        assert_eq!(
+            changes,
+            vec![
+                EpochChange {
+                    epoch: 1,
+                    first_slot: 32,
+                    observed_slot: 33
+                },
+                EpochChange {
+                    epoch: 2,
+                    first_slot: 64,
+                    observed_slot: 70
+                },
+                EpochChange {
+                    epoch: 3,
+                    first_slot: 96,
+                    observed_slot: 100
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,243 @@
+use crate::commitment::CommitmentConfig;
+use crate::grpc_subscription_autoreconnect_streams::create_geyser_reconnecting_stream;
+use crate::grpcmultiplex_fastestwins::{create_multiplexed_stream, FromYellowstoneExtractor};
+use crate::{GeyserFilter, GrpcSourceConfig, Slot};
+use async_stream::stream;
+use futures::{Stream, StreamExt};
+use log::warn;
+use std::collections::BTreeMap;
+use yellowstone_grpc_proto::geyser::subscribe_update::UpdateOneof;
+use yellowstone_grpc_proto::geyser::SubscribeUpdate;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlockRef {
+    pub slot: Slot,
+    pub blockhash: String,
+    pub parent_slot: Slot,
+}
+
+/// extracts slot and blockhash from blocks meta updates
+pub struct BlockRefExtractor;
+
+impl FromYellowstoneExtractor for BlockRefExtractor {
+    type Target = BlockRef;
+    fn map_yellowstone_update(&self, update: SubscribeUpdate) -> Option<(Slot, Self::Target)> {
+        match update.update_oneof {
+            Some(UpdateOneof::BlockMeta(block_meta)) => Some((
+                block_meta.slot,
+                BlockRef {
+                    slot: block_meta.slot,
+                    blockhash: block_meta.blockhash,
+                    parent_slot: block_meta.parent_slot,
+                },
+            )),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FinalizationEvent {
+    Finalized {
+        slot: Slot,
+        blockhash: String,
+    },
+    // confirmed block was skipped by the finalized chain: it lies between a finalized block and its parent
+    NeverFinalized {
+        slot: Slot,
+        blockhash: String,
+    },
+    // finalized blocks before a higher finalized slot were missed (e.g. during a reconnect), so the
+    // chain does not tell whether the confirmed block got finalized
+    Unknown {
+        slot: Slot,
+        blockhash: String,
+    },
+    // slot got finalized with another block than the one confirmed (fork)
+    BlockhashMismatch {
+        slot: Slot,
+        confirmed_blockhash: String,
+        finalized_blockhash: String,
+    },
+}
+
+/// confirmed blocks waiting for finalization
+#[derive(Default)]
+pub struct FinalizationTracker {
+    pending: BTreeMap<Slot, String>,
+    last_finalized: Option<Slot>,
+}
+
+impl FinalizationTracker {
+    pub fn on_confirmed(&mut self, block: BlockRef) {
+        if self
+            .last_finalized
+            .map_or(false, |finalized| block.slot <= finalized)
+        {
+            // confirmed stream is behind the finalized stream
+            return;
+        }
+        self.pending.insert(block.slot, block.blockhash);
+    }
+
+    pub fn on_finalized(&mut self, block: BlockRef) -> Vec<FinalizationEvent> {
+        let mut events = vec![];
+        // pending is sorted: everything up to the finalized slot is decided now
+        let still_pending = self.pending.split_off(&(block.slot + 1));
+        let decided = std::mem::replace(&mut self.pending, still_pending);
+        for (slot, blockhash) in decided {
+            if slot != block.slot {
+                if slot > block.parent_slot {
+                    events.push(FinalizationEvent::NeverFinalized { slot, blockhash });
+                } else {
+                    events.push(FinalizationEvent::Unknown { slot, blockhash });
+                }
+            } else if blockhash != block.blockhash {
+                events.push(FinalizationEvent::BlockhashMismatch {
+                    slot,
+                    confirmed_blockhash: blockhash,
+                    finalized_blockhash: block.blockhash.clone(),
+                });
+            }
+        }
+        self.last_finalized = Some(block.slot);
+        events.push(FinalizationEvent::Finalized {
+            slot: block.slot,
+            blockhash: block.blockhash,
+        });
+        events
+    }
+}
+
+enum Commitment {
+    Confirmed(BlockRef),
+    Finalized(BlockRef),
+}
+
+/// combine the confirmed and the finalized progression into finalization events
+pub fn track_finalization(
+    confirmed: impl Stream<Item = BlockRef>,
+    finalized: impl Stream<Item = BlockRef>,
+) -> impl Stream<Item = FinalizationEvent> {
+    let merged = futures::stream::select(
+        confirmed.map(Commitment::Confirmed),
+        finalized.map(Commitment::Finalized),
+    );
+    stream! {
+        let mut tracker = FinalizationTracker::default();
+        for await update in merged {
+            match update {
+                Commitment::Confirmed(block) => tracker.on_confirmed(block),
+                Commitment::Finalized(block) => {
+                    for event in tracker.on_finalized(block) {
+                        if let FinalizationEvent::BlockhashMismatch { slot, .. } = &event {
+                            warn!("finalized block for slot {} differs from confirmed block", slot);
+                        }
+                        yield event;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// runs a confirmed and a finalized blocks meta multiplexer over the same sources
+pub fn create_finalization_tracker(
+    grpc_sources: Vec<GrpcSourceConfig>,
+) -> impl Stream<Item = FinalizationEvent> {
+    let multiplex = |commitment: CommitmentConfig| {
+        let streams = grpc_sources
+            .iter()
+            .map(|grpc_source| {
+                create_geyser_reconnecting_stream(
+                    grpc_source.clone(),
+                    GeyserFilter(commitment).blocks_meta(),
+                )
+            })
+            .collect();
+        create_multiplexed_stream(streams, BlockRefExtractor)
+    };
+    track_finalization(
+        multiplex(CommitmentConfig::confirmed()),
+        multiplex(CommitmentConfig::finalized()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(slot: Slot, parent_slot: Slot, blockhash: &str) -> BlockRef {
+        BlockRef {
+            slot,
+            blockhash: blockhash.to_string(),
+            parent_slot,
+        }
+    }
+
+    #[test]
+    fn test_finalization_tracker() {
+        let mut tracker = FinalizationTracker::default();
+        tracker.on_confirmed(block(1, 0, "a"));
+        tracker.on_confirmed(block(2, 1, "b"));
+        tracker.on_confirmed(block(3, 2, "c"));
+        tracker.on_confirmed(block(4, 3, "d"));
+
+        assert_eq!(
+            tracker.on_finalized(block(1, 0, "a")),
+            vec![FinalizationEvent::Finalized {
+                slot: 1,
+                blockhash: "a".to_string()
+            }]
+        );
+        assert_eq!(
+            tracker.on_finalized(block(3, 1, "x")),
+            vec![
+                FinalizationEvent::NeverFinalized {
+                    slot: 2,
+                    blockhash: "b".to_string()
+                },
+                FinalizationEvent::BlockhashMismatch {
+                    slot: 3,
+                    confirmed_blockhash: "c".to_string(),
+                    finalized_blockhash: "x".to_string()
+                },
+                FinalizationEvent::Finalized {
+                    slot: 3,
+                    blockhash: "x".to_string()
+                },
+            ]
+        );
+
+        // late confirmed update of an already finalized slot is ignored
+        tracker.on_confirmed(block(3, 2, "c"));
+        assert_eq!(tracker.pending.len(), 1);
+    }
+
+    #[test]
+    fn test_missed_finalized_parent_is_unknown() {
+        let mut tracker = FinalizationTracker::default();
+        tracker.on_confirmed(block(1, 0, "a"));
+        tracker.on_confirmed(block(2, 1, "b"));
+        tracker.on_confirmed(block(3, 2, "c"));
+
+        // finalized 1 and 2 were missed; 2 is the parent, so it is not skipped
+        assert_eq!(
+            tracker.on_finalized(block(3, 2, "c")),
+            vec![
+                FinalizationEvent::Unknown {
+                    slot: 1,
+                    blockhash: "a".to_string()
+                },
+                FinalizationEvent::Unknown {
+                    slot: 2,
+                    blockhash: "b".to_string()
+                },
+                FinalizationEvent::Finalized {
+                    slot: 3,
+                    blockhash: "c".to_string()
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,1119 @@
+use crate::{Message, RateLimit, Slot};
+use futures::{future, Sink, SinkExt, Stream, StreamExt};
+use log::{debug, trace, warn};
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Debug;
+use std::future::Future;
+use std::hash::Hash;
+use std::pin::{pin, Pin};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::{broadcast, mpsc, watch};
+use tokio::task::{JoinError, JoinHandle};
+use tokio::time::{sleep, Duration, Instant};
+use yellowstone_grpc_proto::geyser::SubscribeUpdate;
+use yellowstone_grpc_proto::prost::Message as _;
+
+pub(crate) const CHANNELIZER_CAPACITY: usize = 1000;
+
+/// aborts the task when dropped; awaiting it awaits the task
+pub(crate) struct AbortOnDrop<T>(pub(crate) JoinHandle<T>);
+
+impl<T> Future for AbortOnDrop<T> {
+    type Output = Result<T, JoinError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.0).poll(cx)
+    }
+}
+
+impl<T> Drop for AbortOnDrop<T> {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// why a channelizer task finished
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChannelizerCompletion {
+    // the source stream returned None
+    SourceStreamEnded,
+    // supervised channelizer: source ended again after the last allowed restart
+    RestartLimitReached { restarts: u32 },
+}
+
+/// how ``channelize_stream_supervised`` restarts the pipeline
+#[derive(Clone, Debug)]
+pub struct RestartPolicy {
+    // None means restart forever
+    pub max_restarts: Option<u32>,
+    pub backoff: Duration,
+}
+
+/// spawn a task that forwards the stream (e.g. multiplexed blocks) into a broadcast channel
+/// note: every receiver clones each message - see ``channelize_stream_arc`` for large payloads
+/// note: a panic ends the channelizer, use ``TaskSupervisor::channelize_stream`` to restart it
+pub fn channelize_stream<T>(
+    source_stream: impl Stream<Item = T> + Send + 'static,
+) -> (broadcast::Receiver<T>, JoinHandle<ChannelizerCompletion>)
+where
+    T: Clone + Send + 'static,
+{
+    channelize_stream_with_capacity(source_stream, CHANNELIZER_CAPACITY)
+}
+
+/// like ``channelize_stream`` with ``capacity`` messages retained for lagging receivers; lower it
+/// for full blocks, 1000 mainnet blocks can take gigabytes
+pub fn channelize_stream_with_capacity<T>(
+    source_stream: impl Stream<Item = T> + Send + 'static,
+    capacity: usize,
+) -> (broadcast::Receiver<T>, JoinHandle<ChannelizerCompletion>)
+where
+    T: Clone + Send + 'static,
+{
+    let (tx, rx) = broadcast::channel::<T>(capacity);
+
+    let jh_channelizer = tokio::spawn(async move {
+        forward_to_broadcast(source_stream, &tx).await;
+        warn!("source stream ended - shutting down channelizer");
+        ChannelizerCompletion::SourceStreamEnded
+    });
+
+    (rx, jh_channelizer)
+}
+
+/// like ``channelize_stream`` but runs a cheap synchronous filter/transform before the broadcast,
+/// so messages every receiver would discard are not fanned out
+/// e.g. ``|block| contains_program(&block, program_id).then_some(block)``
+pub fn channelize_stream_filtered<T, U, F>(
+    source_stream: impl Stream<Item = T> + Send + 'static,
+    mut filter_map: F,
+) -> (broadcast::Receiver<U>, JoinHandle<ChannelizerCompletion>)
+where
+    U: Clone + Send + 'static,
+    F: FnMut(T) -> Option<U> + Send + 'static,
+{
+    channelize_stream(source_stream.filter_map(move |payload| future::ready(filter_map(payload))))
+}
+
+/// like ``channelize_stream`` but when the source stream ends, a new one is created by the
+/// factory (e.g. rebuilding the multiplexer) while receivers stay attached
+pub fn channelize_stream_supervised<T, S, F>(
+    stream_factory: F,
+    restart_policy: RestartPolicy,
+) -> (broadcast::Receiver<T>, JoinHandle<ChannelizerCompletion>)
+where
+    T: Clone + Send + 'static,
+    S: Stream<Item = T> + Send + 'static,
+    F: FnMut() -> S + Send + 'static,
+{
+    channelize_stream_supervised_with_capacity(stream_factory, restart_policy, CHANNELIZER_CAPACITY)
+}
+
+/// like ``channelize_stream_supervised`` with the broadcast capacity, see
+/// ``channelize_stream_with_capacity``
+pub fn channelize_stream_supervised_with_capacity<T, S, F>(
+    mut stream_factory: F,
+    restart_policy: RestartPolicy,
+    capacity: usize,
+) -> (broadcast::Receiver<T>, JoinHandle<ChannelizerCompletion>)
+where
+    T: Clone + Send + 'static,
+    S: Stream<Item = T> + Send + 'static,
+    F: FnMut() -> S + Send + 'static,
+{
+    let (tx, rx) = broadcast::channel::<T>(capacity);
+
+    let jh_channelizer = tokio::spawn(async move {
+        let mut restarts = 0;
+        loop {
+            forward_to_broadcast(stream_factory(), &tx).await;
+            if restart_policy
+                .max_restarts
+                .map_or(false, |max_restarts| restarts >= max_restarts)
+            {
+                warn!(
+                    "source stream ended after {} restarts - shutting down channelizer",
+                    restarts
+                );
+                return ChannelizerCompletion::RestartLimitReached { restarts };
+            }
+            restarts += 1;
+            warn!(
+                "source stream ended - restart #{} in {:?}",
+                restarts, restart_policy.backoff
+            );
+            sleep(restart_policy.backoff).await;
+        }
+    });
+
+    (rx, jh_channelizer)
+}
+
+pub(crate) async fn forward_to_broadcast<T>(
+    source_stream: impl Stream<Item = T>,
+    tx: &broadcast::Sender<T>,
+) {
+    let mut source_stream = pin!(source_stream);
+    while let Some(payload) = source_stream.next().await {
+        match tx.send(payload) {
+            Ok(n_receivers) => {
+                trace!("channelized message to {} receivers", n_receivers);
+            }
+            Err(_dropped_msg) => {
+                // decide to continue if no receivers
+                debug!("no receivers - dropping payload and continue");
+            }
+        }
+    }
+}
+
+/// like ``channelize_stream`` but wraps each message in an ``Arc`` so fan-out to many receivers
+/// does not clone the payload (e.g. multi-MB blocks from the multiplexer)
+pub fn channelize_stream_arc<T>(
+    source_stream: impl Stream<Item = T> + Send + 'static,
+) -> (
+    broadcast::Receiver<Arc<T>>,
+    JoinHandle<ChannelizerCompletion>,
+)
+where
+    T: Send + Sync + 'static,
+{
+    channelize_stream(source_stream.map(Arc::new))
+}
+
+/// spawn a task keeping only the newest item, e.g. the latest block for status pages or UIs;
+/// readers never lag behind, intermediate items are skipped; None until the first item
+/// the task stops when the stream ends or all receivers are dropped
+pub fn latest_only<T>(
+    source_stream: impl Stream<Item = T> + Send + 'static,
+) -> (watch::Receiver<Option<T>>, JoinHandle<()>)
+where
+    T: Send + Sync + 'static,
+{
+    let (tx, rx) = watch::channel(None);
+    let jh_latest = tokio::spawn(async move {
+        let mut source_stream = pin!(source_stream);
+        while let Some(payload) = source_stream.next().await {
+            if tx.send(Some(payload)).is_err() {
+                debug!("no receivers - stop tracking latest");
+                return;
+            }
+        }
+        debug!("source stream ended - latest value stays available");
+    });
+    (rx, jh_latest)
+}
+
+/// like ``latest_only`` for a receiver of ``channelize_stream``; the task drains the broadcast
+/// receiver immediately, so it never lags
+pub fn latest_only_from_broadcast<T>(
+    mut receiver: broadcast::Receiver<T>,
+) -> (watch::Receiver<Option<T>>, JoinHandle<()>)
+where
+    T: Clone + Send + Sync + 'static,
+{
+    let (tx, rx) = watch::channel(None);
+    let jh_latest = tokio::spawn(async move {
+        loop {
+            match receiver.recv().await {
+                Ok(payload) => {
+                    if tx.send(Some(payload)).is_err() {
+                        debug!("no receivers - stop tracking latest");
+                        return;
+                    }
+                }
+                // only the newest matters
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return,
+            }
+        }
+    });
+    (rx, jh_latest)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReadinessError {
+    Timeout,
+    // stream was dropped before the first item
+    StreamDropped,
+}
+
+/// readiness of a stream wrapped by ``with_readiness``, e.g. to gate service readiness probes
+#[derive(Clone)]
+pub struct Readiness {
+    ready: watch::Receiver<bool>,
+}
+
+impl Readiness {
+    pub fn is_ready(&self) -> bool {
+        *self.ready.borrow()
+    }
+
+    /// resolves once the first item (e.g. deduplicated block) passed the stream
+    pub async fn ready(&self, timeout: Duration) -> Result<(), ReadinessError> {
+        let mut ready = self.ready.clone();
+        let waited = tokio::time::timeout(timeout, ready.wait_for(|ready| *ready))
+            .await
+            .map(|ready| ready.map(|_| ()));
+        match waited {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(_sender_dropped)) => Err(ReadinessError::StreamDropped),
+            Err(_elapsed) => Err(ReadinessError::Timeout),
+        }
+    }
+}
+
+/// wrap e.g. the multiplexed stream to observe when the pipeline starts flowing
+pub fn with_readiness<S: Stream>(source_stream: S) -> (impl Stream<Item = S::Item>, Readiness) {
+    let (tx, rx) = watch::channel(false);
+    let observed = source_stream.inspect(move |_| {
+        tx.send_if_modified(|ready| !std::mem::replace(ready, true));
+    });
+    (observed, Readiness { ready: rx })
+}
+
+/// what to do when a receiver does not keep up with the source stream
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LagPolicy {
+    // slow receivers miss the oldest messages (tokio broadcast semantics)
+    DropOldest,
+    // wait for the slowest receiver; backpressure propagates to the source stream
+    Block,
+    // close the channel of a receiver as soon as its buffer is full
+    DisconnectSlowConsumer,
+}
+
+/// what ``channelize_stream_with_replay`` retains for receivers attaching later, e.g. a consumer
+/// restarting; replayed items are delivered before live data
+pub enum Replay<T> {
+    None,
+    LastItems(usize),
+    // items of the last ``slots`` slots, relative to the newest item
+    LastSlots { slots: u64, slot_of: fn(&T) -> Slot },
+}
+
+impl<T> Replay<T> {
+    fn trim(&self, buffer: &mut VecDeque<T>) {
+        match self {
+            Replay::None => buffer.clear(),
+            Replay::LastItems(max_items) => {
+                while buffer.len() > *max_items {
+                    buffer.pop_front();
+                }
+            }
+            Replay::LastSlots { slots, slot_of } => {
+                let Some(newest_slot) = buffer.back().map(slot_of) else {
+                    return;
+                };
+                while buffer
+                    .front()
+                    .map_or(false, |oldest| slot_of(oldest) + slots <= newest_slot)
+                {
+                    buffer.pop_front();
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ReceiverStats {
+    pub receiver_id: u64,
+    pub dropped: u64,
+    pub disconnected: bool,
+}
+
+#[derive(Default)]
+struct ReceiverCounters {
+    dropped: AtomicU64,
+    disconnected: AtomicBool,
+}
+
+struct Subscriber<T> {
+    id: u64,
+    sender: mpsc::Sender<T>,
+    counters: Arc<ReceiverCounters>,
+}
+
+impl<T> Clone for Subscriber<T> {
+    fn clone(&self) -> Self {
+        Subscriber {
+            id: self.id,
+            sender: self.sender.clone(),
+            counters: self.counters.clone(),
+        }
+    }
+}
+
+struct ChannelizerShared<T> {
+    policy: LagPolicy,
+    capacity: usize,
+    next_id: AtomicU64,
+    // set to None once the source stream ended
+    broadcast: Mutex<Option<broadcast::Sender<T>>>,
+    subscribers: Mutex<Option<Vec<Subscriber<T>>>>,
+    // all live receivers, for stats
+    receivers: Mutex<Vec<(u64, Arc<ReceiverCounters>)>>,
+    replay: Replay<T>,
+    // also serializes forward against subscribe so a new receiver sees no gap or duplicate
+    replay_buffer: Mutex<VecDeque<T>>,
+}
+
+impl<T: Clone> ChannelizerShared<T> {
+    fn snapshot_subscribers(&self) -> Vec<Subscriber<T>> {
+        self.subscribers
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|subscribers| subscribers.to_vec())
+            .unwrap_or_default()
+    }
+
+    fn remove_subscriber(&self, receiver_id: u64) {
+        if let Some(subscribers) = self.subscribers.lock().unwrap().as_mut() {
+            subscribers.retain(|subscriber| subscriber.id != receiver_id);
+        }
+    }
+
+    async fn forward(&self, payload: T) {
+        let subscribers = {
+            let mut replay_buffer = self.replay_buffer.lock().unwrap();
+            if !matches!(self.replay, Replay::None) {
+                replay_buffer.push_back(payload.clone());
+                self.replay.trim(&mut replay_buffer);
+            }
+            match self.policy {
+                LagPolicy::DropOldest => {
+                    if let Some(sender) = self.broadcast.lock().unwrap().as_ref() {
+                        // lagging receivers account their losses on recv
+                        let _ = sender.send(payload);
+                    }
+                    return;
+                }
+                LagPolicy::Block | LagPolicy::DisconnectSlowConsumer => self.snapshot_subscribers(),
+            }
+        };
+        match self.policy {
+            LagPolicy::DropOldest => {}
+            LagPolicy::Block => {
+                for subscriber in subscribers {
+                    // T need not be Sync: no borrow of payload across the await
+                    let item = payload.clone();
+                    if subscriber.sender.send(item).await.is_err() {
+                        self.remove_subscriber(subscriber.id);
+                    }
+                }
+            }
+            LagPolicy::DisconnectSlowConsumer => {
+                for subscriber in subscribers {
+                    match subscriber.sender.try_send(payload.clone()) {
+                        Ok(()) => {}
+                        Err(TrySendError::Full(_)) => {
+                            warn!("receiver {} too slow - disconnecting", subscriber.id);
+                            subscriber.counters.dropped.fetch_add(1, Ordering::Relaxed);
+                            subscriber
+                                .counters
+                                .disconnected
+                                .store(true, Ordering::Relaxed);
+                            self.remove_subscriber(subscriber.id);
+                        }
+                        Err(TrySendError::Closed(_)) => {
+                            self.remove_subscriber(subscriber.id);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn close(&self) {
+        self.broadcast.lock().unwrap().take();
+        self.subscribers.lock().unwrap().take();
+    }
+}
+
+/// fan-out handle returned by ``channelize_stream_with_policy``; cheap to clone
+pub struct Channelizer<T> {
+    shared: Arc<ChannelizerShared<T>>,
+}
+
+impl<T> Clone for Channelizer<T> {
+    fn clone(&self) -> Self {
+        Channelizer {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T: Clone> Channelizer<T> {
+    pub fn subscribe(&self) -> ChannelReceiver<T> {
+        let id = self.shared.next_id.fetch_add(1, Ordering::Relaxed);
+        let counters = Arc::new(ReceiverCounters::default());
+        self.shared
+            .receivers
+            .lock()
+            .unwrap()
+            .push((id, counters.clone()));
+
+        let replay_buffer = self.shared.replay_buffer.lock().unwrap();
+        let replayed = replay_buffer.clone();
+        let inner = match self.shared.policy {
+            LagPolicy::DropOldest => {
+                let receiver = match self.shared.broadcast.lock().unwrap().as_ref() {
+                    Some(sender) => sender.subscribe(),
+                    // source ended - hand out a closed receiver
+                    None => broadcast::channel(1).1,
+                };
+                ReceiverInner::Broadcast(receiver)
+            }
+            LagPolicy::Block | LagPolicy::DisconnectSlowConsumer => {
+                let (sender, receiver) = mpsc::channel(self.shared.capacity);
+                if let Some(subscribers) = self.shared.subscribers.lock().unwrap().as_mut() {
+                    subscribers.push(Subscriber {
+                        id,
+                        sender,
+                        counters: counters.clone(),
+                    });
+                }
+                ReceiverInner::Mpsc(receiver)
+            }
+        };
+        drop(replay_buffer);
+
+        ChannelReceiver {
+            id,
+            replayed,
+            inner,
+            counters,
+            shared: self.shared.clone(),
+        }
+    }
+
+    /// dropped message counters of all receivers which are not dropped yet
+    pub fn receiver_stats(&self) -> Vec<ReceiverStats> {
+        self.shared
+            .receivers
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(receiver_id, counters)| ReceiverStats {
+                receiver_id: *receiver_id,
+                dropped: counters.dropped.load(Ordering::Relaxed),
+                disconnected: counters.disconnected.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+}
+
+enum ReceiverInner<T> {
+    Broadcast(broadcast::Receiver<T>),
+    Mpsc(mpsc::Receiver<T>),
+}
+
+pub struct ChannelReceiver<T> {
+    id: u64,
+    // delivered before the live channel
+    replayed: VecDeque<T>,
+    inner: ReceiverInner<T>,
+    counters: Arc<ReceiverCounters>,
+    shared: Arc<ChannelizerShared<T>>,
+}
+
+impl<T: Clone> ChannelReceiver<T> {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.counters.dropped.load(Ordering::Relaxed)
+    }
+
+    /// returns None if the source stream ended or the receiver got disconnected
+    pub async fn recv(&mut self) -> Option<T> {
+        if let Some(payload) = self.replayed.pop_front() {
+            return Some(payload);
+        }
+        match &mut self.inner {
+            ReceiverInner::Broadcast(receiver) => loop {
+                match receiver.recv().await {
+                    Ok(payload) => return Some(payload),
+                    Err(RecvError::Lagged(n_missed)) => {
+                        warn!("receiver {} lagged {} messages", self.id, n_missed);
+                        self.counters.dropped.fetch_add(n_missed, Ordering::Relaxed);
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            },
+            ReceiverInner::Mpsc(receiver) => receiver.recv().await,
+        }
+    }
+}
+
+impl<T> Drop for ChannelReceiver<T> {
+    fn drop(&mut self) {
+        self.shared
+            .receivers
+            .lock()
+            .unwrap()
+            .retain(|(receiver_id, _)| *receiver_id != self.id);
+    }
+}
+
+/// like ``channelize_stream`` but with a configurable policy for slow receivers;
+/// ``capacity`` is the buffer size per receiver
+pub fn channelize_stream_with_policy<T>(
+    source_stream: impl Stream<Item = T> + Send + 'static,
+    capacity: usize,
+    policy: LagPolicy,
+) -> (Channelizer<T>, JoinHandle<ChannelizerCompletion>)
+where
+    T: Clone + Send + 'static,
+{
+    channelize_stream_with_replay(source_stream, capacity, policy, Replay::None)
+}
+
+/// like ``channelize_stream_with_policy`` but retains recent items for receivers subscribing later
+pub fn channelize_stream_with_replay<T>(
+    source_stream: impl Stream<Item = T> + Send + 'static,
+    capacity: usize,
+    policy: LagPolicy,
+    replay: Replay<T>,
+) -> (Channelizer<T>, JoinHandle<ChannelizerCompletion>)
+where
+    T: Clone + Send + 'static,
+{
+    let shared = Arc::new(ChannelizerShared {
+        policy,
+        capacity,
+        next_id: AtomicU64::new(0),
+        broadcast: Mutex::new(Some(broadcast::channel(capacity).0)),
+        subscribers: Mutex::new(Some(vec![])),
+        receivers: Mutex::new(vec![]),
+        replay,
+        replay_buffer: Mutex::new(VecDeque::new()),
+    });
+
+    let jh_channelizer = tokio::spawn({
+        let shared = shared.clone();
+        async move {
+            let mut source_stream = pin!(source_stream);
+            while let Some(payload) = source_stream.next().await {
+                shared.forward(payload).await;
+            }
+            warn!("source stream ended - shutting down channelizer");
+            shared.close();
+            ChannelizerCompletion::SourceStreamEnded
+        }
+    });
+
+    (Channelizer { shared }, jh_channelizer)
+}
+
+/// what ``pipe_to_sink`` does when the sink fails to take an item
+#[derive(Clone, Debug)]
+pub enum SinkErrorPolicy {
+    // send the item again after backoff; abort when the retries are exhausted
+    Retry { max_retries: u32, backoff: Duration },
+    // drop the item and continue with the next one
+    Skip,
+    Abort,
+}
+
+/// drive the stream (e.g. multiplexed blocks) into any ``futures::Sink``; the sink is flushed
+/// after every item and closed when the stream ends
+/// returns the error of the sink if forwarding was aborted
+/// note: ``Skip`` and ``Abort`` never clone; with ``Retry`` each item is cloned for every send
+/// but the last allowed one, even if the first send succeeds, as a failed send does not return
+/// the item
+/// note: a sink may not accept items anymore after an error
+pub async fn pipe_to_sink<T, K>(
+    source_stream: impl Stream<Item = T>,
+    sink: K,
+    error_policy: SinkErrorPolicy,
+) -> Result<(), K::Error>
+where
+    T: Clone,
+    K: Sink<T>,
+    K::Error: Debug,
+{
+    let (max_retries, backoff) = match error_policy {
+        SinkErrorPolicy::Retry {
+            max_retries,
+            backoff,
+        } => (max_retries, backoff),
+        SinkErrorPolicy::Skip | SinkErrorPolicy::Abort => (0, Duration::ZERO),
+    };
+    let mut source_stream = pin!(source_stream);
+    let mut sink = pin!(sink);
+    while let Some(payload) = source_stream.next().await {
+        let mut retries = 0;
+        let result = loop {
+            if retries == max_retries {
+                break sink.as_mut().send(payload).await;
+            }
+            match sink.as_mut().send(payload.clone()).await {
+                Ok(()) => break Ok(()),
+                Err(err) => {
+                    retries += 1;
+                    warn!(
+                        "sink failed - retry {}/{} in {:?}: {:?}",
+                        retries, max_retries, backoff, err
+                    );
+                    sleep(backoff).await;
+                }
+            }
+        };
+        match (result, &error_policy) {
+            (Ok(()), _) => {}
+            (Err(err), SinkErrorPolicy::Skip) => {
+                warn!("sink failed - skip item: {:?}", err);
+            }
+            (Err(err), _) => {
+                warn!("sink failed - abort forwarding: {:?}", err);
+                return Err(err);
+            }
+        }
+    }
+    debug!("source stream ended - closing sink");
+    sink.close().await
+}
+
+// token buckets of one source, see ``RateLimit``
+pub(crate) struct RateLimiter {
+    messages: Option<TokenBucket>,
+    bytes: Option<TokenBucket>,
+}
+
+impl RateLimiter {
+    // no limit if none or 0
+    pub(crate) fn new(rate_limit: Option<&RateLimit>) -> Self {
+        let bucket = |rate: Option<u64>| {
+            rate.filter(|rate| *rate > 0)
+                .map(|rate| TokenBucket::new(rate as f64))
+        };
+        RateLimiter {
+            messages: bucket(
+                rate_limit.and_then(|rate_limit| rate_limit.messages_per_second.map(u64::from)),
+            ),
+            bytes: bucket(rate_limit.and_then(|rate_limit| rate_limit.bytes_per_second)),
+        }
+    }
+
+    // how long to hold back the update
+    pub(crate) fn delay(&mut self, update: &SubscribeUpdate) -> Duration {
+        let messages_delay = self
+            .messages
+            .as_mut()
+            .map_or(Duration::ZERO, |bucket| bucket.take(1.0));
+        let bytes_delay = self.bytes.as_mut().map_or(Duration::ZERO, |bucket| {
+            bucket.take(update.encoded_len() as f64)
+        });
+        messages_delay.max(bytes_delay)
+    }
+
+    pub(crate) async fn wait(&mut self, update: &SubscribeUpdate) {
+        let delay = self.delay(update);
+        if !delay.is_zero() {
+            trace!("rate limit exceeded - delay update by {:?}", delay);
+            sleep(delay).await;
+        }
+    }
+}
+
+struct TokenBucket {
+    rate: f64,
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl TokenBucket {
+    // allows a burst of one second worth of tokens
+    fn new(rate: f64) -> Self {
+        TokenBucket {
+            rate,
+            tokens: rate,
+            refilled_at: Instant::now(),
+        }
+    }
+
+    // takes the tokens; a deficit is paid by waiting
+    fn take(&mut self, cost: f64) -> Duration {
+        let now = Instant::now();
+        let refill = now.duration_since(self.refilled_at).as_secs_f64() * self.rate;
+        self.tokens = (self.tokens + refill).min(self.rate) - cost;
+        self.refilled_at = now;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.rate)
+        }
+    }
+}
+
+// keys are remembered for ``window`` slots behind the horizon; keys of slots behind the horizon
+// count as duplicates since they could have been forgotten already
+pub(crate) struct SlotWindowDedup<K> {
+    window: u64,
+    horizon: Slot,
+    seen: HashMap<K, Slot>,
+}
+
+impl<K: Eq + Hash> SlotWindowDedup<K> {
+    pub(crate) fn new(window: u64) -> Self {
+        SlotWindowDedup {
+            window,
+            horizon: 0,
+            seen: HashMap::new(),
+        }
+    }
+
+    // forget keys older than ``window`` slots behind the slot
+    pub(crate) fn advance(&mut self, slot: Slot) {
+        let horizon = slot.saturating_sub(self.window);
+        if horizon > self.horizon {
+            self.horizon = horizon;
+            self.seen.retain(|_, slot| *slot >= horizon);
+        }
+    }
+
+    // true if the key is seen the first time and not behind the horizon
+    pub(crate) fn insert(&mut self, key: K, slot: Slot) -> bool {
+        slot >= self.horizon && self.seen.insert(key, slot).is_none()
+    }
+}
+
+/// delay geyser updates exceeding the limit; slows down only this source (backpressure on its grpc stream)
+pub fn rate_limited(
+    source_stream: impl Stream<Item = Message>,
+    rate_limit: RateLimit,
+) -> impl Stream<Item = Message> {
+    let mut rate_limiter = RateLimiter::new(Some(&rate_limit));
+    source_stream.then(move |message| {
+        let delay = match &message {
+            Message::GeyserSubscribeUpdate(update) => rate_limiter.delay(update),
+            Message::Connecting(_) => Duration::ZERO,
+        };
+        async move {
+            if !delay.is_zero() {
+                trace!("rate limit exceeded - delay update by {:?}", delay);
+                sleep(delay).await;
+            }
+            message
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::stream;
+
+    #[test]
+    fn test_slot_window_dedup() {
+        let mut dedup = SlotWindowDedup::new(10);
+        assert!(dedup.insert("a", 5));
+        assert!(!dedup.insert("a", 5));
+        dedup.advance(16);
+        // forgotten but behind the horizon
+        assert!(!dedup.insert("a", 5));
+        assert!(dedup.insert("a", 6));
+        assert!(!dedup.insert("a", 7));
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited() {
+        let update = || {
+            Message::GeyserSubscribeUpdate(Box::new(SubscribeUpdate {
+                filters: vec![],
+                update_oneof: None,
+            }))
+        };
+        let rate_limit = RateLimit {
+            messages_per_second: Some(20),
+            bytes_per_second: None,
+        };
+
+        // burst of one second worth passes, the remaining 2 updates wait 50ms each
+        let started_at = Instant::now();
+        let limited: Vec<Message> =
+            rate_limited(stream::iter((0..22).map(|_| update())), rate_limit.clone())
+                .collect()
+                .await;
+        assert_eq!(limited.len(), 22);
+        assert!(started_at.elapsed() >= Duration::from_millis(90));
+
+        // status messages are not limited
+        let started_at = Instant::now();
+        let _: Vec<Message> =
+            rate_limited(stream::iter((0..50).map(Message::Connecting)), rate_limit)
+                .collect()
+                .await;
+        assert!(started_at.elapsed() < Duration::from_millis(50));
+    }
+
+    #[test]
+    fn test_zero_rate_is_unlimited() {
+        let mut rate_limiter = RateLimiter::new(Some(&RateLimit {
+            messages_per_second: Some(0),
+            bytes_per_second: Some(0),
+        }));
+        let update = SubscribeUpdate {
+            filters: vec![],
+            update_oneof: None,
+        };
+        for _ in 0..10 {
+            assert_eq!(rate_limiter.delay(&update), Duration::ZERO);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_channelize_arc_shares_payload() {
+        let (mut rx_a, jh_channelizer) = channelize_stream_arc(stream::iter(vec![vec![0u8; 1024]]));
+        let mut rx_b = rx_a.resubscribe();
+
+        let payload_a = rx_a.recv().await.unwrap();
+        let payload_b = rx_b.recv().await.unwrap();
+        assert!(Arc::ptr_eq(&payload_a, &payload_b));
+
+        assert_eq!(
+            jh_channelizer.await.unwrap(),
+            ChannelizerCompletion::SourceStreamEnded
+        );
+        assert!(rx_a.recv().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_channelize_filtered() {
+        let (mut rx, jh_channelizer) =
+            channelize_stream_filtered(stream::iter(1..=10u64), |slot| {
+                (slot % 4 == 0).then(|| slot.to_string())
+            });
+        jh_channelizer.await.unwrap();
+
+        assert_eq!(rx.recv().await.unwrap(), "4");
+        assert_eq!(rx.recv().await.unwrap(), "8");
+        assert!(rx.recv().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_supervised_restarts() {
+        let mut generation = 0;
+        let (mut rx, jh_channelizer) = channelize_stream_supervised(
+            move || {
+                generation += 1;
+                stream::iter(vec![generation])
+            },
+            RestartPolicy {
+                max_restarts: Some(2),
+                backoff: Duration::from_millis(1),
+            },
+        );
+
+        assert_eq!(
+            jh_channelizer.await.unwrap(),
+            ChannelizerCompletion::RestartLimitReached { restarts: 2 }
+        );
+        assert_eq!(rx.recv().await.unwrap(), 1);
+        assert_eq!(rx.recv().await.unwrap(), 2);
+        assert_eq!(rx.recv().await.unwrap(), 3);
+        assert!(rx.recv().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_replay_for_late_receiver() {
+        let (channelizer, jh_channelizer) = channelize_stream_with_replay(
+            stream::iter(0..10u64),
+            10,
+            LagPolicy::Block,
+            Replay::LastItems(3),
+        );
+        jh_channelizer.await.unwrap();
+
+        // attached after the source ended: only the replay is delivered
+        let mut receiver = channelizer.subscribe();
+        let mut received = vec![];
+        while let Some(value) = receiver.recv().await {
+            received.push(value);
+        }
+        assert_eq!(received, vec![7, 8, 9]);
+    }
+
+    #[test]
+    fn test_replay_last_slots() {
+        let replay = Replay::LastSlots {
+            slots: 2,
+            slot_of: |slot: &u64| *slot,
+        };
+        let mut buffer: VecDeque<u64> = vec![1, 2, 2, 3, 4].into();
+        replay.trim(&mut buffer);
+        assert_eq!(buffer, vec![3, 4]);
+    }
+
+    #[tokio::test]
+    async fn test_drop_oldest_counts_lag() {
+        let (channelizer, jh_channelizer) =
+            channelize_stream_with_policy(stream::iter(0..10u64), 2, LagPolicy::DropOldest);
+        let mut receiver = channelizer.subscribe();
+        jh_channelizer.await.unwrap();
+
+        let mut received = vec![];
+        while let Some(value) = receiver.recv().await {
+            received.push(value);
+        }
+        assert_eq!(received, vec![8, 9]);
+        assert_eq!(receiver.dropped(), 8);
+    }
+
+    #[tokio::test]
+    async fn test_block_delivers_all() {
+        let (channelizer, jh_channelizer) =
+            channelize_stream_with_policy(stream::iter(0..10u64), 1, LagPolicy::Block);
+        let mut receiver = channelizer.subscribe();
+
+        let mut received = vec![];
+        while let Some(value) = receiver.recv().await {
+            received.push(value);
+        }
+        jh_channelizer.await.unwrap();
+        assert_eq!(received, (0..10).collect::<Vec<_>>());
+        assert_eq!(receiver.dropped(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_disconnect_slow_consumer() {
+        let (channelizer, jh_channelizer) = channelize_stream_with_policy(
+            stream::iter(0..10u64),
+            2,
+            LagPolicy::DisconnectSlowConsumer,
+        );
+        let mut receiver = channelizer.subscribe();
+        jh_channelizer.await.unwrap();
+
+        let stats = channelizer.receiver_stats();
+        assert_eq!(stats.len(), 1);
+        assert!(stats[0].disconnected);
+        assert_eq!(stats[0].dropped, 1);
+
+        let mut received = vec![];
+        while let Some(value) = receiver.recv().await {
+            received.push(value);
+        }
+        assert_eq!(received, vec![0, 1]);
+
+        drop(receiver);
+        assert!(channelizer.receiver_stats().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_latest_only() {
+        let (latest, jh_latest) = latest_only(stream::iter(1..=5));
+        jh_latest.await.unwrap();
+        assert_eq!(*latest.borrow(), Some(5));
+
+        let (tx, rx) = broadcast::channel(2);
+        for payload in 1..=5 {
+            tx.send(payload).unwrap();
+        }
+        drop(tx);
+        // starts lagged
+        let (latest, jh_latest) = latest_only_from_broadcast(rx);
+        jh_latest.await.unwrap();
+        assert_eq!(*latest.borrow(), Some(5));
+    }
+
+    // fails the given number of sends of an item
+    struct FlakySink {
+        failures: Vec<(u32, u32)>,
+        received: Vec<u32>,
+    }
+
+    impl Sink<u32> for FlakySink {
+        type Error = String;
+
+        fn poll_ready(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<Result<(), String>> {
+            std::task::Poll::Ready(Ok(()))
+        }
+
+        fn start_send(mut self: std::pin::Pin<&mut Self>, item: u32) -> Result<(), String> {
+            if let Some((_, remaining)) = self
+                .failures
+                .iter_mut()
+                .find(|(failing, remaining)| *failing == item && *remaining > 0)
+            {
+                *remaining -= 1;
+                return Err(format!("rejected {}", item));
+            }
+            self.received.push(item);
+            Ok(())
+        }
+
+        fn poll_flush(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<Result<(), String>> {
+            std::task::Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<Result<(), String>> {
+            std::task::Poll::Ready(Ok(()))
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_pipe_to_sink_error_policies() {
+        let pipe = |error_policy: SinkErrorPolicy| async move {
+            let mut sink = FlakySink {
+                failures: vec![(2, 2)],
+                received: vec![],
+            };
+            let result = pipe_to_sink(stream::iter(vec![1, 2, 3]), &mut sink, error_policy).await;
+            (result, sink.received)
+        };
+
+        let retry = SinkErrorPolicy::Retry {
+            max_retries: 2,
+            backoff: Duration::from_millis(100),
+        };
+        assert_eq!(pipe(retry).await, (Ok(()), vec![1, 2, 3]));
+        assert_eq!(pipe(SinkErrorPolicy::Skip).await, (Ok(()), vec![1, 3]));
+        assert_eq!(
+            pipe(SinkErrorPolicy::Abort).await,
+            (Err("rejected 2".to_string()), vec![1])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_readiness() {
+        let (ready_stream, readiness) = with_readiness(stream::iter(vec![1, 2]));
+        assert!(!readiness.is_ready());
+
+        let (mut rx, _jh_channelizer) = channelize_stream(ready_stream);
+        assert_eq!(readiness.ready(Duration::from_secs(5)).await, Ok(()));
+        assert!(readiness.is_ready());
+        assert_eq!(rx.recv().await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_readiness_timeout_and_dropped() {
+        let (pending_stream, readiness) = with_readiness(stream::pending::<u64>());
+        assert_eq!(
+            readiness.ready(Duration::from_millis(10)).await,
+            Err(ReadinessError::Timeout)
+        );
+        drop(pending_stream);
+        assert_eq!(
+            readiness.ready(Duration::from_millis(10)).await,
+            Err(ReadinessError::StreamDropped)
+        );
+    }
+}
@@ -1,28 +1,235 @@
-use crate::{Attempt, GrpcSourceConfig, Message};
+use crate::grpc_stream_utils::{AbortOnDrop, RateLimiter};
+use crate::grpc_unary::connect;
+use crate::lifecycle::LIFECYCLE_TARGET;
+use crate::reconnect_policy::{ExponentialBackoff, ReconnectDecision, ReconnectPolicy};
+use crate::source_events::{
+    parse_oversized_status, ErrorPhase, SourceError, SourceEvent, MAX_CONSECUTIVE_OVERSIZED,
+    OVERSIZED_RESUBSCRIBE_DELAY,
+};
+use crate::{
+    is_keepalive_frame, ping_request, slot_of_update, Attempt, GrpcSourceConfig, Message, Slot,
+};
 use async_stream::stream;
-use futures::{Stream, StreamExt};
-use log::{debug, info, log, trace, warn, Level};
+use futures::future::BoxFuture;
+use futures::{Future, FutureExt, Sink, SinkExt, Stream, StreamExt};
+use log::{debug, trace, warn};
+use std::fmt::Debug;
 use std::time::Duration;
-use tokio::task::JoinHandle;
-use tokio::time::{sleep, timeout};
-use yellowstone_grpc_client::{GeyserGrpcClient, GeyserGrpcClientResult};
+use tokio::sync::broadcast;
+use tokio::task::JoinError;
+use tokio::time::{sleep, timeout, Instant};
+use tracing::{debug_span, info_span, Instrument};
+use yellowstone_grpc_client::{GeyserGrpcClient, GeyserGrpcClientError, GeyserGrpcClientResult};
 use yellowstone_grpc_proto::geyser::{SubscribeRequest, SubscribeUpdate};
+use yellowstone_grpc_proto::tonic::service::Interceptor;
 use yellowstone_grpc_proto::tonic::Status;
 
-enum ConnectionState<S: Stream<Item = Result<SubscribeUpdate, Status>>> {
+type ConnectionTask<K, S> = BoxFuture<'static, Result<GeyserGrpcClientResult<(K, S)>, JoinError>>;
+
+enum ConnectionState<S: Stream<Item = Result<SubscribeUpdate, Status>>, K> {
     NotConnected(Attempt),
-    Connecting(Attempt, JoinHandle<GeyserGrpcClientResult<S>>),
-    Ready(Attempt, S),
+    // spawned task or, with ``ConnectMode::Inline``, the connect future itself
+    Connecting(Attempt, ConnectionTask<K, S>),
+    // subscribe sink is kept to send keepalive pings
+    Ready(Attempt, S, K),
     WaitReconnect(Attempt),
 }
 
 // Take geyser filter, connect to Geyser and return a generic stream of SubscribeUpdate
-// note: stream never terminates
+// note: stream never terminates with the default reconnect policy
+// note: ping/pong frames are consumed and not passed downstream
 pub fn create_geyser_reconnecting_stream(
     grpc_source: GrpcSourceConfig,
     subscribe_filter: SubscribeRequest,
 ) -> impl Stream<Item = Message> {
+    create_geyser_reconnecting_stream_with_policy(
+        vec![grpc_source],
+        subscribe_filter,
+        ExponentialBackoff::default(),
+    )
+}
+
+/// like ``create_geyser_reconnecting_stream`` but the policy decides about waiting, giving up
+/// (the stream ends) and rotating through the given endpoints
+pub fn create_geyser_reconnecting_stream_with_policy(
+    grpc_sources: Vec<GrpcSourceConfig>,
+    subscribe_filter: SubscribeRequest,
+    reconnect_policy: impl ReconnectPolicy,
+) -> impl Stream<Item = Message> {
+    reconnecting_stream(grpc_sources, subscribe_filter, reconnect_policy, None)
+}
+
+/// like ``create_geyser_reconnecting_stream`` but connect and subscribe run inside the stream
+/// instead of a spawned task, e.g. to embed into a runtime where ``tokio::spawn`` is unavailable;
+/// connecting only progresses while the stream is polled
+/// note: tokio time and io drivers are still needed, and tonic spawns the worker of its channel
+pub fn create_geyser_reconnecting_stream_inline(
+    grpc_source: GrpcSourceConfig,
+    subscribe_filter: SubscribeRequest,
+    reconnect_policy: impl ReconnectPolicy,
+) -> impl Stream<Item = Message> {
+    reconnecting_stream_with_connector(
+        vec![grpc_source],
+        subscribe_filter,
+        reconnect_policy,
+        None,
+        ConnectMode::Inline,
+        connect_and_subscribe,
+    )
+}
+
+/// like ``create_geyser_reconnecting_stream`` plus a channel with events like oversized updates
+/// and errors of the source
+pub fn create_geyser_reconnecting_stream_with_events(
+    grpc_source: GrpcSourceConfig,
+    subscribe_filter: SubscribeRequest,
+) -> (
+    impl Stream<Item = Message>,
+    broadcast::Receiver<SourceEvent>,
+) {
+    let (events_tx, events_rx) = broadcast::channel(100);
+    let geyser_stream = reconnecting_stream(
+        vec![grpc_source],
+        subscribe_filter,
+        ExponentialBackoff::default(),
+        Some(events_tx),
+    );
+    (geyser_stream, events_rx)
+}
+
+fn reconnecting_stream(
+    grpc_sources: Vec<GrpcSourceConfig>,
+    subscribe_filter: SubscribeRequest,
+    reconnect_policy: impl ReconnectPolicy,
+    events: Option<broadcast::Sender<SourceEvent>>,
+) -> impl Stream<Item = Message> {
+    reconnecting_stream_with_connector(
+        grpc_sources,
+        subscribe_filter,
+        reconnect_policy,
+        events,
+        ConnectMode::Spawned,
+        connect_and_subscribe,
+    )
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ConnectMode {
+    // connect in a task so it progresses while the stream is not polled
+    Spawned,
+    Inline,
+}
+
+/// like ``create_geyser_reconnecting_stream_with_policy`` but on every (re)connect the client is
+/// built by ``client_factory``, e.g. for custom dns, connection pools or exotic auth;
+/// ``grpc_source`` still provides label, subscribe timeout, probe, keepalive and rate limit
+pub fn create_geyser_reconnecting_stream_with_client_factory<C, F, I>(
+    grpc_source: GrpcSourceConfig,
+    subscribe_filter: SubscribeRequest,
+    reconnect_policy: impl ReconnectPolicy,
+    client_factory: C,
+) -> impl Stream<Item = Message>
+where
+    C: Fn(GrpcSourceConfig) -> F,
+    F: Future<Output = GeyserGrpcClientResult<GeyserGrpcClient<I>>> + Send + 'static,
+    I: Interceptor + Send + 'static,
+{
+    reconnecting_stream_with_connector(
+        vec![grpc_source],
+        subscribe_filter,
+        reconnect_policy,
+        None,
+        ConnectMode::Spawned,
+        move |grpc_source, subscribe_filter| {
+            let connecting = client_factory(grpc_source.clone());
+            async move {
+                let client = connecting.instrument(debug_span!("geyser_connect")).await?;
+                subscribe(client, &grpc_source, subscribe_filter).await
+            }
+        },
+    )
+}
+
+async fn connect_and_subscribe(
+    grpc_source: GrpcSourceConfig,
+    subscribe_filter: SubscribeRequest,
+) -> GeyserGrpcClientResult<(
+    impl Sink<SubscribeRequest, Error = impl Debug>,
+    impl Stream<Item = Result<SubscribeUpdate, Status>>,
+)> {
+    let client = connect(&grpc_source)
+        .instrument(debug_span!("geyser_connect"))
+        .await?;
+    subscribe(client, &grpc_source, subscribe_filter).await
+}
+
+async fn subscribe<I: Interceptor>(
+    mut client: GeyserGrpcClient<I>,
+    grpc_source: &GrpcSourceConfig,
+    subscribe_filter: SubscribeRequest,
+) -> GeyserGrpcClientResult<(
+    impl Sink<SubscribeRequest, Error = impl Debug>,
+    impl Stream<Item = Result<SubscribeUpdate, Status>>,
+)> {
+    let subscribe_timeout = grpc_source.timeouts.as_ref().map(|t| t.subscribe_timeout);
+    let request_timeout = grpc_source.timeouts.as_ref().map(|t| t.request_timeout);
+
+    if grpc_source.probe_on_connect {
+        // a timeout is retried like any other failed attempt
+        let version = timeout(
+            request_timeout.unwrap_or(Duration::MAX),
+            client.get_version(),
+        )
+        .await
+        .map_err(|_| Status::deadline_exceeded("version probe timeout"))??;
+        debug!("Connected to geyser version {}", version.version);
+    }
+
+    debug!("Subscribe with filter {:?}", subscribe_filter);
+
+    let subscribe_result = timeout(
+        subscribe_timeout.unwrap_or(Duration::MAX),
+        client.subscribe_with_request(Some(subscribe_filter)),
+    )
+    .instrument(debug_span!("geyser_subscribe"))
+    .await;
+
+    // maybe not optimal
+    subscribe_result.map_err(|_| Status::unknown("unspecific subscribe timeout"))?
+}
+
+/// the reconnect state machine; ``connector`` connects and subscribes one source, e.g. a mock in
+/// ``simulation``
+pub(crate) fn reconnecting_stream_with_connector<C, F, K, S>(
+    grpc_sources: Vec<GrpcSourceConfig>,
+    subscribe_filter: SubscribeRequest,
+    reconnect_policy: impl ReconnectPolicy,
+    events: Option<broadcast::Sender<SourceEvent>>,
+    connect_mode: ConnectMode,
+    connector: C,
+) -> impl Stream<Item = Message>
+where
+    C: Fn(GrpcSourceConfig, SubscribeRequest) -> F,
+    F: Future<Output = GeyserGrpcClientResult<(K, S)>> + Send + 'static,
+    K: Sink<SubscribeRequest> + Unpin + Send + 'static,
+    K::Error: Debug,
+    S: Stream<Item = Result<SubscribeUpdate, Status>> + Unpin + Send + 'static,
+{
+    assert!(!grpc_sources.is_empty(), "need at least one grpc source");
+    let mut rate_limiter = RateLimiter::new(grpc_sources[0].rate_limit());
     let mut state = ConnectionState::NotConnected(0);
+    let mut endpoint_idx = 0;
+    let mut grpc_source = grpc_sources[endpoint_idx].clone();
+    let mut source_label = grpc_source.label();
+    let mut ping_id = 0;
+    let mut last_ping_at = Instant::now();
+    let mut last_received_at = Instant::now();
+    let mut last_slot: Option<Slot> = None;
+    let mut awaiting_first_message = false;
+    let mut consecutive_oversized = 0;
+    // attempts since the last connection that delivered an update; the policy backs off on this
+    // while ``Message::Connecting`` keeps counting all attempts
+    let mut policy_attempt: Attempt = 0;
 
     // in case of cancellation, we restart from here:
     // thus we want to keep the progression in a state object outside the stream! makro
@@ -34,95 +241,178 @@ pub fn create_geyser_reconnecting_stream(
 
                 ConnectionState::NotConnected(mut attempt) => {
                     attempt += 1;
+                    policy_attempt += 1;
 
-                    let connection_task = tokio::spawn({
-                        let addr = grpc_source.grpc_addr.clone();
-                        let token = grpc_source.grpc_x_token.clone();
-                        let config = grpc_source.tls_config.clone();
-                        let connect_timeout = grpc_source.timeouts.as_ref().map(|t| t.connect_timeout);
-                        let request_timeout = grpc_source.timeouts.as_ref().map(|t| t.request_timeout);
-                        let subscribe_timeout = grpc_source.timeouts.as_ref().map(|t| t.subscribe_timeout);
-                        let subscribe_filter = subscribe_filter.clone();
-                        log!(if attempt > 1 { Level::Warn } else { Level::Debug }, "Connecting attempt #{} to {}", attempt, addr);
-                        async move {
-
-                            let connect_result = GeyserGrpcClient::connect_with_timeout(
-                                    addr, token, config,
-                                    connect_timeout,
-                                    request_timeout,
-                                false)
-                                .await;
-                            let mut client = connect_result?;
-
-
-                            debug!("Subscribe with filter {:?}", subscribe_filter);
-
-                            let subscribe_result = timeout(subscribe_timeout.unwrap_or(Duration::MAX),
-                                client
-                                    .subscribe_once2(subscribe_filter))
-                            .await;
-
-                            // maybe not optimal
-                            subscribe_result.map_err(|_| Status::unknown("unspecific subscribe timeout"))?
-                        }
-                    });
+                    tracing::info!(target: LIFECYCLE_TARGET, event = "connect", source = %source_label, attempt, "connecting to {}", grpc_source);
+                    let connection_span = info_span!("geyser_connection", source = %source_label, attempt);
+                    let connect = connector(grpc_source.clone(), subscribe_filter.clone()).instrument(connection_span);
+                    let connection_task: ConnectionTask<K, S> = match connect_mode {
+                        // aborted if the stream is dropped while connecting
+                        ConnectMode::Spawned => AbortOnDrop(tokio::spawn(connect)).boxed(),
+                        ConnectMode::Inline => connect.map(Ok).boxed(),
+                    };
 
-                    (ConnectionState::Connecting(attempt, connection_task), Message::Connecting(attempt))
+                    (ConnectionState::Connecting(attempt, connection_task), Some(Message::Connecting(attempt)))
                 }
 
                 ConnectionState::Connecting(attempt, connection_task) => {
                     let subscribe_result = connection_task.await;
 
                      match subscribe_result {
-                        Ok(Ok(subscribed_stream)) => (ConnectionState::Ready(attempt, subscribed_stream), Message::Connecting(attempt)),
+                        Ok(Ok((subscribe_tx, subscribed_stream))) => {
+                            last_ping_at = Instant::now();
+                            last_received_at = Instant::now();
+                            awaiting_first_message = true;
+                            tracing::info!(target: LIFECYCLE_TARGET, event = "subscribed", source = %source_label, attempt, "subscribed to {}", grpc_source);
+                            (ConnectionState::Ready(attempt, subscribed_stream, subscribe_tx), Some(Message::Connecting(attempt)))
+                        },
                         Ok(Err(geyser_error)) => {
                              // ATM we consider all errors recoverable
-                            warn!("subscribe failed on {} - retrying: {:?}", grpc_source, geyser_error);
-                            (ConnectionState::WaitReconnect(attempt), Message::Connecting(attempt))
+                            tracing::warn!(target: LIFECYCLE_TARGET, event = "subscribe_failed", source = %source_label, attempt, error = ?geyser_error, "subscribe failed on {} - retrying", grpc_source);
+                            if let Some(events) = &events {
+                                let status = match &geyser_error {
+                                    GeyserGrpcClientError::TonicStatus(status) => status.clone(),
+                                    other => Status::unknown(other.to_string()),
+                                };
+                                // no subscribers is fine
+                                let _ = events.send(SourceEvent::Error(SourceError::from_status(source_label.clone(), ErrorPhase::Subscribe, &status)));
+                            }
+                            (ConnectionState::WaitReconnect(attempt), Some(Message::Connecting(attempt)))
                         },
                         Err(geyser_grpc_task_error) => {
-                            panic!("task aborted - should not happen :{geyser_grpc_task_error}");
+                            // a panic in the connection task is handled like a failed attempt
+                            warn!("connection task on {} failed - retrying: {}", grpc_source, geyser_grpc_task_error);
+                            (ConnectionState::WaitReconnect(attempt), Some(Message::Connecting(attempt)))
                         }
                     }
 
                 }
 
-                ConnectionState::Ready(attempt, mut geyser_stream) => {
-                    let receive_timeout = grpc_source.timeouts.as_ref().map(|t| t.receive_timeout);
-                    match timeout(receive_timeout.unwrap_or(Duration::MAX), geyser_stream.next()).await {
-                        Ok(Some(Ok(update_message))) => {
-                            trace!("> recv update message from {}", grpc_source);
-                            (ConnectionState::Ready(attempt, geyser_stream), Message::GeyserSubscribeUpdate(Box::new(update_message)))
-                        }
-                        Ok(Some(Err(tonic_status))) => {
-                            // ATM we consider all errors recoverable
-                            warn!("error on {} - retrying: {:?}", grpc_source, tonic_status);
-                            (ConnectionState::WaitReconnect(attempt), Message::Connecting(attempt))
-                        }
-                        Ok(None) =>  {
-                            // should not arrive here, Mean the stream close.
-                            warn!("geyser stream closed on {} - retrying", grpc_source);
-                            (ConnectionState::WaitReconnect(attempt), Message::Connecting(attempt))
-                        }
-                        Err(_elapsed) => {
-                            // timeout
-                            warn!("geyser stream timeout on {} - retrying", grpc_source);
-                            (ConnectionState::WaitReconnect(attempt), Message::Connecting(attempt))
+                ConnectionState::Ready(attempt, mut geyser_stream, mut subscribe_tx) => {
+                    let receive_timeout = grpc_source.timeouts.as_ref().map(|t| t.receive_timeout).unwrap_or(Duration::MAX);
+
+                    let mut ping_failed = false;
+                    if let Some(keepalive_interval) = grpc_source.keepalive_interval {
+                        if last_ping_at.elapsed() >= keepalive_interval {
+                            ping_id += 1;
+                            last_ping_at = Instant::now();
+                            trace!("> send ping {} to {}", ping_id, grpc_source);
+                            if let Err(send_error) = subscribe_tx.send(ping_request(ping_id)).await {
+                                tracing::warn!(target: LIFECYCLE_TARGET, event = "disconnected", source = %source_label, attempt, reason = "ping_failed", error = ?send_error, "keepalive ping failed on {} - retrying", grpc_source);
+                                ping_failed = true;
+                            }
                         }
                     }
 
+                    // wake up in time for the next ping
+                    let wait_for = grpc_source.keepalive_interval.map_or(receive_timeout, |keepalive_interval| {
+                        keepalive_interval.saturating_sub(last_ping_at.elapsed()).min(receive_timeout)
+                    });
+
+                    let receive_span = debug_span!("geyser_receive", source = %source_label, slot = tracing::field::Empty);
+                    if ping_failed {
+                        (ConnectionState::WaitReconnect(attempt), Some(Message::Connecting(attempt)))
+                    } else {
+                        match timeout(wait_for, geyser_stream.next()).instrument(receive_span.clone()).await {
+                            Ok(Some(Ok(update_message))) => {
+                                last_received_at = Instant::now();
+                                if is_keepalive_frame(&update_message) {
+                                    trace!("> recv ping/pong from {}", grpc_source);
+                                    (ConnectionState::Ready(attempt, geyser_stream, subscribe_tx), None)
+                                } else {
+                                    trace!("> recv update message from {}", grpc_source);
+                                    consecutive_oversized = 0;
+                                    if let Some(slot) = slot_of_update(&update_message) {
+                                        receive_span.record("slot", slot);
+                                        last_slot = Some(slot);
+                                    }
+                                    if awaiting_first_message {
+                                        awaiting_first_message = false;
+                                        // a healthy connection starts a new series of attempts
+                                        policy_attempt = 1;
+                                        tracing::info!(target: LIFECYCLE_TARGET, event = "first_message", source = %source_label, attempt, slot = ?slot_of_update(&update_message), "first update from {}", grpc_source);
+                                    }
+                                    // backpressure on this source only
+                                    rate_limiter.wait(&update_message).await;
+                                    (ConnectionState::Ready(attempt, geyser_stream, subscribe_tx), Some(Message::GeyserSubscribeUpdate(Box::new(update_message))))
+                                }
+                            }
+                            Ok(Some(Err(tonic_status))) if parse_oversized_status(&tonic_status).is_some() => {
+                                let (size, limit) = parse_oversized_status(&tonic_status).expect("oversized");
+                                warn!("dropped oversized update ({:?} bytes, limit {:?}) after slot {:?} on {} - resubscribing", size, limit, last_slot, grpc_source);
+                                if let Some(events) = &events {
+                                    // no subscribers is fine
+                                    let _ = events.send(SourceEvent::OversizedUpdate {
+                                        source: source_label.clone(),
+                                        slot: last_slot,
+                                        size,
+                                        limit,
+                                    });
+                                }
+                                consecutive_oversized += 1;
+                                if consecutive_oversized >= MAX_CONSECUTIVE_OVERSIZED {
+                                    // likely every update is too large - let the policy back off
+                                    consecutive_oversized = 0;
+                                    tracing::warn!(target: LIFECYCLE_TARGET, event = "disconnected", source = %source_label, attempt, reason = "oversized", "{} consecutive oversized updates on {} - retrying", MAX_CONSECUTIVE_OVERSIZED, grpc_source);
+                                    (ConnectionState::WaitReconnect(attempt), Some(Message::Connecting(attempt)))
+                                } else {
+                                    // not a failure of the source - only a short delay
+                                    sleep(OVERSIZED_RESUBSCRIBE_DELAY).await;
+                                    (ConnectionState::NotConnected(attempt), Some(Message::Connecting(attempt)))
+                                }
+                            }
+                            Ok(Some(Err(tonic_status))) => {
+                                // ATM we consider all errors recoverable
+                                tracing::warn!(target: LIFECYCLE_TARGET, event = "disconnected", source = %source_label, attempt, reason = "error", error = ?tonic_status, "error on {} - retrying", grpc_source);
+                                if let Some(events) = &events {
+                                    // no subscribers is fine
+                                    let _ = events.send(SourceEvent::Error(SourceError::from_status(source_label.clone(), ErrorPhase::Stream, &tonic_status)));
+                                }
+                                (ConnectionState::WaitReconnect(attempt), Some(Message::Connecting(attempt)))
+                            }
+                            Ok(None) =>  {
+                                // should not arrive here, Mean the stream close.
+                                tracing::warn!(target: LIFECYCLE_TARGET, event = "disconnected", source = %source_label, attempt, reason = "closed", "geyser stream closed on {} - retrying", grpc_source);
+                                (ConnectionState::WaitReconnect(attempt), Some(Message::Connecting(attempt)))
+                            }
+                            Err(_elapsed) if last_received_at.elapsed() < receive_timeout => {
+                                // woke up to send the next ping
+                                (ConnectionState::Ready(attempt, geyser_stream, subscribe_tx), None)
+                            }
+                            Err(_elapsed) => {
+                                // timeout
+                                tracing::warn!(target: LIFECYCLE_TARGET, event = "disconnected", source = %source_label, attempt, reason = "timeout", "geyser stream timeout on {} - retrying", grpc_source);
+                                (ConnectionState::WaitReconnect(attempt), Some(Message::Connecting(attempt)))
+                            }
+                        }
+                    }
                 }
 
                 ConnectionState::WaitReconnect(attempt) => {
-                    let backoff_secs = 1.5_f32.powi(attempt as i32).min(15.0);
-                    info!("waiting {} seconds, then reconnect to {}", backoff_secs, grpc_source);
-                    sleep(Duration::from_secs_f32(backoff_secs)).await;
-                    (ConnectionState::NotConnected(attempt), Message::Connecting(attempt))
+                    match reconnect_policy.on_disconnect(policy_attempt) {
+                        ReconnectDecision::Retry { wait, rotate_endpoint } => {
+                            if rotate_endpoint {
+                                endpoint_idx = (endpoint_idx + 1) % grpc_sources.len();
+                                grpc_source = grpc_sources[endpoint_idx].clone();
+                                source_label = grpc_source.label();
+                                rate_limiter = RateLimiter::new(grpc_source.rate_limit());
+                            }
+                            tracing::info!(target: LIFECYCLE_TARGET, event = "reconnect", source = %source_label, attempt, wait_ms = wait.as_millis() as u64, rotate_endpoint, "waiting {:?}, then reconnect to {}", wait, grpc_source);
+                            sleep(wait).await;
+                            (ConnectionState::NotConnected(attempt), Some(Message::Connecting(attempt)))
+                        }
+                        ReconnectDecision::GiveUp => {
+                            tracing::warn!(target: LIFECYCLE_TARGET, event = "give_up", source = %source_label, attempt, "reconnect policy gave up on {} after {} attempts", grpc_source, attempt);
+                            return;
+                        }
+                    }
                 }
 
             }; // -- match
 
-            yield yield_value
+            if let Some(yield_value) = yield_value {
+                yield yield_value
+            }
         }
 
     }; // -- stream!
@@ -133,7 +423,14 @@ pub fn create_geyser_reconnecting_stream(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::reconnect_policy::FixedDelay;
     use crate::GrpcConnectionTimeouts;
+    use futures::future;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+    use yellowstone_grpc_proto::tonic::Request;
+
+    type TestInterceptor = fn(Request<()>) -> Result<Request<()>, Status>;
 
     #[tokio::test]
     async fn test_debug_no_secrets() {
@@ -178,4 +475,36 @@ mod tests {
             "grpc_addr http://localhost:1234"
         );
     }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_client_factory_called_on_every_reconnect() {
+        let factory_calls = Arc::new(AtomicU32::new(0));
+        let calls = factory_calls.clone();
+        let policy = FixedDelay {
+            wait: Duration::from_secs(1),
+            max_attempts: Some(3),
+            rotate_endpoint: false,
+        };
+
+        let messages: Vec<Message> = create_geyser_reconnecting_stream_with_client_factory(
+            GrpcSourceConfig::new_simple("http://127.0.0.1:10000".to_string()),
+            SubscribeRequest::default(),
+            policy,
+            move |_grpc_source| {
+                calls.fetch_add(1, Ordering::Relaxed);
+                future::ready(Err::<GeyserGrpcClient<TestInterceptor>, _>(
+                    GeyserGrpcClientError::TonicStatus(Status::unavailable("connection refused")),
+                ))
+            },
+        )
+        .collect()
+        .await;
+
+        assert_eq!(factory_calls.load(Ordering::Relaxed), 3);
+        let last_attempt = messages.iter().rev().find_map(|message| match message {
+            Message::Connecting(attempt) => Some(*attempt),
+            Message::GeyserSubscribeUpdate(_) => None,
+        });
+        assert_eq!(last_attempt, Some(3));
+    }
 }
@@ -1,22 +1,29 @@
-use crate::{GrpcSourceConfig, Message};
-use futures::{Stream, StreamExt};
-use log::{debug, error, info, log, trace, warn, Level};
+use crate::grpc_stream_utils::RateLimiter;
+use crate::grpc_unary::connect;
+use crate::lifecycle::LIFECYCLE_TARGET;
+use crate::reconnect_policy::{ExponentialBackoff, ReconnectDecision, ReconnectPolicy};
+use crate::source_events::{
+    parse_oversized_status, MAX_CONSECUTIVE_OVERSIZED, OVERSIZED_RESUBSCRIBE_DELAY,
+};
+use crate::{is_keepalive_frame, ping_request, slot_of_update, Attempt, GrpcSourceConfig, Message};
+use futures::{SinkExt, Stream, StreamExt};
+use log::{debug, error, trace, warn};
 use std::time::Duration;
 use tokio::sync::mpsc::error::SendTimeoutError;
 use tokio::sync::mpsc::Receiver;
 use tokio::task::AbortHandle;
 use tokio::time::{sleep, timeout, Instant};
+use tracing::{debug_span, info_span, Instrument};
 use yellowstone_grpc_client::{GeyserGrpcClient, GeyserGrpcClientError};
 use yellowstone_grpc_proto::geyser::{SubscribeRequest, SubscribeUpdate};
 use yellowstone_grpc_proto::tonic::service::Interceptor;
 use yellowstone_grpc_proto::tonic::Status;
 
-type Attempt = u32;
-
-enum ConnectionState<S: Stream<Item = Result<SubscribeUpdate, Status>>, F: Interceptor> {
+enum ConnectionState<S: Stream<Item = Result<SubscribeUpdate, Status>>, F: Interceptor, K> {
     NotConnected(Attempt),
     Connected(Attempt, GeyserGrpcClient<F>),
-    Ready(Attempt, S),
+    // subscribe sink is kept to send keepalive pings
+    Ready(Attempt, S, K),
     // error states
     RecoverableConnectionError(Attempt),
     // non-recoverable error
@@ -37,7 +44,8 @@ pub fn create_geyser_autoconnection_task(
 ) -> (AbortHandle, Receiver<Message>) {
     let (sender, receiver_channel) = tokio::sync::mpsc::channel::<Message>(1);
 
-    let abort_handle = create_geyser_autoconnection_task_with_mpsc(grpc_source, subscribe_filter, sender);
+    let abort_handle =
+        create_geyser_autoconnection_task_with_mpsc(grpc_source, subscribe_filter, sender);
 
     (abort_handle, receiver_channel)
 }
@@ -50,42 +58,47 @@ pub fn create_geyser_autoconnection_task_with_mpsc(
     subscribe_filter: SubscribeRequest,
     mpsc_downstream: tokio::sync::mpsc::Sender<Message>,
 ) -> AbortHandle {
+    create_geyser_autoconnection_task_with_policy(
+        vec![grpc_source],
+        subscribe_filter,
+        mpsc_downstream,
+        ExponentialBackoff::default(),
+    )
+}
+
+/// like ``create_geyser_autoconnection_task_with_mpsc`` but the policy decides about waiting,
+/// giving up (the task exits) and rotating through the given endpoints
+pub fn create_geyser_autoconnection_task_with_policy(
+    grpc_sources: Vec<GrpcSourceConfig>,
+    subscribe_filter: SubscribeRequest,
+    mpsc_downstream: tokio::sync::mpsc::Sender<Message>,
+    reconnect_policy: impl ReconnectPolicy,
+) -> AbortHandle {
+    assert!(!grpc_sources.is_empty(), "need at least one grpc source");
     // read this for argument: http://www.randomhacks.net/2019/03/08/should-rust-channels-panic-on-send/
 
     // task will be aborted when downstream receiver gets dropped
     let jh_geyser_task = tokio::spawn(async move {
+        let mut endpoint_idx = 0;
+        let mut grpc_source = grpc_sources[endpoint_idx].clone();
+        let mut source_label = grpc_source.label();
+        let mut rate_limiter = RateLimiter::new(grpc_source.rate_limit());
         let mut state = ConnectionState::NotConnected(0);
         let mut messages_forwarded = 0;
+        let mut consecutive_oversized = 0;
+        // attempts since the last connection that delivered an update; the policy backs off on this
+        let mut policy_attempt: Attempt = 0;
 
         loop {
             state = match state {
                 ConnectionState::NotConnected(mut attempt) => {
                     attempt += 1;
+                    policy_attempt += 1;
 
-                    let addr = grpc_source.grpc_addr.clone();
-                    let token = grpc_source.grpc_x_token.clone();
-                    let config = grpc_source.tls_config.clone();
-                    let connect_timeout = grpc_source.timeouts.as_ref().map(|t| t.connect_timeout);
-                    let request_timeout = grpc_source.timeouts.as_ref().map(|t| t.request_timeout);
-                    log!(
-                        if attempt > 1 {
-                            Level::Warn
-                        } else {
-                            Level::Debug
-                        },
-                        "Connecting attempt #{} to {}",
-                        attempt,
-                        addr
-                    );
-                    let connect_result = GeyserGrpcClient::connect_with_timeout(
-                        addr,
-                        token,
-                        config,
-                        connect_timeout,
-                        request_timeout,
-                        false,
-                    )
-                    .await;
+                    tracing::info!(target: LIFECYCLE_TARGET, event = "connect", source = %source_label, attempt, "connecting to {}", grpc_source);
+                    let connect_result = connect(&grpc_source)
+                        .instrument(info_span!("geyser_connect", source = %source_label, attempt))
+                        .await;
 
                     match connect_result {
                         Ok(client) => ConnectionState::Connected(attempt, client),
@@ -113,22 +126,38 @@ pub fn create_geyser_autoconnection_task_with_mpsc(
                             ConnectionState::FatalError(attempt, FatalErrorReason::NetworkError)
                         }
                         Err(GeyserGrpcClientError::TonicStatus(tonic_status)) => {
-                            warn!(
-                                "connect failed on {} - retrying: {:?}",
-                                grpc_source, tonic_status
-                            );
+                            tracing::warn!(target: LIFECYCLE_TARGET, event = "subscribe_failed", source = %source_label, attempt, error = ?tonic_status, "connect failed on {} - retrying", grpc_source);
                             ConnectionState::RecoverableConnectionError(attempt)
                         }
                         Err(GeyserGrpcClientError::SubscribeSendError(send_error)) => {
-                            warn!(
-                                "connect failed with send error on {} - retrying: {:?}",
-                                grpc_source, send_error
-                            );
+                            tracing::warn!(target: LIFECYCLE_TARGET, event = "subscribe_failed", source = %source_label, attempt, error = ?send_error, "connect failed with send error on {} - retrying", grpc_source);
                             ConnectionState::RecoverableConnectionError(attempt)
                         }
                     }
                 }
                 ConnectionState::Connected(attempt, mut client) => {
+                    if grpc_source.probe_on_connect {
+                        let request_timeout = grpc_source
+                            .timeouts
+                            .as_ref()
+                            .map(|t| t.request_timeout)
+                            .unwrap_or(Duration::MAX);
+                        match timeout(request_timeout, client.get_version()).await {
+                            Ok(Ok(version)) => {
+                                debug!("Connected to geyser version {}", version.version);
+                            }
+                            Ok(Err(probe_error)) => {
+                                tracing::warn!(target: LIFECYCLE_TARGET, event = "subscribe_failed", source = %source_label, attempt, error = ?probe_error, "version probe failed on {} - retrying", grpc_source);
+                                state = ConnectionState::RecoverableConnectionError(attempt);
+                                continue;
+                            }
+                            Err(_elapsed) => {
+                                tracing::warn!(target: LIFECYCLE_TARGET, event = "subscribe_failed", source = %source_label, attempt, error = "timeout", "version probe timed out on {} - retrying", grpc_source);
+                                state = ConnectionState::RecoverableConnectionError(attempt);
+                                continue;
+                            }
+                        }
+                    }
                     let subscribe_timeout =
                         grpc_source.timeouts.as_ref().map(|t| t.subscribe_timeout);
                     let subscribe_filter = subscribe_filter.clone();
@@ -136,20 +165,24 @@ pub fn create_geyser_autoconnection_task_with_mpsc(
 
                     let subscribe_result_timeout = timeout(
                         subscribe_timeout.unwrap_or(Duration::MAX),
-                        client.subscribe_once2(subscribe_filter),
+                        client.subscribe_with_request(Some(subscribe_filter)),
                     )
+                    .instrument(info_span!("geyser_subscribe", source = %source_label, attempt))
                     .await;
 
                     match subscribe_result_timeout {
                         Ok(subscribe_result) => {
                             match subscribe_result {
-                                Ok(geyser_stream) => ConnectionState::Ready(attempt, geyser_stream),
-                                Err(GeyserGrpcClientError::TonicError(_)) => {
-                                    warn!("subscribe failed on {} - retrying", grpc_source);
+                                Ok((subscribe_tx, geyser_stream)) => {
+                                    tracing::info!(target: LIFECYCLE_TARGET, event = "subscribed", source = %source_label, attempt, "subscribed to {}", grpc_source);
+                                    ConnectionState::Ready(attempt, geyser_stream, subscribe_tx)
+                                }
+                                Err(GeyserGrpcClientError::TonicError(tonic_error)) => {
+                                    tracing::warn!(target: LIFECYCLE_TARGET, event = "subscribe_failed", source = %source_label, attempt, error = ?tonic_error, "subscribe failed on {} - retrying", grpc_source);
                                     ConnectionState::RecoverableConnectionError(attempt)
                                 }
-                                Err(GeyserGrpcClientError::TonicStatus(_)) => {
-                                    warn!("subscribe failed on {} - retrying", grpc_source);
+                                Err(GeyserGrpcClientError::TonicStatus(tonic_status)) => {
+                                    tracing::warn!(target: LIFECYCLE_TARGET, event = "subscribe_failed", source = %source_label, attempt, error = ?tonic_status, "subscribe failed on {} - retrying", grpc_source);
                                     ConnectionState::RecoverableConnectionError(attempt)
                                 }
                                 // non-recoverable
@@ -166,22 +199,32 @@ pub fn create_geyser_autoconnection_task_with_mpsc(
                             }
                         }
                         Err(_elapsed) => {
-                            warn!(
-                                "subscribe failed with timeout on {} - retrying",
-                                grpc_source
-                            );
+                            tracing::warn!(target: LIFECYCLE_TARGET, event = "subscribe_failed", source = %source_label, attempt, error = "timeout", "subscribe failed with timeout on {} - retrying", grpc_source);
                             ConnectionState::RecoverableConnectionError(attempt)
                         }
                     }
                 }
                 ConnectionState::RecoverableConnectionError(attempt) => {
-                    let backoff_secs = 1.5_f32.powi(attempt as i32).min(15.0);
-                    info!(
-                        "waiting {} seconds, then reconnect to {}",
-                        backoff_secs, grpc_source
-                    );
-                    sleep(Duration::from_secs_f32(backoff_secs)).await;
-                    ConnectionState::NotConnected(attempt)
+                    match reconnect_policy.on_disconnect(policy_attempt) {
+                        ReconnectDecision::Retry {
+                            wait,
+                            rotate_endpoint,
+                        } => {
+                            if rotate_endpoint {
+                                endpoint_idx = (endpoint_idx + 1) % grpc_sources.len();
+                                grpc_source = grpc_sources[endpoint_idx].clone();
+                                source_label = grpc_source.label();
+                                rate_limiter = RateLimiter::new(grpc_source.rate_limit());
+                            }
+                            tracing::info!(target: LIFECYCLE_TARGET, event = "reconnect", source = %source_label, attempt, wait_ms = wait.as_millis() as u64, rotate_endpoint, "waiting {:?}, then reconnect to {}", wait, grpc_source);
+                            sleep(wait).await;
+                            ConnectionState::NotConnected(attempt)
+                        }
+                        ReconnectDecision::GiveUp => {
+                            tracing::warn!(target: LIFECYCLE_TARGET, event = "give_up", source = %source_label, attempt, "reconnect policy gave up on {} after {} attempts", grpc_source, attempt);
+                            return;
+                        }
+                    }
                 }
                 ConnectionState::FatalError(_attempt, reason) => match reason {
                     FatalErrorReason::DownstreamChannelClosed => {
@@ -202,25 +245,88 @@ pub fn create_geyser_autoconnection_task_with_mpsc(
                     }
                 },
                 ConnectionState::WaitReconnect(attempt) => {
-                    let backoff_secs = 1.5_f32.powi(attempt as i32).min(15.0);
-                    info!(
-                        "waiting {} seconds, then reconnect to {}",
-                        backoff_secs, grpc_source
-                    );
-                    sleep(Duration::from_secs_f32(backoff_secs)).await;
-                    ConnectionState::NotConnected(attempt)
+                    match reconnect_policy.on_disconnect(policy_attempt) {
+                        ReconnectDecision::Retry {
+                            wait,
+                            rotate_endpoint,
+                        } => {
+                            if rotate_endpoint {
+                                endpoint_idx = (endpoint_idx + 1) % grpc_sources.len();
+                                grpc_source = grpc_sources[endpoint_idx].clone();
+                                source_label = grpc_source.label();
+                                rate_limiter = RateLimiter::new(grpc_source.rate_limit());
+                            }
+                            tracing::info!(target: LIFECYCLE_TARGET, event = "reconnect", source = %source_label, attempt, wait_ms = wait.as_millis() as u64, rotate_endpoint, "waiting {:?}, then reconnect to {}", wait, grpc_source);
+                            sleep(wait).await;
+                            ConnectionState::NotConnected(attempt)
+                        }
+                        ReconnectDecision::GiveUp => {
+                            tracing::warn!(target: LIFECYCLE_TARGET, event = "give_up", source = %source_label, attempt, "reconnect policy gave up on {} after {} attempts", grpc_source, attempt);
+                            return;
+                        }
+                    }
                 }
-                ConnectionState::Ready(attempt, mut geyser_stream) => {
-                    let receive_timeout = grpc_source.timeouts.as_ref().map(|t| t.receive_timeout);
+                ConnectionState::Ready(attempt, mut geyser_stream, mut subscribe_tx) => {
+                    let receive_timeout = grpc_source
+                        .timeouts
+                        .as_ref()
+                        .map(|t| t.receive_timeout)
+                        .unwrap_or(Duration::MAX);
+                    let mut ping_id = 0;
+                    let mut last_ping_at = Instant::now();
+                    let mut last_received_at = Instant::now();
+                    let mut awaiting_first_message = true;
                     'recv_loop: loop {
-                        match timeout(
-                            receive_timeout.unwrap_or(Duration::MAX),
-                            geyser_stream.next(),
-                        )
-                        .await
+                        if let Some(keepalive_interval) = grpc_source.keepalive_interval {
+                            if last_ping_at.elapsed() >= keepalive_interval {
+                                ping_id += 1;
+                                last_ping_at = Instant::now();
+                                trace!("> send ping {} to {}", ping_id, grpc_source);
+                                if let Err(send_error) =
+                                    subscribe_tx.send(ping_request(ping_id)).await
+                                {
+                                    tracing::warn!(target: LIFECYCLE_TARGET, event = "disconnected", source = %source_label, attempt, reason = "ping_failed", error = ?send_error, "keepalive ping failed on {} - retrying", grpc_source);
+                                    break 'recv_loop ConnectionState::WaitReconnect(attempt);
+                                }
+                            }
+                        }
+                        // wake up in time for the next ping
+                        let wait_for = grpc_source.keepalive_interval.map_or(
+                            receive_timeout,
+                            |keepalive_interval| {
+                                keepalive_interval
+                                    .saturating_sub(last_ping_at.elapsed())
+                                    .min(receive_timeout)
+                            },
+                        );
+
+                        let receive_span = debug_span!(
+                            "geyser_receive",
+                            source = %source_label,
+                            slot = tracing::field::Empty
+                        );
+                        match timeout(wait_for, geyser_stream.next())
+                            .instrument(receive_span.clone())
+                            .await
                         {
                             Ok(Some(Ok(update_message))) => {
+                                last_received_at = Instant::now();
+                                if is_keepalive_frame(&update_message) {
+                                    trace!("> recv ping/pong from {}", grpc_source);
+                                    continue 'recv_loop;
+                                }
                                 trace!("> recv update message from {}", grpc_source);
+                                consecutive_oversized = 0;
+                                if let Some(slot) = slot_of_update(&update_message) {
+                                    receive_span.record("slot", slot);
+                                }
+                                if awaiting_first_message {
+                                    awaiting_first_message = false;
+                                    // a healthy connection starts a new series of attempts
+                                    policy_attempt = 1;
+                                    tracing::info!(target: LIFECYCLE_TARGET, event = "first_message", source = %source_label, attempt, slot = ?slot_of_update(&update_message), "first update from {}", grpc_source);
+                                }
+                                rate_limiter.wait(&update_message).await;
                                 // note: first send never blocks as the mpsc channel has capacity 1
                                 let warning_threshold = if messages_forwarded == 1 {
                                     Duration::from_millis(3000)
@@ -279,17 +385,40 @@ pub fn create_geyser_autoconnection_task_with_mpsc(
                                     }
                                 }
                             }
+                            Ok(Some(Err(tonic_status)))
+                                if parse_oversized_status(&tonic_status).is_some() =>
+                            {
+                                warn!(
+                                    "dropped oversized update on {} - resubscribing: {}",
+                                    grpc_source,
+                                    tonic_status.message()
+                                );
+                                consecutive_oversized += 1;
+                                if consecutive_oversized >= MAX_CONSECUTIVE_OVERSIZED {
+                                    // likely every update is too large - let the policy back off
+                                    consecutive_oversized = 0;
+                                    tracing::warn!(target: LIFECYCLE_TARGET, event = "disconnected", source = %source_label, attempt, reason = "oversized", "{} consecutive oversized updates on {} - retrying", MAX_CONSECUTIVE_OVERSIZED, grpc_source);
+                                    break 'recv_loop ConnectionState::WaitReconnect(attempt);
+                                }
+                                // not a failure of the source - only a short delay
+                                sleep(OVERSIZED_RESUBSCRIBE_DELAY).await;
+                                break 'recv_loop ConnectionState::NotConnected(attempt);
+                            }
                             Ok(Some(Err(tonic_status))) => {
                                 // all tonic errors are recoverable
-                                warn!("error on {} - retrying: {:?}", grpc_source, tonic_status);
+                                tracing::warn!(target: LIFECYCLE_TARGET, event = "disconnected", source = %source_label, attempt, reason = "error", error = ?tonic_status, "error on {} - retrying", grpc_source);
                                 break 'recv_loop ConnectionState::WaitReconnect(attempt);
                             }
                             Ok(None) => {
-                                warn!("geyser stream closed on {} - retrying", grpc_source);
+                                tracing::warn!(target: LIFECYCLE_TARGET, event = "disconnected", source = %source_label, attempt, reason = "closed", "geyser stream closed on {} - retrying", grpc_source);
                                 break 'recv_loop ConnectionState::WaitReconnect(attempt);
                             }
+                            Err(_elapsed) if last_received_at.elapsed() < receive_timeout => {
+                                // woke up to send the next ping
+                                continue 'recv_loop;
+                            }
                             Err(_elapsed) => {
-                                warn!("timeout on {} - retrying", grpc_source);
+                                tracing::warn!(target: LIFECYCLE_TARGET, event = "disconnected", source = %source_label, attempt, reason = "timeout", "timeout on {} - retrying", grpc_source);
                                 break 'recv_loop ConnectionState::WaitReconnect(attempt);
                             }
                         }
@@ -0,0 +1,294 @@
+use crate::auth::AuthInterceptor;
+use crate::commitment::CommitmentConfig;
+use crate::connector::{unix_socket_path, ProxyConfig};
+use crate::{map_commitment_level, GrpcSourceConfig, Slot};
+use anyhow::{anyhow, bail};
+use futures::stream::FuturesUnordered;
+use futures::{Future, StreamExt};
+use log::debug;
+use std::time::{Duration, Instant};
+use tokio::time::timeout;
+use tonic_health::pb::health_client::HealthClient;
+use tower::service_fn;
+use yellowstone_grpc_client::{GeyserGrpcClient, GeyserGrpcClientError, GeyserGrpcClientResult};
+use yellowstone_grpc_proto::geyser::geyser_client::GeyserClient;
+use yellowstone_grpc_proto::geyser::GetLatestBlockhashResponse;
+use yellowstone_grpc_proto::tonic::transport::{Channel, Uri};
+use yellowstone_grpc_proto::tonic::{Code, Status};
+
+// bound of a unary call on a source without timeouts, e.g. a black-holed endpoint
+const DEFAULT_UNARY_TIMEOUT: Duration = Duration::from_secs(10);
+
+// connect with the timeouts and auth scheme of the source; no subscription
+pub(crate) async fn connect(
+    grpc_source: &GrpcSourceConfig,
+) -> GeyserGrpcClientResult<GeyserGrpcClient<AuthInterceptor>> {
+    let interceptor = grpc_source
+        .auth
+        .interceptor(grpc_source.x_token().await?)?
+        .with_request_interceptors(grpc_source.request_interceptors.clone());
+
+    let unix_socket_path = unix_socket_path(&grpc_source.grpc_addr).map(ToString::to_string);
+    // tonic needs a http uri even if the connector ignores it
+    let endpoint_addr = match unix_socket_path {
+        Some(_) => "http://localhost".to_string(),
+        None => grpc_source.grpc_addr.clone(),
+    };
+    let mut endpoint = Channel::from_shared(endpoint_addr)?;
+    if let Some(timeouts) = &grpc_source.timeouts {
+        endpoint = endpoint
+            .connect_timeout(timeouts.connect_timeout)
+            .timeout(timeouts.request_timeout);
+    }
+    if let Some(tls_config) = &grpc_source.tls_config {
+        endpoint = endpoint.tls_config(tls_config.clone())?;
+    }
+    let channel = match (unix_socket_path, &grpc_source.proxy_url) {
+        #[cfg(unix)]
+        (Some(path), _) => {
+            endpoint
+                .connect_with_connector(service_fn(move |_: Uri| {
+                    tokio::net::UnixStream::connect(path.clone())
+                }))
+                .await?
+        }
+        #[cfg(not(unix))]
+        (Some(_), _) => {
+            return Err(
+                Status::invalid_argument("unix sockets not supported on this platform").into(),
+            )
+        }
+        (None, Some(proxy_url)) => {
+            let proxy = ProxyConfig::parse(proxy_url)
+                .map_err(|err| Status::invalid_argument(format!("invalid proxy url: {err}")))?;
+            endpoint
+                .connect_with_connector(service_fn(move |target: Uri| {
+                    let proxy = proxy.clone();
+                    async move { proxy.connect(&target).await }
+                }))
+                .await?
+        }
+        (None, None) => endpoint.connect().await?,
+    };
+
+    Ok(GeyserGrpcClient::new(
+        HealthClient::with_interceptor(channel.clone(), interceptor.clone()),
+        // blocks easily exceed the tonic default limit of 4MB
+        GeyserClient::with_interceptor(channel, interceptor)
+            .max_decoding_message_size(grpc_source.max_decoding_message_size.unwrap_or(usize::MAX)),
+    ))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProbeAuth {
+    Accepted,
+    // x-token missing, invalid or without permission
+    Rejected,
+}
+
+/// result of ``probe_source``
+#[derive(Clone, Debug)]
+pub struct SourceProbe {
+    // None if the request was rejected
+    pub version: Option<String>,
+    pub connect_latency: Duration,
+    // round trip of get_version
+    pub request_latency: Duration,
+    pub auth: ProbeAuth,
+}
+
+/// validate a source without subscribing: connect and call get_version
+/// returns an error if the endpoint is unreachable or fails for other reasons than auth
+pub async fn probe_source(grpc_source: &GrpcSourceConfig) -> anyhow::Result<SourceProbe> {
+    let started_at = Instant::now();
+    let mut client = connect(grpc_source).await?;
+    let connect_latency = started_at.elapsed();
+
+    let started_at = Instant::now();
+    let version_result = client.get_version().await;
+    let request_latency = started_at.elapsed();
+
+    let (version, auth) = match version_result {
+        Ok(response) => (Some(response.version), ProbeAuth::Accepted),
+        Err(GeyserGrpcClientError::TonicStatus(status))
+            if matches!(
+                status.code(),
+                Code::Unauthenticated | Code::PermissionDenied
+            ) =>
+        {
+            (None, ProbeAuth::Rejected)
+        }
+        Err(err) => return Err(err.into()),
+    };
+    debug!(
+        "probed {}: version {:?}, auth {:?}, connect {:?}, request {:?}",
+        grpc_source, version, auth, connect_latency, request_latency
+    );
+
+    Ok(SourceProbe {
+        version,
+        connect_latency,
+        request_latency,
+        auth,
+    })
+}
+
+/// version string of the source answering first
+pub async fn race_get_version(grpc_sources: &[GrpcSourceConfig]) -> anyhow::Result<String> {
+    let calls = grpc_sources.iter().map(|grpc_source| async move {
+        let mut client = connect(grpc_source).await?;
+        Ok::<_, anyhow::Error>(client.get_version().await?.version)
+    });
+    first_ok(grpc_sources, calls).await
+}
+
+/// highest slot reported by any source; waits for all sources (bounded by their request timeout)
+pub async fn race_get_slot(
+    grpc_sources: &[GrpcSourceConfig],
+    commitment: CommitmentConfig,
+) -> anyhow::Result<Slot> {
+    let commitment_level = map_commitment_level(commitment);
+    let calls = grpc_sources.iter().map(|grpc_source| async move {
+        let mut client = connect(grpc_source).await?;
+        Ok::<_, anyhow::Error>(client.get_slot(Some(commitment_level)).await?.slot)
+    });
+    highest_ok(grpc_sources, calls, |slot| *slot).await
+}
+
+/// highest block height reported by any source
+pub async fn race_get_block_height(
+    grpc_sources: &[GrpcSourceConfig],
+    commitment: CommitmentConfig,
+) -> anyhow::Result<u64> {
+    let commitment_level = map_commitment_level(commitment);
+    let calls = grpc_sources.iter().map(|grpc_source| async move {
+        let mut client = connect(grpc_source).await?;
+        Ok::<_, anyhow::Error>(
+            client
+                .get_block_height(Some(commitment_level))
+                .await?
+                .block_height,
+        )
+    });
+    highest_ok(grpc_sources, calls, |block_height| *block_height).await
+}
+
+/// latest blockhash of the source which is furthest ahead
+pub async fn race_get_latest_blockhash(
+    grpc_sources: &[GrpcSourceConfig],
+    commitment: CommitmentConfig,
+) -> anyhow::Result<GetLatestBlockhashResponse> {
+    let commitment_level = map_commitment_level(commitment);
+    let calls = grpc_sources.iter().map(|grpc_source| async move {
+        let mut client = connect(grpc_source).await?;
+        Ok::<_, anyhow::Error>(client.get_latest_blockhash(Some(commitment_level)).await?)
+    });
+    highest_ok(grpc_sources, calls, |response| response.slot).await
+}
+
+// connect plus request of the source
+async fn with_timeout<T>(
+    grpc_source: &GrpcSourceConfig,
+    call: impl Future<Output = anyhow::Result<T>>,
+) -> anyhow::Result<T> {
+    let call_timeout = grpc_source
+        .timeouts
+        .as_ref()
+        .map_or(DEFAULT_UNARY_TIMEOUT, |timeouts| {
+            timeouts.connect_timeout + timeouts.request_timeout
+        });
+    timeout(call_timeout, call)
+        .await
+        .map_err(|_| anyhow!("no answer from {} within {:?}", grpc_source, call_timeout))?
+}
+
+// first successful answer; error only if all sources failed
+async fn first_ok<T>(
+    grpc_sources: &[GrpcSourceConfig],
+    calls: impl Iterator<Item = impl Future<Output = anyhow::Result<T>>>,
+) -> anyhow::Result<T> {
+    if grpc_sources.is_empty() {
+        bail!("Must have at least one grpc source");
+    }
+    let mut pending: FuturesUnordered<_> = calls
+        .zip(grpc_sources)
+        .map(|(call, grpc_source)| with_timeout(grpc_source, call))
+        .enumerate()
+        .map(|(idx, call)| async move { (idx, call.await) })
+        .collect();
+    let mut last_error = None;
+    while let Some((idx, result)) = pending.next().await {
+        match result {
+            Ok(answer) => {
+                debug!("{} answered first", grpc_sources[idx]);
+                return Ok(answer);
+            }
+            Err(err) => {
+                debug!("unary call on {} failed: {:?}", grpc_sources[idx], err);
+                last_error = Some(err);
+            }
+        }
+    }
+    Err(last_error.unwrap_or_else(|| anyhow!("no answer")))
+}
+
+// answer with the highest key among all successful answers; a source without answer delays the
+// result by its timeout
+async fn highest_ok<T, K: Ord>(
+    grpc_sources: &[GrpcSourceConfig],
+    calls: impl Iterator<Item = impl Future<Output = anyhow::Result<T>>>,
+    key: impl Fn(&T) -> K,
+) -> anyhow::Result<T> {
+    if grpc_sources.is_empty() {
+        bail!("Must have at least one grpc source");
+    }
+    let results = futures::future::join_all(
+        calls
+            .zip(grpc_sources)
+            .map(|(call, grpc_source)| with_timeout(grpc_source, call)),
+    )
+    .await;
+    let mut last_error = None;
+    let mut highest: Option<T> = None;
+    for (idx, result) in results.into_iter().enumerate() {
+        match result {
+            Ok(answer) => {
+                if highest
+                    .as_ref()
+                    .map_or(true, |best| key(&answer) > key(best))
+                {
+                    highest = Some(answer);
+                }
+            }
+            Err(err) => {
+                debug!("unary call on {} failed: {:?}", grpc_sources[idx], err);
+                last_error = Some(err);
+            }
+        }
+    }
+    highest.ok_or_else(|| last_error.unwrap_or_else(|| anyhow!("no answer")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_black_hole_source_times_out() {
+        // accepts tcp in the kernel backlog but never answers
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let grpc_sources = vec![GrpcSourceConfig::new_simple(format!(
+            "http://{}",
+            listener.local_addr().unwrap()
+        ))];
+
+        let started_at = tokio::time::Instant::now();
+        assert!(race_get_version(&grpc_sources).await.is_err());
+        assert!(race_get_slot(&grpc_sources, CommitmentConfig::confirmed())
+            .await
+            .is_err());
+        assert!(started_at.elapsed() <= DEFAULT_UNARY_TIMEOUT * 2);
+        drop(listener);
+    }
+}
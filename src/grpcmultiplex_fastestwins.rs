@@ -1,16 +1,173 @@
-use crate::Message;
+use crate::block_source::BlockSource;
+use crate::commitment::CommitmentLevel;
+use crate::grpc_stream_utils::{
+    channelize_stream_with_capacity, AbortOnDrop, ChannelizerCompletion, CHANNELIZER_CAPACITY,
+};
+use crate::grpc_subscription_autoreconnect_streams::create_geyser_reconnecting_stream;
+use crate::lifecycle::LIFECYCLE_TARGET;
+use crate::multiplex_diagnostics::{
+    BlockFingerprint, ConsistencyViolation, DeadLetter, DeadLetterReason, LateArrival,
+    LateArrivalDiagnostics,
+};
+use crate::multiplexer_stats::MultiplexerStats;
 use crate::Message::GeyserSubscribeUpdate;
+use crate::{
+    is_keepalive_frame, slot_of_update, Attempt, GeyserFilter, GeyserFilterKind, GrpcSourceConfig,
+    Message, Slot,
+};
 use async_stream::stream;
-use futures::{Stream, StreamExt};
-use log::{info, warn};
+use futures::future::{self, BoxFuture};
+use futures::stream::BoxStream;
+use futures::{FutureExt, Stream, StreamExt};
+use log::{debug, info, warn};
 use merge_streams::MergeStreams;
-use solana_sdk::clock::Slot;
-use yellowstone_grpc_proto::geyser::SubscribeUpdate;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::marker::PhantomData;
+use std::pin::pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::JoinHandle;
+use tokio::time::{timeout, Instant};
+use yellowstone_grpc_proto::geyser::subscribe_update::UpdateOneof;
+use yellowstone_grpc_proto::geyser::{
+    SubscribeRequest, SubscribeUpdate, SubscribeUpdateBlock, SubscribeUpdateBlockMeta,
+};
+
+// how many yielded slots we remember to measure late arrivals
+const LATE_ARRIVAL_SLOT_WINDOW: u64 = 256;
 
 pub trait FromYellowstoneExtractor {
     // Target is something like ProducedBlock
     type Target;
     fn map_yellowstone_update(&self, update: SubscribeUpdate) -> Option<(Slot, Self::Target)>;
+
+    /// like ``map_yellowstone_update`` but hands back an update it does not extract, used for dead
+    /// letters; the default clones every update, override it to move the update back instead
+    // the update is handed back as received, boxing it would allocate for every dead letter
+    #[allow(clippy::result_large_err)]
+    fn try_map_yellowstone_update(
+        &self,
+        update: SubscribeUpdate,
+    ) -> Result<(Slot, Self::Target), SubscribeUpdate> {
+        self.map_yellowstone_update(update.clone()).ok_or(update)
+    }
+}
+
+/// generic variant of ``FromYellowstoneExtractor``: one extractor type can build several output
+/// types (blocks, accounts, transactions, ...) and declares the filters it needs
+pub trait FromGeyserUpdate<T> {
+    // named after the trait, the extractor itself is not converted
+    #[allow(clippy::wrong_self_convention)]
+    fn from_geyser_update(&self, update: SubscribeUpdate) -> Option<(Slot, T)>;
+    /// update kinds this extractor handles
+    fn filter_kinds(&self) -> Vec<GeyserFilterKind>;
+}
+
+/// adapter to use a ``FromGeyserUpdate`` with the multiplexer
+pub struct GeyserUpdateExtractor<X, T> {
+    extractor: X,
+    _target: PhantomData<fn() -> T>,
+}
+
+impl<X: FromGeyserUpdate<T>, T> GeyserUpdateExtractor<X, T> {
+    pub fn new(extractor: X) -> Self {
+        Self {
+            extractor,
+            _target: PhantomData,
+        }
+    }
+
+    /// request covering all filter kinds declared by the extractor
+    pub fn subscribe_request(&self, filter: &GeyserFilter) -> SubscribeRequest {
+        filter.subscribe_request(&self.extractor.filter_kinds())
+    }
+}
+
+impl<X: FromGeyserUpdate<T>, T> FromYellowstoneExtractor for GeyserUpdateExtractor<X, T> {
+    type Target = T;
+    fn map_yellowstone_update(&self, update: SubscribeUpdate) -> Option<(Slot, T)> {
+        self.extractor.from_geyser_update(update)
+    }
+}
+
+/// yields full blocks; use with ``GeyserFilter::blocks_and_txs``
+pub struct BlockExtractor;
+
+impl FromYellowstoneExtractor for BlockExtractor {
+    type Target = SubscribeUpdateBlock;
+    fn map_yellowstone_update(&self, update: SubscribeUpdate) -> Option<(Slot, Self::Target)> {
+        self.try_map_yellowstone_update(update).ok()
+    }
+
+    fn try_map_yellowstone_update(
+        &self,
+        update: SubscribeUpdate,
+    ) -> Result<(Slot, Self::Target), SubscribeUpdate> {
+        match update.update_oneof {
+            Some(UpdateOneof::Block(block)) => Ok((block.slot, block)),
+            update_oneof => Err(SubscribeUpdate {
+                update_oneof,
+                ..update
+            }),
+        }
+    }
+}
+
+pub struct BlockMetaExtractor;
+
+impl FromYellowstoneExtractor for BlockMetaExtractor {
+    type Target = SubscribeUpdateBlockMeta;
+    fn map_yellowstone_update(&self, update: SubscribeUpdate) -> Option<(Slot, Self::Target)> {
+        self.try_map_yellowstone_update(update).ok()
+    }
+
+    fn try_map_yellowstone_update(
+        &self,
+        update: SubscribeUpdate,
+    ) -> Result<(Slot, Self::Target), SubscribeUpdate> {
+        match update.update_oneof {
+            Some(UpdateOneof::BlockMeta(block_meta)) => Ok((block_meta.slot, block_meta)),
+            update_oneof => Err(SubscribeUpdate {
+                update_oneof,
+                ..update
+            }),
+        }
+    }
+}
+
+/// wraps the payload of an extractor in an ``Arc``, e.g. to hand multi-MB blocks to many tasks
+/// without cloning; see ``create_multiplexed_stream_arc``
+pub struct ArcExtractor<E>(pub E);
+
+impl<E: FromYellowstoneExtractor> FromYellowstoneExtractor for ArcExtractor<E> {
+    type Target = Arc<E::Target>;
+    fn map_yellowstone_update(&self, update: SubscribeUpdate) -> Option<(Slot, Self::Target)> {
+        self.0
+            .map_yellowstone_update(update)
+            .map(|(slot, payload)| (slot, Arc::new(payload)))
+    }
+
+    fn try_map_yellowstone_update(
+        &self,
+        update: SubscribeUpdate,
+    ) -> Result<(Slot, Self::Target), SubscribeUpdate> {
+        self.0
+            .try_map_yellowstone_update(update)
+            .map(|(slot, payload)| (slot, Arc::new(payload)))
+    }
+}
+
+/// like ``FromYellowstoneExtractor`` but extraction may await, e.g. to enrich from a cache
+/// note: the returned future must not borrow from the extractor; clone what is needed into it
+pub trait AsyncExtractor {
+    type Target;
+    fn map_yellowstone_update(
+        &self,
+        update: SubscribeUpdate,
+    ) -> BoxFuture<'static, Option<(Slot, Self::Target)>>;
 }
 
 /// use streams created by ``create_geyser_reconnecting_stream``
@@ -23,6 +180,795 @@ pub fn create_multiplexed_stream<E>(
 where
     E: FromYellowstoneExtractor,
 {
+    let merged_streams = merge_tagged_streams(grpc_source_streams);
+    extract_payload_from_geyser_updates(merged_streams, extractor, MultiplexObservers::default())
+}
+
+/// like ``create_multiplexed_stream`` but yields shared payloads, e.g. to feed ``channelize_stream``
+/// without a deep clone per receiver
+pub fn create_multiplexed_stream_arc<E>(
+    grpc_source_streams: Vec<impl Stream<Item = Message>>,
+    extractor: E,
+) -> impl Stream<Item = Arc<E::Target>>
+where
+    E: FromYellowstoneExtractor,
+{
+    create_multiplexed_stream(grpc_source_streams, ArcExtractor(extractor))
+}
+
+/// blocks meta (blockhash, parent, block height, tx count) from all sources, one per slot
+/// use this instead of full blocks if transactions are not needed - a fraction of the bandwidth
+pub fn create_multiplex_blockmeta(
+    grpc_sources: Vec<GrpcSourceConfig>,
+    commitment_level: CommitmentLevel,
+) -> impl Stream<Item = SubscribeUpdateBlockMeta> {
+    let streams: Vec<_> = grpc_sources
+        .into_iter()
+        .map(|grpc_source| {
+            create_geyser_reconnecting_stream(
+                grpc_source,
+                GeyserFilter(commitment_level.into()).blocks_meta(),
+            )
+        })
+        .collect();
+    create_multiplexed_stream(streams, BlockMetaExtractor)
+}
+
+/// update as received from one source, see ``create_multiplex_raw``
+#[derive(Clone, Debug)]
+pub struct SourceUpdate {
+    // ``GrpcSourceConfig::label`` of the source
+    pub source: Arc<str>,
+    pub update: Box<SubscribeUpdate>,
+}
+
+/// all updates of all sources merged in arrival order - no extraction, no dedup; reconnects are
+/// handled per source like in ``create_geyser_reconnecting_stream``
+pub fn create_multiplex_raw(
+    grpc_sources: Vec<GrpcSourceConfig>,
+    subscribe_filter: SubscribeRequest,
+) -> impl Stream<Item = SourceUpdate> {
+    let streams: Vec<_> = grpc_sources
+        .into_iter()
+        .map(|grpc_source| {
+            let source: Arc<str> = grpc_source.label().into();
+            create_geyser_reconnecting_stream(grpc_source, subscribe_filter.clone()).filter_map(
+                move |message| {
+                    future::ready(match message {
+                        GeyserSubscribeUpdate(update) => Some(SourceUpdate {
+                            source: source.clone(),
+                            update,
+                        }),
+                        Message::Connecting(_) => None,
+                    })
+                },
+            )
+        })
+        .collect();
+    streams.merge()
+}
+
+/// item of ``create_multiplex_bounded``
+#[derive(Clone, Debug, PartialEq)]
+pub enum BoundedItem<T> {
+    Data(T),
+    // the source reconnected, e.g. because the server dropped it for reading too slowly; it
+    // resumes at the live tip, so slots in between are missing unless another source had them
+    SourceReconnected { source_idx: usize, attempt: Attempt },
+}
+
+/// fully bounded pipeline: when the consumer falls behind, the bounded channel fills up, the
+/// multiplexer stops polling and grpc flow control pushes back to the sources - there is no
+/// in-process drop (unlike broadcast from ``channelize_stream``); the task ends when the receiver is
+/// dropped
+///
+/// yellowstone servers disconnect clients which stay behind for too long; such a reconnect is
+/// reported as ``BoundedItem::SourceReconnected`` close to (not exactly at) the gap in the data
+pub fn create_multiplex_bounded<E>(
+    grpc_sources: Vec<GrpcSourceConfig>,
+    subscribe_filter: SubscribeRequest,
+    extractor: E,
+    capacity: usize,
+) -> (mpsc::Receiver<BoundedItem<E::Target>>, JoinHandle<()>)
+where
+    E: FromYellowstoneExtractor + Send + 'static,
+    E::Target: Send + 'static,
+{
+    let streams: Vec<_> = grpc_sources
+        .into_iter()
+        .map(|grpc_source| create_geyser_reconnecting_stream(grpc_source, subscribe_filter.clone()))
+        .collect();
+    forward_bounded(with_reconnect_markers(streams, extractor), capacity)
+}
+
+fn with_reconnect_markers<E>(
+    grpc_source_streams: Vec<impl Stream<Item = Message> + Send + 'static>,
+    extractor: E,
+) -> impl Stream<Item = BoundedItem<E::Target>>
+where
+    E: FromYellowstoneExtractor,
+{
+    let (reconnects_tx, reconnects_rx) = mpsc::unbounded_channel();
+    let streams: Vec<_> = grpc_source_streams
+        .into_iter()
+        .enumerate()
+        .map(|(source_idx, grpc_stream)| {
+            let reconnects_tx = reconnects_tx.clone();
+            grpc_stream.inspect(move |message| {
+                if let Message::Connecting(attempt) = message {
+                    if *attempt > 1 {
+                        let _ = reconnects_tx.send(BoundedItem::SourceReconnected {
+                            source_idx,
+                            attempt: *attempt,
+                        });
+                    }
+                }
+            })
+        })
+        .collect();
+    // the markers end once all source streams are dropped
+    drop(reconnects_tx);
+    let mut reconnects_rx = reconnects_rx;
+    let reconnects = stream! {
+        while let Some(marker) = reconnects_rx.recv().await {
+            yield marker;
+        }
+    };
+    futures::stream::select(
+        create_multiplexed_stream(streams, extractor).map(BoundedItem::Data),
+        reconnects,
+    )
+}
+
+/// memory footprint of the multiplex pipeline
+#[derive(Clone, Debug)]
+pub struct MultiplexOptions {
+    // messages retained by the broadcast channel for lagging receivers; must be at least 1
+    pub channel_capacity: usize,
+    // updates read ahead per source while the multiplexer is busy; 0 polls the sources directly
+    pub source_buffer: usize,
+}
+
+impl Default for MultiplexOptions {
+    fn default() -> Self {
+        MultiplexOptions {
+            channel_capacity: CHANNELIZER_CAPACITY,
+            source_buffer: 0,
+        }
+    }
+}
+
+/// like ``create_multiplexed_stream`` with per-source read-ahead from ``options``
+pub fn create_multiplexed_stream_with_options<E>(
+    grpc_source_streams: Vec<impl Stream<Item = Message> + Send + 'static>,
+    extractor: E,
+    options: &MultiplexOptions,
+) -> impl Stream<Item = E::Target>
+where
+    E: FromYellowstoneExtractor,
+{
+    let source_buffer = options.source_buffer;
+    let streams: Vec<BoxStream<'static, Message>> = grpc_source_streams
+        .into_iter()
+        .map(|grpc_stream| {
+            if source_buffer == 0 {
+                grpc_stream.boxed()
+            } else {
+                read_ahead(grpc_stream, source_buffer).boxed()
+            }
+        })
+        .collect();
+    create_multiplexed_stream(streams, extractor)
+}
+
+/// multiplex and channelize with the capacities from ``options``
+pub fn channelize_multiplexed_stream<E>(
+    grpc_source_streams: Vec<impl Stream<Item = Message> + Send + 'static>,
+    extractor: E,
+    options: &MultiplexOptions,
+) -> (
+    broadcast::Receiver<E::Target>,
+    JoinHandle<ChannelizerCompletion>,
+)
+where
+    E: FromYellowstoneExtractor + Send + 'static,
+    E::Target: Clone + Send + 'static,
+{
+    let multiplexed =
+        create_multiplexed_stream_with_options(grpc_source_streams, extractor, options);
+    channelize_stream_with_capacity(multiplexed, options.channel_capacity)
+}
+
+// the forwarding task is aborted when the returned stream is dropped
+pub(crate) fn read_ahead(
+    source_stream: impl Stream<Item = Message> + Send + 'static,
+    buffer: usize,
+) -> impl Stream<Item = Message> {
+    let (tx, mut rx) = mpsc::channel(buffer);
+    let jh_read_ahead = tokio::spawn(async move {
+        let mut source_stream = pin!(source_stream);
+        while let Some(message) = source_stream.next().await {
+            if tx.send(message).await.is_err() {
+                break;
+            }
+        }
+    });
+    let abort_on_drop = AbortOnDrop(jh_read_ahead);
+    stream! {
+        let _abort_on_drop = abort_on_drop;
+        while let Some(message) = rx.recv().await {
+            yield message;
+        }
+    }
+}
+
+fn forward_bounded<T: Send + 'static>(
+    source_stream: impl Stream<Item = T> + Send + 'static,
+    capacity: usize,
+) -> (mpsc::Receiver<T>, JoinHandle<()>) {
+    let (tx, rx) = mpsc::channel(capacity);
+    let jh_forwarder = tokio::spawn(async move {
+        let mut source_stream = pin!(source_stream);
+        while let Some(item) = source_stream.next().await {
+            let item = match tx.try_send(item) {
+                Ok(()) => continue,
+                Err(mpsc::error::TrySendError::Closed(_)) => break,
+                Err(mpsc::error::TrySendError::Full(item)) => item,
+            };
+            debug!("bounded channel is full - applying backpressure");
+            if tx.send(item).await.is_err() {
+                break;
+            }
+        }
+        debug!("bounded multiplexer stopped");
+    });
+    (rx, jh_forwarder)
+}
+
+/// item of ``create_multiplexed_stream_with_heartbeat``
+#[derive(Clone, Debug, PartialEq)]
+pub enum MultiplexItem<T> {
+    Data(T),
+    // nothing was yielded within the heartbeat interval
+    Heartbeat {
+        // highest slot received from any source, also if it was not yielded
+        highest_slot: Option<Slot>,
+        // sources which delivered updates since their last (re)connect
+        sources_ready: usize,
+    },
+}
+
+/// like ``create_multiplexed_stream`` but yields a ``MultiplexItem::Heartbeat`` whenever no data
+/// was yielded for ``heartbeat_interval``, so consumers can tell a quiet chain (sources ready) from
+/// a stalled pipeline (no source ready)
+pub fn create_multiplexed_stream_with_heartbeat<E>(
+    grpc_source_streams: Vec<impl Stream<Item = Message>>,
+    extractor: E,
+    heartbeat_interval: Duration,
+) -> impl Stream<Item = MultiplexItem<E::Target>>
+where
+    E: FromYellowstoneExtractor,
+{
+    let ready = Arc::new(Mutex::new(vec![false; grpc_source_streams.len()]));
+    let highest_slot = Arc::new(Mutex::new(None::<Slot>));
+    let observed_streams: Vec<_> = grpc_source_streams
+        .into_iter()
+        .enumerate()
+        .map(|(stream_idx, grpc_stream)| {
+            let ready = ready.clone();
+            let highest_slot = highest_slot.clone();
+            grpc_stream.inspect(move |message| match message {
+                GeyserSubscribeUpdate(update) => {
+                    ready.lock().unwrap()[stream_idx] = true;
+                    if let Some(slot) = slot_of_update(update) {
+                        let mut highest_slot = highest_slot.lock().unwrap();
+                        *highest_slot = highest_slot.max(Some(slot));
+                    }
+                }
+                Message::Connecting(_) => ready.lock().unwrap()[stream_idx] = false,
+            })
+        })
+        .collect();
+    let multiplexed = create_multiplexed_stream(observed_streams, extractor);
+
+    stream! {
+        let mut multiplexed = pin!(multiplexed);
+        loop {
+            // next() is cancel-safe
+            match timeout(heartbeat_interval, multiplexed.next()).await {
+                Ok(Some(payload)) => yield MultiplexItem::Data(payload),
+                Ok(None) => return,
+                Err(_elapsed) => {
+                    let sources_ready = ready.lock().unwrap().iter().filter(|ready| **ready).count();
+                    let highest_slot = *highest_slot.lock().unwrap();
+                    yield MultiplexItem::Heartbeat { highest_slot, sources_ready };
+                }
+            }
+        }
+    }
+}
+
+/// like ``create_multiplexed_stream`` but accepts heterogeneous sources, e.g. grpc together with a
+/// websocket fallback; stream indices follow the order of ``sources``
+pub fn create_multiplexed_stream_from_sources<E>(
+    sources: Vec<Box<dyn BlockSource>>,
+    extractor: E,
+) -> impl Stream<Item = E::Target>
+where
+    E: FromYellowstoneExtractor,
+{
+    let source_streams = sources
+        .into_iter()
+        .enumerate()
+        .map(|(stream_idx, source)| {
+            info!("Stream-{} is source {}", stream_idx, source.label());
+            source.into_stream()
+        })
+        .collect();
+    create_multiplexed_stream(source_streams, extractor)
+}
+
+/// like ``create_multiplexed_stream`` but records for each source how much later it delivered
+/// slots that were already yielded from a faster source
+pub fn create_multiplexed_stream_with_diagnostics<E>(
+    grpc_source_streams: Vec<impl Stream<Item = Message>>,
+    extractor: E,
+) -> (impl Stream<Item = E::Target>, LateArrivalDiagnostics)
+where
+    E: FromYellowstoneExtractor,
+{
+    let diagnostics = LateArrivalDiagnostics::new();
+    let merged_streams = merge_tagged_streams(grpc_source_streams);
+    let observers = MultiplexObservers {
+        late_arrivals: Some(diagnostics.clone()),
+        ..Default::default()
+    };
+    let multiplexed = extract_payload_from_geyser_updates(merged_streams, extractor, observers);
+    (multiplexed, diagnostics)
+}
+
+/// like ``create_multiplexed_stream`` but tracks each labeled source in ``stats`` including how
+/// often it won or lost a slot against the other sources
+pub fn create_multiplexed_stream_with_stats<E>(
+    labeled_streams: Vec<(String, impl Stream<Item = Message>)>,
+    extractor: E,
+    stats: &MultiplexerStats,
+) -> impl Stream<Item = E::Target>
+where
+    E: FromYellowstoneExtractor,
+{
+    let (labels, streams): (Vec<String>, Vec<_>) = labeled_streams
+        .into_iter()
+        .map(|(label, source_stream)| {
+            let tracked = stats.track(label.clone(), source_stream);
+            (label, tracked)
+        })
+        .unzip();
+    let merged_streams = merge_tagged_streams(streams);
+    let observers = MultiplexObservers {
+        source_stats: Some((stats.clone(), labels)),
+        ..Default::default()
+    };
+    extract_payload_from_geyser_updates(merged_streams, extractor, observers)
+}
+
+/// like ``create_multiplexed_stream`` but compares blockhash and transaction count of the copies
+/// arriving after the fastest block was yielded; mismatches are reported as ``ConsistencyViolation``
+pub fn create_multiplexed_stream_with_consistency_check<E>(
+    grpc_source_streams: Vec<impl Stream<Item = Message>>,
+    extractor: E,
+) -> (
+    impl Stream<Item = E::Target>,
+    broadcast::Receiver<ConsistencyViolation>,
+)
+where
+    E: FromYellowstoneExtractor,
+{
+    let (violations_tx, violations_rx) = broadcast::channel(100);
+    let merged_streams = merge_tagged_streams(grpc_source_streams);
+    let observers = MultiplexObservers {
+        consistency_violations: Some(violations_tx),
+        ..Default::default()
+    };
+    let multiplexed = extract_payload_from_geyser_updates(merged_streams, extractor, observers);
+    (multiplexed, violations_rx)
+}
+
+/// like ``create_multiplexed_stream`` but pushes updates the extractor did not map or which were
+/// overtaken by a higher slot into a dead-letter channel
+/// note: every update gets cloned before extraction
+pub fn create_multiplexed_stream_with_dead_letters<E>(
+    grpc_source_streams: Vec<impl Stream<Item = Message>>,
+    extractor: E,
+) -> (
+    impl Stream<Item = E::Target>,
+    broadcast::Receiver<DeadLetter>,
+)
+where
+    E: FromYellowstoneExtractor,
+{
+    let (dead_letters_tx, dead_letters_rx) = broadcast::channel(100);
+    let merged_streams = merge_tagged_streams(grpc_source_streams);
+    let observers = MultiplexObservers {
+        dead_letters: Some(dead_letters_tx),
+        ..Default::default()
+    };
+    let multiplexed = extract_payload_from_geyser_updates(merged_streams, extractor, observers);
+    (multiplexed, dead_letters_rx)
+}
+
+/// like ``create_multiplexed_stream`` but with an async extractor; up to
+/// ``max_concurrent_extractions`` updates are extracted concurrently, results keep the arrival order
+pub fn create_multiplexed_stream_async<E>(
+    grpc_source_streams: Vec<impl Stream<Item = Message>>,
+    extractor: E,
+    max_concurrent_extractions: usize,
+) -> impl Stream<Item = E::Target>
+where
+    E: AsyncExtractor,
+{
+    assert!(
+        max_concurrent_extractions > 0,
+        "need at least one extraction slot"
+    );
+    let extractions = merge_tagged_streams(grpc_source_streams)
+        .filter_map(
+            move |TaggedMessage {
+                      stream_idx,
+                      payload,
+                  }| {
+                let extraction = match payload {
+                    GeyserSubscribeUpdate(update) => Some(
+                        extractor
+                            .map_yellowstone_update(*update)
+                            .map(move |extracted| (stream_idx, extracted)),
+                    ),
+                    Message::Connecting(attempt) => {
+                        if attempt > 1 {
+                            warn!(
+                                "Stream-{} performs reconnect attempt {}",
+                                stream_idx, attempt
+                            );
+                        }
+                        None
+                    }
+                };
+                future::ready(extraction)
+            },
+        )
+        .buffered(max_concurrent_extractions);
+
+    let mut tip: Slot = 0;
+    stream! {
+        for await (stream_idx, extracted) in extractions {
+            let Some((proposed_slot, block)) = extracted else {
+                continue;
+            };
+            if proposed_slot > tip {
+                tip = proposed_slot;
+                tracing::debug!(stream_idx, slot = proposed_slot, "multiplexer yields from fastest source");
+                yield block;
+            }
+        }
+    }
+}
+
+/// like ``create_multiplexed_stream`` but extraction runs on up to ``workers`` blocking threads so
+/// the task polling the grpc streams is not stalled by heavy blocks; arrival order is kept and
+/// updates for slots already yielded are dropped before they reach a worker
+/// note: protobuf decoding itself happens inside tonic on the stream task
+pub fn create_multiplexed_stream_offloaded<E>(
+    grpc_source_streams: Vec<impl Stream<Item = Message>>,
+    extractor: E,
+    workers: usize,
+) -> impl Stream<Item = E::Target>
+where
+    E: FromYellowstoneExtractor + Send + Sync + 'static,
+    E::Target: Send + 'static,
+{
+    assert!(workers > 0, "need at least one worker");
+    let extractor = Arc::new(extractor);
+    let tip = Arc::new(AtomicU64::new(0));
+    let tip_filter = tip.clone();
+    let extractions = merge_tagged_streams(grpc_source_streams)
+        .filter_map(
+            move |TaggedMessage {
+                      stream_idx,
+                      payload,
+                  }| {
+                let extraction = match payload {
+                    GeyserSubscribeUpdate(update) => {
+                        let behind_tip = slot_of_update(&update)
+                            .map_or(false, |slot| slot <= tip_filter.load(Ordering::Relaxed));
+                        if behind_tip {
+                            None
+                        } else {
+                            let extractor = extractor.clone();
+                            Some(async move {
+                                let extracted = tokio::task::spawn_blocking(move || {
+                                    extractor.map_yellowstone_update(*update)
+                                })
+                                .await;
+                                match extracted {
+                                    Ok(extracted) => {
+                                        extracted.map(|extracted| (stream_idx, extracted))
+                                    }
+                                    Err(join_error) => {
+                                        warn!(
+                                            "extraction worker failed for Stream-{}: {}",
+                                            stream_idx, join_error
+                                        );
+                                        None
+                                    }
+                                }
+                            })
+                        }
+                    }
+                    Message::Connecting(attempt) => {
+                        if attempt > 1 {
+                            warn!(
+                                "Stream-{} performs reconnect attempt {}",
+                                stream_idx, attempt
+                            );
+                        }
+                        None
+                    }
+                };
+                future::ready(extraction)
+            },
+        )
+        .buffered(workers);
+
+    stream! {
+        for await extracted in extractions {
+            let Some((stream_idx, (proposed_slot, block))) = extracted else {
+                continue;
+            };
+            if proposed_slot > tip.load(Ordering::Relaxed) {
+                tip.store(proposed_slot, Ordering::Relaxed);
+                tracing::debug!(stream_idx, slot = proposed_slot, "multiplexer yields from fastest source");
+                yield block;
+            }
+        }
+    }
+}
+
+/// bounds for ``create_multiplexed_stream_ordered``
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ReorderBufferConfig {
+    // release the lowest buffered slot once a slot this far ahead was received
+    pub max_slots: u64,
+    // release a buffered slot at the latest after this delay
+    #[serde(rename = "max_delay_ms", with = "crate::config_file::duration_millis")]
+    pub max_delay: Duration,
+}
+
+/// like ``create_multiplexed_stream`` but instead of dropping every slot lower than the tip,
+/// out-of-order arrivals are held back in a small buffer and emitted strictly increasing by slot;
+/// slots arriving after a higher slot was emitted get dropped
+pub fn create_multiplexed_stream_ordered<E>(
+    grpc_source_streams: Vec<impl Stream<Item = Message>>,
+    extractor: E,
+    config: ReorderBufferConfig,
+) -> impl Stream<Item = E::Target>
+where
+    E: FromYellowstoneExtractor,
+{
+    let merged_streams = merge_tagged_streams(grpc_source_streams);
+    stream! {
+        let mut merged_streams = pin!(merged_streams);
+        let mut last_emitted: Slot = 0;
+        let mut buffer: BTreeMap<Slot, (Instant, E::Target)> = BTreeMap::new();
+        loop {
+            // release what is contiguous, overdue or pushed out of the slot window
+            loop {
+                let Some((&lowest, (received_at, _))) = buffer.first_key_value() else {
+                    break;
+                };
+                let highest = *buffer.keys().next_back().expect("buffer not empty");
+                let release = lowest == last_emitted + 1
+                    || received_at.elapsed() >= config.max_delay
+                    || highest - lowest >= config.max_slots;
+                if !release {
+                    break;
+                }
+                let (slot, (_, block)) = buffer.pop_first().expect("buffer not empty");
+                last_emitted = slot;
+                yield block;
+            }
+
+            let remaining = buffer
+                .first_key_value()
+                .map(|(_, (received_at, _))| config.max_delay.saturating_sub(received_at.elapsed()));
+            let next = match remaining {
+                Some(remaining) => match timeout(remaining, merged_streams.next()).await {
+                    Ok(next) => next,
+                    // oldest buffered slot is overdue
+                    Err(_elapsed) => continue,
+                },
+                None => merged_streams.next().await,
+            };
+
+            let Some(TaggedMessage { stream_idx, payload }) = next else {
+                break;
+            };
+
+            match payload {
+                GeyserSubscribeUpdate(update) => {
+                    if let Some((proposed_slot, block)) = extractor.map_yellowstone_update(*update) {
+                        if proposed_slot <= last_emitted {
+                            debug!("Stream-{} delivered slot {} after slot {} was emitted - dropping", stream_idx, proposed_slot, last_emitted);
+                        } else {
+                            // first arrival wins
+                            buffer.entry(proposed_slot).or_insert_with(|| (Instant::now(), block));
+                        }
+                    }
+                }
+                Message::Connecting(attempt) => {
+                    if attempt > 1 {
+                        warn!("Stream-{} performs reconnect attempt {}", stream_idx, attempt);
+                    }
+                }
+            }
+        }
+
+        // all sources ended
+        while let Some((_, (_, block))) = buffer.pop_first() {
+            yield block;
+        }
+    }
+}
+
+/// like ``create_multiplexed_stream`` but a slot first delivered by a lower weighted source is held
+/// back for up to ``tie_window``; if a higher weighted source delivers in time, its update wins
+/// use ``GrpcSourceConfig::weight`` to prefer e.g. self-hosted over paid sources
+pub fn create_multiplexed_stream_weighted<E>(
+    weighted_streams: Vec<(u32, impl Stream<Item = Message>)>,
+    extractor: E,
+    tie_window: Duration,
+) -> impl Stream<Item = E::Target>
+where
+    E: FromYellowstoneExtractor,
+{
+    let (weights, streams): (Vec<u32>, Vec<_>) = weighted_streams.into_iter().unzip();
+    let max_weight = weights.iter().copied().max().unwrap_or(0);
+    let merged_streams = merge_tagged_streams(streams);
+    stream! {
+        let mut merged_streams = pin!(merged_streams);
+        let mut tip: Slot = 0;
+        // (slot, weight, received_at, block)
+        let mut pending: Option<(Slot, u32, Instant, E::Target)> = None;
+        loop {
+            // copied out so the pending block is not borrowed across the await
+            let pending_since = pending.as_ref().map(|(_, _, received_at, _)| *received_at);
+            let next = match pending_since {
+                Some(received_at) => match timeout(tie_window.saturating_sub(received_at.elapsed()), merged_streams.next()).await {
+                    Ok(next) => next,
+                    Err(_elapsed) => {
+                        let (slot, _, _, block) = pending.take().expect("pending");
+                        tip = slot;
+                        yield block;
+                        continue;
+                    }
+                },
+                None => merged_streams.next().await,
+            };
+
+            let Some(TaggedMessage { stream_idx, payload }) = next else {
+                break;
+            };
+            let update = match payload {
+                GeyserSubscribeUpdate(update) => update,
+                Message::Connecting(attempt) => {
+                    if attempt > 1 {
+                        warn!("Stream-{} performs reconnect attempt {}", stream_idx, attempt);
+                    }
+                    continue;
+                }
+            };
+            let Some((proposed_slot, block)) = extractor.map_yellowstone_update(*update) else {
+                continue;
+            };
+            if proposed_slot <= tip {
+                continue;
+            }
+            let weight = weights[stream_idx];
+
+            let pending_slot = pending.as_ref().map(|(slot, _, _, _)| *slot);
+            match pending_slot {
+                Some(pending_slot) if proposed_slot < pending_slot => continue,
+                Some(pending_slot) if proposed_slot == pending_slot => {
+                    let pending_weight = pending.as_ref().map_or(0, |(_, weight, _, _)| *weight);
+                    if weight <= pending_weight {
+                        continue;
+                    }
+                    debug!("Stream-{} with weight {} takes over slot {}", stream_idx, weight, proposed_slot);
+                    let received_at = pending.take().map(|(_, _, received_at, _)| received_at).expect("pending");
+                    pending = Some((proposed_slot, weight, received_at, block));
+                }
+                Some(_) => {
+                    // a higher slot decides the pending one
+                    let (slot, _, _, pending_block) = pending.take().expect("pending");
+                    tip = slot;
+                    yield pending_block;
+                    pending = Some((proposed_slot, weight, Instant::now(), block));
+                }
+                None => {
+                    pending = Some((proposed_slot, weight, Instant::now(), block));
+                }
+            }
+
+            // nobody can beat the preferred source
+            if weight == max_weight {
+                let (slot, _, _, block) = pending.take().expect("pending");
+                tip = slot;
+                tracing::debug!(stream_idx, slot, "multiplexer yields from preferred source");
+                yield block;
+            }
+        }
+
+        if let Some((_, _, _, block)) = pending {
+            yield block;
+        }
+    }
+}
+
+/// instead of racing every slot, stick to one source and switch only if it reconnects or did not
+/// deliver a new slot for ``stall_timeout``; gives stable per-source latency characteristics
+pub fn create_multiplexed_stream_sticky<E>(
+    grpc_source_streams: Vec<impl Stream<Item = Message>>,
+    extractor: E,
+    stall_timeout: Duration,
+) -> impl Stream<Item = E::Target>
+where
+    E: FromYellowstoneExtractor,
+{
+    let merged_streams = merge_tagged_streams(grpc_source_streams);
+    stream! {
+        let mut tip: Slot = 0;
+        let mut active: Option<usize> = None;
+        let mut last_active_at = Instant::now();
+        for await TaggedMessage { stream_idx, payload } in merged_streams {
+            match payload {
+                GeyserSubscribeUpdate(update) => {
+                    let Some((proposed_slot, block)) = extractor.map_yellowstone_update(*update) else {
+                        continue;
+                    };
+                    if proposed_slot <= tip {
+                        continue;
+                    }
+                    match active {
+                        Some(active_idx) if active_idx == stream_idx => {}
+                        Some(active_idx) => {
+                            if last_active_at.elapsed() < stall_timeout {
+                                continue;
+                            }
+                            info!("Stream-{} stalled for {:?} - switching to Stream-{}", active_idx, last_active_at.elapsed(), stream_idx);
+                            active = Some(stream_idx);
+                        }
+                        None => {
+                            info!("multiplexer sticks to Stream-{}", stream_idx);
+                            active = Some(stream_idx);
+                        }
+                    }
+                    tip = proposed_slot;
+                    last_active_at = Instant::now();
+                    yield block;
+                }
+                Message::Connecting(attempt) => {
+                    if active == Some(stream_idx) {
+                        warn!("active Stream-{} performs reconnect attempt {} - switching", stream_idx, attempt);
+                        active = None;
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn merge_tagged_streams(
+    grpc_source_streams: Vec<impl Stream<Item = Message>>,
+) -> impl Stream<Item = TaggedMessage> {
     if grpc_source_streams.is_empty() {
         panic!("Must have at least one grpc source");
     }
@@ -41,9 +987,7 @@ where
         streams.push(Box::pin(tagged));
     }
 
-    let merged_streams = streams.merge();
-
-    extract_payload_from_geyser_updates(merged_streams, extractor)
+    streams.merge()
 }
 
 struct TaggedMessage {
@@ -51,32 +995,616 @@ struct TaggedMessage {
     pub payload: Message,
 }
 
+// optional side channels of the multiplexer; all disabled by default
+#[derive(Default)]
+struct MultiplexObservers {
+    late_arrivals: Option<LateArrivalDiagnostics>,
+    consistency_violations: Option<broadcast::Sender<ConsistencyViolation>>,
+    dead_letters: Option<broadcast::Sender<DeadLetter>>,
+    // win/loss counters with the label of each stream index
+    source_stats: Option<(MultiplexerStats, Vec<String>)>,
+}
+
+impl MultiplexObservers {
+    fn is_active(&self) -> bool {
+        self.late_arrivals.is_some()
+            || self.consistency_violations.is_some()
+            || self.dead_letters.is_some()
+            || self.source_stats.is_some()
+    }
+
+    fn record_win(&self, stream_idx: usize) {
+        if let Some(diagnostics) = &self.late_arrivals {
+            diagnostics.record_win(stream_idx);
+        }
+        if let Some((stats, labels)) = &self.source_stats {
+            stats.record_win(&labels[stream_idx]);
+        }
+    }
+
+    fn record_loss(&self, late_arrival: LateArrival) {
+        if let Some((stats, labels)) = &self.source_stats {
+            stats.record_loss(&labels[late_arrival.stream_idx], late_arrival.lost_by);
+        }
+        if let Some(diagnostics) = &self.late_arrivals {
+            diagnostics.record(late_arrival);
+        }
+    }
+
+    fn send_dead_letter(
+        &self,
+        stream_idx: usize,
+        reason: DeadLetterReason,
+        update: Option<Box<SubscribeUpdate>>,
+    ) {
+        if let (Some(dead_letters), Some(update)) = (&self.dead_letters, update) {
+            // no subscribers is fine
+            let _ = dead_letters.send(DeadLetter {
+                stream_idx,
+                slot: slot_of_update(&update),
+                reason,
+                update,
+            });
+        }
+    }
+}
+
+struct YieldedSlot {
+    stream_idx: usize,
+    yielded_at: Instant,
+    fingerprint: Option<BlockFingerprint>,
+}
+
 fn extract_payload_from_geyser_updates<E>(
     merged_stream: impl Stream<Item = TaggedMessage>,
     extractor: E,
+    observers: MultiplexObservers,
 ) -> impl Stream<Item = E::Target>
 where
     E: FromYellowstoneExtractor,
 {
     let mut tip: Slot = 0;
+    let mut yielded_slots: BTreeMap<Slot, YieldedSlot> = BTreeMap::new();
     stream! {
         for await TaggedMessage {stream_idx, payload} in merged_stream {
             match payload {
                 GeyserSubscribeUpdate(update) => {
+                    if is_keepalive_frame(&update) {
+                        continue;
+                    }
+                    // must be taken before the extractor consumes the update
+                    let fingerprint = if observers.consistency_violations.is_some() {
+                        BlockFingerprint::from_update(&update)
+                    } else {
+                        None
+                    };
+                    // only updates behind the tip which were not yielded can be out of order; they are
+                    // rare, so only these are cloned for the dead letter
+                    let outdated_update = match (&observers.dead_letters, slot_of_update(&update)) {
+                        (Some(_), Some(slot)) if slot <= tip && !yielded_slots.contains_key(&slot) => Some(update.clone()),
+                        _ => None,
+                    };
                     // take only the update messages we want
-                    if let Some((proposed_slot, block)) = extractor.map_yellowstone_update(*update) {
-                        if proposed_slot > tip {
-                            tip = proposed_slot;
-                            yield block;
+                    let (proposed_slot, block) = if observers.dead_letters.is_some() {
+                        match extractor.try_map_yellowstone_update(*update) {
+                            Ok(extracted) => extracted,
+                            Err(update) => {
+                                observers.send_dead_letter(stream_idx, DeadLetterReason::NotExtracted, Some(Box::new(update)));
+                                continue;
+                            }
+                        }
+                    } else {
+                        let Some(extracted) = extractor.map_yellowstone_update(*update) else {
+                            continue;
+                        };
+                        extracted
+                    };
+
+                    if proposed_slot > tip {
+                        tip = proposed_slot;
+                        if observers.is_active() {
+                            yielded_slots.insert(proposed_slot, YieldedSlot {
+                                stream_idx,
+                                yielded_at: Instant::now(),
+                                fingerprint,
+                            });
+                            yielded_slots = yielded_slots.split_off(&tip.saturating_sub(LATE_ARRIVAL_SLOT_WINDOW));
+                        }
+                        observers.record_win(stream_idx);
+                        tracing::debug!(target: LIFECYCLE_TARGET, event = "dedup", stream_idx, slot = proposed_slot, decision = "yield");
+                        yield block;
+                    } else if let Some(winner) = yielded_slots.get(&proposed_slot) {
+                        if winner.stream_idx == stream_idx {
+                            // duplicate from the winner itself
+                            continue;
+                        }
+                        tracing::debug!(target: LIFECYCLE_TARGET, event = "dedup", stream_idx, slot = proposed_slot, decision = "duplicate");
+                        observers.record_loss(LateArrival {
+                            stream_idx,
+                            slot: proposed_slot,
+                            lost_by: winner.yielded_at.elapsed(),
+                        });
+                        if let (Some(violations), Some(expected), Some(observed)) =
+                            (&observers.consistency_violations, &winner.fingerprint, fingerprint) {
+                            if *expected != observed {
+                                warn!("Stream-{} delivered slot {} with {:?} but Stream-{} yielded {:?}",
+                                    stream_idx, proposed_slot, observed, winner.stream_idx, expected);
+                                // no subscribers is fine
+                                let _ = violations.send(ConsistencyViolation {
+                                    slot: proposed_slot,
+                                    winner_stream_idx: winner.stream_idx,
+                                    expected: expected.clone(),
+                                    stream_idx,
+                                    observed,
+                                });
+                            }
+                        }
+                    } else {
+                        tracing::debug!(target: LIFECYCLE_TARGET, event = "dedup", stream_idx, slot = proposed_slot, decision = "outdated");
+                        if proposed_slot + LATE_ARRIVAL_SLOT_WINDOW > tip {
+                            // inside the window but never yielded: overtaken by a higher slot
+                            observers.send_dead_letter(stream_idx, DeadLetterReason::OutOfOrder { tip }, outdated_update);
                         }
                     }
                 }
-                Message::Connecting(attempt) => {
-                    if attempt > 1 {
-                        warn!("Stream-{} performs reconnect attempt {}", stream_idx, attempt);
-                    }
+                Message::Connecting(_attempt) => {
+                    // reconnects are logged by the source, see ``lifecycle``
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block_source::StreamSource;
+    use crate::simulation::slot_message;
+    use futures::stream;
+    use yellowstone_grpc_proto::geyser::{
+        SubscribeUpdateBlockMeta, SubscribeUpdatePing, SubscribeUpdateTransaction,
+    };
+
+    struct SlotExtractor;
+
+    impl FromYellowstoneExtractor for SlotExtractor {
+        type Target = Slot;
+        fn map_yellowstone_update(&self, update: SubscribeUpdate) -> Option<(Slot, Self::Target)> {
+            match update.update_oneof {
+                Some(UpdateOneof::Slot(update_slot)) => Some((update_slot.slot, update_slot.slot)),
+                Some(UpdateOneof::BlockMeta(block_meta)) => {
+                    Some((block_meta.slot, block_meta.slot))
                 }
+                _ => None,
+            }
+        }
+    }
+
+    struct DelayedSlotExtractor;
+
+    impl AsyncExtractor for DelayedSlotExtractor {
+        type Target = Slot;
+        fn map_yellowstone_update(
+            &self,
+            update: SubscribeUpdate,
+        ) -> BoxFuture<'static, Option<(Slot, Self::Target)>> {
+            async move {
+                let (slot, _) = SlotExtractor.map_yellowstone_update(update)?;
+                // later slots resolve faster
+                tokio::time::sleep(Duration::from_millis(50u64.saturating_sub(slot * 10))).await;
+                Some((slot, slot))
             }
+            .boxed()
+        }
+    }
+
+    fn blockmeta_message(slot: Slot, blockhash: &str) -> Message {
+        GeyserSubscribeUpdate(Box::new(SubscribeUpdate {
+            filters: vec![],
+            update_oneof: Some(UpdateOneof::BlockMeta(SubscribeUpdateBlockMeta {
+                slot,
+                blockhash: blockhash.to_string(),
+                executed_transaction_count: 10,
+                ..Default::default()
+            })),
+        }))
+    }
+
+    fn delayed_slots(slots: Vec<Slot>, delay: Duration) -> impl Stream<Item = Message> {
+        stream::iter(slots).then(move |slot| async move {
+            tokio::time::sleep(delay).await;
+            slot_message(slot)
+        })
+    }
+
+    #[tokio::test]
+    async fn test_multiplex_arc() {
+        let slots: Vec<Arc<Slot>> = create_multiplexed_stream_arc(
+            vec![stream::iter(vec![slot_message(1), slot_message(2)])],
+            SlotExtractor,
+        )
+        .collect()
+        .await;
+
+        assert_eq!(slots, vec![Arc::new(1), Arc::new(2)]);
+    }
+
+    #[tokio::test]
+    async fn test_multiplex_offloaded() {
+        let fast = delayed_slots(vec![1, 2, 3, 5], Duration::from_millis(5));
+        let slow = delayed_slots(vec![1, 2, 3, 4, 5, 6], Duration::from_millis(8));
+
+        let slots: Vec<Slot> =
+            create_multiplexed_stream_offloaded(vec![fast.boxed(), slow.boxed()], SlotExtractor, 2)
+                .collect()
+                .await;
+
+        assert_eq!(slots.first(), Some(&1));
+        assert_eq!(slots.last(), Some(&6));
+        assert!(slots.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[tokio::test]
+    async fn test_channelize_with_options() {
+        let options = MultiplexOptions {
+            channel_capacity: 2,
+            source_buffer: 1,
+        };
+        let source = stream::iter((1..=5).map(slot_message).collect::<Vec<_>>());
+
+        let (mut rx, jh_channelizer) =
+            channelize_multiplexed_stream(vec![source], SlotExtractor, &options);
+        jh_channelizer.await.unwrap();
+
+        assert!(matches!(
+            rx.recv().await,
+            Err(broadcast::error::RecvError::Lagged(3))
+        ));
+        assert_eq!(rx.recv().await.unwrap(), 4);
+        assert_eq!(rx.recv().await.unwrap(), 5);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_heartbeat() {
+        let source = stream::iter(vec![Message::Connecting(1), slot_message(7)])
+            .chain(delayed_slots(vec![8], Duration::from_millis(250)));
+
+        let items: Vec<MultiplexItem<Slot>> = create_multiplexed_stream_with_heartbeat(
+            vec![source],
+            SlotExtractor,
+            Duration::from_millis(100),
+        )
+        .collect()
+        .await;
+
+        let heartbeat = MultiplexItem::Heartbeat {
+            highest_slot: Some(7),
+            sources_ready: 1,
+        };
+        assert_eq!(
+            items,
+            vec![
+                MultiplexItem::Data(7),
+                heartbeat.clone(),
+                heartbeat,
+                MultiplexItem::Data(8)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_forward_bounded_backpressure() {
+        let produced = Arc::new(AtomicU64::new(0));
+        let produced_counter = produced.clone();
+        let source = stream::iter(1..=10u64).map(move |slot| {
+            produced_counter.fetch_add(1, Ordering::Relaxed);
+            slot
+        });
+
+        let (mut rx, jh_forwarder) = forward_bounded(source, 2);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        // two buffered plus one waiting for capacity
+        assert_eq!(produced.load(Ordering::Relaxed), 3);
+
+        let mut received = vec![];
+        while let Some(slot) = rx.recv().await {
+            received.push(slot);
         }
+        assert_eq!(received, (1..=10).collect::<Vec<_>>());
+        jh_forwarder.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_markers() {
+        let first = stream::iter(vec![
+            Message::Connecting(1),
+            slot_message(1),
+            Message::Connecting(2),
+            slot_message(5),
+        ]);
+        let second = stream::iter(vec![Message::Connecting(1)]);
+
+        let items: Vec<BoundedItem<Slot>> =
+            with_reconnect_markers(vec![first.boxed(), second.boxed()], SlotExtractor)
+                .collect()
+                .await;
+
+        let slots: Vec<Slot> = items
+            .iter()
+            .filter_map(|item| match item {
+                BoundedItem::Data(slot) => Some(*slot),
+                BoundedItem::SourceReconnected { .. } => None,
+            })
+            .collect();
+        assert_eq!(slots, vec![1, 5]);
+        // the initial connects are no gap
+        let reconnects: Vec<&BoundedItem<Slot>> = items
+            .iter()
+            .filter(|item| matches!(item, BoundedItem::SourceReconnected { .. }))
+            .collect();
+        assert_eq!(
+            reconnects,
+            vec![&BoundedItem::SourceReconnected {
+                source_idx: 0,
+                attempt: 2
+            }]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_multiplex_with_stats() {
+        let stats = MultiplexerStats::new();
+        let fast = delayed_slots(vec![1, 2, 3], Duration::from_millis(5));
+        let slow = delayed_slots(vec![1, 2, 3, 4], Duration::from_millis(20));
+
+        let slots: Vec<Slot> = create_multiplexed_stream_with_stats(
+            vec![
+                ("fast".to_string(), fast.boxed()),
+                ("slow".to_string(), slow.boxed()),
+            ],
+            SlotExtractor,
+            &stats,
+        )
+        .collect()
+        .await;
+
+        assert_eq!(slots, vec![1, 2, 3, 4]);
+        let fast = stats.source_stats("fast").unwrap();
+        let slow = stats.source_stats("slow").unwrap();
+        assert_eq!((fast.wins, fast.losses), (3, 0));
+        assert_eq!((slow.wins, slow.losses), (1, 3));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_multiplex_weighted() {
+        // cheap source is a bit faster
+        let cheap = stream::iter(vec![
+            blockmeta_message(1, "cheap"),
+            blockmeta_message(2, "cheap"),
+        ])
+        .then(|message| async move {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            message
+        });
+        let preferred =
+            stream::iter(vec![blockmeta_message(1, "preferred")]).then(|message| async move {
+                tokio::time::sleep(Duration::from_millis(15)).await;
+                message
+            });
+
+        let blocks_meta: Vec<SubscribeUpdateBlockMeta> = create_multiplexed_stream_weighted(
+            vec![(0, cheap.boxed()), (10, preferred.boxed())],
+            BlockMetaExtractor,
+            Duration::from_millis(50),
+        )
+        .collect()
+        .await;
+
+        let blockhashes: Vec<&str> = blocks_meta
+            .iter()
+            .map(|block_meta| block_meta.blockhash.as_str())
+            .collect();
+        assert_eq!(blockhashes, vec!["preferred", "cheap"]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_multiplex_sticky() {
+        let first = stream::iter(vec![slot_message(1), slot_message(2)]).chain(
+            stream::iter(vec![slot_message(5)]).then(|message| async move {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                message
+            }),
+        );
+        let second = stream::iter(vec![2, 3, 4]).then(|slot| async move {
+            tokio::time::sleep(Duration::from_millis(10 * slot)).await;
+            slot_message(slot)
+        });
+
+        let slots: Vec<Slot> = create_multiplexed_stream_sticky(
+            vec![first.boxed(), second.boxed()],
+            SlotExtractor,
+            Duration::from_millis(25),
+        )
+        .collect()
+        .await;
+
+        // sticks to the first source, switches to the second once the first stalled;
+        // slot 5 of the first source is ignored as the second one is healthy
+        assert_eq!(slots, vec![1, 2, 3, 4]);
+    }
+
+    #[tokio::test]
+    async fn test_multiplex_blockmeta() {
+        let first = stream::iter(vec![blockmeta_message(1, "a"), blockmeta_message(2, "b")]);
+        let second = stream::iter(vec![blockmeta_message(1, "a"), slot_message(3)]);
+
+        let blocks_meta: Vec<SubscribeUpdateBlockMeta> =
+            create_multiplexed_stream(vec![first.boxed(), second.boxed()], BlockMetaExtractor)
+                .collect()
+                .await;
+
+        let slots: Vec<Slot> = blocks_meta
+            .iter()
+            .map(|block_meta| block_meta.slot)
+            .collect();
+        assert_eq!(slots, vec![1, 2]);
+        assert_eq!(blocks_meta[1].blockhash, "b");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_late_arrival_diagnostics() {
+        let fast = delayed_slots(vec![1, 2], Duration::ZERO);
+        let slow = delayed_slots(vec![1, 2], Duration::from_millis(30));
+
+        let (multiplexed, diagnostics) =
+            create_multiplexed_stream_with_diagnostics(vec![fast, slow], SlotExtractor);
+        let yielded: Vec<Slot> = multiplexed.collect().await;
+
+        assert_eq!(yielded, vec![1, 2]);
+        assert_eq!(diagnostics.source_wins(0), 2);
+        assert_eq!(diagnostics.source_wins(1), 0);
+        assert!(diagnostics.source_stats(0).is_none());
+        let slow_stats = diagnostics.source_stats(1).unwrap();
+        assert_eq!(slow_stats.count, 2);
+        assert!(slow_stats.max >= Duration::from_millis(20));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_consistency_violation() {
+        let fast = stream::iter(vec![
+            blockmeta_message(1, "hash-a"),
+            blockmeta_message(2, "hash-b"),
+        ]);
+        let slow = stream::iter(vec![
+            blockmeta_message(1, "hash-a"),
+            blockmeta_message(2, "hash-x"),
+        ])
+        .then(|msg| async move {
+            tokio::time::sleep(Duration::from_millis(30)).await;
+            msg
+        });
+
+        let (multiplexed, mut violations) = create_multiplexed_stream_with_consistency_check(
+            vec![fast.boxed(), slow.boxed()],
+            SlotExtractor,
+        );
+        let yielded: Vec<Slot> = multiplexed.collect().await;
+
+        assert_eq!(yielded, vec![1, 2]);
+        let violation = violations.try_recv().unwrap();
+        assert_eq!(violation.slot, 2);
+        assert_eq!(violation.winner_stream_idx, 0);
+        assert_eq!(violation.stream_idx, 1);
+        assert_eq!(violation.expected.blockhash, "hash-b");
+        assert_eq!(violation.observed.blockhash, "hash-x");
+        assert!(violations.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_ordered_reorders_within_window() {
+        let source = stream::iter(vec![slot_message(2), slot_message(1), slot_message(3)]);
+        let config = ReorderBufferConfig {
+            max_slots: 10,
+            max_delay: Duration::from_millis(50),
+        };
+
+        let yielded: Vec<Slot> =
+            create_multiplexed_stream_ordered(vec![source], SlotExtractor, config)
+                .collect()
+                .await;
+
+        assert_eq!(yielded, vec![1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_ordered_drops_too_late() {
+        let source = stream::iter(vec![slot_message(5), slot_message(7), slot_message(3)]);
+        let config = ReorderBufferConfig {
+            max_slots: 1,
+            max_delay: Duration::from_secs(10),
+        };
+
+        let yielded: Vec<Slot> =
+            create_multiplexed_stream_ordered(vec![source], SlotExtractor, config)
+                .collect()
+                .await;
+
+        assert_eq!(yielded, vec![5, 7]);
+    }
+
+    #[tokio::test]
+    async fn test_dead_letters() {
+        let ping = GeyserSubscribeUpdate(Box::new(SubscribeUpdate {
+            filters: vec![],
+            update_oneof: Some(UpdateOneof::Ping(SubscribeUpdatePing {})),
+        }));
+        let transaction = GeyserSubscribeUpdate(Box::new(SubscribeUpdate {
+            filters: vec![],
+            update_oneof: Some(UpdateOneof::Transaction(SubscribeUpdateTransaction {
+                slot: 3,
+                transaction: None,
+            })),
+        }));
+        let source = stream::iter(vec![
+            slot_message(2),
+            // keepalive frames are no dead letters
+            ping,
+            transaction,
+            slot_message(1),
+            slot_message(2),
+        ]);
+
+        let (multiplexed, mut dead_letters) =
+            create_multiplexed_stream_with_dead_letters(vec![source], SlotExtractor);
+        let yielded: Vec<Slot> = multiplexed.collect().await;
+
+        assert_eq!(yielded, vec![2]);
+        let not_extracted = dead_letters.try_recv().unwrap();
+        assert_eq!(not_extracted.reason, DeadLetterReason::NotExtracted);
+        assert_eq!(not_extracted.slot, Some(3));
+        let out_of_order = dead_letters.try_recv().unwrap();
+        assert_eq!(out_of_order.reason, DeadLetterReason::OutOfOrder { tip: 2 });
+        assert_eq!(out_of_order.slot, Some(1));
+        // duplicate of the yielded slot is not a dead letter
+        assert!(dead_letters.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_async_extractor() {
+        let source = stream::iter(vec![slot_message(1), slot_message(2)]);
+
+        let yielded: Vec<Slot> =
+            create_multiplexed_stream_async(vec![source], DelayedSlotExtractor, 4)
+                .collect()
+                .await;
+        // slot 2 finishes extraction first but must not overtake slot 1
+        assert_eq!(yielded, vec![1, 2]);
+
+        let source = stream::iter(vec![slot_message(1), slot_message(2)]);
+        let yielded: Vec<Slot> =
+            create_multiplexed_stream_async(vec![source], DelayedSlotExtractor, 1)
+                .collect()
+                .await;
+        assert_eq!(yielded, vec![1, 2]);
+    }
+
+    #[tokio::test]
+    async fn test_multiplex_from_sources() {
+        let sources: Vec<Box<dyn BlockSource>> = vec![
+            Box::new(StreamSource::new(
+                "fixture-a",
+                stream::iter(vec![slot_message(1), slot_message(3)]),
+            )),
+            Box::new(StreamSource::new(
+                "fixture-b",
+                delayed_slots(vec![2, 3, 4], Duration::from_millis(10)),
+            )),
+        ];
+
+        let yielded: Vec<Slot> = create_multiplexed_stream_from_sources(sources, SlotExtractor)
+            .collect()
+            .await;
+
+        assert_eq!(yielded, vec![1, 3, 4]);
     }
 }
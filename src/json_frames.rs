@@ -0,0 +1,155 @@
+use crate::Message;
+#[cfg(any(feature = "websocket", feature = "sse"))]
+use log::{info, warn};
+use serde::Serialize;
+use solana_sdk::clock::Slot;
+use solana_sdk::signature::Signature;
+#[cfg(any(feature = "websocket", feature = "sse"))]
+use std::sync::Arc;
+#[cfg(any(feature = "websocket", feature = "sse"))]
+use tokio::sync::broadcast::{self, error::RecvError};
+use yellowstone_grpc_proto::geyser::subscribe_update::UpdateOneof;
+use yellowstone_grpc_proto::geyser::{CommitmentLevel, SubscribeUpdate};
+
+/// compact json representation of geyser updates for non-rust consumers (websocket, sse)
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum JsonFrame {
+    Slot {
+        slot: Slot,
+        parent: Option<Slot>,
+        status: String,
+    },
+    Block {
+        slot: Slot,
+        blockhash: String,
+        parent_slot: Slot,
+        block_height: Option<u64>,
+        block_time: Option<i64>,
+        transaction_count: u64,
+    },
+    Transaction {
+        slot: Slot,
+        signature: String,
+        is_vote: bool,
+        failed: bool,
+    },
+}
+
+impl JsonFrame {
+    /// slots, blocks (full or meta) and transactions; None for other updates
+    pub fn from_update(update: &SubscribeUpdate) -> Option<Self> {
+        match update.update_oneof.as_ref()? {
+            UpdateOneof::Slot(slot) => Some(JsonFrame::Slot {
+                slot: slot.slot,
+                parent: slot.parent,
+                status: CommitmentLevel::try_from(slot.status)
+                    .map_or("unknown", |status| status.as_str_name())
+                    .to_lowercase(),
+            }),
+            UpdateOneof::Block(block) => Some(JsonFrame::Block {
+                slot: block.slot,
+                blockhash: block.blockhash.clone(),
+                parent_slot: block.parent_slot,
+                block_height: block.block_height.as_ref().map(|h| h.block_height),
+                block_time: block.block_time.as_ref().map(|t| t.timestamp),
+                transaction_count: block.executed_transaction_count,
+            }),
+            UpdateOneof::BlockMeta(block_meta) => Some(JsonFrame::Block {
+                slot: block_meta.slot,
+                blockhash: block_meta.blockhash.clone(),
+                parent_slot: block_meta.parent_slot,
+                block_height: block_meta.block_height.as_ref().map(|h| h.block_height),
+                block_time: block_meta.block_time.as_ref().map(|t| t.timestamp),
+                transaction_count: block_meta.executed_transaction_count,
+            }),
+            UpdateOneof::Transaction(transaction) => {
+                let info = transaction.transaction.as_ref()?;
+                Some(JsonFrame::Transaction {
+                    slot: transaction.slot,
+                    signature: Signature::try_from(info.signature.as_slice())
+                        .ok()?
+                        .to_string(),
+                    is_vote: info.is_vote,
+                    failed: info.meta.as_ref().map_or(false, |meta| meta.err.is_some()),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    pub fn from_message(message: &Message) -> Option<Self> {
+        match message {
+            Message::GeyserSubscribeUpdate(update) => Self::from_update(update),
+            Message::Connecting(_) => None,
+        }
+    }
+
+    /// short name of the frame type, e.g. for sse event names
+    pub fn kind(&self) -> &'static str {
+        match self {
+            JsonFrame::Slot { .. } => "slot",
+            JsonFrame::Block { .. } => "block",
+            JsonFrame::Transaction { .. } => "transaction",
+        }
+    }
+}
+
+/// serialize each update once for all clients of a server
+#[cfg(any(feature = "websocket", feature = "sse"))]
+pub(crate) async fn encode_frames(
+    mut updates: broadcast::Receiver<Message>,
+    frames_tx: broadcast::Sender<Arc<str>>,
+) {
+    loop {
+        let message = match updates.recv().await {
+            Ok(message) => message,
+            Err(RecvError::Lagged(n_missed)) => {
+                warn!("frame encoder lagged {} updates", n_missed);
+                continue;
+            }
+            Err(RecvError::Closed) => {
+                info!("update channel closed - stop encoding frames");
+                return;
+            }
+        };
+        let Some(frame) = JsonFrame::from_message(&message) else {
+            continue;
+        };
+        match serde_json::to_string(&frame) {
+            // no clients is fine
+            Ok(json) => {
+                let _ = frames_tx.send(json.into());
+            }
+            Err(encode_error) => warn!("cannot encode frame: {}", encode_error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yellowstone_grpc_proto::geyser::SubscribeUpdateSlot;
+
+    #[test]
+    fn test_slot_frame() {
+        let update = SubscribeUpdate {
+            filters: vec![],
+            update_oneof: Some(UpdateOneof::Slot(SubscribeUpdateSlot {
+                slot: 42,
+                parent: Some(41),
+                status: CommitmentLevel::Confirmed as i32,
+            })),
+        };
+        let frame = JsonFrame::from_update(&update).unwrap();
+        assert_eq!(frame.kind(), "slot");
+        assert_eq!(
+            frame,
+            JsonFrame::Slot {
+                slot: 42,
+                parent: Some(41),
+                status: "confirmed".to_string(),
+            }
+        );
+    }
+}
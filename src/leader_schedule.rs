@@ -0,0 +1,256 @@
+use crate::grpcmultiplex_fastestwins::{AsyncExtractor, FromYellowstoneExtractor};
+use anyhow::anyhow;
+use futures::future::BoxFuture;
+use futures::{Future, FutureExt};
+use log::{debug, warn};
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::clock::{Epoch, Slot};
+use solana_sdk::epoch_schedule::EpochSchedule;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::OnceCell;
+use tokio::time::Instant;
+use yellowstone_grpc_proto::geyser::SubscribeUpdate;
+
+// current and previous epoch are enough for blocks arriving around the epoch boundary
+const CACHED_EPOCHS: u64 = 2;
+// unknown epochs and failed fetches are retried after this
+const NEGATIVE_CACHE_TTL: Duration = Duration::from_secs(30);
+
+type EpochLeaders = Arc<HashMap<Slot, Pubkey>>;
+
+// one schedule fetch; concurrent lookups of the epoch wait for the same fetch
+struct EpochFetch {
+    fetched_at: Instant,
+    // none if the rpc node does not know the schedule, error message if the fetch failed
+    leaders: Result<Option<EpochLeaders>, String>,
+}
+
+impl EpochFetch {
+    fn expired(&self) -> bool {
+        !matches!(self.leaders, Ok(Some(_))) && self.fetched_at.elapsed() >= NEGATIVE_CACHE_TTL
+    }
+}
+
+/// leader schedule fetched via rpc once per epoch
+pub struct LeaderScheduleCache {
+    rpc_client: RpcClient,
+    epoch_schedule: OnceCell<EpochSchedule>,
+    // the lock is only held to look up the cell, never across rpc calls
+    leaders: Mutex<HashMap<Epoch, Arc<OnceCell<EpochFetch>>>>,
+}
+
+impl LeaderScheduleCache {
+    pub fn new(rpc_url: String) -> Self {
+        Self {
+            rpc_client: RpcClient::new(rpc_url),
+            epoch_schedule: OnceCell::new(),
+            leaders: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// None if the rpc node does not know the schedule for the slot
+    pub async fn leader_for_slot(&self, slot: Slot) -> anyhow::Result<Option<Pubkey>> {
+        let epoch_schedule = self
+            .epoch_schedule
+            .get_or_try_init(|| self.rpc_client.get_epoch_schedule())
+            .await?;
+        let epoch = epoch_schedule.get_epoch(slot);
+        let first_slot = epoch_schedule.get_first_slot_in_epoch(epoch);
+        let epoch_leaders = self
+            .epoch_leaders(epoch, || async move {
+                let schedule = self
+                    .rpc_client
+                    .get_leader_schedule(Some(first_slot))
+                    .await?;
+                debug!("fetched leader schedule for epoch {}", epoch);
+                Ok(schedule.map(|schedule| leaders_by_slot(schedule, first_slot)))
+            })
+            .await?;
+        Ok(epoch_leaders.and_then(|epoch_leaders| epoch_leaders.get(&slot).copied()))
+    }
+
+    async fn epoch_leaders<F, Fut>(
+        &self,
+        epoch: Epoch,
+        fetch: F,
+    ) -> anyhow::Result<Option<EpochLeaders>>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = anyhow::Result<Option<HashMap<Slot, Pubkey>>>>,
+    {
+        let cell = {
+            let mut leaders = self.leaders.lock().unwrap();
+            let cell = leaders.entry(epoch).or_default();
+            if cell.get().map_or(false, EpochFetch::expired) {
+                *cell = Arc::default();
+            }
+            let cell = cell.clone();
+            leaders.retain(|cached_epoch, _| cached_epoch + CACHED_EPOCHS > epoch);
+            cell
+        };
+        let epoch_fetch = cell
+            .get_or_init(|| async {
+                let leaders = match fetch().await {
+                    Ok(leaders) => Ok(leaders.map(Arc::new)),
+                    Err(err) => {
+                        warn!(
+                            "fetching leader schedule for epoch {} failed: {:?}",
+                            epoch, err
+                        );
+                        Err(err.to_string())
+                    }
+                };
+                EpochFetch {
+                    fetched_at: Instant::now(),
+                    leaders,
+                }
+            })
+            .await;
+        epoch_fetch.leaders.clone().map_err(|err| anyhow!(err))
+    }
+}
+
+// rpc schedule maps identity to slot indices relative to the first slot of the epoch
+fn leaders_by_slot(
+    schedule: HashMap<String, Vec<usize>>,
+    first_slot: Slot,
+) -> HashMap<Slot, Pubkey> {
+    let mut leaders = HashMap::new();
+    for (identity, slot_indices) in schedule {
+        let Ok(identity) = Pubkey::from_str(&identity) else {
+            warn!("invalid leader identity {} in schedule", identity);
+            continue;
+        };
+        for slot_index in slot_indices {
+            leaders.insert(first_slot + slot_index as Slot, identity);
+        }
+    }
+    leaders
+}
+
+#[derive(Clone, Debug)]
+pub struct WithLeader<T> {
+    // None if the leader could not be determined
+    pub leader: Option<Pubkey>,
+    pub inner: T,
+}
+
+/// wraps an extractor and annotates each item with the leader of its slot;
+/// use with ``create_multiplexed_stream_async``
+pub struct LeaderAnnotatingExtractor<E> {
+    extractor: E,
+    leader_schedule: Arc<LeaderScheduleCache>,
+}
+
+impl<E> LeaderAnnotatingExtractor<E> {
+    pub fn new(extractor: E, leader_schedule: Arc<LeaderScheduleCache>) -> Self {
+        Self {
+            extractor,
+            leader_schedule,
+        }
+    }
+}
+
+impl<E> AsyncExtractor for LeaderAnnotatingExtractor<E>
+where
+    E: FromYellowstoneExtractor,
+    E::Target: Send + 'static,
+{
+    type Target = WithLeader<E::Target>;
+
+    fn map_yellowstone_update(
+        &self,
+        update: SubscribeUpdate,
+    ) -> BoxFuture<'static, Option<(Slot, Self::Target)>> {
+        let extracted = self.extractor.map_yellowstone_update(update);
+        let leader_schedule = self.leader_schedule.clone();
+        async move {
+            let (slot, inner) = extracted?;
+            let leader = match leader_schedule.leader_for_slot(slot).await {
+                Ok(leader) => leader,
+                Err(err) => {
+                    warn!("leader lookup for slot {} failed: {:?}", slot, err);
+                    None
+                }
+            };
+            Some((slot, WithLeader { leader, inner }))
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_leaders_by_slot() {
+        let leader_a = Pubkey::new_unique();
+        let leader_b = Pubkey::new_unique();
+        let schedule = HashMap::from([
+            (leader_a.to_string(), vec![0, 1, 2, 3]),
+            (leader_b.to_string(), vec![4, 5, 6, 7]),
+            ("not-a-pubkey".to_string(), vec![8]),
+        ]);
+
+        let leaders = leaders_by_slot(schedule, 1000);
+        assert_eq!(leaders.len(), 8);
+        assert_eq!(leaders[&1000], leader_a);
+        assert_eq!(leaders[&1005], leader_b);
+        assert!(!leaders.contains_key(&1008));
+    }
+
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn cache() -> LeaderScheduleCache {
+        LeaderScheduleCache::new("http://127.0.0.1:1".to_string())
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_lookups_fetch_once() {
+        let cache = cache();
+        let leader = Pubkey::new_unique();
+        let fetch_count = AtomicU32::new(0);
+        let fetches = &fetch_count;
+        let fetch = move || async move {
+            fetches.fetch_add(1, Ordering::Relaxed);
+            tokio::task::yield_now().await;
+            Ok(Some(HashMap::from([(10, leader)])))
+        };
+
+        let (first, second) =
+            tokio::join!(cache.epoch_leaders(0, fetch), cache.epoch_leaders(0, fetch));
+        assert_eq!(first.unwrap().unwrap()[&10], leader);
+        assert_eq!(second.unwrap().unwrap()[&10], leader);
+        assert_eq!(fetches.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_negative_cache_expires() {
+        let cache = cache();
+        let fetch_count = AtomicU32::new(0);
+        let fetches = &fetch_count;
+        let unknown = move || async move {
+            fetches.fetch_add(1, Ordering::Relaxed);
+            Ok(None)
+        };
+        let failing = move || async move {
+            fetches.fetch_add(1, Ordering::Relaxed);
+            Err(anyhow!("connection refused"))
+        };
+
+        assert!(cache.epoch_leaders(0, unknown).await.unwrap().is_none());
+        assert!(cache.epoch_leaders(0, unknown).await.unwrap().is_none());
+        assert_eq!(fetches.load(Ordering::Relaxed), 1);
+
+        tokio::time::advance(NEGATIVE_CACHE_TTL).await;
+        assert!(cache.epoch_leaders(0, failing).await.is_err());
+        // cached failure is reported without a fetch
+        assert!(cache.epoch_leaders(0, unknown).await.is_err());
+        assert_eq!(fetches.load(Ordering::Relaxed), 2);
+    }
+}
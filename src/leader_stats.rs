@@ -0,0 +1,238 @@
+use crate::leader_schedule::LeaderScheduleCache;
+use crate::skipped_slots::{detect_skipped_slots, BlockOrSkipped};
+use async_stream::stream;
+use futures::{Future, Stream};
+use log::warn;
+use solana_sdk::clock::Slot;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use yellowstone_grpc_proto::geyser::SubscribeUpdateBlock;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LeaderStats {
+    pub leader: Pubkey,
+    pub blocks_produced: u64,
+    pub slots_skipped: u64,
+    // per produced block
+    pub avg_tx_count: f64,
+    // summed from the transaction metas - 0 unless the block subscription includes transactions
+    pub avg_compute_units: f64,
+}
+
+/// aggregates over the last ``window_slots`` slots up to ``slot``
+#[derive(Clone, Debug, PartialEq)]
+pub struct LeaderStatsReport {
+    pub slot: Slot,
+    pub window_slots: u64,
+    // most blocks produced first
+    pub leaders: Vec<LeaderStats>,
+}
+
+struct SlotRecord {
+    slot: Slot,
+    leader: Pubkey,
+    // (transactions, compute units) if a block was produced
+    produced: Option<(u64, u64)>,
+}
+
+/// one report per multiplexed block; use with confirmed or finalized blocks, see ``detect_skipped_slots``
+/// slots whose leader cannot be determined are not counted
+///
+/// transaction counts come from the block header, compute units need ``include_transactions``
+pub fn leader_stats_stream(
+    blocks: impl Stream<Item = SubscribeUpdateBlock>,
+    leader_schedule: Arc<LeaderScheduleCache>,
+    window_slots: u64,
+) -> impl Stream<Item = LeaderStatsReport> {
+    leader_stats_with(
+        blocks,
+        move |slot| {
+            let leader_schedule = leader_schedule.clone();
+            async move {
+                match leader_schedule.leader_for_slot(slot).await {
+                    Ok(leader) => leader,
+                    Err(err) => {
+                        warn!("leader lookup for slot {} failed: {:?}", slot, err);
+                        None
+                    }
+                }
+            }
+        },
+        window_slots,
+    )
+}
+
+fn leader_stats_with<F, L>(
+    blocks: impl Stream<Item = SubscribeUpdateBlock>,
+    leader_for_slot: L,
+    window_slots: u64,
+) -> impl Stream<Item = LeaderStatsReport>
+where
+    L: Fn(Slot) -> F,
+    F: Future<Output = Option<Pubkey>>,
+{
+    stream! {
+        let mut window: VecDeque<SlotRecord> = VecDeque::new();
+        let blocks_or_skipped = detect_skipped_slots(blocks, |block| (block.slot, block.parent_slot));
+        for await block_or_skipped in blocks_or_skipped {
+            let (slot, produced) = match &block_or_skipped {
+                BlockOrSkipped::Block(block) => (
+                    block.slot,
+                    Some((block.executed_transaction_count, compute_units_of(block))),
+                ),
+                BlockOrSkipped::SlotSkipped(slot) => (*slot, None),
+            };
+            let Some(leader) = leader_for_slot(slot).await else {
+                continue;
+            };
+            window.push_back(SlotRecord { slot, leader, produced });
+            while window
+                .front()
+                .map_or(false, |oldest| oldest.slot + window_slots <= slot)
+            {
+                window.pop_front();
+            }
+            if produced.is_some() {
+                yield report_of(slot, window_slots, &window);
+            }
+        }
+    }
+}
+
+fn compute_units_of(block: &SubscribeUpdateBlock) -> u64 {
+    block
+        .transactions
+        .iter()
+        .filter_map(|tx| {
+            tx.meta
+                .as_ref()
+                .and_then(|meta| meta.compute_units_consumed)
+        })
+        .sum()
+}
+
+fn report_of(slot: Slot, window_slots: u64, window: &VecDeque<SlotRecord>) -> LeaderStatsReport {
+    // (blocks, skipped, transactions, compute units)
+    let mut per_leader: HashMap<Pubkey, (u64, u64, u64, u64)> = HashMap::new();
+    for record in window {
+        let totals = per_leader.entry(record.leader).or_default();
+        match record.produced {
+            Some((transactions, compute_units)) => {
+                totals.0 += 1;
+                totals.2 += transactions;
+                totals.3 += compute_units;
+            }
+            None => totals.1 += 1,
+        }
+    }
+
+    let mut leaders: Vec<LeaderStats> = per_leader
+        .into_iter()
+        .map(
+            |(leader, (blocks_produced, slots_skipped, transactions, compute_units))| {
+                let blocks = blocks_produced.max(1) as f64;
+                LeaderStats {
+                    leader,
+                    blocks_produced,
+                    slots_skipped,
+                    avg_tx_count: transactions as f64 / blocks,
+                    avg_compute_units: compute_units as f64 / blocks,
+                }
+            },
+        )
+        .collect();
+    // tie-break on pubkey to be deterministic
+    leaders.sort_unstable_by(|a, b| {
+        b.blocks_produced
+            .cmp(&a.blocks_produced)
+            .then(a.leader.cmp(&b.leader))
+    });
+    LeaderStatsReport {
+        slot,
+        window_slots,
+        leaders,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{future, stream, StreamExt};
+    use yellowstone_grpc_proto::geyser::SubscribeUpdateTransactionInfo;
+    use yellowstone_grpc_proto::prelude::TransactionStatusMeta;
+
+    fn block(slot: Slot, parent_slot: Slot, transactions: usize) -> SubscribeUpdateBlock {
+        SubscribeUpdateBlock {
+            slot,
+            parent_slot,
+            executed_transaction_count: transactions as u64,
+            transactions: (0..transactions)
+                .map(|_| SubscribeUpdateTransactionInfo {
+                    meta: Some(TransactionStatusMeta {
+                        compute_units_consumed: Some(1000),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_leader_stats() {
+        let leader_a = Pubkey::new_unique();
+        let leader_b = Pubkey::new_unique();
+        // slot 12 is skipped
+        let blocks = stream::iter(vec![
+            block(10, 9, 2),
+            block(11, 10, 4),
+            block(13, 11, 6),
+            block(14, 13, 10),
+        ]);
+
+        let reports: Vec<LeaderStatsReport> = leader_stats_with(
+            blocks,
+            |slot| future::ready(Some(if slot < 14 { leader_a } else { leader_b })),
+            4,
+        )
+        .collect()
+        .await;
+
+        assert_eq!(reports.len(), 4);
+        let last = reports.last().unwrap();
+        assert_eq!(last.slot, 14);
+        // window is 11..=14
+        let stats_a = last
+            .leaders
+            .iter()
+            .find(|stats| stats.leader == leader_a)
+            .unwrap();
+        assert_eq!(stats_a.blocks_produced, 2);
+        assert_eq!(stats_a.slots_skipped, 1);
+        assert_eq!(stats_a.avg_tx_count, 5.0);
+        assert_eq!(stats_a.avg_compute_units, 5000.0);
+        assert_eq!(last.leaders[1].leader, leader_b);
+        assert_eq!(last.leaders[1].blocks_produced, 1);
+    }
+
+    #[tokio::test]
+    async fn test_leader_stats_without_transactions() {
+        let leader = Pubkey::new_unique();
+        let blocks = stream::iter(vec![SubscribeUpdateBlock {
+            slot: 10,
+            parent_slot: 9,
+            executed_transaction_count: 7,
+            ..Default::default()
+        }]);
+
+        let reports: Vec<LeaderStatsReport> =
+            leader_stats_with(blocks, |_slot| future::ready(Some(leader)), 4)
+                .collect()
+                .await;
+
+        assert_eq!(reports[0].leaders[0].avg_tx_count, 7.0);
+        assert_eq!(reports[0].leaders[0].avg_compute_units, 0.0);
+    }
+}
@@ -1,20 +1,99 @@
-use solana_sdk::commitment_config::CommitmentConfig;
+use crate::auth::{AuthScheme, RequestInterceptor};
+use crate::commitment::CommitmentConfig;
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fmt::{Debug, Display};
+use std::sync::Arc;
 use std::time::Duration;
+use yellowstone_grpc_proto::geyser::subscribe_update::UpdateOneof;
 use yellowstone_grpc_proto::geyser::{
-    CommitmentLevel, SubscribeRequest, SubscribeRequestFilterBlocks,
-    SubscribeRequestFilterBlocksMeta, SubscribeRequestFilterSlots, SubscribeUpdate,
+    CommitmentLevel, SubscribeRequest, SubscribeRequestFilterAccounts,
+    SubscribeRequestFilterBlocks, SubscribeRequestFilterBlocksMeta, SubscribeRequestFilterEntry,
+    SubscribeRequestFilterSlots, SubscribeRequestFilterTransactions, SubscribeRequestPing,
+    SubscribeUpdate,
 };
-use yellowstone_grpc_proto::tonic::transport::ClientTlsConfig;
+use yellowstone_grpc_proto::tonic::transport::{Certificate, ClientTlsConfig, Identity};
+use yellowstone_grpc_proto::tonic::{Request, Status};
 
+#[cfg(feature = "account-snapshot")]
+pub mod account_snapshot;
+#[cfg(feature = "solana-sdk")]
+pub mod account_stream;
+pub mod adaptive_selection;
+pub mod auth;
+#[cfg(feature = "solana-sdk")]
+pub mod block_analytics;
+pub mod block_completeness;
+pub mod block_pruning;
+pub mod block_source;
+pub mod block_time_drift;
+#[cfg(feature = "solana-sdk")]
+pub mod blockhash_cache;
+pub mod chain_integrity;
 pub mod channel_plugger;
+pub mod chaos;
+pub mod checkpoint;
+pub mod commitment;
+pub mod config_file;
+pub mod connector;
+pub mod entries;
+#[cfg(feature = "solana-sdk")]
+pub mod epoch_events;
+pub mod finalization;
+pub mod grpc_stream_utils;
 pub mod grpc_subscription_autoreconnect_streams;
 pub mod grpc_subscription_autoreconnect_tasks;
+pub mod grpc_unary;
 pub mod grpcmultiplex_fastestwins;
+#[cfg(feature = "solana-sdk")]
+pub mod json_frames;
+#[cfg(feature = "leader-schedule")]
+pub mod leader_schedule;
+#[cfg(feature = "leader-schedule")]
+pub mod leader_stats;
+pub mod lifecycle;
+pub mod multiplex_diagnostics;
+pub mod multiplex_handle;
+pub mod multiplexer_builder;
+pub mod multiplexer_stats;
 mod obfuscate;
+pub mod prelude;
+#[cfg(feature = "solana-sdk")]
+pub mod program_account_cache;
+#[cfg(feature = "pubsub")]
+pub mod pubsub_source;
+pub mod reconnect_policy;
+pub mod shared_connection;
+#[cfg(feature = "solana-sdk")]
+pub mod signature_stream;
+pub mod simulation;
+pub mod sinks;
+pub mod skipped_slots;
+#[cfg(feature = "slot-lag")]
+pub mod slot_lag;
+#[cfg(feature = "solana-conversions")]
+pub mod solana_conversions;
+pub mod source_events;
+#[cfg(feature = "sse")]
+pub mod sse_server;
+pub mod subscription_manager;
+pub mod supervisor;
+pub mod tip_slot;
+#[cfg(feature = "opentelemetry")]
+pub mod trace_context;
+pub mod transaction_stream;
+#[cfg(feature = "solana-sdk")]
+pub mod transaction_watcher;
+#[cfg(feature = "solana-sdk")]
+pub mod vote_stream;
+#[cfg(feature = "websocket")]
+pub mod ws_server;
 
-type Attempt = u32;
+pub type Attempt = u32;
+
+// same as solana_sdk::clock::Slot
+pub type Slot = u64;
 
 // wraps payload and status messages
 // clone is required by broacast channel
@@ -25,29 +104,92 @@ pub enum Message {
     Connecting(Attempt),
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct GrpcConnectionTimeouts {
+    #[serde(rename = "connect_timeout_ms", with = "config_file::duration_millis")]
     pub connect_timeout: Duration,
+    #[serde(rename = "request_timeout_ms", with = "config_file::duration_millis")]
     pub request_timeout: Duration,
+    #[serde(rename = "subscribe_timeout_ms", with = "config_file::duration_millis")]
     pub subscribe_timeout: Duration,
+    #[serde(rename = "receive_timeout_ms", with = "config_file::duration_millis")]
     pub receive_timeout: Duration,
 }
 
-#[derive(Clone)]
+/// per source inbound limit, applied by the reconnecting streams and tasks; see ``grpc_stream_utils::rate_limited``
+///
+/// 0 is rejected by the config loader and means no limit if set in code
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct RateLimit {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub messages_per_second: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bytes_per_second: Option<u64>,
+}
+
+/// supplies the x-token on every (re)connect, e.g. short-lived tokens fetched from a secrets manager
+pub trait TokenProvider: Send + Sync {
+    fn get_token(&self) -> BoxFuture<'static, anyhow::Result<String>>;
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(
+    try_from = "config_file::GrpcSourceConfigFile",
+    into = "config_file::GrpcSourceConfigFile"
+)]
 pub struct GrpcSourceConfig {
     pub grpc_addr: String,
     pub grpc_x_token: Option<String>,
     tls_config: Option<ClientTlsConfig>,
+    // what tls_config was built from in a config file, kept for serialization
+    tls_files: Option<config_file::TlsFiles>,
+    // what tls_config was built from via the builder, kept for serialization
+    tls_pems: Option<config_file::TlsPems>,
+    tls_domain_name: Option<String>,
     timeouts: Option<GrpcConnectionTimeouts>,
+    // call get_version before subscribing, see ``grpc_unary::probe_source``
+    probe_on_connect: bool,
+    // send ping on the subscription so idle subscriptions are not dropped by proxies
+    keepalive_interval: Option<Duration>,
+    // tie-breaking preference, see ``create_multiplexed_stream_weighted``; higher wins
+    weight: u32,
+    rate_limit: Option<RateLimit>,
+    // takes precedence over grpc_x_token; not serialized
+    token_provider: Option<Arc<dyn TokenProvider>>,
+    auth: AuthScheme,
+    // http:// (CONNECT) or socks5:// proxy, see ``connector::ProxyConfig``
+    proxy_url: Option<String>,
+    // unlimited if not set
+    max_decoding_message_size: Option<usize>,
+    // shown instead of the obfuscated grpc_addr
+    label: Option<String>,
+    // not serialized
+    request_interceptors: Vec<RequestInterceptor>,
 }
 
 impl Display for GrpcSourceConfig {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "grpc_addr {}",
-            crate::obfuscate::url_obfuscate_api_token(&self.grpc_addr)
-        )
+        let grpc_addr = crate::obfuscate::url_obfuscate_api_token(&self.grpc_addr);
+        match &self.label {
+            Some(label) => write!(f, "{} (grpc_addr {})", label, grpc_addr),
+            None => write!(f, "grpc_addr {}", grpc_addr),
+        }
+    }
+}
+
+/// sources are equal if they connect to the same endpoint, regardless of token and settings;
+/// e.g. to diff two source lists
+impl PartialEq for GrpcSourceConfig {
+    fn eq(&self, other: &Self) -> bool {
+        self.grpc_addr == other.grpc_addr
+    }
+}
+
+impl Eq for GrpcSourceConfig {}
+
+impl std::hash::Hash for GrpcSourceConfig {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.grpc_addr.hash(state);
     }
 }
 
@@ -64,7 +206,20 @@ impl GrpcSourceConfig {
             grpc_addr,
             grpc_x_token: None,
             tls_config: None,
+            tls_files: None,
+            tls_pems: None,
+            tls_domain_name: None,
             timeouts: None,
+            probe_on_connect: false,
+            keepalive_interval: None,
+            weight: 0,
+            rate_limit: None,
+            token_provider: None,
+            auth: AuthScheme::XToken,
+            proxy_url: None,
+            max_decoding_message_size: None,
+            label: None,
+            request_interceptors: vec![],
         }
     }
     pub fn new(
@@ -77,9 +232,220 @@ impl GrpcSourceConfig {
             grpc_addr,
             grpc_x_token,
             tls_config,
+            tls_files: None,
+            tls_pems: None,
+            tls_domain_name: None,
             timeouts: Some(timeouts),
+            probe_on_connect: false,
+            keepalive_interval: None,
+            weight: 0,
+            rate_limit: None,
+            token_provider: None,
+            auth: AuthScheme::XToken,
+            proxy_url: None,
+            max_decoding_message_size: None,
+            label: None,
+            request_interceptors: vec![],
         }
     }
+
+    /// verify the endpoint with get_version before each subscribe; a failing probe counts as failed connect
+    pub fn with_probe_on_connect(mut self) -> Self {
+        self.probe_on_connect = true;
+        self
+    }
+
+    /// prefer this source over lower weighted ones which deliver within the tie window
+    pub fn with_weight(mut self, weight: u32) -> Self {
+        self.weight = weight;
+        self
+    }
+
+    pub fn weight(&self) -> u32 {
+        self.weight
+    }
+
+    /// fetch the x-token from the provider on each connect instead of using a static one
+    pub fn with_token_provider(mut self, token_provider: impl TokenProvider + 'static) -> Self {
+        self.token_provider = Some(Arc::new(token_provider));
+        self
+    }
+
+    /// send the token as Bearer or Basic auth instead of x-token, e.g. for gateways behind http auth
+    pub fn with_auth_scheme(mut self, auth: AuthScheme) -> Self {
+        self.auth = auth;
+        self
+    }
+
+    /// present a client certificate (mtls); enables tls if not configured yet; note that serializing
+    /// the config writes out the key
+    pub fn with_client_identity(
+        mut self,
+        cert_pem: impl AsRef<[u8]>,
+        key_pem: impl AsRef<[u8]>,
+    ) -> Self {
+        let tls_config = self.tls_config.take().unwrap_or_default();
+        let tls_pems = self.tls_pems.get_or_insert_with(Default::default);
+        tls_pems.client_cert_pem = Some(String::from_utf8_lossy(cert_pem.as_ref()).into_owned());
+        tls_pems.client_key_pem = Some(String::from_utf8_lossy(key_pem.as_ref()).into_owned());
+        self.tls_config = Some(tls_config.identity(Identity::from_pem(cert_pem, key_pem)));
+        self
+    }
+
+    /// trust the given ca certificate(s); enables tls if not configured yet
+    pub fn with_ca_certificate(mut self, ca_pem: impl AsRef<[u8]>) -> Self {
+        let tls_config = self.tls_config.take().unwrap_or_default();
+        let tls_pems = self.tls_pems.get_or_insert_with(Default::default);
+        tls_pems.ca_cert_pem = Some(String::from_utf8_lossy(ca_pem.as_ref()).into_owned());
+        self.tls_config = Some(tls_config.ca_certificate(Certificate::from_pem(ca_pem)));
+        self
+    }
+
+    /// trust the ca certificate(s) of a pem file, e.g. for self-signed internal deployments
+    pub fn with_ca_bundle_file(self, path: impl AsRef<std::path::Path>) -> anyhow::Result<Self> {
+        let ca_pem = config_file::read_pem(path.as_ref())?;
+        Ok(self.with_ca_certificate(ca_pem))
+    }
+
+    /// verify the server certificate against this name instead of the host of grpc_addr
+    pub fn with_tls_domain_name(mut self, domain_name: impl Into<String>) -> Self {
+        let domain_name = domain_name.into();
+        let tls_config = self.tls_config.take().unwrap_or_default();
+        self.tls_config = Some(tls_config.domain_name(domain_name.clone()));
+        self.tls_domain_name = Some(domain_name);
+        self
+    }
+
+    /// updates above this size are dropped, see ``source_events::SourceEvent::OversizedUpdate``
+    pub fn with_max_decoding_message_size(mut self, max_decoding_message_size: usize) -> Self {
+        self.max_decoding_message_size = Some(max_decoding_message_size);
+        self
+    }
+
+    /// run ``interceptor`` on every request to this source after the auth header was added;
+    /// returning an error fails the request, e.g. the subscribe of a connection attempt
+    /// note: tower layers are not supported as the client type is fixed by yellowstone-grpc-client
+    pub fn with_interceptor(
+        mut self,
+        interceptor: impl Fn(Request<()>) -> Result<Request<()>, Status> + Send + Sync + 'static,
+    ) -> Self {
+        self.request_interceptors.push(Arc::new(interceptor));
+        self
+    }
+
+    /// send the trace context of the connection span with every request, see ``trace_context``
+    #[cfg(feature = "opentelemetry")]
+    pub fn with_trace_context(self) -> Self {
+        self.with_interceptor(trace_context::inject_trace_context)
+    }
+
+    /// connect through an outbound http CONNECT or socks5 proxy
+    pub fn with_proxy(mut self, proxy_url: impl Into<String>) -> Self {
+        self.proxy_url = Some(proxy_url.into());
+        self
+    }
+
+    /// current x-token: from the token provider if set, static otherwise
+    pub(crate) async fn x_token(&self) -> Result<Option<String>, Status> {
+        match &self.token_provider {
+            Some(token_provider) => token_provider
+                .get_token()
+                .await
+                .map(Some)
+                .map_err(|err| Status::unauthenticated(format!("token provider failed: {err}"))),
+            None => Ok(self.grpc_x_token.clone()),
+        }
+    }
+
+    /// throttle inbound updates so a source replaying a burst cannot starve the others
+    pub fn with_rate_limit(mut self, rate_limit: RateLimit) -> Self {
+        self.rate_limit = Some(rate_limit);
+        self
+    }
+
+    /// ping the server periodically on idle and busy subscriptions
+    pub fn with_keepalive(mut self, keepalive_interval: Duration) -> Self {
+        self.keepalive_interval = Some(keepalive_interval);
+        self
+    }
+
+    /// name the source in logs, stats and events instead of the obfuscated grpc_addr
+    pub fn with_label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    /// safe for logging: the label if set, otherwise grpc_addr with the api token in the url path
+    /// obfuscated; x-token is never included
+    pub fn label(&self) -> String {
+        match &self.label {
+            Some(label) => label.clone(),
+            None => crate::obfuscate::url_obfuscate_api_token(&self.grpc_addr).into_owned(),
+        }
+    }
+
+    /// note: may contain an api token in the url path, see ``label``
+    pub fn grpc_addr(&self) -> &str {
+        &self.grpc_addr
+    }
+
+    pub fn has_x_token(&self) -> bool {
+        self.grpc_x_token.is_some() || self.token_provider.is_some()
+    }
+
+    pub fn is_tls(&self) -> bool {
+        self.tls_config.is_some()
+    }
+
+    pub fn tls_domain_name(&self) -> Option<&str> {
+        self.tls_domain_name.as_deref()
+    }
+
+    pub fn timeouts(&self) -> Option<&GrpcConnectionTimeouts> {
+        self.timeouts.as_ref()
+    }
+
+    pub fn probe_on_connect(&self) -> bool {
+        self.probe_on_connect
+    }
+
+    pub fn keepalive_interval(&self) -> Option<Duration> {
+        self.keepalive_interval
+    }
+
+    pub fn rate_limit(&self) -> Option<&RateLimit> {
+        self.rate_limit.as_ref()
+    }
+
+    pub fn auth_scheme(&self) -> &AuthScheme {
+        &self.auth
+    }
+
+    pub fn proxy_url(&self) -> Option<&str> {
+        self.proxy_url.as_deref()
+    }
+
+    pub fn max_decoding_message_size(&self) -> Option<usize> {
+        self.max_decoding_message_size
+    }
+}
+
+/// kinds of geyser updates that can be subscribed, see ``GeyserFilter::subscribe_request``
+#[derive(Clone, Debug)]
+pub enum GeyserFilterKind {
+    BlocksAndTransactions,
+    BlocksMeta,
+    Slots,
+    Accounts {
+        account: Vec<String>,
+        owner: Vec<String>,
+    },
+    Transactions {
+        account_include: Vec<String>,
+        vote: Option<bool>,
+        failed: Option<bool>,
+    },
+    Entries,
 }
 
 #[derive(Clone)]
@@ -87,58 +453,69 @@ pub struct GeyserFilter(pub CommitmentConfig);
 
 impl GeyserFilter {
     pub fn blocks_and_txs(&self) -> SubscribeRequest {
-        let mut blocks_subs = HashMap::new();
-        blocks_subs.insert(
-            "client".to_string(),
-            SubscribeRequestFilterBlocks {
-                account_include: Default::default(),
-                include_transactions: Some(true),
-                include_accounts: Some(false),
-                include_entries: Some(false),
-            },
-        );
-
-        SubscribeRequest {
-            slots: HashMap::new(),
-            accounts: Default::default(),
-            transactions: HashMap::new(),
-            entry: Default::default(),
-            blocks: blocks_subs,
-            blocks_meta: HashMap::new(),
-            commitment: Some(map_commitment_level(self.0) as i32),
-            accounts_data_slice: Default::default(),
-            ping: None,
-        }
+        self.subscribe_request(&[GeyserFilterKind::BlocksAndTransactions])
     }
 
     pub fn blocks_meta(&self) -> SubscribeRequest {
-        let mut blocksmeta_subs = HashMap::new();
-        blocksmeta_subs.insert("client".to_string(), SubscribeRequestFilterBlocksMeta {});
-
-        SubscribeRequest {
-            slots: HashMap::new(),
-            accounts: Default::default(),
-            transactions: HashMap::new(),
-            entry: Default::default(),
-            blocks: HashMap::new(),
-            blocks_meta: blocksmeta_subs,
-            commitment: Some(map_commitment_level(self.0) as i32),
-            accounts_data_slice: Default::default(),
-            ping: None,
-        }
+        self.subscribe_request(&[GeyserFilterKind::BlocksMeta])
     }
 
     pub fn slots(&self) -> SubscribeRequest {
-        let mut slots_subs = HashMap::new();
-        slots_subs.insert(
-            "client".to_string(),
-            SubscribeRequestFilterSlots {
-                filter_by_commitment: Some(true),
+        self.subscribe_request(&[GeyserFilterKind::Slots])
+    }
+
+    /// blocks and slot ticks on one stream, see ``shared_connection::split_blocks_and_slots``
+    pub fn blocks_and_slots(&self) -> SubscribeRequest {
+        self.subscribe_request(&[
+            GeyserFilterKind::BlocksAndTransactions,
+            GeyserFilterKind::Slots,
+        ])
+    }
+
+    pub fn accounts(&self, account: Vec<String>, owner: Vec<String>) -> SubscribeRequest {
+        self.subscribe_request(&[GeyserFilterKind::Accounts { account, owner }])
+    }
+
+    pub fn transactions(&self, account_include: Vec<String>) -> SubscribeRequest {
+        self.subscribe_request(&[GeyserFilterKind::Transactions {
+            account_include,
+            vote: None,
+            failed: None,
+        }])
+    }
+
+    /// transactions matching a ``transaction_stream::TransactionFilter``
+    pub fn transactions_with(
+        &self,
+        filter: transaction_stream::TransactionFilter,
+    ) -> SubscribeRequest {
+        let mut request = self.subscribe_request(&[]);
+        request
+            .transactions
+            .insert("client".to_string(), filter.build());
+        request
+    }
+
+    /// blocks meta plus non-vote transactions, see ``signature_stream``
+    pub fn signatures(&self) -> SubscribeRequest {
+        self.subscribe_request(&[
+            GeyserFilterKind::BlocksMeta,
+            GeyserFilterKind::Transactions {
+                account_include: vec![],
+                vote: Some(false),
+                failed: None,
             },
-        );
+        ])
+    }
+
+    pub fn entries(&self) -> SubscribeRequest {
+        self.subscribe_request(&[GeyserFilterKind::Entries])
+    }
 
-        SubscribeRequest {
-            slots: slots_subs,
+    /// one request combining all given filter kinds
+    pub fn subscribe_request(&self, filter_kinds: &[GeyserFilterKind]) -> SubscribeRequest {
+        let mut request = SubscribeRequest {
+            slots: HashMap::new(),
             accounts: Default::default(),
             transactions: HashMap::new(),
             entry: Default::default(),
@@ -147,27 +524,251 @@ impl GeyserFilter {
             commitment: Some(map_commitment_level(self.0) as i32),
             accounts_data_slice: Default::default(),
             ping: None,
+        };
+
+        for filter_kind in filter_kinds {
+            match filter_kind {
+                GeyserFilterKind::BlocksAndTransactions => {
+                    request.blocks.insert(
+                        "client".to_string(),
+                        SubscribeRequestFilterBlocks {
+                            account_include: Default::default(),
+                            include_transactions: Some(true),
+                            include_accounts: Some(false),
+                            include_entries: Some(false),
+                        },
+                    );
+                }
+                GeyserFilterKind::BlocksMeta => {
+                    request
+                        .blocks_meta
+                        .insert("client".to_string(), SubscribeRequestFilterBlocksMeta {});
+                }
+                GeyserFilterKind::Slots => {
+                    request.slots.insert(
+                        "client".to_string(),
+                        SubscribeRequestFilterSlots {
+                            filter_by_commitment: Some(true),
+                        },
+                    );
+                }
+                GeyserFilterKind::Accounts { account, owner } => {
+                    request.accounts.insert(
+                        "client".to_string(),
+                        SubscribeRequestFilterAccounts {
+                            account: account.clone(),
+                            owner: owner.clone(),
+                            ..Default::default()
+                        },
+                    );
+                }
+                GeyserFilterKind::Transactions {
+                    account_include,
+                    vote,
+                    failed,
+                } => {
+                    request.transactions.insert(
+                        "client".to_string(),
+                        SubscribeRequestFilterTransactions {
+                            vote: *vote,
+                            failed: *failed,
+                            account_include: account_include.clone(),
+                            ..Default::default()
+                        },
+                    );
+                }
+                GeyserFilterKind::Entries => {
+                    request
+                        .entry
+                        .insert("client".to_string(), SubscribeRequestFilterEntry {});
+                }
+            }
         }
+
+        request
+    }
+}
+
+// ping on an existing subscription; server answers with pong and keeps the filters
+pub(crate) fn ping_request(id: i32) -> SubscribeRequest {
+    SubscribeRequest {
+        ping: Some(SubscribeRequestPing { id }),
+        ..Default::default()
+    }
+}
+
+// server pings and answers to our pings; not passed downstream
+pub(crate) fn is_keepalive_frame(update: &SubscribeUpdate) -> bool {
+    matches!(
+        update.update_oneof,
+        Some(UpdateOneof::Ping(_)) | Some(UpdateOneof::Pong(_))
+    )
+}
+
+// slot of the update if the update type carries one
+pub(crate) fn slot_of_update(update: &SubscribeUpdate) -> Option<Slot> {
+    match &update.update_oneof {
+        Some(UpdateOneof::Account(account)) => Some(account.slot),
+        Some(UpdateOneof::Slot(slot)) => Some(slot.slot),
+        Some(UpdateOneof::Transaction(transaction)) => Some(transaction.slot),
+        Some(UpdateOneof::Block(block)) => Some(block.slot),
+        Some(UpdateOneof::BlockMeta(block_meta)) => Some(block_meta.slot),
+        Some(UpdateOneof::Entry(entry)) => Some(entry.slot),
+        _ => None,
     }
 }
 
+#[cfg(not(feature = "solana-sdk"))]
 fn map_commitment_level(commitment_config: CommitmentConfig) -> CommitmentLevel {
-    // solana_sdk -> yellowstone
+    commitment_config.commitment
+}
+
+// solana_sdk -> yellowstone; deprecated levels map to their replacement as documented in solana_sdk
+#[cfg(feature = "solana-sdk")]
+#[allow(deprecated)]
+fn map_commitment_level(commitment_config: CommitmentConfig) -> CommitmentLevel {
+    use solana_sdk::commitment_config::CommitmentLevel as SolanaCommitmentLevel;
     match commitment_config.commitment {
-        solana_sdk::commitment_config::CommitmentLevel::Processed => {
-            yellowstone_grpc_proto::prelude::CommitmentLevel::Processed
-        }
-        solana_sdk::commitment_config::CommitmentLevel::Confirmed => {
-            yellowstone_grpc_proto::prelude::CommitmentLevel::Confirmed
+        SolanaCommitmentLevel::Processed | SolanaCommitmentLevel::Recent => {
+            CommitmentLevel::Processed
         }
-        solana_sdk::commitment_config::CommitmentLevel::Finalized => {
-            yellowstone_grpc_proto::prelude::CommitmentLevel::Finalized
-        }
-        _ => {
-            panic!(
-                "unsupported commitment level {}",
-                commitment_config.commitment
-            )
+        SolanaCommitmentLevel::Confirmed
+        | SolanaCommitmentLevel::Single
+        | SolanaCommitmentLevel::SingleGossip => CommitmentLevel::Confirmed,
+        SolanaCommitmentLevel::Finalized
+        | SolanaCommitmentLevel::Max
+        | SolanaCommitmentLevel::Root => CommitmentLevel::Finalized,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_label_no_secrets() {
+        let config = GrpcSourceConfig::new(
+            "http://mango.rpcpool.com/121sdfsdf21".to_string(),
+            Some("my-secret".to_string()),
+            None,
+            GrpcConnectionTimeouts {
+                connect_timeout: Duration::from_secs(1),
+                request_timeout: Duration::from_secs(2),
+                subscribe_timeout: Duration::from_secs(3),
+                receive_timeout: Duration::from_secs(3),
+            },
+        );
+        assert_eq!(config.label(), "http://mango.rpcpool.com/omitted-secret");
+        assert!(!format!("{:?}", config).contains("my-secret"));
+        assert!(!format!("{}", config).contains("121sdfsdf21"));
+    }
+
+    #[test]
+    fn test_label_and_endpoint_equality() {
+        let config = GrpcSourceConfig::new_simple("http://127.0.0.1:10000".to_string())
+            .with_keepalive(Duration::from_secs(5))
+            .with_label("green");
+        assert_eq!(config.label(), "green");
+        assert_eq!(
+            config.to_string(),
+            "green (grpc_addr http://127.0.0.1:10000)"
+        );
+        assert_eq!(config.grpc_addr(), "http://127.0.0.1:10000");
+        assert_eq!(config.keepalive_interval(), Some(Duration::from_secs(5)));
+
+        let same_endpoint = GrpcSourceConfig::new_simple("http://127.0.0.1:10000".to_string());
+        let other_endpoint = GrpcSourceConfig::new_simple("http://127.0.0.1:10001".to_string());
+        assert_eq!(config, same_endpoint);
+        assert_ne!(config, other_endpoint);
+    }
+
+    #[test]
+    fn test_combined_subscribe_request() {
+        let request = GeyserFilter(CommitmentConfig::confirmed()).subscribe_request(&[
+            GeyserFilterKind::BlocksMeta,
+            GeyserFilterKind::Slots,
+            GeyserFilterKind::Accounts {
+                account: vec![],
+                owner: vec!["TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA".to_string()],
+            },
+        ]);
+        assert_eq!(request.blocks_meta.len(), 1);
+        assert_eq!(request.slots.len(), 1);
+        assert_eq!(request.accounts["client"].owner.len(), 1);
+        assert!(request.blocks.is_empty());
+        assert!(request.transactions.is_empty());
+        assert!(request.entry.is_empty());
+        assert_eq!(request.commitment, Some(CommitmentLevel::Confirmed as i32));
+    }
+
+    #[cfg(feature = "solana-sdk")]
+    #[test]
+    #[allow(deprecated)]
+    fn test_map_deprecated_commitment_level() {
+        use solana_sdk::commitment_config::CommitmentLevel as SolanaCommitmentLevel;
+        let mapped = |commitment| map_commitment_level(CommitmentConfig { commitment });
+        assert_eq!(
+            mapped(SolanaCommitmentLevel::Recent),
+            CommitmentLevel::Processed
+        );
+        assert_eq!(
+            mapped(SolanaCommitmentLevel::SingleGossip),
+            CommitmentLevel::Confirmed
+        );
+        assert_eq!(
+            mapped(SolanaCommitmentLevel::Max),
+            CommitmentLevel::Finalized
+        );
+    }
+
+    struct RotatingTokens(std::sync::atomic::AtomicU32);
+
+    impl TokenProvider for RotatingTokens {
+        fn get_token(&self) -> BoxFuture<'static, anyhow::Result<String>> {
+            let generation = self.0.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            Box::pin(async move { Ok(format!("token-{generation}")) })
         }
     }
+
+    #[tokio::test]
+    async fn test_token_provider() {
+        let static_token = GrpcSourceConfig::new_simple("http://127.0.0.1:10000".to_string());
+        assert_eq!(static_token.x_token().await.unwrap(), None);
+
+        let rotating = static_token.with_token_provider(RotatingTokens(Default::default()));
+        assert_eq!(
+            rotating.x_token().await.unwrap().as_deref(),
+            Some("token-0")
+        );
+        assert_eq!(
+            rotating.x_token().await.unwrap().as_deref(),
+            Some("token-1")
+        );
+    }
+
+    #[test]
+    fn test_keepalive_frames() {
+        let ping = ping_request(7);
+        assert_eq!(ping.ping.unwrap().id, 7);
+        assert!(ping.slots.is_empty() && ping.blocks.is_empty());
+
+        let pong = SubscribeUpdate {
+            filters: vec![],
+            update_oneof: Some(UpdateOneof::Pong(
+                yellowstone_grpc_proto::geyser::SubscribeUpdatePong { id: 7 },
+            )),
+        };
+        assert!(is_keepalive_frame(&pong));
+        let slot = SubscribeUpdate {
+            filters: vec![],
+            update_oneof: Some(UpdateOneof::Slot(
+                yellowstone_grpc_proto::geyser::SubscribeUpdateSlot {
+                    slot: 1,
+                    parent: None,
+                    status: 0,
+                },
+            )),
+        };
+        assert!(!is_keepalive_frame(&slot));
+    }
 }
@@ -0,0 +1,23 @@
+//! structured lifecycle events of sources and the multiplexer
+//!
+//! all events are ``tracing`` events with target ``LIFECYCLE_TARGET`` and an ``event`` field, e.g.
+//! ``tracing_subscriber::fmt().json()`` with the filter ``geyser_grpc_connector::lifecycle=info``
+//! gives one json line per event; without a tracing subscriber the events are emitted as ``log``
+//! records with the same target. Emitted by the reconnecting streams and tasks:
+//!
+//! | event                | level | fields                                    |
+//! |----------------------|-------|-------------------------------------------|
+//! | ``connect``          | info  | source, attempt                           |
+//! | ``subscribed``       | info  | source, attempt                           |
+//! | ``subscribe_failed`` | warn  | source, attempt, error                    |
+//! | ``first_message``    | info  | source, attempt, slot                     |
+//! | ``disconnected``     | warn  | source, attempt, reason, error            |
+//! | ``reconnect``        | info  | source, attempt, wait_ms, rotate_endpoint |
+//! | ``give_up``          | warn  | source, attempt                           |
+//! | ``dedup``            | debug | stream_idx, slot, decision                |
+//!
+//! ``decision`` of ``dedup`` is ``yield`` (fastest source), ``duplicate`` (slot already yielded
+//! from another source) or ``outdated`` (slot below the tip, never yielded); ``reason`` of
+//! ``disconnected`` is ``error``, ``closed``, ``timeout``, ``ping_failed`` or ``oversized``
+
+pub const LIFECYCLE_TARGET: &str = "geyser_grpc_connector::lifecycle";
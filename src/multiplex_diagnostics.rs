@@ -0,0 +1,250 @@
+use crate::Slot;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast;
+use yellowstone_grpc_proto::geyser::subscribe_update::UpdateOneof;
+use yellowstone_grpc_proto::geyser::SubscribeUpdate;
+
+// keep enough samples per source to get stable percentiles without unbounded growth
+const MAX_SAMPLES_PER_SOURCE: usize = 10_000;
+
+/// a source delivered a slot that was already yielded from a faster source
+#[derive(Clone, Debug)]
+pub struct LateArrival {
+    pub stream_idx: usize,
+    pub slot: Slot,
+    // time between the winner was yielded and this source delivered the same slot
+    pub lost_by: Duration,
+}
+
+/// distribution of "lost by" delays for one source
+#[derive(Clone, Debug)]
+pub struct LostBySummary {
+    // total number of late arrivals since start
+    pub count: u64,
+    pub min: Duration,
+    pub max: Duration,
+    pub mean: Duration,
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+}
+
+#[derive(Default)]
+struct SourceSamples {
+    count: u64,
+    samples: VecDeque<Duration>,
+}
+
+#[derive(Default)]
+pub struct LateArrivalStats {
+    sources: HashMap<usize, SourceSamples>,
+    wins: HashMap<usize, u64>,
+}
+
+impl LateArrivalStats {
+    pub fn record_win(&mut self, stream_idx: usize) {
+        *self.wins.entry(stream_idx).or_default() += 1;
+    }
+
+    /// number of slots this source delivered first
+    pub fn source_wins(&self, stream_idx: usize) -> u64 {
+        self.wins.get(&stream_idx).copied().unwrap_or_default()
+    }
+
+    pub fn record(&mut self, late_arrival: &LateArrival) {
+        let source = self.sources.entry(late_arrival.stream_idx).or_default();
+        source.count += 1;
+        if source.samples.len() >= MAX_SAMPLES_PER_SOURCE {
+            source.samples.pop_front();
+        }
+        source.samples.push_back(late_arrival.lost_by);
+    }
+
+    /// percentiles are computed over the most recent samples only
+    pub fn source_stats(&self, stream_idx: usize) -> Option<LostBySummary> {
+        let source = self.sources.get(&stream_idx)?;
+        if source.samples.is_empty() {
+            return None;
+        }
+
+        let mut sorted: Vec<Duration> = source.samples.iter().copied().collect();
+        sorted.sort();
+        let total: Duration = sorted.iter().sum();
+
+        Some(LostBySummary {
+            count: source.count,
+            min: sorted[0],
+            max: sorted[sorted.len() - 1],
+            mean: total / sorted.len() as u32,
+            p50: percentile(&sorted, 50),
+            p90: percentile(&sorted, 90),
+            p99: percentile(&sorted, 99),
+        })
+    }
+
+    pub fn all_source_stats(&self) -> Vec<(usize, LostBySummary)> {
+        let mut stream_indices: Vec<usize> = self.sources.keys().copied().collect();
+        stream_indices.sort();
+        stream_indices
+            .into_iter()
+            .filter_map(|idx| self.source_stats(idx).map(|summary| (idx, summary)))
+            .collect()
+    }
+}
+
+// nearest-rank on sorted samples
+pub(crate) fn percentile<T: Copy>(sorted: &[T], pct: usize) -> T {
+    let rank = (sorted.len() * pct + 99) / 100;
+    sorted[rank.saturating_sub(1).min(sorted.len() - 1)]
+}
+
+/// handle shared with the multiplexer; cheap to clone
+#[derive(Clone)]
+pub struct LateArrivalDiagnostics {
+    sender: broadcast::Sender<LateArrival>,
+    stats: Arc<Mutex<LateArrivalStats>>,
+}
+
+impl LateArrivalDiagnostics {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(1000);
+        Self {
+            sender,
+            stats: Arc::new(Mutex::new(LateArrivalStats::default())),
+        }
+    }
+
+    /// stream of late arrivals; lagging receivers will miss events
+    pub fn subscribe(&self) -> broadcast::Receiver<LateArrival> {
+        self.sender.subscribe()
+    }
+
+    pub fn source_stats(&self, stream_idx: usize) -> Option<LostBySummary> {
+        self.stats.lock().unwrap().source_stats(stream_idx)
+    }
+
+    pub fn all_source_stats(&self) -> Vec<(usize, LostBySummary)> {
+        self.stats.lock().unwrap().all_source_stats()
+    }
+
+    pub fn source_wins(&self, stream_idx: usize) -> u64 {
+        self.stats.lock().unwrap().source_wins(stream_idx)
+    }
+
+    pub(crate) fn record_win(&self, stream_idx: usize) {
+        self.stats.lock().unwrap().record_win(stream_idx);
+    }
+
+    pub(crate) fn record(&self, late_arrival: LateArrival) {
+        self.stats.lock().unwrap().record(&late_arrival);
+        // no subscribers is fine - stats are still collected
+        let _ = self.sender.send(late_arrival);
+    }
+}
+
+impl Default for LateArrivalDiagnostics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// cheap summary of a block used to compare copies from different sources
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlockFingerprint {
+    pub blockhash: String,
+    pub transaction_count: u64,
+}
+
+impl BlockFingerprint {
+    /// supports blocks and blocks meta updates; other updates have no fingerprint
+    pub fn from_update(update: &SubscribeUpdate) -> Option<Self> {
+        match &update.update_oneof {
+            Some(UpdateOneof::Block(block)) => Some(BlockFingerprint {
+                blockhash: block.blockhash.clone(),
+                transaction_count: block.executed_transaction_count,
+            }),
+            Some(UpdateOneof::BlockMeta(block_meta)) => Some(BlockFingerprint {
+                blockhash: block_meta.blockhash.clone(),
+                transaction_count: block_meta.executed_transaction_count,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// a later copy of an already yielded block did not match the fastest copy
+#[derive(Clone, Debug)]
+pub struct ConsistencyViolation {
+    pub slot: Slot,
+    pub winner_stream_idx: usize,
+    pub expected: BlockFingerprint,
+    pub stream_idx: usize,
+    pub observed: BlockFingerprint,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeadLetterReason {
+    // extractor did not map the update
+    NotExtracted,
+    // arrived after a higher slot was yielded and was never yielded itself
+    OutOfOrder { tip: Slot },
+}
+
+/// update the multiplexer did not yield
+#[derive(Clone, Debug)]
+pub struct DeadLetter {
+    pub stream_idx: usize,
+    pub slot: Option<Slot>,
+    pub reason: DeadLetterReason,
+    pub update: Box<SubscribeUpdate>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn late(stream_idx: usize, lost_by_ms: u64) -> LateArrival {
+        LateArrival {
+            stream_idx,
+            slot: 42,
+            lost_by: Duration::from_millis(lost_by_ms),
+        }
+    }
+
+    #[test]
+    fn test_percentiles() {
+        let mut stats = LateArrivalStats::default();
+        for ms in 1..=100 {
+            stats.record(&late(0, ms));
+        }
+        stats.record(&late(1, 7));
+
+        let summary = stats.source_stats(0).unwrap();
+        assert_eq!(summary.count, 100);
+        assert_eq!(summary.min, Duration::from_millis(1));
+        assert_eq!(summary.max, Duration::from_millis(100));
+        assert_eq!(summary.p50, Duration::from_millis(50));
+        assert_eq!(summary.p90, Duration::from_millis(90));
+        assert_eq!(summary.p99, Duration::from_millis(99));
+
+        let summary = stats.source_stats(1).unwrap();
+        assert_eq!(summary.p50, Duration::from_millis(7));
+        assert_eq!(summary.p99, Duration::from_millis(7));
+
+        assert!(stats.source_stats(2).is_none());
+        assert_eq!(stats.all_source_stats().len(), 2);
+    }
+
+    #[test]
+    fn test_bounded_samples() {
+        let mut stats = LateArrivalStats::default();
+        for _ in 0..MAX_SAMPLES_PER_SOURCE + 10 {
+            stats.record(&late(0, 1));
+        }
+        let summary = stats.source_stats(0).unwrap();
+        assert_eq!(summary.count, (MAX_SAMPLES_PER_SOURCE + 10) as u64);
+        assert_eq!(stats.sources[&0].samples.len(), MAX_SAMPLES_PER_SOURCE);
+    }
+}
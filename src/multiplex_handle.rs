@@ -0,0 +1,208 @@
+use crate::grpc_subscription_autoreconnect_streams::create_geyser_reconnecting_stream;
+use crate::grpcmultiplex_fastestwins::{create_multiplexed_stream, FromYellowstoneExtractor};
+use crate::{GrpcSourceConfig, Message};
+use anyhow::{anyhow, bail};
+use async_stream::stream;
+use futures::future::{self, Either};
+use futures::stream::{AbortHandle, Abortable};
+use futures::{Stream, StreamExt};
+use log::info;
+use std::collections::HashMap;
+use std::pin::pin;
+use std::sync::Arc;
+use tokio::sync::watch;
+use yellowstone_grpc_proto::geyser::SubscribeRequest;
+
+/// lifecycle control of a multiplexer created by ``create_multiplex_with_handle``; cheap to clone
+#[derive(Clone)]
+pub struct MultiplexHandle {
+    abort_handle: AbortHandle,
+    // keyed by ``GrpcSourceConfig::label``
+    paused: Arc<HashMap<String, watch::Sender<bool>>>,
+}
+
+impl MultiplexHandle {
+    /// ends the multiplexed stream; the source connections are closed once the stream is dropped
+    pub fn abort(&self) {
+        self.abort_handle.abort();
+    }
+
+    pub fn is_aborted(&self) -> bool {
+        self.abort_handle.is_aborted()
+    }
+
+    /// disconnect the source until ``resume_source``; the other sources continue
+    pub fn pause_source(&self, label: &str) -> anyhow::Result<()> {
+        self.set_paused(label, true)
+    }
+
+    /// reconnect a paused source
+    pub fn resume_source(&self, label: &str) -> anyhow::Result<()> {
+        self.set_paused(label, false)
+    }
+
+    pub fn is_paused(&self, label: &str) -> Option<bool> {
+        self.paused.get(label).map(|paused| *paused.borrow())
+    }
+
+    fn set_paused(&self, label: &str, paused: bool) -> anyhow::Result<()> {
+        let sender = self
+            .paused
+            .get(label)
+            .ok_or_else(|| anyhow!("unknown source {}", label))?;
+        sender.send_replace(paused);
+        Ok(())
+    }
+}
+
+/// like ``create_multiplexed_stream`` over reconnecting streams of the given sources, with a
+/// handle to abort the multiplexer or pause single sources
+///
+/// sources are paused by label, so the labels must be unique
+pub fn create_multiplex_with_handle<E>(
+    grpc_sources: Vec<GrpcSourceConfig>,
+    subscribe_filter: SubscribeRequest,
+    extractor: E,
+) -> anyhow::Result<(impl Stream<Item = E::Target>, MultiplexHandle)>
+where
+    E: FromYellowstoneExtractor,
+{
+    let mut paused = HashMap::new();
+    let mut streams = Vec::with_capacity(grpc_sources.len());
+    for grpc_source in grpc_sources {
+        let label = grpc_source.label();
+        if paused.contains_key(&label) {
+            bail!("duplicate source label {}", label);
+        }
+        let (paused_tx, paused_rx) = watch::channel(false);
+        paused.insert(label.clone(), paused_tx);
+        let subscribe_filter = subscribe_filter.clone();
+        streams.push(pausable_source(
+            label,
+            move || {
+                create_geyser_reconnecting_stream(grpc_source.clone(), subscribe_filter.clone())
+            },
+            paused_rx,
+        ));
+    }
+
+    let (abort_handle, abort_registration) = AbortHandle::new_pair();
+    let multiplexed = Abortable::new(
+        create_multiplexed_stream(streams, extractor),
+        abort_registration,
+    );
+
+    Ok((
+        multiplexed,
+        MultiplexHandle {
+            abort_handle,
+            paused: Arc::new(paused),
+        },
+    ))
+}
+
+// drops the source stream while paused and creates a new one on resume
+fn pausable_source<S, F>(
+    label: String,
+    mut stream_factory: F,
+    mut paused: watch::Receiver<bool>,
+) -> impl Stream<Item = Message>
+where
+    S: Stream<Item = Message>,
+    F: FnMut() -> S,
+{
+    stream! {
+        loop {
+            while *paused.borrow_and_update() {
+                if paused.changed().await.is_err() {
+                    // handle dropped while paused: no resume possible
+                    return;
+                }
+            }
+            info!("source {} active", label);
+            let mut source_stream = pin!(stream_factory());
+            loop {
+                let next_event = {
+                    let next_message = source_stream.next();
+                    let pause_changed = paused.changed();
+                    match future::select(pin!(next_message), pin!(pause_changed)).await {
+                        Either::Left((message, _)) => Either::Left(message),
+                        Either::Right((changed, _)) => Either::Right(changed.is_ok()),
+                    }
+                };
+                match next_event {
+                    Either::Left(Some(message)) => yield message,
+                    Either::Left(None) => return,
+                    Either::Right(true) => {
+                        if *paused.borrow() {
+                            info!("source {} paused", label);
+                            break;
+                        }
+                    }
+                    Either::Right(false) => {
+                        // handle dropped: cannot be paused anymore
+                        while let Some(message) = source_stream.next().await {
+                            yield message;
+                        }
+                        return;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grpcmultiplex_fastestwins::BlockMetaExtractor;
+    use crate::simulation::slot_message;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_pause_and_resume_source() {
+        let connects = Arc::new(AtomicU32::new(0));
+        let factory_connects = connects.clone();
+        let (paused_tx, paused_rx) = watch::channel(false);
+        let source = pausable_source(
+            "test".to_string(),
+            move || {
+                factory_connects.fetch_add(1, Ordering::Relaxed);
+                futures::stream::iter(1..).then(|slot| async move {
+                    tokio::time::sleep(Duration::from_millis(5)).await;
+                    slot_message(slot)
+                })
+            },
+            paused_rx,
+        );
+        let mut source = pin!(source);
+
+        assert!(source.next().await.is_some());
+        assert_eq!(connects.load(Ordering::Relaxed), 1);
+
+        paused_tx.send_replace(true);
+        let while_paused = tokio::time::timeout(Duration::from_millis(30), source.next()).await;
+        assert!(while_paused.is_err());
+
+        paused_tx.send_replace(false);
+        assert!(source.next().await.is_some());
+        assert_eq!(connects.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn test_duplicate_labels_rejected() {
+        let grpc_sources = vec![
+            GrpcSourceConfig::new_simple("http://a.test".to_string()).with_label("rpc"),
+            GrpcSourceConfig::new_simple("http://b.test".to_string()).with_label("rpc"),
+        ];
+
+        let result = create_multiplex_with_handle(
+            grpc_sources,
+            SubscribeRequest::default(),
+            BlockMetaExtractor,
+        );
+
+        assert!(result.is_err());
+    }
+}
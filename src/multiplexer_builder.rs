@@ -0,0 +1,302 @@
+use crate::commitment::CommitmentLevel;
+use crate::grpc_stream_utils::{channelize_stream_with_capacity, ChannelizerCompletion};
+use crate::grpc_subscription_autoreconnect_streams::create_geyser_reconnecting_stream;
+use crate::grpcmultiplex_fastestwins::{
+    create_multiplexed_stream, create_multiplexed_stream_ordered, create_multiplexed_stream_sticky,
+    create_multiplexed_stream_weighted, create_multiplexed_stream_with_stats, read_ahead,
+    BlockExtractor, BlockMetaExtractor, FromYellowstoneExtractor, MultiplexOptions,
+    ReorderBufferConfig,
+};
+use crate::multiplexer_stats::MultiplexerStats;
+use crate::{GeyserFilter, GrpcSourceConfig, Message};
+use anyhow::ensure;
+use futures::stream::BoxStream;
+use futures::{Stream, StreamExt};
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use yellowstone_grpc_proto::geyser::{
+    SubscribeRequest, SubscribeUpdateBlock, SubscribeUpdateBlockMeta,
+};
+
+/// how the multiplexer picks one copy per slot
+#[derive(Clone, Debug, Default)]
+pub enum DedupStrategy {
+    // see ``create_multiplexed_stream``
+    #[default]
+    FastestWins,
+    // see ``create_multiplexed_stream_ordered``
+    Ordered(ReorderBufferConfig),
+    // see ``create_multiplexed_stream_weighted``, uses ``GrpcSourceConfig::weight``
+    Weighted {
+        tie_window: Duration,
+    },
+    // see ``create_multiplexed_stream_sticky``
+    Sticky {
+        stall_timeout: Duration,
+    },
+}
+
+/// multiplexer setup without positional arguments, e.g.
+/// ``MultiplexerBuilder::new().add_source(green).add_source(blue).commitment(CommitmentLevel::Finalized).build_blocks_stream()``
+#[derive(Clone)]
+pub struct MultiplexerBuilder {
+    sources: Vec<GrpcSourceConfig>,
+    commitment: CommitmentLevel,
+    dedup: DedupStrategy,
+    options: MultiplexOptions,
+    metrics: Option<MultiplexerStats>,
+}
+
+impl Default for MultiplexerBuilder {
+    fn default() -> Self {
+        MultiplexerBuilder {
+            sources: vec![],
+            commitment: CommitmentLevel::Confirmed,
+            dedup: DedupStrategy::default(),
+            options: MultiplexOptions::default(),
+            metrics: None,
+        }
+    }
+}
+
+impl MultiplexerBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_source(mut self, grpc_source: GrpcSourceConfig) -> Self {
+        self.sources.push(grpc_source);
+        self
+    }
+
+    pub fn add_sources(mut self, grpc_sources: impl IntoIterator<Item = GrpcSourceConfig>) -> Self {
+        self.sources.extend(grpc_sources);
+        self
+    }
+
+    /// default is confirmed
+    pub fn commitment(mut self, commitment: CommitmentLevel) -> Self {
+        self.commitment = commitment;
+        self
+    }
+
+    pub fn dedup(mut self, dedup: DedupStrategy) -> Self {
+        self.dedup = dedup;
+        self
+    }
+
+    /// broadcast capacity of the ``build_*_channel`` variants, see ``MultiplexOptions``; must be
+    /// at least 1
+    pub fn channel_capacity(mut self, channel_capacity: usize) -> Self {
+        self.options.channel_capacity = channel_capacity;
+        self
+    }
+
+    /// per-source read-ahead, see ``MultiplexOptions``
+    pub fn source_buffer(mut self, source_buffer: usize) -> Self {
+        self.options.source_buffer = source_buffer;
+        self
+    }
+
+    /// track all sources in ``stats`` under their label
+    pub fn metrics(mut self, stats: &MultiplexerStats) -> Self {
+        self.metrics = Some(stats.clone());
+        self
+    }
+
+    /// full blocks with transactions
+    pub fn build_blocks_stream(self) -> anyhow::Result<BoxStream<'static, SubscribeUpdateBlock>> {
+        self.build_stream(GeyserFilter::blocks_and_txs, BlockExtractor)
+    }
+
+    pub fn build_blocks_meta_stream(
+        self,
+    ) -> anyhow::Result<BoxStream<'static, SubscribeUpdateBlockMeta>> {
+        self.build_stream(GeyserFilter::blocks_meta, BlockMetaExtractor)
+    }
+
+    /// like ``build_blocks_stream`` but channelized with ``channel_capacity``
+    pub fn build_blocks_channel(
+        self,
+    ) -> anyhow::Result<(
+        broadcast::Receiver<SubscribeUpdateBlock>,
+        JoinHandle<ChannelizerCompletion>,
+    )> {
+        let channel_capacity = self.options.channel_capacity;
+        ensure!(channel_capacity > 0, "channel capacity must be at least 1");
+        Ok(channelize_stream_with_capacity(
+            self.build_blocks_stream()?,
+            channel_capacity,
+        ))
+    }
+
+    /// any subscription and extractor, e.g. ``build_stream(GeyserFilter::slots, SlotExtractor)``
+    pub fn build_stream<E>(
+        self,
+        subscribe_request: impl Fn(&GeyserFilter) -> SubscribeRequest,
+        extractor: E,
+    ) -> anyhow::Result<BoxStream<'static, E::Target>>
+    where
+        E: FromYellowstoneExtractor + Send + 'static,
+        E::Target: Send + 'static,
+    {
+        let subscribe_request = subscribe_request(&GeyserFilter(self.commitment.into()));
+        let source_streams = self
+            .sources
+            .iter()
+            .map(|grpc_source| {
+                (
+                    grpc_source.clone(),
+                    create_geyser_reconnecting_stream(
+                        grpc_source.clone(),
+                        subscribe_request.clone(),
+                    ),
+                )
+            })
+            .collect();
+        self.build_from_streams(source_streams, extractor)
+    }
+
+    // sources are given with their streams to allow testing without endpoints
+    fn build_from_streams<E>(
+        self,
+        source_streams: Vec<(
+            GrpcSourceConfig,
+            impl Stream<Item = Message> + Send + 'static,
+        )>,
+        extractor: E,
+    ) -> anyhow::Result<BoxStream<'static, E::Target>>
+    where
+        E: FromYellowstoneExtractor + Send + 'static,
+        E::Target: Send + 'static,
+    {
+        ensure!(!source_streams.is_empty(), "need at least one source");
+        let source_buffer = self.options.source_buffer;
+        let source_streams = source_streams
+            .into_iter()
+            .map(|(grpc_source, source_stream)| {
+                let source_stream = if source_buffer > 0 {
+                    read_ahead(source_stream, source_buffer).boxed()
+                } else {
+                    source_stream.boxed()
+                };
+                (grpc_source, source_stream)
+            })
+            .collect::<Vec<_>>();
+
+        if let (Some(stats), DedupStrategy::FastestWins) = (&self.metrics, &self.dedup) {
+            let labeled_streams = source_streams
+                .into_iter()
+                .map(|(grpc_source, source_stream)| (grpc_source.label(), source_stream))
+                .collect();
+            return Ok(
+                create_multiplexed_stream_with_stats(labeled_streams, extractor, stats).boxed(),
+            );
+        }
+
+        let tracked_streams: Vec<(GrpcSourceConfig, BoxStream<'static, Message>)> = source_streams
+            .into_iter()
+            .map(|(grpc_source, source_stream)| {
+                let source_stream = match &self.metrics {
+                    Some(stats) => stats.track(grpc_source.label(), source_stream).boxed(),
+                    None => source_stream.boxed(),
+                };
+                (grpc_source, source_stream)
+            })
+            .collect();
+
+        Ok(match self.dedup {
+            DedupStrategy::FastestWins => {
+                create_multiplexed_stream(streams_of(tracked_streams), extractor).boxed()
+            }
+            DedupStrategy::Ordered(config) => {
+                create_multiplexed_stream_ordered(streams_of(tracked_streams), extractor, config)
+                    .boxed()
+            }
+            DedupStrategy::Weighted { tie_window } => {
+                let weighted_streams = tracked_streams
+                    .into_iter()
+                    .map(|(grpc_source, source_stream)| (grpc_source.weight(), source_stream))
+                    .collect();
+                create_multiplexed_stream_weighted(weighted_streams, extractor, tie_window).boxed()
+            }
+            DedupStrategy::Sticky { stall_timeout } => create_multiplexed_stream_sticky(
+                streams_of(tracked_streams),
+                extractor,
+                stall_timeout,
+            )
+            .boxed(),
+        })
+    }
+}
+
+fn streams_of(
+    source_streams: Vec<(GrpcSourceConfig, BoxStream<'static, Message>)>,
+) -> Vec<BoxStream<'static, Message>> {
+    source_streams
+        .into_iter()
+        .map(|(_, source_stream)| source_stream)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::block_meta_message;
+    use futures::stream;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_builder_with_metrics() {
+        let stats = MultiplexerStats::new();
+        let green =
+            GrpcSourceConfig::new_simple("http://127.0.0.1:10000".to_string()).with_label("green");
+        let blue =
+            GrpcSourceConfig::new_simple("http://127.0.0.1:10001".to_string()).with_label("blue");
+        let source_streams = vec![
+            (
+                green,
+                stream::iter(vec![block_meta_message(1, 0), block_meta_message(2, 1)]).boxed(),
+            ),
+            (
+                blue,
+                stream::iter(vec![block_meta_message(2, 1), block_meta_message(3, 2)])
+                    .then(|message| async move {
+                        tokio::time::sleep(Duration::from_millis(10)).await;
+                        message
+                    })
+                    .boxed(),
+            ),
+        ];
+
+        let slots: Vec<u64> = MultiplexerBuilder::new()
+            .metrics(&stats)
+            .build_from_streams(source_streams, BlockMetaExtractor)
+            .unwrap()
+            .map(|block_meta| block_meta.slot)
+            .collect()
+            .await;
+
+        assert_eq!(slots, vec![1, 2, 3]);
+        assert_eq!(stats.source_stats("green").unwrap().messages_received, 2);
+        assert_eq!(stats.source_stats("blue").unwrap().wins, 1);
+    }
+
+    #[test]
+    fn test_builder_rejects_zero_channel_capacity() {
+        assert!(MultiplexerBuilder::new()
+            .add_source(GrpcSourceConfig::new_simple(
+                "http://127.0.0.1:10000".to_string()
+            ))
+            .channel_capacity(0)
+            .build_blocks_channel()
+            .is_err());
+    }
+
+    #[test]
+    fn test_builder_without_sources() {
+        assert!(MultiplexerBuilder::new()
+            .build_blocks_meta_stream()
+            .is_err());
+    }
+}
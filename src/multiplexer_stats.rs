@@ -0,0 +1,399 @@
+use crate::{Attempt, Message};
+use futures::{Stream, StreamExt};
+use hdrhistogram::Histogram;
+use log::warn;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tokio::sync::{broadcast, watch};
+use yellowstone_grpc_proto::prost::Message as _;
+
+// name, help text and value of a prometheus counter
+type MetricCounter = (&'static str, &'static str, fn(&SourceStats) -> u64);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SourceState {
+    // connect (attempt=1) or reconnect(attempt=2..)
+    Connecting(Attempt),
+    // receiving updates
+    Ready,
+}
+
+#[derive(Clone, Debug)]
+pub struct SourceStats {
+    pub messages_received: u64,
+    // protobuf encoded size of the updates
+    pub bytes_received: u64,
+    pub reconnects: u64,
+    pub last_message_at: Option<SystemTime>,
+    pub state: SourceState,
+    // fastest-wins multiplexer: slots this source delivered first / after another source
+    pub wins: u64,
+    pub losses: u64,
+    // microseconds after the winner's copy of each contested slot; 0 for slots this source won
+    pub arrival_delta_us: Histogram<u64>,
+}
+
+/// quantiles of ``SourceStats::arrival_delta_us``
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ArrivalDeltas {
+    pub count: u64,
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub p999: Duration,
+    pub max: Duration,
+}
+
+impl SourceStats {
+    fn new() -> Self {
+        SourceStats {
+            messages_received: 0,
+            bytes_received: 0,
+            reconnects: 0,
+            last_message_at: None,
+            state: SourceState::Connecting(0),
+            wins: 0,
+            losses: 0,
+            // 1us to 10 minutes with 3 significant digits
+            arrival_delta_us: Histogram::new_with_bounds(1, 600_000_000, 3)
+                .expect("valid histogram bounds"),
+        }
+    }
+
+    pub fn arrival_deltas(&self) -> Option<ArrivalDeltas> {
+        let histogram = &self.arrival_delta_us;
+        let quantile = |q: f64| Duration::from_micros(histogram.value_at_quantile(q));
+        (!histogram.is_empty()).then(|| ArrivalDeltas {
+            count: histogram.len(),
+            p50: quantile(0.5),
+            p90: quantile(0.9),
+            p99: quantile(0.99),
+            p999: quantile(0.999),
+            max: Duration::from_micros(histogram.max()),
+        })
+    }
+
+    /// share of contested slots won by this source
+    pub fn win_rate(&self) -> Option<f64> {
+        let total = self.wins + self.losses;
+        (total > 0).then_some(self.wins as f64 / total as f64)
+    }
+
+    fn observe(&mut self, message: &Message) {
+        match message {
+            Message::GeyserSubscribeUpdate(update) => {
+                self.messages_received += 1;
+                self.bytes_received += update.encoded_len() as u64;
+                self.last_message_at = Some(SystemTime::now());
+                self.state = SourceState::Ready;
+            }
+            Message::Connecting(attempt) => {
+                // the stream emits Connecting for every state transition, count each attempt once
+                if let SourceState::Connecting(previous) = self.state {
+                    if *attempt > previous && *attempt > 1 {
+                        self.reconnects += 1;
+                    }
+                } else if *attempt > 1 {
+                    self.reconnects += 1;
+                }
+                self.state = SourceState::Connecting(*attempt);
+            }
+        }
+    }
+}
+
+/// transitions of the tracked sources as a whole
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AvailabilityEvent {
+    // every tracked source is connecting or waiting to reconnect
+    AllSourcesDisconnected,
+    // at least one source delivers updates again
+    Recovered,
+}
+
+struct Availability {
+    // true while no tracked source is Ready; also true before the first source connected
+    all_down: watch::Sender<bool>,
+    events: broadcast::Sender<AvailabilityEvent>,
+}
+
+impl Availability {
+    fn update(&self, all_down: bool) {
+        let was_down = *self.all_down.borrow();
+        if was_down == all_down {
+            return;
+        }
+        let _ = self.all_down.send(all_down);
+        let event = if all_down {
+            warn!("all sources disconnected - data is not fresh");
+            AvailabilityEvent::AllSourcesDisconnected
+        } else {
+            AvailabilityEvent::Recovered
+        };
+        // no subscribers is fine
+        let _ = self.events.send(event);
+    }
+}
+
+/// per-source statistics which can be queried at any time by the embedding application
+///
+/// wrap each source stream with ``track`` before passing them to the multiplexer
+#[derive(Clone)]
+pub struct MultiplexerStats {
+    sources: Arc<Mutex<HashMap<String, SourceStats>>>,
+    availability: Arc<Availability>,
+}
+
+impl Default for MultiplexerStats {
+    fn default() -> Self {
+        MultiplexerStats {
+            sources: Default::default(),
+            availability: Arc::new(Availability {
+                all_down: watch::channel(true).0,
+                events: broadcast::channel(16).0,
+            }),
+        }
+    }
+}
+
+impl MultiplexerStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// true while none of the tracked sources is ready, e.g. to pause work needing fresh data
+    pub fn watch_all_sources_down(&self) -> watch::Receiver<bool> {
+        self.availability.all_down.subscribe()
+    }
+
+    /// ``AllSourcesDisconnected``/``Recovered`` transitions; the initial connect emits ``Recovered``
+    pub fn subscribe_availability(&self) -> broadcast::Receiver<AvailabilityEvent> {
+        self.availability.events.subscribe()
+    }
+
+    /// record all messages passing through the stream under the given label (e.g. ``GrpcSourceConfig::label``)
+    pub fn track(
+        &self,
+        label: impl Into<String>,
+        source_stream: impl Stream<Item = Message>,
+    ) -> impl Stream<Item = Message> {
+        let label = label.into();
+        self.sources
+            .lock()
+            .unwrap()
+            .insert(label.clone(), SourceStats::new());
+        let sources = self.sources.clone();
+        let availability = self.availability.clone();
+        source_stream.inspect(move |message| {
+            let mut sources = sources.lock().unwrap();
+            if let Some(source) = sources.get_mut(&label) {
+                source.observe(message);
+            }
+            let all_down = sources
+                .values()
+                .all(|source| source.state != SourceState::Ready);
+            availability.update(all_down);
+        })
+    }
+
+    pub(crate) fn record_win(&self, label: &str) {
+        if let Some(source) = self.sources.lock().unwrap().get_mut(label) {
+            source.wins += 1;
+            source.arrival_delta_us.saturating_record(0);
+        }
+    }
+
+    pub(crate) fn record_loss(&self, label: &str, lost_by: Duration) {
+        if let Some(source) = self.sources.lock().unwrap().get_mut(label) {
+            source.losses += 1;
+            source
+                .arrival_delta_us
+                .saturating_record(lost_by.as_micros() as u64);
+        }
+    }
+
+    pub fn source_stats(&self, label: &str) -> Option<SourceStats> {
+        self.sources.lock().unwrap().get(label).cloned()
+    }
+
+    pub fn all_source_stats(&self) -> Vec<(String, SourceStats)> {
+        let mut all: Vec<(String, SourceStats)> = self
+            .sources
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(label, stats)| (label.clone(), stats.clone()))
+            .collect();
+        all.sort_by(|a, b| a.0.cmp(&b.0));
+        all
+    }
+
+    /// counters in prometheus text exposition format, e.g. to serve on a metrics endpoint
+    pub fn render_metrics(&self) -> String {
+        let all = self.all_source_stats();
+        let counters: [MetricCounter; 5] = [
+            (
+                "geyser_source_messages_received_total",
+                "updates received from the source",
+                |stats| stats.messages_received,
+            ),
+            (
+                "geyser_source_bytes_received_total",
+                "protobuf encoded bytes received from the source",
+                |stats| stats.bytes_received,
+            ),
+            (
+                "geyser_source_reconnects_total",
+                "reconnect attempts of the source",
+                |stats| stats.reconnects,
+            ),
+            (
+                "geyser_source_wins_total",
+                "slots the source delivered first",
+                |stats| stats.wins,
+            ),
+            (
+                "geyser_source_losses_total",
+                "slots the source delivered after another source",
+                |stats| stats.losses,
+            ),
+        ];
+        let mut rendered = String::new();
+        for (name, help, value_of) in counters {
+            rendered.push_str(&format!(
+                "# HELP {} {}\n# TYPE {} counter\n",
+                name, help, name
+            ));
+            for (label, stats) in &all {
+                rendered.push_str(&format!(
+                    "{}{{source=\"{}\"}} {}\n",
+                    name,
+                    label.replace('\\', "\\\\").replace('"', "\\\""),
+                    value_of(stats)
+                ));
+            }
+        }
+        let name = "geyser_source_arrival_delta_seconds";
+        rendered.push_str(&format!(
+            "# HELP {} time after the winner's copy of a slot\n# TYPE {} summary\n",
+            name, name
+        ));
+        for (label, stats) in &all {
+            let label = label.replace('\\', "\\\\").replace('"', "\\\"");
+            let Some(deltas) = stats.arrival_deltas() else {
+                continue;
+            };
+            for (quantile, value) in [
+                ("0.5", deltas.p50),
+                ("0.9", deltas.p90),
+                ("0.99", deltas.p99),
+                ("0.999", deltas.p999),
+            ] {
+                rendered.push_str(&format!(
+                    "{}{{source=\"{}\",quantile=\"{}\"}} {}\n",
+                    name,
+                    label,
+                    quantile,
+                    value.as_secs_f64()
+                ));
+            }
+            rendered.push_str(&format!(
+                "{}_count{{source=\"{}\"}} {}\n",
+                name, label, deltas.count
+            ));
+        }
+        rendered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::slot_message;
+    use futures::stream;
+
+    #[tokio::test]
+    async fn test_track_source() {
+        let stats = MultiplexerStats::new();
+        let source = stream::iter(vec![
+            Message::Connecting(1),
+            Message::Connecting(1),
+            slot_message(1),
+            slot_message(2),
+            Message::Connecting(1),
+            Message::Connecting(2),
+            Message::Connecting(2),
+        ]);
+
+        let tracked = stats.track("green", source);
+        assert_eq!(
+            stats.source_stats("green").unwrap().state,
+            SourceState::Connecting(0)
+        );
+        let _: Vec<Message> = tracked.collect().await;
+
+        let green = stats.source_stats("green").unwrap();
+        assert_eq!(green.messages_received, 2);
+        assert!(green.bytes_received > 0);
+        assert_eq!(green.reconnects, 1);
+        assert!(green.last_message_at.is_some());
+        assert_eq!(green.state, SourceState::Connecting(2));
+        assert!(stats.source_stats("blue").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_all_sources_down() {
+        let stats = MultiplexerStats::new();
+        let green = stats.track(
+            "green",
+            stream::iter(vec![slot_message(1), Message::Connecting(2)]),
+        );
+        let blue = stats.track(
+            "blue",
+            stream::iter(vec![Message::Connecting(1), Message::Connecting(2)]),
+        );
+        let all_down = stats.watch_all_sources_down();
+        let mut events = stats.subscribe_availability();
+        assert!(*all_down.borrow());
+
+        let _: Vec<Message> = green.collect().await;
+        let _: Vec<Message> = blue.collect().await;
+
+        assert!(*all_down.borrow());
+        assert_eq!(events.recv().await.unwrap(), AvailabilityEvent::Recovered);
+        assert_eq!(
+            events.recv().await.unwrap(),
+            AvailabilityEvent::AllSourcesDisconnected
+        );
+        assert!(events.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_win_loss_metrics() {
+        let stats = MultiplexerStats::new();
+        let _ = stats.track("green", stream::empty::<Message>());
+        let _ = stats.track("blue", stream::empty::<Message>());
+        stats.record_win("green");
+        stats.record_win("green");
+        stats.record_win("green");
+        stats.record_loss("green", Duration::from_millis(40));
+        stats.record_loss("blue", Duration::from_millis(5));
+
+        let green = stats.source_stats("green").unwrap();
+        assert_eq!((green.wins, green.losses), (3, 1));
+        assert_eq!(green.win_rate(), Some(0.75));
+        let deltas = green.arrival_deltas().unwrap();
+        assert_eq!(deltas.count, 4);
+        assert_eq!(deltas.p50, Duration::ZERO);
+        // 3 significant digits
+        assert!(
+            deltas.max >= Duration::from_millis(40) && deltas.max < Duration::from_micros(40100)
+        );
+
+        let metrics = stats.render_metrics();
+        assert!(metrics.contains("geyser_source_wins_total{source=\"green\"} 3\n"));
+        assert!(metrics.contains("geyser_source_losses_total{source=\"blue\"} 1\n"));
+        assert!(metrics.contains("geyser_source_arrival_delta_seconds_count{source=\"green\"} 4\n"));
+    }
+}
@@ -0,0 +1,18 @@
+//! the yellowstone proto, tonic and client types at the versions this crate is built against,
+//! so downstream crates can name them without pinning matching versions, e.g.
+//! ``use geyser_grpc_connector::prelude::{SubscribeUpdateBlock, Status};``
+//!
+//! also includes the solana storage types of ``yellowstone_grpc_proto::prelude`` (transactions, rewards)
+
+pub use yellowstone_grpc_client;
+pub use yellowstone_grpc_client::{
+    GeyserGrpcClient, GeyserGrpcClientError, GeyserGrpcClientResult,
+};
+pub use yellowstone_grpc_proto;
+pub use yellowstone_grpc_proto::geyser::subscribe_update::UpdateOneof;
+pub use yellowstone_grpc_proto::prelude::*;
+pub use yellowstone_grpc_proto::tonic;
+pub use yellowstone_grpc_proto::tonic::metadata::AsciiMetadataValue;
+pub use yellowstone_grpc_proto::tonic::service::Interceptor;
+pub use yellowstone_grpc_proto::tonic::transport::{Certificate, ClientTlsConfig, Identity};
+pub use yellowstone_grpc_proto::tonic::{Code, Request, Status};
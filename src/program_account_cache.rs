@@ -0,0 +1,221 @@
+#[cfg(feature = "account-snapshot")]
+use crate::account_snapshot::AccountFeedItem;
+use crate::account_stream::{create_account_multiplex, AccountUpdate};
+use crate::commitment::CommitmentLevel;
+use crate::{GeyserFilter, GrpcSourceConfig, Slot};
+use futures::StreamExt;
+use log::warn;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::HashMap;
+use std::pin::pin;
+use std::sync::{Arc, RwLock};
+use tokio::task::JoinHandle;
+
+// closed accounts are remembered for that many slots, so older updates cannot revive them
+const TOMBSTONE_SLOTS: Slot = 1000;
+
+#[derive(Default)]
+struct CacheState {
+    accounts: HashMap<Pubkey, AccountUpdate>,
+    // (slot, write_version) of the close
+    closed: HashMap<Pubkey, (Slot, u64)>,
+    // tombstones below this slot are pruned
+    horizon: Slot,
+}
+
+impl CacheState {
+    fn prune_closed(&mut self, slot: Slot) {
+        let horizon = slot.saturating_sub(TOMBSTONE_SLOTS);
+        // only every hundredth slot to avoid a scan per update
+        if horizon < self.horizon + 100 {
+            return;
+        }
+        self.horizon = horizon;
+        self.closed
+            .retain(|_, (closed_slot, _)| *closed_slot >= horizon);
+    }
+}
+
+/// live state of the accounts of a subscription; cheap to clone
+///
+/// an update replaces the cached account if it is newer by (slot, write_version); write_version
+/// is only compared within a slot, where ``dedup_account_updates`` takes all updates from one source
+/// note: an account reassigned to another owner gets no more updates from an owner subscription
+/// and is never evicted
+#[derive(Clone, Default)]
+pub struct ProgramAccountCache {
+    state: Arc<RwLock<CacheState>>,
+}
+
+impl ProgramAccountCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, pubkey: &Pubkey) -> Option<AccountUpdate> {
+        self.state.read().unwrap().accounts.get(pubkey).cloned()
+    }
+
+    /// consistent copy of all accounts at the time of the call; data is shared, not copied
+    pub fn iter(&self) -> impl Iterator<Item = AccountUpdate> {
+        let accounts: Vec<AccountUpdate> = self
+            .state
+            .read()
+            .unwrap()
+            .accounts
+            .values()
+            .cloned()
+            .collect();
+        accounts.into_iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.state.read().unwrap().accounts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// false if the cache already has the same or a newer version of the account
+    /// closed accounts (0 lamports) are removed; the close is remembered for ``TOMBSTONE_SLOTS``
+    pub fn update(&self, account_update: AccountUpdate) -> bool {
+        let mut state = self.state.write().unwrap();
+        let version = (account_update.slot, account_update.write_version);
+        let cached_version = state
+            .accounts
+            .get(&account_update.pubkey)
+            .map(|cached| (cached.slot, cached.write_version))
+            .or_else(|| state.closed.get(&account_update.pubkey).copied());
+        if cached_version.map_or(false, |cached_version| version <= cached_version) {
+            return false;
+        }
+        state.prune_closed(account_update.slot);
+        if account_update.lamports == 0 {
+            state.accounts.remove(&account_update.pubkey);
+            state.closed.insert(account_update.pubkey, version);
+        } else {
+            state.closed.remove(&account_update.pubkey);
+            state.accounts.insert(account_update.pubkey, account_update);
+        }
+        true
+    }
+
+    /// a snapshot replaces the whole state
+    #[cfg(feature = "account-snapshot")]
+    pub fn apply_feed_item(&self, feed_item: AccountFeedItem) {
+        match feed_item {
+            AccountFeedItem::Snapshot(snapshot) => {
+                let accounts = snapshot
+                    .accounts
+                    .into_iter()
+                    .filter(|account| account.lamports > 0)
+                    .map(|account| (account.pubkey, account))
+                    .collect();
+                *self.state.write().unwrap() = CacheState {
+                    accounts,
+                    ..Default::default()
+                };
+            }
+            AccountFeedItem::Update(account_update) => {
+                self.update(account_update);
+            }
+        }
+    }
+}
+
+/// cache of the accounts owned by ``program_id``, fed by the account multiplexer of all sources
+/// note: only accounts written after the start are known; see ``ProgramAccountCache::apply_feed_item``
+/// to start from a snapshot
+pub fn spawn_program_account_cache(
+    grpc_sources: Vec<GrpcSourceConfig>,
+    program_id: Pubkey,
+    commitment: CommitmentLevel,
+) -> (ProgramAccountCache, JoinHandle<()>) {
+    let cache = ProgramAccountCache::new();
+    let account_updates = create_account_multiplex(
+        grpc_sources,
+        GeyserFilter(commitment.into()),
+        vec![],
+        vec![program_id.to_string()],
+    );
+    let jh_updater = tokio::spawn({
+        let cache = cache.clone();
+        async move {
+            let mut account_updates = pin!(account_updates);
+            while let Some(account_update) = account_updates.next().await {
+                cache.update(account_update);
+            }
+            warn!("account stream of program {} ended", program_id);
+        }
+    });
+    (cache, jh_updater)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+
+    fn account_update(
+        pubkey: Pubkey,
+        slot: Slot,
+        write_version: u64,
+        lamports: u64,
+    ) -> AccountUpdate {
+        AccountUpdate {
+            slot,
+            pubkey,
+            owner: Pubkey::default(),
+            lamports,
+            executable: false,
+            rent_epoch: 0,
+            write_version,
+            data: Bytes::new(),
+        }
+    }
+
+    #[test]
+    fn test_update_ordering() {
+        let cache = ProgramAccountCache::new();
+        let pubkey = Pubkey::new_unique();
+
+        assert!(cache.update(account_update(pubkey, 10, 5, 1)));
+        assert!(cache.update(account_update(pubkey, 10, 6, 2)));
+        // older write in the same slot and older slot
+        assert!(!cache.update(account_update(pubkey, 10, 4, 3)));
+        assert!(!cache.update(account_update(pubkey, 9, 100, 4)));
+        assert_eq!(cache.get(&pubkey).unwrap().lamports, 2);
+
+        let other = Pubkey::new_unique();
+        cache.update(account_update(other, 11, 0, 7));
+        assert_eq!(cache.iter().count(), 2);
+
+        // closed
+        assert!(cache.update(account_update(pubkey, 12, 0, 0)));
+        assert!(cache.get(&pubkey).is_none());
+        assert_eq!(cache.len(), 1);
+        // a late update from before the close does not revive it
+        assert!(!cache.update(account_update(pubkey, 11, 0, 5)));
+        assert!(cache.get(&pubkey).is_none());
+        // reopened
+        assert!(cache.update(account_update(pubkey, 13, 0, 6)));
+        assert_eq!(cache.get(&pubkey).unwrap().lamports, 6);
+    }
+
+    #[test]
+    fn test_tombstones_pruned() {
+        let cache = ProgramAccountCache::new();
+        let pubkey = Pubkey::new_unique();
+        cache.update(account_update(pubkey, 10, 0, 0));
+        assert_eq!(cache.state.read().unwrap().closed.len(), 1);
+
+        cache.update(account_update(
+            Pubkey::new_unique(),
+            10 + TOMBSTONE_SLOTS + 100,
+            0,
+            1,
+        ));
+        assert!(cache.state.read().unwrap().closed.is_empty());
+    }
+}
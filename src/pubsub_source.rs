@@ -0,0 +1,241 @@
+use crate::block_source::BlockSource;
+use crate::{Attempt, Message};
+use async_stream::stream;
+use futures::future;
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use log::{debug, info, warn};
+use solana_pubsub_client::nonblocking::pubsub_client::PubsubClient;
+use solana_rpc_client_api::config::{RpcBlockSubscribeConfig, RpcBlockSubscribeFilter};
+use solana_sdk::clock::Slot;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_transaction_status::{TransactionDetails, UiConfirmedBlock};
+use std::pin::pin;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::time::sleep;
+use yellowstone_grpc_proto::geyser::subscribe_update::UpdateOneof;
+use yellowstone_grpc_proto::geyser::{
+    CommitmentLevel, SubscribeUpdate, SubscribeUpdateBlockMeta, SubscribeUpdateSlot,
+};
+use yellowstone_grpc_proto::prelude::{BlockHeight, UnixTimestamp};
+
+#[derive(Clone, Debug)]
+pub enum PubsubSubscription {
+    // slotSubscribe; notifies processed slots only
+    Slots,
+    // blockSubscribe; requires --rpc-pubsub-enable-block-subscription on the rpc node
+    // yields block meta only (no transactions, no rewards)
+    BlocksMeta(CommitmentConfig),
+}
+
+/// lower-fidelity fallback source using the standard solana rpc websocket
+///
+/// updates are converted to geyser ``SubscribeUpdate`` so the source can be multiplexed together with grpc sources
+/// note: ``Slots`` are processed slots which run ahead of confirmed grpc slots and push the tip of a
+/// confirmed multiplexer; use ``standby_until_all_down`` so they only flow while no grpc source does
+pub struct PubsubSource {
+    ws_url: String,
+    subscription: PubsubSubscription,
+    // connected only while true, see ``MultiplexerStats::watch_all_sources_down``
+    all_grpc_down: Option<watch::Receiver<bool>>,
+}
+
+impl PubsubSource {
+    pub fn new(ws_url: String, subscription: PubsubSubscription) -> Self {
+        Self {
+            ws_url,
+            subscription,
+            all_grpc_down: None,
+        }
+    }
+
+    /// connect only while all grpc sources are down and disconnect once one recovers;
+    /// the pubsub source itself must not be tracked in the same ``MultiplexerStats``
+    pub fn standby_until_all_down(mut self, all_grpc_down: watch::Receiver<bool>) -> Self {
+        self.all_grpc_down = Some(all_grpc_down);
+        self
+    }
+}
+
+// resolves once a grpc source is up again; never without standby
+async fn grpc_recovered(all_grpc_down: Option<watch::Receiver<bool>>) {
+    match all_grpc_down {
+        Some(mut all_grpc_down) => {
+            let _ = all_grpc_down.wait_for(|all_down| !*all_down).await;
+        }
+        None => future::pending().await,
+    }
+}
+
+fn grpc_is_up(all_grpc_down: &Option<watch::Receiver<bool>>) -> bool {
+    all_grpc_down
+        .as_ref()
+        .map_or(false, |all_grpc_down| !*all_grpc_down.borrow())
+}
+
+impl BlockSource for PubsubSource {
+    fn label(&self) -> String {
+        crate::obfuscate::url_obfuscate_api_token(&self.ws_url).into_owned()
+    }
+
+    fn into_stream(self: Box<Self>) -> BoxStream<'static, Message> {
+        let label = self.label();
+        let PubsubSource {
+            ws_url,
+            subscription,
+            mut all_grpc_down,
+        } = *self;
+
+        // note: stream never terminates, except if the standby watch is dropped
+        stream! {
+            let mut attempt: Attempt = 0;
+            loop {
+                if let Some(all_grpc_down) = all_grpc_down.as_mut() {
+                    if all_grpc_down.wait_for(|all_down| *all_down).await.is_err() {
+                        warn!("availability of grpc sources unknown - stopping pubsub {}", label);
+                        return;
+                    }
+                    info!("all grpc sources down - activating pubsub fallback {}", label);
+                }
+                attempt += 1;
+                yield Message::Connecting(attempt);
+                debug!("Connecting attempt #{} to pubsub {}", attempt, label);
+
+                match PubsubClient::new(&ws_url).await {
+                    Ok(client) => match &subscription {
+                        PubsubSubscription::Slots => match client.slot_subscribe().await {
+                            Ok((slots, _unsubscribe)) => {
+                                info!("subscribed to slots on pubsub {}", label);
+                                let mut slots = pin!(slots.take_until(grpc_recovered(all_grpc_down.clone())));
+                                while let Some(slot_info) = slots.next().await {
+                                    yield slot_update(slot_info.slot, slot_info.parent);
+                                }
+                            }
+                            Err(err) => warn!("slot subscribe failed on pubsub {}: {:?}", label, err),
+                        },
+                        PubsubSubscription::BlocksMeta(commitment_config) => {
+                            let config = RpcBlockSubscribeConfig {
+                                commitment: Some(*commitment_config),
+                                encoding: None,
+                                transaction_details: Some(TransactionDetails::Signatures),
+                                show_rewards: Some(false),
+                                max_supported_transaction_version: Some(0),
+                            };
+                            match client.block_subscribe(RpcBlockSubscribeFilter::All, Some(config)).await {
+                                Ok((blocks, _unsubscribe)) => {
+                                    info!("subscribed to blocks on pubsub {}", label);
+                                    let mut blocks = pin!(blocks.take_until(grpc_recovered(all_grpc_down.clone())));
+                                    while let Some(response) = blocks.next().await {
+                                        let block_update = response.value;
+                                        if let Some(err) = block_update.err {
+                                            warn!("block notification error for slot {} on pubsub {}: {:?}", block_update.slot, label, err);
+                                            continue;
+                                        }
+                                        if let Some(block) = block_update.block {
+                                            yield block_meta_update(block_update.slot, &block);
+                                        }
+                                    }
+                                }
+                                Err(err) => warn!("block subscribe failed on pubsub {}: {:?}", label, err),
+                            }
+                        }
+                    },
+                    Err(err) => warn!("connect failed on pubsub {}: {:?}", label, err),
+                }
+
+                if grpc_is_up(&all_grpc_down) {
+                    info!("grpc source recovered - deactivating pubsub fallback {}", label);
+                    attempt = 0;
+                    continue;
+                }
+
+                // ATM we consider all errors recoverable
+                let backoff_secs = 1.5_f32.powi(attempt as i32).min(15.0);
+                info!("waiting {} seconds, then reconnect to pubsub {}", backoff_secs, label);
+                sleep(Duration::from_secs_f32(backoff_secs)).await;
+            }
+        }
+        .boxed()
+    }
+}
+
+fn slot_update(slot: Slot, parent: Slot) -> Message {
+    Message::GeyserSubscribeUpdate(Box::new(SubscribeUpdate {
+        filters: vec![],
+        update_oneof: Some(UpdateOneof::Slot(SubscribeUpdateSlot {
+            slot,
+            parent: Some(parent),
+            status: CommitmentLevel::Processed as i32,
+        })),
+    }))
+}
+
+fn block_meta_update(slot: Slot, block: &UiConfirmedBlock) -> Message {
+    let executed_transaction_count = block
+        .signatures
+        .as_ref()
+        .map(|signatures| signatures.len() as u64)
+        .unwrap_or_default();
+    Message::GeyserSubscribeUpdate(Box::new(SubscribeUpdate {
+        filters: vec![],
+        update_oneof: Some(UpdateOneof::BlockMeta(SubscribeUpdateBlockMeta {
+            slot,
+            blockhash: block.blockhash.clone(),
+            rewards: None,
+            block_time: block
+                .block_time
+                .map(|timestamp| UnixTimestamp { timestamp }),
+            block_height: block
+                .block_height
+                .map(|block_height| BlockHeight { block_height }),
+            parent_slot: block.parent_slot,
+            parent_blockhash: block.previous_blockhash.clone(),
+            executed_transaction_count,
+            ..Default::default()
+        })),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_grpc_recovered() {
+        let (all_down_tx, all_down_rx) = watch::channel(true);
+        assert!(!grpc_is_up(&Some(all_down_rx.clone())));
+        assert!(!grpc_is_up(&None));
+
+        let recovered = tokio::spawn(grpc_recovered(Some(all_down_rx.clone())));
+        all_down_tx.send(false).unwrap();
+        recovered.await.unwrap();
+        assert!(grpc_is_up(&Some(all_down_rx)));
+    }
+
+    #[test]
+    fn test_block_meta_update() {
+        let block = UiConfirmedBlock {
+            previous_blockhash: "prev-hash".to_string(),
+            blockhash: "hash".to_string(),
+            parent_slot: 41,
+            transactions: None,
+            signatures: Some(vec!["sig-1".to_string(), "sig-2".to_string()]),
+            rewards: None,
+            block_time: Some(1_700_000_000),
+            block_height: Some(1000),
+        };
+
+        let Message::GeyserSubscribeUpdate(update) = block_meta_update(42, &block) else {
+            panic!("expected update");
+        };
+        let Some(UpdateOneof::BlockMeta(block_meta)) = update.update_oneof else {
+            panic!("expected block meta");
+        };
+        assert_eq!(block_meta.slot, 42);
+        assert_eq!(block_meta.blockhash, "hash");
+        assert_eq!(block_meta.parent_blockhash, "prev-hash");
+        assert_eq!(block_meta.executed_transaction_count, 2);
+        assert_eq!(block_meta.block_height.unwrap().block_height, 1000);
+    }
+}
@@ -0,0 +1,113 @@
+use crate::Attempt;
+use std::time::Duration;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ReconnectDecision {
+    Retry {
+        wait: Duration,
+        // continue with the next configured endpoint
+        rotate_endpoint: bool,
+    },
+    // the stream ends / the task exits
+    GiveUp,
+}
+
+/// decides what to do after a connection attempt failed or an established connection was lost
+///
+/// ``attempt`` counts the connection attempts since the last connection that delivered an update,
+/// starting at 1
+pub trait ReconnectPolicy: Send + Sync + 'static {
+    fn on_disconnect(&self, attempt: Attempt) -> ReconnectDecision;
+}
+
+/// default behavior: wait 1.5^attempt seconds, capped at 15 seconds; retry forever on the same endpoint
+#[derive(Clone, Debug)]
+pub struct ExponentialBackoff {
+    pub factor: f32,
+    pub max_wait: Duration,
+    pub rotate_endpoint: bool,
+}
+
+impl Default for ExponentialBackoff {
+    fn default() -> Self {
+        ExponentialBackoff {
+            factor: 1.5,
+            max_wait: Duration::from_secs(15),
+            rotate_endpoint: false,
+        }
+    }
+}
+
+impl ReconnectPolicy for ExponentialBackoff {
+    fn on_disconnect(&self, attempt: Attempt) -> ReconnectDecision {
+        let wait_secs = self
+            .factor
+            .powi(attempt as i32)
+            .min(self.max_wait.as_secs_f32());
+        ReconnectDecision::Retry {
+            wait: Duration::from_secs_f32(wait_secs),
+            rotate_endpoint: self.rotate_endpoint,
+        }
+    }
+}
+
+/// constant wait; optionally gives up after a number of attempts without an update in between
+#[derive(Clone, Debug)]
+pub struct FixedDelay {
+    pub wait: Duration,
+    pub max_attempts: Option<Attempt>,
+    pub rotate_endpoint: bool,
+}
+
+impl ReconnectPolicy for FixedDelay {
+    fn on_disconnect(&self, attempt: Attempt) -> ReconnectDecision {
+        if self.max_attempts.map_or(false, |max| attempt >= max) {
+            return ReconnectDecision::GiveUp;
+        }
+        ReconnectDecision::Retry {
+            wait: self.wait,
+            rotate_endpoint: self.rotate_endpoint,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exponential_backoff() {
+        let policy = ExponentialBackoff::default();
+        assert_eq!(
+            policy.on_disconnect(2),
+            ReconnectDecision::Retry {
+                wait: Duration::from_secs_f32(2.25),
+                rotate_endpoint: false
+            }
+        );
+        assert_eq!(
+            policy.on_disconnect(100),
+            ReconnectDecision::Retry {
+                wait: Duration::from_secs(15),
+                rotate_endpoint: false
+            }
+        );
+    }
+
+    #[test]
+    fn test_fixed_delay_gives_up() {
+        let policy = FixedDelay {
+            wait: Duration::from_millis(100),
+            max_attempts: Some(3),
+            rotate_endpoint: true,
+        };
+        assert!(matches!(
+            policy.on_disconnect(2),
+            ReconnectDecision::Retry {
+                rotate_endpoint: true,
+                ..
+            }
+        ));
+        assert_eq!(policy.on_disconnect(3), ReconnectDecision::GiveUp);
+    }
+}
@@ -0,0 +1,237 @@
+use crate::grpc_subscription_autoreconnect_streams::create_geyser_reconnecting_stream;
+use crate::{Attempt, GrpcSourceConfig, Message};
+use anyhow::bail;
+use async_stream::stream;
+use futures::{future, Stream, StreamExt};
+use log::warn;
+use std::collections::HashMap;
+use std::pin::pin;
+use tokio::sync::mpsc;
+use yellowstone_grpc_proto::geyser::subscribe_update::UpdateOneof;
+use yellowstone_grpc_proto::geyser::{SubscribeRequest, SubscribeUpdateBlock, SubscribeUpdateSlot};
+
+const DEMULTIPLEXER_CAPACITY: usize = 1000;
+
+/// one subscription per source carrying several filters, e.g. blocks and slots for different multiplexers
+///
+/// returns one stream per named filter in the given order; ``Connecting`` is passed to all streams
+/// note: all streams share one channel per source - a consumer which does not keep up delays the others
+pub fn create_shared_geyser_streams(
+    grpc_source: GrpcSourceConfig,
+    named_filters: Vec<(String, SubscribeRequest)>,
+) -> anyhow::Result<Vec<impl Stream<Item = Message>>> {
+    let filter_ids: Vec<String> = named_filters.iter().map(|(id, _)| id.clone()).collect();
+    let combined_filter = merge_subscribe_requests(named_filters)?;
+    let source_stream = create_geyser_reconnecting_stream(grpc_source, combined_filter);
+    Ok(demultiplex_by_filter(source_stream, filter_ids))
+}
+
+/// prefix the filter names of each request with its id so updates can be routed back
+pub fn merge_subscribe_requests(
+    named_filters: Vec<(String, SubscribeRequest)>,
+) -> anyhow::Result<SubscribeRequest> {
+    let mut combined = SubscribeRequest::default();
+    let mut commitment = None;
+    for (idx, (filter_id, request)) in named_filters.into_iter().enumerate() {
+        if idx > 0 && request.commitment != commitment {
+            bail!("filter {} uses a different commitment level", filter_id);
+        }
+        commitment = request.commitment;
+        merge_named(&mut combined.slots, &filter_id, request.slots);
+        merge_named(&mut combined.accounts, &filter_id, request.accounts);
+        merge_named(&mut combined.transactions, &filter_id, request.transactions);
+        merge_named(&mut combined.entry, &filter_id, request.entry);
+        merge_named(&mut combined.blocks, &filter_id, request.blocks);
+        merge_named(&mut combined.blocks_meta, &filter_id, request.blocks_meta);
+        combined
+            .accounts_data_slice
+            .extend(request.accounts_data_slice);
+    }
+    combined.commitment = commitment;
+    Ok(combined)
+}
+
+fn merge_named<F>(combined: &mut HashMap<String, F>, filter_id: &str, filters: HashMap<String, F>) {
+    for (name, filter) in filters {
+        combined.insert(prefixed(filter_id, &name), filter);
+    }
+}
+
+fn prefixed(filter_id: &str, name: &str) -> String {
+    format!("{}:{}", filter_id, name)
+}
+
+/// route updates by the filter names the server reports as matched
+pub fn demultiplex_by_filter(
+    source_stream: impl Stream<Item = Message> + Send + 'static,
+    filter_ids: Vec<String>,
+) -> Vec<impl Stream<Item = Message>> {
+    let (senders, receivers): (Vec<_>, Vec<_>) = filter_ids
+        .iter()
+        .map(|_| mpsc::channel::<Message>(DEMULTIPLEXER_CAPACITY))
+        .unzip();
+
+    tokio::spawn(async move {
+        let mut source_stream = pin!(source_stream);
+        while let Some(message) = source_stream.next().await {
+            let targets: Vec<usize> = match &message {
+                Message::GeyserSubscribeUpdate(update) => filter_ids
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, filter_id)| matches_filter(&update.filters, filter_id))
+                    .map(|(idx, _)| idx)
+                    .collect(),
+                Message::Connecting(_) => (0..senders.len()).collect(),
+            };
+            for idx in targets {
+                if senders[idx].send(message.clone()).await.is_err() {
+                    warn!("consumer of filter {} dropped", filter_ids[idx]);
+                }
+            }
+            if senders.iter().all(|sender| sender.is_closed()) {
+                return;
+            }
+        }
+    });
+
+    receivers.into_iter().map(receiver_stream).collect()
+}
+
+/// item of ``split_blocks_and_slots``
+#[derive(Clone, Debug)]
+pub enum BlockOrSlot {
+    // see ``Message::Connecting``; ticks and blocks before it may be missing
+    Connecting(Attempt),
+    Block(SubscribeUpdateBlock),
+    Slot(SubscribeUpdateSlot),
+}
+
+/// split a stream subscribed with ``GeyserFilter::blocks_and_slots`` into blocks and slot ticks
+///
+/// one stream in source order, so a consumer sees each slot tick and block as the source
+/// delivered them; nothing is dropped and no task is spawned
+pub fn split_blocks_and_slots(
+    source_stream: impl Stream<Item = Message>,
+) -> impl Stream<Item = BlockOrSlot> {
+    source_stream.filter_map(|message| {
+        future::ready(match message {
+            Message::Connecting(attempt) => Some(BlockOrSlot::Connecting(attempt)),
+            Message::GeyserSubscribeUpdate(update) => match update.update_oneof {
+                Some(UpdateOneof::Block(block)) => Some(BlockOrSlot::Block(block)),
+                Some(UpdateOneof::Slot(slot)) => Some(BlockOrSlot::Slot(slot)),
+                _ => None,
+            },
+        })
+    })
+}
+
+pub(crate) fn matches_filter(matched_filters: &[String], filter_id: &str) -> bool {
+    matched_filters.iter().any(|name| {
+        name.strip_prefix(filter_id)
+            .map_or(false, |rest| rest.starts_with(':'))
+    })
+}
+
+pub(crate) fn receiver_stream<T>(mut receiver: mpsc::Receiver<T>) -> impl Stream<Item = T> {
+    stream! {
+        while let Some(item) = receiver.recv().await {
+            yield item;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commitment::CommitmentConfig;
+    use crate::GeyserFilter;
+    use futures::stream;
+    use yellowstone_grpc_proto::geyser::SubscribeUpdate;
+
+    #[test]
+    fn test_merge_subscribe_requests() {
+        let filter = GeyserFilter(CommitmentConfig::confirmed());
+        let combined = merge_subscribe_requests(vec![
+            ("blocks".to_string(), filter.blocks_meta()),
+            ("slots".to_string(), filter.slots()),
+        ])
+        .unwrap();
+        assert!(combined.blocks_meta.contains_key("blocks:client"));
+        assert!(combined.slots.contains_key("slots:client"));
+        assert_eq!(combined.commitment, filter.slots().commitment);
+
+        let finalized = GeyserFilter(CommitmentConfig::finalized());
+        assert!(merge_subscribe_requests(vec![
+            ("blocks".to_string(), filter.blocks_meta()),
+            ("slots".to_string(), finalized.slots()),
+        ])
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_demultiplex_by_filter() {
+        let update = |filter: &str, slot| {
+            Message::GeyserSubscribeUpdate(Box::new(SubscribeUpdate {
+                filters: vec![filter.to_string()],
+                update_oneof: Some(UpdateOneof::Slot(SubscribeUpdateSlot {
+                    slot,
+                    parent: None,
+                    status: 0,
+                })),
+            }))
+        };
+        let source = stream::iter(vec![
+            Message::Connecting(1),
+            update("slots:client", 1),
+            update("slotsfast:client", 2),
+            update("slots:client", 3),
+        ]);
+
+        let mut streams =
+            demultiplex_by_filter(source, vec!["slots".to_string(), "slotsfast".to_string()]);
+        let fast: Vec<Message> = streams.pop().unwrap().collect().await;
+        let slow: Vec<Message> = streams.pop().unwrap().collect().await;
+
+        assert_eq!(slow.len(), 3);
+        assert_eq!(fast.len(), 2);
+        assert!(matches!(fast[0], Message::Connecting(1)));
+    }
+
+    #[tokio::test]
+    async fn test_split_blocks_and_slots() {
+        let update = |update_oneof| {
+            Message::GeyserSubscribeUpdate(Box::new(SubscribeUpdate {
+                filters: vec!["client".to_string()],
+                update_oneof: Some(update_oneof),
+            }))
+        };
+        let slot = |slot| {
+            update(UpdateOneof::Slot(SubscribeUpdateSlot {
+                slot,
+                parent: None,
+                status: 0,
+            }))
+        };
+        let block = |slot| {
+            update(UpdateOneof::Block(SubscribeUpdateBlock {
+                slot,
+                ..Default::default()
+            }))
+        };
+        let source = stream::iter(vec![Message::Connecting(1), slot(10), block(10), slot(11)]);
+
+        let items: Vec<String> = split_blocks_and_slots(source)
+            .map(|item| match item {
+                BlockOrSlot::Connecting(attempt) => format!("connecting {attempt}"),
+                BlockOrSlot::Block(block) => format!("block {}", block.slot),
+                BlockOrSlot::Slot(slot) => format!("slot {}", slot.slot),
+            })
+            .collect()
+            .await;
+
+        assert_eq!(
+            items,
+            vec!["connecting 1", "slot 10", "block 10", "slot 11"]
+        );
+    }
+}
@@ -0,0 +1,165 @@
+use crate::grpc_stream_utils::SlotWindowDedup;
+use crate::grpc_subscription_autoreconnect_streams::create_geyser_reconnecting_stream;
+use crate::{GeyserFilter, GrpcSourceConfig, Message};
+use async_stream::stream;
+use futures::{Stream, StreamExt};
+use log::warn;
+use merge_streams::MergeStreams;
+use solana_sdk::clock::Slot;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::signature::Signature;
+use solana_sdk::transaction::TransactionError;
+use std::pin::pin;
+use yellowstone_grpc_proto::geyser::subscribe_update::UpdateOneof;
+use yellowstone_grpc_proto::geyser::SubscribeUpdateTransaction;
+
+// signatures are remembered for that many slots behind the latest block meta
+const DEDUP_SLOTS: u64 = 150;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SignatureStatus {
+    pub slot: Slot,
+    pub signature: Signature,
+    pub err: Option<TransactionError>,
+}
+
+impl SignatureStatus {
+    pub fn from_update(update: &SubscribeUpdateTransaction) -> anyhow::Result<Self> {
+        let info = update
+            .transaction
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("transaction update without transaction"))?;
+        let signature = Signature::try_from(info.signature.as_slice())
+            .map_err(|_| anyhow::anyhow!("invalid signature"))?;
+        let err = match info.meta.as_ref().and_then(|meta| meta.err.as_ref()) {
+            None => None,
+            Some(err) => Some(bincode::deserialize::<TransactionError>(&err.err)?),
+        };
+        Ok(SignatureStatus {
+            slot: update.slot,
+            signature,
+            err,
+        })
+    }
+}
+
+/// ``(slot, signature, err)`` of all non-vote transactions from all sources, each signature once
+///
+/// note: geyser has no status-only filter in this proto version, so the payload still goes over the wire
+pub fn create_signature_stream(
+    grpc_sources: Vec<GrpcSourceConfig>,
+    commitment_config: CommitmentConfig,
+) -> impl Stream<Item = SignatureStatus> {
+    let streams: Vec<_> = grpc_sources
+        .into_iter()
+        .map(|grpc_source| {
+            create_geyser_reconnecting_stream(
+                grpc_source,
+                GeyserFilter(commitment_config).signatures(),
+            )
+        })
+        .collect();
+    dedup_signatures(streams.merge())
+}
+
+/// block meta updates are used as clock to forget old signatures; transactions behind that
+/// horizon are dropped as they could be duplicates of forgotten signatures
+pub fn dedup_signatures(
+    geyser_stream: impl Stream<Item = Message>,
+) -> impl Stream<Item = SignatureStatus> {
+    stream! {
+        let mut geyser_stream = pin!(geyser_stream);
+        let mut seen = SlotWindowDedup::new(DEDUP_SLOTS);
+        while let Some(message) = geyser_stream.next().await {
+            let Message::GeyserSubscribeUpdate(update) = message else {
+                continue;
+            };
+            match update.update_oneof {
+                Some(UpdateOneof::Transaction(transaction)) => {
+                    match SignatureStatus::from_update(&transaction) {
+                        Ok(status) => {
+                            if seen.insert(status.signature, status.slot) {
+                                yield status;
+                            }
+                        }
+                        Err(err) => {
+                            warn!("skip transaction update in slot {}: {}", transaction.slot, err);
+                        }
+                    }
+                }
+                Some(UpdateOneof::BlockMeta(block_meta)) => {
+                    seen.advance(block_meta.slot);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::stream;
+    use solana_sdk::instruction::InstructionError;
+    use yellowstone_grpc_proto::geyser::{
+        SubscribeUpdate, SubscribeUpdateBlockMeta, SubscribeUpdateTransactionInfo,
+    };
+    use yellowstone_grpc_proto::prelude::{
+        TransactionError as ProtoTransactionError, TransactionStatusMeta,
+    };
+
+    fn message(update_oneof: UpdateOneof) -> Message {
+        Message::GeyserSubscribeUpdate(Box::new(SubscribeUpdate {
+            filters: vec!["client".to_string()],
+            update_oneof: Some(update_oneof),
+        }))
+    }
+
+    fn transaction(slot: Slot, signature: u8, err: Option<TransactionError>) -> Message {
+        message(UpdateOneof::Transaction(SubscribeUpdateTransaction {
+            slot,
+            transaction: Some(SubscribeUpdateTransactionInfo {
+                signature: vec![signature; 64],
+                meta: Some(TransactionStatusMeta {
+                    err: err.map(|err| ProtoTransactionError {
+                        err: bincode::serialize(&err).unwrap(),
+                    }),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+        }))
+    }
+
+    fn block_meta(slot: Slot) -> Message {
+        message(UpdateOneof::BlockMeta(SubscribeUpdateBlockMeta {
+            slot,
+            ..Default::default()
+        }))
+    }
+
+    #[tokio::test]
+    async fn test_dedup_signatures() {
+        let failed = TransactionError::InstructionError(0, InstructionError::Custom(1));
+        let source = stream::iter(vec![
+            transaction(10, 1, None),
+            transaction(10, 2, Some(failed.clone())),
+            // second source
+            transaction(10, 1, None),
+            block_meta(10),
+            // dropped from the dedup set after this
+            block_meta(10 + DEDUP_SLOTS + 1),
+            // late duplicate behind the horizon
+            transaction(10, 1, None),
+            transaction(10 + DEDUP_SLOTS + 1, 3, None),
+        ]);
+
+        let statuses: Vec<SignatureStatus> = dedup_signatures(source).collect().await;
+
+        assert_eq!(statuses.len(), 3);
+        assert_eq!(statuses[0].signature, Signature::from([1; 64]));
+        assert_eq!(statuses[0].err, None);
+        assert_eq!(statuses[1].err, Some(failed));
+        assert_eq!(statuses[2].slot, 10 + DEDUP_SLOTS + 1);
+    }
+}
@@ -0,0 +1,444 @@
+//! scripted mock sources for deterministic tests of reconnects, backoff and multiplexing
+//!
+//! sources run the real reconnect state machine against a script instead of a geyser endpoint;
+//! combine with ``tokio::time::pause`` (``#[tokio::test(start_paused = true)]``) so delays and
+//! backoff take no wall-clock time
+
+use crate::grpc_subscription_autoreconnect_streams::{
+    reconnecting_stream_with_connector, ConnectMode,
+};
+use crate::reconnect_policy::ReconnectPolicy;
+use crate::{GrpcConnectionTimeouts, GrpcSourceConfig, Message, Slot};
+use async_stream::stream;
+use futures::future::{self, BoxFuture};
+use futures::stream::BoxStream;
+use futures::{FutureExt, Stream, StreamExt};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::sleep;
+use yellowstone_grpc_client::{GeyserGrpcClientError, GeyserGrpcClientResult};
+use yellowstone_grpc_proto::geyser::subscribe_update::UpdateOneof;
+use yellowstone_grpc_proto::geyser::{
+    SubscribeRequest, SubscribeUpdate, SubscribeUpdateBlockMeta, SubscribeUpdateSlot,
+};
+use yellowstone_grpc_proto::tonic::Status;
+
+/// what an established subscription does next
+// scripts are small, boxing the updates would only clutter them
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, Debug)]
+pub enum SimStep {
+    Update(SubscribeUpdate),
+    Sleep(Duration),
+    // the subscription fails with the status
+    Fail(Status),
+    // no more updates, the subscription stays open
+    Hang,
+}
+
+/// outcome of one connection attempt
+#[derive(Clone, Debug)]
+pub enum SimConnection {
+    // subscribe succeeds after the delay; the subscription closes after the last step
+    Accept {
+        delay: Duration,
+        steps: Vec<SimStep>,
+    },
+    // connect or subscribe fails after the delay
+    Reject {
+        delay: Duration,
+        status: Status,
+    },
+}
+
+type SimSubscription = (
+    futures::sink::Drain<SubscribeRequest>,
+    BoxStream<'static, Result<SubscribeUpdate, Status>>,
+);
+
+/// a source answering connection attempts in script order; attempts beyond the script never
+/// complete, like an unreachable endpoint without connect timeout
+#[derive(Clone)]
+pub struct SimulatedSource {
+    grpc_source: GrpcSourceConfig,
+    connections: Arc<Mutex<VecDeque<SimConnection>>>,
+    attempts: Arc<AtomicU32>,
+}
+
+impl SimulatedSource {
+    pub fn new(name: &str) -> Self {
+        SimulatedSource {
+            grpc_source: GrpcSourceConfig::new_simple(format!("http://{}.sim", name)),
+            connections: Default::default(),
+            attempts: Default::default(),
+        }
+    }
+
+    pub fn accept(self, steps: Vec<SimStep>) -> Self {
+        self.connection(SimConnection::Accept {
+            delay: Duration::ZERO,
+            steps,
+        })
+    }
+
+    pub fn reject(self, status: Status) -> Self {
+        self.connection(SimConnection::Reject {
+            delay: Duration::ZERO,
+            status,
+        })
+    }
+
+    pub fn connection(self, connection: SimConnection) -> Self {
+        self.connections.lock().unwrap().push_back(connection);
+        self
+    }
+
+    /// e.g. a receive timeout to detect a hanging subscription
+    pub fn with_timeouts(mut self, timeouts: GrpcConnectionTimeouts) -> Self {
+        self.grpc_source = GrpcSourceConfig::new(
+            self.grpc_source.grpc_addr().to_string(),
+            None,
+            None,
+            timeouts,
+        );
+        self
+    }
+
+    /// connection attempts made so far
+    pub fn connect_attempts(&self) -> u32 {
+        self.attempts.load(Ordering::Relaxed)
+    }
+
+    /// like ``create_geyser_reconnecting_stream_with_policy`` with this source
+    pub fn stream(&self, reconnect_policy: impl ReconnectPolicy) -> impl Stream<Item = Message> {
+        self.stream_with_mode(reconnect_policy, ConnectMode::Spawned)
+    }
+
+    /// like ``create_geyser_reconnecting_stream_inline`` with this source
+    pub fn stream_inline(
+        &self,
+        reconnect_policy: impl ReconnectPolicy,
+    ) -> impl Stream<Item = Message> {
+        self.stream_with_mode(reconnect_policy, ConnectMode::Inline)
+    }
+
+    fn stream_with_mode(
+        &self,
+        reconnect_policy: impl ReconnectPolicy,
+        connect_mode: ConnectMode,
+    ) -> impl Stream<Item = Message> {
+        let connections = self.connections.clone();
+        let attempts = self.attempts.clone();
+        reconnecting_stream_with_connector(
+            vec![self.grpc_source.clone()],
+            SubscribeRequest::default(),
+            reconnect_policy,
+            None,
+            connect_mode,
+            move |_grpc_source, _subscribe_filter| {
+                attempts.fetch_add(1, Ordering::Relaxed);
+                let connection = connections.lock().unwrap().pop_front();
+                simulate_connection(connection)
+            },
+        )
+    }
+}
+
+fn simulate_connection(
+    connection: Option<SimConnection>,
+) -> BoxFuture<'static, GeyserGrpcClientResult<SimSubscription>> {
+    async move {
+        match connection {
+            None => future::pending().await,
+            Some(SimConnection::Reject { delay, status }) => {
+                sleep(delay).await;
+                Err(GeyserGrpcClientError::TonicStatus(status))
+            }
+            Some(SimConnection::Accept { delay, steps }) => {
+                sleep(delay).await;
+                let updates = stream! {
+                    for step in steps {
+                        match step {
+                            SimStep::Update(update) => yield Ok(update),
+                            SimStep::Sleep(duration) => sleep(duration).await,
+                            SimStep::Fail(status) => {
+                                yield Err(status);
+                                return;
+                            }
+                            SimStep::Hang => future::pending::<()>().await,
+                        }
+                    }
+                };
+                Ok((futures::sink::drain(), updates.boxed()))
+            }
+        }
+    }
+    .boxed()
+}
+
+pub fn slot_update(slot: Slot) -> SubscribeUpdate {
+    SubscribeUpdate {
+        filters: vec![],
+        update_oneof: Some(UpdateOneof::Slot(SubscribeUpdateSlot {
+            slot,
+            parent: slot.checked_sub(1),
+            status: 0,
+        })),
+    }
+}
+
+/// ``slot_update`` as stream item
+#[cfg(test)]
+pub(crate) fn slot_message(slot: Slot) -> Message {
+    Message::GeyserSubscribeUpdate(Box::new(slot_update(slot)))
+}
+
+/// block meta linked to the parent by blockhash ``<slot>``
+pub fn block_meta_update(slot: Slot, parent_slot: Slot) -> SubscribeUpdate {
+    SubscribeUpdate {
+        filters: vec![],
+        update_oneof: Some(UpdateOneof::BlockMeta(SubscribeUpdateBlockMeta {
+            slot,
+            blockhash: slot.to_string(),
+            parent_slot,
+            parent_blockhash: parent_slot.to_string(),
+            ..Default::default()
+        })),
+    }
+}
+
+#[cfg(test)]
+pub(crate) fn block_meta_message(slot: Slot, parent_slot: Slot) -> Message {
+    Message::GeyserSubscribeUpdate(Box::new(block_meta_update(slot, parent_slot)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grpcmultiplex_fastestwins::create_multiplexed_stream;
+    use crate::reconnect_policy::{ExponentialBackoff, FixedDelay};
+    use crate::tip_slot::SlotExtractor;
+    use tokio::time::Instant;
+
+    fn slots(messages: &[Message]) -> Vec<Slot> {
+        messages
+            .iter()
+            .filter_map(|message| match message {
+                Message::GeyserSubscribeUpdate(update) => crate::slot_of_update(update),
+                Message::Connecting(_) => None,
+            })
+            .collect()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_reconnect_with_backoff() {
+        let source = SimulatedSource::new("green")
+            .accept(vec![
+                SimStep::Update(slot_update(1)),
+                SimStep::Fail(Status::unavailable("connection reset")),
+            ])
+            .reject(Status::resource_exhausted("too many connections"))
+            .accept(vec![SimStep::Update(slot_update(2)), SimStep::Hang]);
+
+        let started_at = Instant::now();
+        let messages: Vec<Message> = source
+            .stream(ExponentialBackoff::default())
+            .take_while(|message| {
+                let done = matches!(message, Message::GeyserSubscribeUpdate(update)
+                    if crate::slot_of_update(update) == Some(2));
+                future::ready(!done)
+            })
+            .collect()
+            .await;
+
+        assert_eq!(slots(&messages), vec![1]);
+        assert_eq!(source.connect_attempts(), 3);
+        // 1.5^1 + 1.5^2 seconds backoff in virtual time
+        let elapsed = started_at.elapsed();
+        assert!(elapsed >= Duration::from_millis(3700) && elapsed < Duration::from_millis(3800));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_repeated_oversized_updates_back_off() {
+        let oversized = || {
+            SimStep::Fail(Status::out_of_range(
+                "Error, message length too large: found 5000000 bytes, the limit is: 4194304 bytes",
+            ))
+        };
+        let source = SimulatedSource::new("green")
+            .accept(vec![oversized()])
+            .accept(vec![oversized()])
+            .accept(vec![oversized()])
+            .accept(vec![SimStep::Update(slot_update(1)), SimStep::Hang]);
+        let policy = FixedDelay {
+            wait: Duration::from_secs(5),
+            max_attempts: None,
+            rotate_endpoint: false,
+        };
+
+        let started_at = Instant::now();
+        let mut updates = std::pin::pin!(source
+            .stream(policy)
+            .filter(|message| future::ready(matches!(message, Message::GeyserSubscribeUpdate(_)))));
+        let first_update = updates.next().await;
+
+        assert!(first_update.is_some());
+        assert_eq!(source.connect_attempts(), 4);
+        // two short resubscribe delays, then the policy backoff
+        let elapsed = started_at.elapsed();
+        assert!(elapsed >= Duration::from_millis(5400) && elapsed < Duration::from_millis(5500));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_inline_reconnect_after_receive_timeout() {
+        let source = SimulatedSource::new("green")
+            .with_timeouts(GrpcConnectionTimeouts {
+                connect_timeout: Duration::from_secs(1),
+                request_timeout: Duration::from_secs(1),
+                subscribe_timeout: Duration::from_secs(1),
+                receive_timeout: Duration::from_secs(1),
+            })
+            .accept(vec![SimStep::Update(slot_update(1)), SimStep::Hang])
+            .reject(Status::unavailable("connection refused"))
+            .accept(vec![SimStep::Update(slot_update(2)), SimStep::Hang]);
+        let policy = FixedDelay {
+            wait: Duration::from_millis(100),
+            max_attempts: None,
+            rotate_endpoint: false,
+        };
+
+        let started_at = Instant::now();
+        let messages: Vec<Message> = source
+            .stream_inline(policy)
+            .take_while(|message| {
+                let done = matches!(message, Message::GeyserSubscribeUpdate(update)
+                    if crate::slot_of_update(update) == Some(2));
+                future::ready(!done)
+            })
+            .collect()
+            .await;
+
+        assert_eq!(slots(&messages), vec![1]);
+        assert_eq!(source.connect_attempts(), 3);
+        // receive timeout, then two reconnect delays
+        let elapsed = started_at.elapsed();
+        assert!(elapsed >= Duration::from_millis(1200) && elapsed < Duration::from_millis(1300));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_policy_gives_up() {
+        let source = SimulatedSource::new("green")
+            .reject(Status::unauthenticated("invalid token"))
+            .reject(Status::unauthenticated("invalid token"));
+        let policy = FixedDelay {
+            wait: Duration::from_secs(1),
+            max_attempts: Some(2),
+            rotate_endpoint: false,
+        };
+
+        let messages: Vec<Message> = source.stream(policy).collect().await;
+
+        assert!(slots(&messages).is_empty());
+        assert_eq!(source.connect_attempts(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_fastest_wins_across_reconnect() {
+        let step = Duration::from_millis(400);
+        // green is faster but drops out after slot 2 and is back at slot 5
+        let green = SimulatedSource::new("green")
+            .accept(vec![
+                SimStep::Update(slot_update(1)),
+                SimStep::Sleep(step),
+                SimStep::Update(slot_update(2)),
+                SimStep::Fail(Status::unavailable("connection reset")),
+            ])
+            .connection(SimConnection::Accept {
+                delay: Duration::from_millis(1000),
+                steps: vec![SimStep::Update(slot_update(5)), SimStep::Hang],
+            });
+        let blue = SimulatedSource::new("blue").connection(SimConnection::Accept {
+            delay: Duration::from_millis(100),
+            steps: (1..=6)
+                .flat_map(|slot| [SimStep::Update(slot_update(slot)), SimStep::Sleep(step)])
+                .chain([SimStep::Hang])
+                .collect(),
+        });
+        let policy = || FixedDelay {
+            wait: Duration::from_millis(100),
+            max_attempts: None,
+            rotate_endpoint: false,
+        };
+
+        let multiplexed = create_multiplexed_stream(
+            vec![green.stream(policy()), blue.stream(policy())],
+            SlotExtractor,
+        );
+        let yielded: Vec<Slot> = multiplexed.take(6).collect().await;
+
+        assert_eq!(yielded, vec![1, 2, 3, 4, 5, 6]);
+        assert_eq!(green.connect_attempts(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_backoff_resets_after_healthy_connection() {
+        let source = SimulatedSource::new("green")
+            .reject(Status::unavailable("connection refused"))
+            .reject(Status::unavailable("connection refused"))
+            .accept(vec![
+                SimStep::Update(slot_update(1)),
+                SimStep::Fail(Status::unavailable("connection reset")),
+            ])
+            .reject(Status::unavailable("connection refused"))
+            .accept(vec![SimStep::Update(slot_update(2)), SimStep::Hang]);
+
+        let started_at = Instant::now();
+        let messages: Vec<Message> = source
+            .stream(ExponentialBackoff::default())
+            .take_while(|message| {
+                let done = matches!(message, Message::GeyserSubscribeUpdate(update)
+                    if crate::slot_of_update(update) == Some(2));
+                future::ready(!done)
+            })
+            .collect()
+            .await;
+
+        assert_eq!(slots(&messages), vec![1]);
+        assert_eq!(source.connect_attempts(), 5);
+        // 1.5^1 + 1.5^2 seconds twice, the backoff starts over after slot 1
+        let elapsed = started_at.elapsed();
+        assert!(elapsed >= Duration::from_millis(7500) && elapsed < Duration::from_millis(7600));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_max_attempts_count_consecutive_failures() {
+        let source = SimulatedSource::new("green")
+            .accept(vec![
+                SimStep::Update(slot_update(1)),
+                SimStep::Fail(Status::unavailable("connection reset")),
+            ])
+            .accept(vec![
+                SimStep::Update(slot_update(2)),
+                SimStep::Fail(Status::unavailable("connection reset")),
+            ])
+            .reject(Status::unavailable("connection refused"));
+        let policy = FixedDelay {
+            wait: Duration::from_secs(1),
+            max_attempts: Some(2),
+            rotate_endpoint: false,
+        };
+
+        let messages: Vec<Message> = source.stream(policy).collect().await;
+
+        assert_eq!(slots(&messages), vec![1, 2]);
+        assert_eq!(source.connect_attempts(), 3);
+        // the connecting counter does not start over
+        let last_attempt = messages.iter().rev().find_map(|message| match message {
+            Message::Connecting(attempt) => Some(*attempt),
+            Message::GeyserSubscribeUpdate(_) => None,
+        });
+        assert_eq!(last_attempt, Some(3));
+    }
+}
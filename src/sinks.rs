@@ -0,0 +1,25 @@
+//! forward the multiplexed stream to external systems; each backend is behind its own feature
+
+use crate::Slot;
+
+#[cfg(feature = "kafka")]
+pub mod kafka;
+#[cfg(feature = "nats")]
+pub mod nats;
+#[cfg(feature = "parquet")]
+pub mod parquet;
+#[cfg(feature = "postgres")]
+pub mod postgres;
+#[cfg(feature = "redis")]
+pub mod redis;
+
+/// item of the multiplexed stream that can be written to a sink
+pub trait SinkRecord {
+    fn slot(&self) -> Slot;
+    /// wire format is up to the application, e.g. bincode or json
+    fn encode(&self) -> anyhow::Result<Vec<u8>>;
+    /// unique per record; override if there are several records per slot (e.g. transactions)
+    fn record_id(&self) -> String {
+        self.slot().to_string()
+    }
+}
@@ -0,0 +1,204 @@
+use crate::sinks::SinkRecord;
+use crate::Slot;
+use futures::{Stream, StreamExt};
+use log::{debug, warn};
+use rdkafka::config::ClientConfig;
+use rdkafka::producer::{FutureProducer, FutureRecord};
+use rdkafka::util::Timeout;
+use std::pin::pin;
+use std::time::Duration;
+use tokio::time::sleep;
+
+#[derive(Clone, Debug)]
+pub struct KafkaSinkConfig {
+    // bootstrap.servers
+    pub brokers: String,
+    pub topic: String,
+    // partition by slot modulo partitions; if None the partitioner of the producer decides by key
+    pub partitions: Option<i32>,
+    pub max_retries: u32,
+    pub retry_backoff: Duration,
+    // per attempt; includes time in the producer queue
+    pub delivery_timeout: Duration,
+}
+
+/// broker acknowledged the record
+#[derive(Clone, Debug)]
+pub struct DeliveryReport {
+    pub slot: Slot,
+    pub partition: i32,
+    pub offset: i64,
+    // 0 on first attempt
+    pub retries: u32,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct KafkaSinkStats {
+    pub delivered: u64,
+    // delivery failed after all retries
+    pub failed: u64,
+    // broker delivery retries only
+    pub retries: u64,
+    // record could not be encoded, nothing was sent
+    pub encode_failed: u64,
+}
+
+pub struct KafkaSink {
+    producer: FutureProducer,
+    config: KafkaSinkConfig,
+}
+
+impl KafkaSink {
+    pub fn new(config: KafkaSinkConfig) -> anyhow::Result<Self> {
+        let producer: FutureProducer = ClientConfig::new()
+            .set("bootstrap.servers", &config.brokers)
+            .set(
+                "message.timeout.ms",
+                config.delivery_timeout.as_millis().to_string(),
+            )
+            .create()?;
+        Ok(Self { producer, config })
+    }
+
+    /// publish one record; retries with fixed backoff until ``max_retries`` is exhausted
+    pub async fn publish(&self, record: &impl SinkRecord) -> anyhow::Result<DeliveryReport> {
+        let payload = record.encode()?;
+        self.publish_payload(record.slot(), &payload).await
+    }
+
+    async fn publish_payload(&self, slot: Slot, payload: &[u8]) -> anyhow::Result<DeliveryReport> {
+        let key = slot.to_string();
+        let partition = self
+            .config
+            .partitions
+            .map(|partitions| partition_for_slot(slot, partitions));
+
+        let mut retries = 0;
+        loop {
+            let mut kafka_record = FutureRecord::to(&self.config.topic)
+                .key(&key)
+                .payload(payload);
+            if let Some(partition) = partition {
+                kafka_record = kafka_record.partition(partition);
+            }
+
+            match self
+                .producer
+                .send(kafka_record, Timeout::After(self.config.delivery_timeout))
+                .await
+            {
+                Ok((partition, offset)) => {
+                    debug!(
+                        "delivered slot {} to partition {} offset {}",
+                        slot, partition, offset
+                    );
+                    return Ok(DeliveryReport {
+                        slot,
+                        partition,
+                        offset,
+                        retries,
+                    });
+                }
+                Err((kafka_error, _message)) if retries < self.config.max_retries => {
+                    retries += 1;
+                    warn!(
+                        "delivery of slot {} failed - retry {}/{}: {}",
+                        slot, retries, self.config.max_retries, kafka_error
+                    );
+                    sleep(self.config.retry_backoff).await;
+                }
+                Err((kafka_error, _message)) => {
+                    return Err(anyhow::anyhow!(
+                        "delivery of slot {} failed after {} retries: {}",
+                        slot,
+                        retries,
+                        kafka_error
+                    ));
+                }
+            }
+        }
+    }
+
+    /// consume the multiplexed stream until it ends; records that could not be delivered are logged and skipped
+    pub async fn consume_stream<R: SinkRecord>(
+        &self,
+        multiplexed_stream: impl Stream<Item = R>,
+    ) -> KafkaSinkStats {
+        let mut multiplexed_stream = pin!(multiplexed_stream);
+        let mut stats = KafkaSinkStats::default();
+        while let Some(record) = multiplexed_stream.next().await {
+            let payload = match record.encode() {
+                Ok(payload) => payload,
+                Err(err) => {
+                    stats.encode_failed += 1;
+                    warn!("dropping slot {} - cannot encode: {:?}", record.slot(), err);
+                    continue;
+                }
+            };
+            match self.publish_payload(record.slot(), &payload).await {
+                Ok(report) => {
+                    stats.delivered += 1;
+                    stats.retries += report.retries as u64;
+                }
+                Err(err) => {
+                    stats.failed += 1;
+                    stats.retries += self.config.max_retries as u64;
+                    warn!("dropping slot {}: {:?}", record.slot(), err);
+                }
+            }
+        }
+        stats
+    }
+}
+
+// consecutive slots go to consecutive partitions
+fn partition_for_slot(slot: Slot, partitions: i32) -> i32 {
+    (slot % partitions.max(1) as u64) as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partition_for_slot() {
+        assert_eq!(partition_for_slot(0, 4), 0);
+        assert_eq!(partition_for_slot(5, 4), 1);
+        assert_eq!(partition_for_slot(7, 1), 0);
+        // misconfigured partition count does not panic
+        assert_eq!(partition_for_slot(7, 0), 0);
+    }
+
+    struct Unencodable(Slot);
+
+    impl SinkRecord for Unencodable {
+        fn slot(&self) -> Slot {
+            self.0
+        }
+        fn encode(&self) -> anyhow::Result<Vec<u8>> {
+            anyhow::bail!("not encodable")
+        }
+    }
+
+    #[tokio::test]
+    async fn test_encode_errors_are_not_retries() {
+        // no broker needed, nothing is sent
+        let sink = KafkaSink::new(KafkaSinkConfig {
+            brokers: "127.0.0.1:1".to_string(),
+            topic: "blocks".to_string(),
+            partitions: None,
+            max_retries: 3,
+            retry_backoff: Duration::from_millis(10),
+            delivery_timeout: Duration::from_millis(100),
+        })
+        .unwrap();
+
+        let stats = sink
+            .consume_stream(futures::stream::iter(vec![Unencodable(1), Unencodable(2)]))
+            .await;
+
+        assert_eq!(stats.encode_failed, 2);
+        assert_eq!(stats.failed, 0);
+        assert_eq!(stats.retries, 0);
+    }
+}
@@ -0,0 +1,306 @@
+//! minimal jetstream publisher on the plain nats protocol
+//!
+//! async-nats is not used: its nkeys dependency needs zeroize >= 1.4 which conflicts with the
+//! curve25519-dalek pin of solana 1.17; only ``nats://`` without tls and auth is supported
+
+use crate::sinks::SinkRecord;
+use anyhow::{anyhow, bail, Context};
+use futures::{Stream, StreamExt};
+use log::debug;
+use serde::Deserialize;
+use std::pin::pin;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+use url::Url;
+
+// jetstream drops messages with a known id inside the duplicate window of the stream
+const MESSAGE_ID_HEADER: &str = "Nats-Msg-Id";
+const DEFAULT_PORT: u16 = 4222;
+
+#[derive(Clone, Debug)]
+pub struct NatsSinkConfig {
+    // e.g. nats://127.0.0.1:4222
+    pub url: String,
+    // must be bound to a jetstream stream, e.g. "solana.blocks"
+    pub subject: String,
+}
+
+/// publish to jetstream with message ids derived from ``SinkRecord::record_id``
+pub struct NatsSink {
+    connection: Mutex<Connection>,
+    config: NatsSinkConfig,
+}
+
+struct Connection {
+    reader: BufReader<OwnedReadHalf>,
+    writer: OwnedWriteHalf,
+    // acks are requested on <inbox>.<n>
+    inbox: String,
+    next_reply: u64,
+}
+
+#[derive(Deserialize)]
+struct PubAck {
+    #[serde(default)]
+    duplicate: bool,
+    error: Option<ApiError>,
+}
+
+#[derive(Deserialize)]
+struct ApiError {
+    description: String,
+}
+
+impl NatsSink {
+    pub async fn connect(config: NatsSinkConfig) -> anyhow::Result<Self> {
+        let url = Url::parse(&config.url).context("invalid nats url")?;
+        if url.scheme() != "nats" {
+            bail!(
+                "unsupported nats url scheme {} - only nats:// is supported",
+                url.scheme()
+            );
+        }
+        let host = url
+            .host_str()
+            .ok_or_else(|| anyhow!("nats url without host"))?;
+        let tcp_stream = TcpStream::connect((host, url.port().unwrap_or(DEFAULT_PORT))).await?;
+        let (reader, writer) = tcp_stream.into_split();
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos();
+        let mut connection = Connection {
+            reader: BufReader::new(reader),
+            writer,
+            inbox: format!("_INBOX.geyser.{}.{}", std::process::id(), nanos),
+            next_reply: 0,
+        };
+        connection.handshake().await?;
+        Ok(Self {
+            connection: Mutex::new(connection),
+            config,
+        })
+    }
+
+    /// waits for the jetstream ack; returns false if jetstream considered it a duplicate
+    pub async fn publish(&self, record: &impl SinkRecord) -> anyhow::Result<bool> {
+        let message_id = message_id(&self.config.subject, record);
+        let payload = record.encode()?;
+
+        let mut connection = self.connection.lock().await;
+        let reply = connection
+            .publish(&self.config.subject, &message_id, &payload)
+            .await?;
+        let ack = connection.read_ack(&reply).await?;
+        if let Some(error) = ack.error {
+            bail!("jetstream rejected {}: {}", message_id, error.description);
+        }
+        if ack.duplicate {
+            debug!("jetstream dropped duplicate {}", message_id);
+        }
+        Ok(!ack.duplicate)
+    }
+
+    /// consume the multiplexed stream until it ends or nats fails
+    pub async fn consume_stream<R: SinkRecord>(
+        &self,
+        multiplexed_stream: impl Stream<Item = R>,
+    ) -> anyhow::Result<()> {
+        let mut multiplexed_stream = pin!(multiplexed_stream);
+        while let Some(record) = multiplexed_stream.next().await {
+            self.publish(&record).await?;
+        }
+        Ok(())
+    }
+}
+
+impl Connection {
+    async fn handshake(&mut self) -> anyhow::Result<()> {
+        let info = self.read_line().await?;
+        if !info.starts_with("INFO ") {
+            bail!("unexpected nats greeting: {}", info);
+        }
+        let connect = format!(
+            "CONNECT {{\"verbose\":false,\"pedantic\":false,\"headers\":true,\"no_responders\":true,\"lang\":\"rust\",\"version\":\"{}\"}}\r\nSUB {}.* 1\r\nPING\r\n",
+            env!("CARGO_PKG_VERSION"),
+            self.inbox
+        );
+        self.writer.write_all(connect.as_bytes()).await?;
+        loop {
+            let line = self.read_line().await?;
+            match line.as_str() {
+                "PONG" => return Ok(()),
+                "+OK" => {}
+                _ => bail!("nats handshake failed: {}", line),
+            }
+        }
+    }
+
+    // returns the reply subject of the ack
+    async fn publish(
+        &mut self,
+        subject: &str,
+        message_id: &str,
+        payload: &[u8],
+    ) -> anyhow::Result<String> {
+        self.next_reply += 1;
+        let reply = format!("{}.{}", self.inbox, self.next_reply);
+        let headers = format!("NATS/1.0\r\n{}: {}\r\n\r\n", MESSAGE_ID_HEADER, message_id);
+        let command = format!(
+            "HPUB {} {} {} {}\r\n",
+            subject,
+            reply,
+            headers.len(),
+            headers.len() + payload.len()
+        );
+        self.writer.write_all(command.as_bytes()).await?;
+        self.writer.write_all(headers.as_bytes()).await?;
+        self.writer.write_all(payload).await?;
+        self.writer.write_all(b"\r\n").await?;
+        self.writer.flush().await?;
+        Ok(reply)
+    }
+
+    async fn read_ack(&mut self, reply: &str) -> anyhow::Result<PubAck> {
+        loop {
+            let line = self.read_line().await?;
+            let mut parts = line.split_whitespace();
+            match parts.next() {
+                Some("PING") => self.writer.write_all(b"PONG\r\n").await?,
+                Some("+OK") | Some("PONG") | Some("INFO") => {}
+                Some("-ERR") => bail!("nats error: {}", line),
+                // MSG <subject> <sid> <size>
+                Some("MSG") => {
+                    let subject = parts.next().unwrap_or_default().to_string();
+                    let size = parse_size(parts.last())?;
+                    let body = self.read_body(size).await?;
+                    if subject == reply {
+                        return Ok(serde_json::from_slice(&body)?);
+                    }
+                }
+                // HMSG <subject> <sid> <header size> <total size>; a 503 status means no stream
+                // listens on the subject
+                Some("HMSG") => {
+                    let subject = parts.next().unwrap_or_default().to_string();
+                    let fields: Vec<&str> = parts.collect();
+                    let header_size = parse_size(fields.iter().rev().nth(1).copied())?;
+                    let total_size = parse_size(fields.last().copied())?;
+                    let body = self.read_body(total_size).await?;
+                    if subject != reply {
+                        continue;
+                    }
+                    let headers = String::from_utf8_lossy(&body[..header_size.min(body.len())]);
+                    if headers.starts_with("NATS/1.0 503") {
+                        bail!("no jetstream stream bound to the subject");
+                    }
+                    return Ok(serde_json::from_slice(
+                        &body[header_size.min(body.len())..],
+                    )?);
+                }
+                _ => bail!("unexpected nats message: {}", line),
+            }
+        }
+    }
+
+    async fn read_line(&mut self) -> anyhow::Result<String> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line).await? == 0 {
+            bail!("nats connection closed");
+        }
+        Ok(line.trim_end().to_string())
+    }
+
+    // payload followed by \r\n
+    async fn read_body(&mut self, size: usize) -> anyhow::Result<Vec<u8>> {
+        let mut body = vec![0; size + 2];
+        self.reader.read_exact(&mut body).await?;
+        body.truncate(size);
+        Ok(body)
+    }
+}
+
+fn parse_size(field: Option<&str>) -> anyhow::Result<usize> {
+    field
+        .and_then(|field| field.parse().ok())
+        .ok_or_else(|| anyhow!("malformed nats message size"))
+}
+
+// subject is part of the id so blocks and transactions of the same slot do not collide
+fn message_id(subject: &str, record: &impl SinkRecord) -> String {
+    format!("{}:{}", subject, record.record_id())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Slot;
+    use tokio::net::TcpListener;
+
+    struct TestBlock(Slot);
+
+    impl SinkRecord for TestBlock {
+        fn slot(&self) -> Slot {
+            self.0
+        }
+        fn encode(&self) -> anyhow::Result<Vec<u8>> {
+            Ok(self.0.to_le_bytes().to_vec())
+        }
+    }
+
+    #[test]
+    fn test_message_id() {
+        assert_eq!(
+            message_id("solana.blocks", &TestBlock(42)),
+            "solana.blocks:42"
+        );
+    }
+
+    // acks the first publish and reports the second one as duplicate
+    async fn fake_jetstream(listener: TcpListener) {
+        let (tcp_stream, _) = listener.accept().await.unwrap();
+        let (reader, mut writer) = tcp_stream.into_split();
+        let mut reader = BufReader::new(reader);
+        writer
+            .write_all(b"INFO {\"headers\":true}\r\n")
+            .await
+            .unwrap();
+        let mut duplicate = false;
+        let mut line = String::new();
+        while reader.read_line(&mut line).await.unwrap() > 0 {
+            let fields: Vec<String> = line.split_whitespace().map(str::to_string).collect();
+            line.clear();
+            match fields[0].as_str() {
+                "PING" => writer.write_all(b"PONG\r\n").await.unwrap(),
+                "HPUB" => {
+                    let total_size: usize = fields[4].parse().unwrap();
+                    let mut body = vec![0; total_size + 2];
+                    reader.read_exact(&mut body).await.unwrap();
+                    assert!(String::from_utf8_lossy(&body).contains("Nats-Msg-Id: solana.blocks:"));
+                    let ack =
+                        format!("{{\"stream\":\"blocks\",\"seq\":1,\"duplicate\":{duplicate}}}");
+                    let msg = format!("MSG {} 1 {}\r\n{}\r\n", fields[2], ack.len(), ack);
+                    writer.write_all(msg.as_bytes()).await.unwrap();
+                    duplicate = true;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_publish_with_ack() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(fake_jetstream(listener));
+
+        let sink = NatsSink::connect(NatsSinkConfig {
+            url: format!("nats://127.0.0.1:{port}"),
+            subject: "solana.blocks".to_string(),
+        })
+        .await
+        .unwrap();
+
+        assert!(sink.publish(&TestBlock(1)).await.unwrap());
+        assert!(!sink.publish(&TestBlock(1)).await.unwrap());
+    }
+}
@@ -0,0 +1,437 @@
+use arrow::array::{ArrayRef, BooleanArray, Int64Array, StringArray, UInt64Array};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use arrow::record_batch::RecordBatch;
+use futures::{Stream, StreamExt};
+use log::{debug, info};
+use parquet::arrow::ArrowWriter;
+use solana_sdk::clock::{Slot, DEFAULT_SLOTS_PER_EPOCH};
+use solana_sdk::signature::Signature;
+use std::fs::{self, File};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::pin::pin;
+use std::sync::Arc;
+use std::time::SystemTime;
+use tokio::sync::mpsc;
+use yellowstone_grpc_proto::geyser::SubscribeUpdateBlock;
+
+// blocks queued for the blocking writer thread of ``archive_blocks``
+const ARCHIVE_QUEUE_CAPACITY: usize = 16;
+
+#[derive(Clone, Copy, Debug)]
+pub enum PartitionBy {
+    // utc day of the block time, e.g. day=2024-01-31
+    Day,
+    // e.g. epoch=512
+    Epoch { slots_per_epoch: u64 },
+}
+
+impl PartitionBy {
+    pub fn epoch() -> Self {
+        PartitionBy::Epoch {
+            slots_per_epoch: DEFAULT_SLOTS_PER_EPOCH,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ParquetArchiveConfig {
+    pub base_dir: PathBuf,
+    pub partition_by: PartitionBy,
+    // rows kept in memory before they are written as one row group
+    pub rows_per_batch: usize,
+    // start a new file once this many rows were written
+    pub max_rows_per_file: usize,
+}
+
+/// row type of an archive table
+pub trait ArchiveRow: Sized {
+    // subdirectory below base_dir
+    const TABLE: &'static str;
+    fn schema() -> SchemaRef;
+    fn to_record_batch(rows: &[Self]) -> anyhow::Result<RecordBatch>;
+    fn slot(&self) -> Slot;
+    fn block_time(&self) -> Option<i64>;
+}
+
+#[derive(Clone, Debug)]
+pub struct BlockSummaryRow {
+    pub slot: Slot,
+    pub blockhash: String,
+    pub parent_slot: Slot,
+    pub block_time: Option<i64>,
+    pub transaction_count: u64,
+    pub failed_transaction_count: u64,
+    pub vote_transaction_count: u64,
+    pub total_fee: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct TransactionSummaryRow {
+    pub slot: Slot,
+    pub signature: String,
+    pub block_time: Option<i64>,
+    pub is_vote: bool,
+    pub failed: bool,
+    pub fee: u64,
+    pub compute_units_consumed: Option<u64>,
+}
+
+impl TransactionSummaryRow {
+    pub fn from_block(block: &SubscribeUpdateBlock) -> Vec<Self> {
+        let block_time = block.block_time.as_ref().map(|t| t.timestamp);
+        block
+            .transactions
+            .iter()
+            .map(|tx| TransactionSummaryRow {
+                slot: block.slot,
+                signature: Signature::try_from(tx.signature.as_slice())
+                    .map(|signature| signature.to_string())
+                    .unwrap_or_default(),
+                block_time,
+                is_vote: tx.is_vote,
+                failed: tx.meta.as_ref().map_or(false, |meta| meta.err.is_some()),
+                fee: tx.meta.as_ref().map_or(0, |meta| meta.fee),
+                compute_units_consumed: tx
+                    .meta
+                    .as_ref()
+                    .and_then(|meta| meta.compute_units_consumed),
+            })
+            .collect()
+    }
+}
+
+impl BlockSummaryRow {
+    pub fn from_block(block: &SubscribeUpdateBlock) -> Self {
+        let transactions = TransactionSummaryRow::from_block(block);
+        BlockSummaryRow {
+            slot: block.slot,
+            blockhash: block.blockhash.clone(),
+            parent_slot: block.parent_slot,
+            block_time: block.block_time.as_ref().map(|t| t.timestamp),
+            transaction_count: block.executed_transaction_count,
+            failed_transaction_count: transactions.iter().filter(|tx| tx.failed).count() as u64,
+            vote_transaction_count: transactions.iter().filter(|tx| tx.is_vote).count() as u64,
+            total_fee: transactions.iter().map(|tx| tx.fee).sum(),
+        }
+    }
+}
+
+impl ArchiveRow for BlockSummaryRow {
+    const TABLE: &'static str = "blocks";
+
+    fn schema() -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            Field::new("slot", DataType::UInt64, false),
+            Field::new("blockhash", DataType::Utf8, false),
+            Field::new("parent_slot", DataType::UInt64, false),
+            Field::new("block_time", DataType::Int64, true),
+            Field::new("transaction_count", DataType::UInt64, false),
+            Field::new("failed_transaction_count", DataType::UInt64, false),
+            Field::new("vote_transaction_count", DataType::UInt64, false),
+            Field::new("total_fee", DataType::UInt64, false),
+        ]))
+    }
+
+    fn to_record_batch(rows: &[Self]) -> anyhow::Result<RecordBatch> {
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(UInt64Array::from_iter_values(rows.iter().map(|r| r.slot))),
+            Arc::new(StringArray::from_iter_values(
+                rows.iter().map(|r| &r.blockhash),
+            )),
+            Arc::new(UInt64Array::from_iter_values(
+                rows.iter().map(|r| r.parent_slot),
+            )),
+            Arc::new(Int64Array::from_iter(rows.iter().map(|r| r.block_time))),
+            Arc::new(UInt64Array::from_iter_values(
+                rows.iter().map(|r| r.transaction_count),
+            )),
+            Arc::new(UInt64Array::from_iter_values(
+                rows.iter().map(|r| r.failed_transaction_count),
+            )),
+            Arc::new(UInt64Array::from_iter_values(
+                rows.iter().map(|r| r.vote_transaction_count),
+            )),
+            Arc::new(UInt64Array::from_iter_values(
+                rows.iter().map(|r| r.total_fee),
+            )),
+        ];
+        Ok(RecordBatch::try_new(Self::schema(), columns)?)
+    }
+
+    fn slot(&self) -> Slot {
+        self.slot
+    }
+
+    fn block_time(&self) -> Option<i64> {
+        self.block_time
+    }
+}
+
+impl ArchiveRow for TransactionSummaryRow {
+    const TABLE: &'static str = "transactions";
+
+    fn schema() -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            Field::new("slot", DataType::UInt64, false),
+            Field::new("signature", DataType::Utf8, false),
+            Field::new("block_time", DataType::Int64, true),
+            Field::new("is_vote", DataType::Boolean, false),
+            Field::new("failed", DataType::Boolean, false),
+            Field::new("fee", DataType::UInt64, false),
+            Field::new("compute_units_consumed", DataType::UInt64, true),
+        ]))
+    }
+
+    fn to_record_batch(rows: &[Self]) -> anyhow::Result<RecordBatch> {
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(UInt64Array::from_iter_values(rows.iter().map(|r| r.slot))),
+            Arc::new(StringArray::from_iter_values(
+                rows.iter().map(|r| &r.signature),
+            )),
+            Arc::new(Int64Array::from_iter(rows.iter().map(|r| r.block_time))),
+            Arc::new(BooleanArray::from_iter(
+                rows.iter().map(|r| Some(r.is_vote)),
+            )),
+            Arc::new(BooleanArray::from_iter(rows.iter().map(|r| Some(r.failed)))),
+            Arc::new(UInt64Array::from_iter_values(rows.iter().map(|r| r.fee))),
+            Arc::new(UInt64Array::from_iter(
+                rows.iter().map(|r| r.compute_units_consumed),
+            )),
+        ];
+        Ok(RecordBatch::try_new(Self::schema(), columns)?)
+    }
+
+    fn slot(&self) -> Slot {
+        self.slot
+    }
+
+    fn block_time(&self) -> Option<i64> {
+        self.block_time
+    }
+}
+
+struct OpenFile {
+    partition: String,
+    path: PathBuf,
+    writer: ArrowWriter<File>,
+    rows: usize,
+}
+
+/// writes rows into ``{base_dir}/{table}/{partition}/part-{first_slot}.parquet``; existing files are
+/// never overwritten, e.g. after a restart the file gets a ``-<n>`` suffix
+///
+/// memory is bounded by ``rows_per_batch`` plus the row group buffered by the parquet writer
+/// note: file io is blocking; run on a dedicated task or thread
+pub struct ParquetArchiveWriter<R: ArchiveRow> {
+    config: ParquetArchiveConfig,
+    buffer: Vec<R>,
+    current: Option<OpenFile>,
+}
+
+impl<R: ArchiveRow> ParquetArchiveWriter<R> {
+    pub fn new(config: ParquetArchiveConfig) -> Self {
+        Self {
+            buffer: Vec::with_capacity(config.rows_per_batch),
+            config,
+            current: None,
+        }
+    }
+
+    pub fn push(&mut self, row: R) -> anyhow::Result<()> {
+        let partition = self.partition_of(&row);
+        let partition_changed = self
+            .current
+            .as_ref()
+            .map_or(false, |current| current.partition != partition);
+        if partition_changed {
+            self.close_file()?;
+        }
+        if self.current.is_none() {
+            self.open_file(partition, row.slot())?;
+        }
+
+        self.buffer.push(row);
+        if self.buffer.len() >= self.config.rows_per_batch {
+            self.write_buffer()?;
+        }
+        Ok(())
+    }
+
+    /// flush buffered rows and finish the current file
+    pub fn close(&mut self) -> anyhow::Result<()> {
+        self.close_file()
+    }
+
+    fn partition_of(&self, row: &R) -> String {
+        match self.config.partition_by {
+            PartitionBy::Day => {
+                let block_time = row.block_time().unwrap_or_else(|| {
+                    SystemTime::now()
+                        .duration_since(SystemTime::UNIX_EPOCH)
+                        .map(|since_epoch| since_epoch.as_secs() as i64)
+                        .unwrap_or_default()
+                });
+                format!("day={}", utc_date(block_time))
+            }
+            PartitionBy::Epoch { slots_per_epoch } => {
+                format!("epoch={}", row.slot() / slots_per_epoch.max(1))
+            }
+        }
+    }
+
+    fn open_file(&mut self, partition: String, first_slot: Slot) -> anyhow::Result<()> {
+        let dir = self.config.base_dir.join(R::TABLE).join(&partition);
+        fs::create_dir_all(&dir)?;
+        let (path, file) = create_new_file(&dir, first_slot)?;
+        let writer = ArrowWriter::try_new(file, R::schema(), None)?;
+        debug!("opened archive file {}", path.display());
+        self.current = Some(OpenFile {
+            partition,
+            path,
+            writer,
+            rows: 0,
+        });
+        Ok(())
+    }
+
+    fn write_buffer(&mut self) -> anyhow::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let Some(current) = self.current.as_mut() else {
+            return Ok(());
+        };
+        let batch = R::to_record_batch(&self.buffer)?;
+        current.writer.write(&batch)?;
+        current.rows += self.buffer.len();
+        self.buffer.clear();
+
+        if current.rows >= self.config.max_rows_per_file {
+            self.close_file()?;
+        }
+        Ok(())
+    }
+
+    fn close_file(&mut self) -> anyhow::Result<()> {
+        self.write_buffer()?;
+        if let Some(current) = self.current.take() {
+            current.writer.close()?;
+            info!("wrote {} rows to {}", current.rows, current.path.display());
+        }
+        Ok(())
+    }
+}
+
+// part-<first_slot>.parquet or the first free part-<first_slot>-<n>.parquet
+fn create_new_file(dir: &Path, first_slot: Slot) -> anyhow::Result<(PathBuf, File)> {
+    let mut path = dir.join(format!("part-{}.parquet", first_slot));
+    let mut n = 0;
+    loop {
+        match File::options().write(true).create_new(true).open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => {
+                n += 1;
+                path = dir.join(format!("part-{}-{}.parquet", first_slot, n));
+            }
+            Err(err) => return Err(err.into()),
+        }
+    }
+}
+
+/// archive the multiplexed blocks until the stream ends; the files are written on a blocking thread
+pub async fn archive_blocks(
+    config: ParquetArchiveConfig,
+    multiplexed_blocks: impl Stream<Item = SubscribeUpdateBlock>,
+) -> anyhow::Result<()> {
+    let (blocks_tx, mut blocks_rx) = mpsc::channel::<SubscribeUpdateBlock>(ARCHIVE_QUEUE_CAPACITY);
+    let jh_writer = tokio::task::spawn_blocking(move || -> anyhow::Result<()> {
+        let mut blocks_writer = ParquetArchiveWriter::<BlockSummaryRow>::new(config.clone());
+        let mut transactions_writer = ParquetArchiveWriter::<TransactionSummaryRow>::new(config);
+        while let Some(block) = blocks_rx.blocking_recv() {
+            blocks_writer.push(BlockSummaryRow::from_block(&block))?;
+            for transaction in TransactionSummaryRow::from_block(&block) {
+                transactions_writer.push(transaction)?;
+            }
+        }
+        blocks_writer.close()?;
+        transactions_writer.close()
+    });
+
+    let mut multiplexed_blocks = pin!(multiplexed_blocks);
+    while let Some(block) = multiplexed_blocks.next().await {
+        if blocks_tx.send(block).await.is_err() {
+            // writer failed, its error is returned below
+            break;
+        }
+    }
+    drop(blocks_tx);
+    jh_writer.await?
+}
+
+// proleptic gregorian calendar (civil_from_days by Howard Hinnant)
+fn utc_date(unix_timestamp: i64) -> String {
+    let days = unix_timestamp.div_euclid(86_400) + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_utc_date() {
+        assert_eq!(utc_date(0), "1970-01-01");
+        assert_eq!(utc_date(951_782_400), "2000-02-29");
+        assert_eq!(utc_date(1_706_745_599), "2024-01-31");
+    }
+
+    #[test]
+    fn test_partitioned_files() {
+        let base_dir = std::env::temp_dir().join(format!("geyser-archive-{}", std::process::id()));
+        let config = ParquetArchiveConfig {
+            base_dir: base_dir.clone(),
+            partition_by: PartitionBy::Epoch {
+                slots_per_epoch: 10,
+            },
+            rows_per_batch: 2,
+            max_rows_per_file: 100,
+        };
+        let write_slots = |slots: &[Slot]| {
+            let mut writer = ParquetArchiveWriter::<BlockSummaryRow>::new(config.clone());
+            for slot in slots {
+                writer
+                    .push(BlockSummaryRow {
+                        slot: *slot,
+                        blockhash: format!("hash-{}", slot),
+                        parent_slot: slot - 1,
+                        block_time: None,
+                        transaction_count: 1,
+                        failed_transaction_count: 0,
+                        vote_transaction_count: 0,
+                        total_fee: 5000,
+                    })
+                    .unwrap();
+            }
+            writer.close().unwrap();
+        };
+        write_slots(&[8, 9, 10, 11, 12]);
+
+        assert!(base_dir.join("blocks/epoch=0/part-8.parquet").exists());
+        assert!(base_dir.join("blocks/epoch=1/part-10.parquet").exists());
+
+        // restart at the same slot
+        write_slots(&[10]);
+        assert!(base_dir.join("blocks/epoch=1/part-10-1.parquet").exists());
+        fs::remove_dir_all(base_dir).unwrap();
+    }
+}
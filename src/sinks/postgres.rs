@@ -0,0 +1,273 @@
+use crate::Slot;
+use anyhow::bail;
+use log::{debug, warn};
+use std::collections::BTreeMap;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time::{timeout, Instant};
+use tokio_postgres::types::ToSql;
+use tokio_postgres::{Client, NoTls};
+use yellowstone_grpc_proto::geyser::subscribe_update::UpdateOneof;
+use yellowstone_grpc_proto::geyser::SubscribeUpdate;
+use yellowstone_grpc_proto::prost::Message as _;
+
+// columns of the block table in insert order
+const BLOCK_COLUMNS: [&str; 6] = [
+    "slot",
+    "blockhash",
+    "parent_slot",
+    "block_time",
+    "transaction_count",
+    "raw",
+];
+
+/// rows per statement; postgres allows at most 65535 bind parameters
+pub const MAX_BATCH_SIZE: usize = u16::MAX as usize / BLOCK_COLUMNS.len();
+
+#[derive(Clone, Debug)]
+pub struct PostgresSinkConfig {
+    // e.g. "host=localhost user=postgres dbname=blocks"
+    pub connection_string: String,
+    // trusted configuration; used verbatim in the sql
+    pub table: String,
+    // at most ``MAX_BATCH_SIZE``
+    pub batch_size: usize,
+    // write a partial batch at the latest after this delay
+    pub flush_interval: Duration,
+}
+
+impl PostgresSinkConfig {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.batch_size > MAX_BATCH_SIZE {
+            bail!(
+                "batch_size {} exceeds the maximum of {} rows per statement",
+                self.batch_size,
+                MAX_BATCH_SIZE
+            );
+        }
+        Ok(())
+    }
+}
+
+/// one row per slot; later writes for the same slot replace the row
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlockRow {
+    pub slot: Slot,
+    pub blockhash: String,
+    pub parent_slot: Slot,
+    pub block_time: Option<i64>,
+    pub transaction_count: u64,
+    // protobuf encoded update
+    pub raw: Option<Vec<u8>>,
+}
+
+impl BlockRow {
+    /// supports blocks and blocks meta updates
+    pub fn from_update(update: &SubscribeUpdate, include_raw: bool) -> Option<Self> {
+        let raw = include_raw.then(|| update.encode_to_vec());
+        match &update.update_oneof {
+            Some(UpdateOneof::Block(block)) => Some(BlockRow {
+                slot: block.slot,
+                blockhash: block.blockhash.clone(),
+                parent_slot: block.parent_slot,
+                block_time: block.block_time.as_ref().map(|t| t.timestamp),
+                transaction_count: block.executed_transaction_count,
+                raw,
+            }),
+            Some(UpdateOneof::BlockMeta(block_meta)) => Some(BlockRow {
+                slot: block_meta.slot,
+                blockhash: block_meta.blockhash.clone(),
+                parent_slot: block_meta.parent_slot,
+                block_time: block_meta.block_time.as_ref().map(|t| t.timestamp),
+                transaction_count: block_meta.executed_transaction_count,
+                raw,
+            }),
+            _ => None,
+        }
+    }
+}
+
+pub struct PostgresSink {
+    client: Client,
+    config: PostgresSinkConfig,
+}
+
+impl PostgresSink {
+    pub async fn connect(config: PostgresSinkConfig) -> anyhow::Result<Self> {
+        config.validate()?;
+        let (client, connection) =
+            tokio_postgres::connect(&config.connection_string, NoTls).await?;
+        tokio::spawn(async move {
+            if let Err(err) = connection.await {
+                warn!("postgres connection closed: {:?}", err);
+            }
+        });
+        Ok(Self { client, config })
+    }
+
+    pub async fn create_table_if_not_exists(&self) -> anyhow::Result<()> {
+        let statement = format!(
+            "CREATE TABLE IF NOT EXISTS {} (
+                slot BIGINT PRIMARY KEY,
+                blockhash TEXT NOT NULL,
+                parent_slot BIGINT NOT NULL,
+                block_time BIGINT,
+                transaction_count BIGINT NOT NULL,
+                raw BYTEA
+            )",
+            self.config.table
+        );
+        self.client.batch_execute(&statement).await?;
+        Ok(())
+    }
+
+    /// upsert on slot; returns number of rows written
+    ///
+    /// more than ``MAX_BATCH_SIZE`` rows are written with several statements
+    pub async fn write_batch(&self, rows: &[BlockRow]) -> anyhow::Result<u64> {
+        let mut written = 0;
+        for chunk in rows.chunks(MAX_BATCH_SIZE) {
+            written += self.write_statement(chunk).await?;
+        }
+        Ok(written)
+    }
+
+    async fn write_statement(&self, rows: &[BlockRow]) -> anyhow::Result<u64> {
+        let statement = upsert_statement(&self.config.table, rows.len());
+
+        let numeric: Vec<(i64, i64, i64)> = rows
+            .iter()
+            .map(|row| {
+                (
+                    row.slot as i64,
+                    row.parent_slot as i64,
+                    row.transaction_count as i64,
+                )
+            })
+            .collect();
+        let mut params: Vec<&(dyn ToSql + Sync)> =
+            Vec::with_capacity(rows.len() * BLOCK_COLUMNS.len());
+        for (row, (slot, parent_slot, transaction_count)) in rows.iter().zip(numeric.iter()) {
+            params.push(slot);
+            params.push(&row.blockhash);
+            params.push(parent_slot);
+            params.push(&row.block_time);
+            params.push(transaction_count);
+            params.push(&row.raw);
+        }
+
+        Ok(self.client.execute(&statement, &params).await?)
+    }
+
+    /// drive the sink from a receiver of ``channelize_stream``; returns once the channel is closed
+    pub async fn consume_receiver<T>(
+        &self,
+        mut receiver: broadcast::Receiver<T>,
+    ) -> anyhow::Result<()>
+    where
+        T: Clone + Into<BlockRow>,
+    {
+        // keyed by slot: sql upsert cannot touch the same row twice in one statement
+        let mut batch: BTreeMap<Slot, BlockRow> = BTreeMap::new();
+        let mut batch_started = Instant::now();
+        loop {
+            let next = if batch.is_empty() {
+                Some(receiver.recv().await)
+            } else {
+                let remaining = self
+                    .config
+                    .flush_interval
+                    .saturating_sub(batch_started.elapsed());
+                timeout(remaining, receiver.recv()).await.ok()
+            };
+
+            let closed = match next {
+                Some(Ok(item)) => {
+                    if batch.is_empty() {
+                        batch_started = Instant::now();
+                    }
+                    let row: BlockRow = item.into();
+                    batch.insert(row.slot, row);
+                    false
+                }
+                Some(Err(RecvError::Lagged(skipped))) => {
+                    warn!("postgres sink lagging - skipped {} blocks", skipped);
+                    false
+                }
+                Some(Err(RecvError::Closed)) => true,
+                // flush interval elapsed
+                None => false,
+            };
+
+            let flush = closed
+                || batch.len() >= self.config.batch_size
+                || (!batch.is_empty() && batch_started.elapsed() >= self.config.flush_interval);
+            if flush && !batch.is_empty() {
+                let rows: Vec<BlockRow> = std::mem::take(&mut batch).into_values().collect();
+                let written = self.write_batch(&rows).await?;
+                debug!("wrote {} block rows to {}", written, self.config.table);
+            }
+
+            if closed {
+                return Ok(());
+            }
+        }
+    }
+}
+
+fn upsert_statement(table: &str, row_count: usize) -> String {
+    let columns = BLOCK_COLUMNS.len();
+    let values = (0..row_count)
+        .map(|row| {
+            let placeholders = (1..=columns)
+                .map(|column| format!("${}", row * columns + column))
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!("({})", placeholders)
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    let updates = BLOCK_COLUMNS[1..]
+        .iter()
+        .map(|column| format!("{column} = EXCLUDED.{column}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!(
+        "INSERT INTO {} ({}) VALUES {} ON CONFLICT (slot) DO UPDATE SET {}",
+        table,
+        BLOCK_COLUMNS.join(", "),
+        values,
+        updates
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_upsert_statement() {
+        assert_eq!(
+            upsert_statement("blocks", 2),
+            "INSERT INTO blocks (slot, blockhash, parent_slot, block_time, transaction_count, raw) \
+             VALUES ($1, $2, $3, $4, $5, $6), ($7, $8, $9, $10, $11, $12) \
+             ON CONFLICT (slot) DO UPDATE SET blockhash = EXCLUDED.blockhash, \
+             parent_slot = EXCLUDED.parent_slot, block_time = EXCLUDED.block_time, \
+             transaction_count = EXCLUDED.transaction_count, raw = EXCLUDED.raw"
+        );
+    }
+
+    #[test]
+    fn test_validate_batch_size() {
+        let mut config = PostgresSinkConfig {
+            connection_string: "host=localhost".to_string(),
+            table: "blocks".to_string(),
+            batch_size: MAX_BATCH_SIZE,
+            flush_interval: Duration::from_secs(1),
+        };
+        assert!(config.validate().is_ok());
+        config.batch_size = MAX_BATCH_SIZE + 1;
+        assert!(config.validate().is_err());
+    }
+}
@@ -0,0 +1,113 @@
+use crate::sinks::SinkRecord;
+use crate::Slot;
+use futures::{Stream, StreamExt};
+use log::debug;
+use redis::aio::MultiplexedConnection;
+use std::collections::HashMap;
+use std::pin::pin;
+
+#[derive(Clone, Debug)]
+pub struct RedisSinkConfig {
+    // e.g. redis://127.0.0.1/
+    pub url: String,
+    pub channel: String,
+    // if set, the highest published slot is stored under this key
+    pub latest_slot_key: Option<String>,
+}
+
+/// republish the multiplexed stream on a redis pub/sub channel
+pub struct RedisSink {
+    connection: MultiplexedConnection,
+    config: RedisSinkConfig,
+    deduplicator: SlotDeduplicator,
+}
+
+impl RedisSink {
+    pub async fn connect(config: RedisSinkConfig) -> anyhow::Result<Self> {
+        let client = redis::Client::open(config.url.as_str())?;
+        let connection = client.get_multiplexed_tokio_connection().await?;
+        Ok(Self {
+            connection,
+            config,
+            deduplicator: SlotDeduplicator::default(),
+        })
+    }
+
+    /// returns false if the slot was already published (or is older than the latest published slot)
+    /// for this record type; e.g. blocks and slot ticks of the same slot are both published
+    pub async fn publish<R: SinkRecord>(&mut self, record: &R) -> anyhow::Result<bool> {
+        let slot = record.slot();
+        if !self.deduplicator.is_new(std::any::type_name::<R>(), slot) {
+            debug!(
+                "skip duplicate slot {} on redis channel {}",
+                slot, self.config.channel
+            );
+            return Ok(false);
+        }
+        let payload = record.encode()?;
+
+        let mut pipe = redis::pipe();
+        pipe.publish(&self.config.channel, payload).ignore();
+        if let Some(latest_slot_key) = &self.config.latest_slot_key {
+            pipe.set(latest_slot_key, slot).ignore();
+        }
+        pipe.query_async::<_, ()>(&mut self.connection).await?;
+        Ok(true)
+    }
+
+    /// consume the multiplexed stream until it ends or redis fails
+    pub async fn consume_stream<R: SinkRecord>(
+        &mut self,
+        multiplexed_stream: impl Stream<Item = R>,
+    ) -> anyhow::Result<()> {
+        let mut multiplexed_stream = pin!(multiplexed_stream);
+        while let Some(record) = multiplexed_stream.next().await {
+            self.publish(&record).await?;
+        }
+        Ok(())
+    }
+}
+
+// only strictly increasing slots pass, tracked per record kind
+#[derive(Default)]
+struct SlotDeduplicator {
+    latest: HashMap<&'static str, Slot>,
+}
+
+impl SlotDeduplicator {
+    fn is_new(&mut self, kind: &'static str, slot: Slot) -> bool {
+        match self.latest.get(kind) {
+            Some(latest) if slot <= *latest => false,
+            _ => {
+                self.latest.insert(kind, slot);
+                true
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deduplicator() {
+        let mut deduplicator = SlotDeduplicator::default();
+        assert!(deduplicator.is_new("block", 0));
+        assert!(deduplicator.is_new("block", 5));
+        assert!(!deduplicator.is_new("block", 5));
+        assert!(!deduplicator.is_new("block", 3));
+        assert!(deduplicator.is_new("block", 6));
+    }
+
+    #[test]
+    fn test_deduplicator_per_kind() {
+        let mut deduplicator = SlotDeduplicator::default();
+        assert!(deduplicator.is_new("block", 5));
+        // the block of slot 5 does not hide the slot tick of slot 5
+        assert!(deduplicator.is_new("slot", 5));
+        assert!(!deduplicator.is_new("slot", 3));
+        assert!(deduplicator.is_new("slot", 6));
+        assert!(!deduplicator.is_new("block", 5));
+    }
+}
@@ -0,0 +1,70 @@
+use crate::Slot;
+use async_stream::stream;
+use futures::Stream;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BlockOrSkipped<T> {
+    Block(T),
+    // no block was produced for the slot; emitted before the block whose parent skips it
+    SlotSkipped(Slot),
+}
+
+/// interleaves ``SlotSkipped`` for the slots between a block and its parent
+///
+/// ``slot_and_parent`` returns (slot, parent_slot) of an item, e.g. from ``SubscribeUpdateBlock``;
+/// slots between the previous block and the parent of the next one are unknown (missed), not skipped
+/// note: use with confirmed or finalized commitment only
+pub fn detect_skipped_slots<T, F>(
+    blocks: impl Stream<Item = T>,
+    slot_and_parent: F,
+) -> impl Stream<Item = BlockOrSkipped<T>>
+where
+    F: Fn(&T) -> (Slot, Slot),
+{
+    stream! {
+        let mut last_slot: Option<Slot> = None;
+        for await block in blocks {
+            let (slot, parent_slot) = slot_and_parent(&block);
+            // do not report slots again which were already covered on the chain
+            let first_skipped = match last_slot {
+                Some(last_slot) => (parent_slot + 1).max(last_slot + 1),
+                None => parent_slot + 1,
+            };
+            for skipped in first_skipped..slot {
+                yield BlockOrSkipped::SlotSkipped(skipped);
+            }
+            last_slot = Some(last_slot.map_or(slot, |last_slot| last_slot.max(slot)));
+            yield BlockOrSkipped::Block(block);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{stream, StreamExt};
+
+    #[tokio::test]
+    async fn test_detect_skipped_slots() {
+        // (slot, parent)
+        let blocks = stream::iter(vec![(10, 9), (13, 10), (14, 13), (20, 17)]);
+
+        let output: Vec<BlockOrSkipped<(Slot, Slot)>> =
+            detect_skipped_slots(blocks, |block| *block).collect().await;
+
+        assert_eq!(
+            output,
+            vec![
+                BlockOrSkipped::Block((10, 9)),
+                BlockOrSkipped::SlotSkipped(11),
+                BlockOrSkipped::SlotSkipped(12),
+                BlockOrSkipped::Block((13, 10)),
+                BlockOrSkipped::Block((14, 13)),
+                // 15..=17 were missed on the stream, not skipped by the cluster
+                BlockOrSkipped::SlotSkipped(18),
+                BlockOrSkipped::SlotSkipped(19),
+                BlockOrSkipped::Block((20, 17)),
+            ]
+        );
+    }
+}
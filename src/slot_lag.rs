@@ -0,0 +1,180 @@
+use crate::commitment::CommitmentLevel;
+use futures::Future;
+use log::warn;
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::clock::Slot;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, watch};
+use tokio::task::JoinHandle;
+use tokio::time::{interval, MissedTickBehavior};
+
+#[derive(Clone, Debug)]
+pub struct SlotLagConfig {
+    pub interval: Duration,
+    // alert when the pipeline is more slots behind the reference
+    pub alert_threshold: u64,
+    // of getSlot; should match the commitment of the pipeline
+    pub commitment: CommitmentLevel,
+}
+
+impl Default for SlotLagConfig {
+    fn default() -> Self {
+        SlotLagConfig {
+            interval: Duration::from_secs(5),
+            alert_threshold: 20,
+            commitment: CommitmentLevel::Confirmed,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SlotLag {
+    // highest slot seen by the pipeline
+    pub pipeline_slot: Slot,
+    pub reference_slot: Slot,
+    // 0 if the pipeline is ahead of the reference
+    pub lag: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SlotLagEvent {
+    // lag exceeded the alert threshold
+    Behind(SlotLag),
+    // lag is back within the threshold
+    CaughtUp(SlotLag),
+}
+
+/// compare the highest slot of the pipeline (e.g. from ``tip_slot::spawn_tip_slot_watch``) with
+/// ``getSlot`` of a reference rpc node, to detect the whole pipeline falling behind the cluster
+///
+/// returns the latest measurement and ``Behind``/``CaughtUp`` transitions
+pub fn spawn_slot_lag_monitor(
+    pipeline_slot: watch::Receiver<Slot>,
+    rpc_url: String,
+    config: SlotLagConfig,
+) -> (
+    watch::Receiver<Option<SlotLag>>,
+    broadcast::Receiver<SlotLagEvent>,
+    JoinHandle<()>,
+) {
+    let rpc_client = Arc::new(RpcClient::new(rpc_url));
+    let commitment_config = config.commitment.commitment_config();
+    spawn_monitor(
+        pipeline_slot,
+        move || {
+            let rpc_client = rpc_client.clone();
+            async move {
+                Ok(rpc_client
+                    .get_slot_with_commitment(commitment_config)
+                    .await?)
+            }
+        },
+        config,
+    )
+}
+
+fn spawn_monitor<F, Fut>(
+    pipeline_slot: watch::Receiver<Slot>,
+    mut reference_slot: F,
+    config: SlotLagConfig,
+) -> (
+    watch::Receiver<Option<SlotLag>>,
+    broadcast::Receiver<SlotLagEvent>,
+    JoinHandle<()>,
+)
+where
+    F: FnMut() -> Fut + Send + 'static,
+    Fut: Future<Output = anyhow::Result<Slot>> + Send,
+{
+    let (lag_tx, lag_rx) = watch::channel(None);
+    let (events_tx, events_rx) = broadcast::channel(16);
+
+    let jh_monitor = tokio::spawn(async move {
+        let mut ticks = interval(config.interval);
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut behind = false;
+        loop {
+            ticks.tick().await;
+            let reference_slot = match reference_slot().await {
+                Ok(reference_slot) => reference_slot,
+                Err(err) => {
+                    warn!("failed to get reference slot: {:?}", err);
+                    continue;
+                }
+            };
+            let pipeline_slot = *pipeline_slot.borrow();
+            let slot_lag = SlotLag {
+                pipeline_slot,
+                reference_slot,
+                lag: reference_slot.saturating_sub(pipeline_slot),
+            };
+            if lag_tx.send(Some(slot_lag)).is_err() && events_tx.receiver_count() == 0 {
+                // nobody is interested anymore
+                return;
+            }
+
+            let now_behind = slot_lag.lag > config.alert_threshold;
+            if now_behind == behind {
+                continue;
+            }
+            behind = now_behind;
+            let event = if behind {
+                warn!(
+                    "pipeline is {} slots behind the reference ({} vs {})",
+                    slot_lag.lag, pipeline_slot, reference_slot
+                );
+                SlotLagEvent::Behind(slot_lag)
+            } else {
+                SlotLagEvent::CaughtUp(slot_lag)
+            };
+            // no subscribers is fine
+            let _ = events_tx.send(event);
+        }
+    });
+
+    (lag_rx, events_rx, jh_monitor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_slot_lag_events() {
+        let (_pipeline_tx, pipeline_rx) = watch::channel(100);
+        let mut reference_slots = vec![105, 150, 110].into_iter();
+        let config = SlotLagConfig {
+            interval: Duration::from_secs(1),
+            ..Default::default()
+        };
+
+        let (lag_rx, mut events, jh_monitor) = spawn_monitor(
+            pipeline_rx,
+            move || future_slot(reference_slots.next().unwrap_or(100)),
+            config,
+        );
+
+        let behind = SlotLag {
+            pipeline_slot: 100,
+            reference_slot: 150,
+            lag: 50,
+        };
+        assert_eq!(events.recv().await.unwrap(), SlotLagEvent::Behind(behind));
+        let caught_up = SlotLag {
+            pipeline_slot: 100,
+            reference_slot: 110,
+            lag: 10,
+        };
+        assert_eq!(
+            events.recv().await.unwrap(),
+            SlotLagEvent::CaughtUp(caught_up)
+        );
+        assert_eq!(*lag_rx.borrow(), Some(caught_up));
+        jh_monitor.abort();
+    }
+
+    async fn future_slot(slot: Slot) -> anyhow::Result<Slot> {
+        Ok(slot)
+    }
+}
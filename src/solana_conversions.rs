@@ -0,0 +1,459 @@
+use anyhow::{anyhow, bail, Context};
+use solana_account_decoder::parse_token::UiTokenAmount;
+use solana_sdk::hash::Hash;
+use solana_sdk::instruction::CompiledInstruction;
+use solana_sdk::message::v0::{LoadedAddresses, MessageAddressTableLookup};
+use solana_sdk::message::{legacy, v0, MessageHeader, VersionedMessage};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::reward_type::RewardType;
+use solana_sdk::signature::Signature;
+use solana_sdk::transaction::{TransactionError, VersionedTransaction};
+use solana_sdk::transaction_context::TransactionReturnData;
+use solana_transaction_status::{
+    BlockEncodingOptions, ConfirmedBlock, InnerInstruction, InnerInstructions, Reward,
+    TransactionDetails, TransactionStatusMeta, TransactionTokenBalance, UiConfirmedBlock,
+    UiTransactionEncoding, VersionedConfirmedBlock, VersionedTransactionWithStatusMeta,
+};
+use yellowstone_grpc_proto::geyser::{SubscribeUpdateBlock, SubscribeUpdateTransactionInfo};
+use yellowstone_grpc_proto::prelude as proto;
+
+/// geyser block that converts into the types of ``solana-transaction-status``
+///
+/// ```ignore
+/// let confirmed_block = ConfirmedBlock::try_from(GeyserBlock(update_block))?;
+/// ```
+pub struct GeyserBlock(pub SubscribeUpdateBlock);
+
+impl TryFrom<GeyserBlock> for VersionedConfirmedBlock {
+    type Error = anyhow::Error;
+
+    fn try_from(GeyserBlock(block): GeyserBlock) -> Result<Self, Self::Error> {
+        let transactions = block
+            .transactions
+            .into_iter()
+            .map(versioned_transaction_with_meta)
+            .collect::<anyhow::Result<Vec<_>>>()
+            .with_context(|| format!("convert transactions of block {}", block.slot))?;
+
+        Ok(VersionedConfirmedBlock {
+            previous_blockhash: block.parent_blockhash,
+            blockhash: block.blockhash,
+            parent_slot: block.parent_slot,
+            transactions,
+            rewards: block
+                .rewards
+                .map(|rewards| rewards.rewards.into_iter().map(reward).collect())
+                .unwrap_or_default(),
+            block_time: block.block_time.map(|t| t.timestamp),
+            block_height: block.block_height.map(|h| h.block_height),
+        })
+    }
+}
+
+impl TryFrom<GeyserBlock> for ConfirmedBlock {
+    type Error = anyhow::Error;
+
+    fn try_from(block: GeyserBlock) -> Result<Self, Self::Error> {
+        VersionedConfirmedBlock::try_from(block).map(Into::into)
+    }
+}
+
+/// how ``encode_ui_block`` renders the block, same knobs as the getBlock rpc call
+#[derive(Clone, Debug)]
+pub struct UiBlockEncodingConfig {
+    // e.g. Base64 or JsonParsed
+    pub encoding: UiTransactionEncoding,
+    pub transaction_details: TransactionDetails,
+    pub show_rewards: bool,
+    // None rejects blocks with versioned transactions like the rpc does
+    pub max_supported_transaction_version: Option<u8>,
+}
+
+impl Default for UiBlockEncodingConfig {
+    fn default() -> Self {
+        Self {
+            encoding: UiTransactionEncoding::Base64,
+            transaction_details: TransactionDetails::Full,
+            show_rewards: true,
+            max_supported_transaction_version: Some(0),
+        }
+    }
+}
+
+/// rpc-compatible representation of a geyser block
+pub fn encode_ui_block(
+    block: GeyserBlock,
+    config: &UiBlockEncodingConfig,
+) -> anyhow::Result<UiConfirmedBlock> {
+    let confirmed_block = ConfirmedBlock::try_from(block)?;
+    let ui_block = confirmed_block.encode_with_options(
+        config.encoding,
+        BlockEncodingOptions {
+            transaction_details: config.transaction_details,
+            show_rewards: config.show_rewards,
+            max_supported_transaction_version: config.max_supported_transaction_version,
+        },
+    )?;
+    Ok(ui_block)
+}
+
+/// json as returned in the result of the getBlock rpc call
+pub fn encode_ui_block_json(
+    block: GeyserBlock,
+    config: &UiBlockEncodingConfig,
+) -> anyhow::Result<String> {
+    Ok(serde_json::to_string(&encode_ui_block(block, config)?)?)
+}
+
+/// transaction and meta of a geyser block or transaction update
+pub fn versioned_transaction_with_meta(
+    transaction_info: SubscribeUpdateTransactionInfo,
+) -> anyhow::Result<VersionedTransactionWithStatusMeta> {
+    let transaction = transaction_info
+        .transaction
+        .ok_or_else(|| anyhow!("transaction missing"))?;
+    let meta = transaction_info
+        .meta
+        .ok_or_else(|| anyhow!("transaction meta missing"))?;
+    Ok(VersionedTransactionWithStatusMeta {
+        transaction: versioned_transaction(transaction)?,
+        meta: transaction_status_meta(meta)?,
+    })
+}
+
+pub fn versioned_transaction(
+    transaction: proto::Transaction,
+) -> anyhow::Result<VersionedTransaction> {
+    let signatures = transaction
+        .signatures
+        .iter()
+        .map(|signature| {
+            Signature::try_from(signature.as_slice()).map_err(|_| anyhow!("invalid signature"))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let message = transaction
+        .message
+        .ok_or_else(|| anyhow!("transaction message missing"))?;
+    Ok(VersionedTransaction {
+        signatures,
+        message: versioned_message(message)?,
+    })
+}
+
+fn versioned_message(message: proto::Message) -> anyhow::Result<VersionedMessage> {
+    let header = message
+        .header
+        .ok_or_else(|| anyhow!("message header missing"))?;
+    let header = MessageHeader {
+        num_required_signatures: u8::try_from(header.num_required_signatures)?,
+        num_readonly_signed_accounts: u8::try_from(header.num_readonly_signed_accounts)?,
+        num_readonly_unsigned_accounts: u8::try_from(header.num_readonly_unsigned_accounts)?,
+    };
+    let account_keys = message
+        .account_keys
+        .iter()
+        .map(|key| pubkey(key))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let recent_blockhash = <[u8; 32]>::try_from(message.recent_blockhash.as_slice())
+        .map(Hash::new_from_array)
+        .map_err(|_| anyhow!("invalid recent blockhash"))?;
+    let instructions = message
+        .instructions
+        .into_iter()
+        .map(|ix| {
+            Ok(CompiledInstruction {
+                program_id_index: u8::try_from(ix.program_id_index)?,
+                accounts: ix.accounts,
+                data: ix.data,
+            })
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    if !message.versioned {
+        return Ok(VersionedMessage::Legacy(legacy::Message {
+            header,
+            account_keys,
+            recent_blockhash,
+            instructions,
+        }));
+    }
+
+    let address_table_lookups = message
+        .address_table_lookups
+        .into_iter()
+        .map(|lookup| {
+            Ok(MessageAddressTableLookup {
+                account_key: pubkey(&lookup.account_key)?,
+                writable_indexes: lookup.writable_indexes,
+                readonly_indexes: lookup.readonly_indexes,
+            })
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    Ok(VersionedMessage::V0(v0::Message {
+        header,
+        account_keys,
+        recent_blockhash,
+        instructions,
+        address_table_lookups,
+    }))
+}
+
+pub fn transaction_status_meta(
+    meta: proto::TransactionStatusMeta,
+) -> anyhow::Result<TransactionStatusMeta> {
+    let status = match meta.err {
+        None => Ok(()),
+        Some(err) => Err(bincode::deserialize::<TransactionError>(&err.err)
+            .context("deserialize transaction error")?),
+    };
+
+    let inner_instructions = if meta.inner_instructions_none {
+        None
+    } else {
+        Some(
+            meta.inner_instructions
+                .into_iter()
+                .map(inner_instructions)
+                .collect::<anyhow::Result<Vec<_>>>()?,
+        )
+    };
+    let log_messages = (!meta.log_messages_none).then_some(meta.log_messages);
+    let return_data = if meta.return_data_none {
+        None
+    } else {
+        meta.return_data
+            .map(|return_data| {
+                Ok::<_, anyhow::Error>(TransactionReturnData {
+                    program_id: pubkey(&return_data.program_id)?,
+                    data: return_data.data,
+                })
+            })
+            .transpose()?
+    };
+
+    Ok(TransactionStatusMeta {
+        status,
+        fee: meta.fee,
+        pre_balances: meta.pre_balances,
+        post_balances: meta.post_balances,
+        inner_instructions,
+        log_messages,
+        pre_token_balances: Some(
+            meta.pre_token_balances
+                .into_iter()
+                .map(token_balance)
+                .collect::<anyhow::Result<Vec<_>>>()?,
+        ),
+        post_token_balances: Some(
+            meta.post_token_balances
+                .into_iter()
+                .map(token_balance)
+                .collect::<anyhow::Result<Vec<_>>>()?,
+        ),
+        rewards: Some(meta.rewards.into_iter().map(reward).collect()),
+        loaded_addresses: LoadedAddresses {
+            writable: meta
+                .loaded_writable_addresses
+                .iter()
+                .map(|key| pubkey(key))
+                .collect::<anyhow::Result<Vec<_>>>()?,
+            readonly: meta
+                .loaded_readonly_addresses
+                .iter()
+                .map(|key| pubkey(key))
+                .collect::<anyhow::Result<Vec<_>>>()?,
+        },
+        return_data,
+        compute_units_consumed: meta.compute_units_consumed,
+    })
+}
+
+fn inner_instructions(inner: proto::InnerInstructions) -> anyhow::Result<InnerInstructions> {
+    Ok(InnerInstructions {
+        index: u8::try_from(inner.index)?,
+        instructions: inner
+            .instructions
+            .into_iter()
+            .map(|ix| {
+                Ok(InnerInstruction {
+                    instruction: CompiledInstruction {
+                        program_id_index: u8::try_from(ix.program_id_index)?,
+                        accounts: ix.accounts,
+                        data: ix.data,
+                    },
+                    stack_height: ix.stack_height,
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?,
+    })
+}
+
+fn token_balance(balance: proto::TokenBalance) -> anyhow::Result<TransactionTokenBalance> {
+    let ui_token_amount = balance
+        .ui_token_amount
+        .ok_or_else(|| anyhow!("ui token amount missing"))?;
+    Ok(TransactionTokenBalance {
+        account_index: u8::try_from(balance.account_index)?,
+        mint: balance.mint,
+        ui_token_amount: UiTokenAmount {
+            ui_amount: Some(ui_token_amount.ui_amount),
+            decimals: u8::try_from(ui_token_amount.decimals)?,
+            amount: ui_token_amount.amount,
+            ui_amount_string: ui_token_amount.ui_amount_string,
+        },
+        owner: balance.owner,
+        program_id: balance.program_id,
+    })
+}
+
+fn reward(reward: proto::Reward) -> Reward {
+    Reward {
+        pubkey: reward.pubkey,
+        lamports: reward.lamports,
+        post_balance: reward.post_balance,
+        // numbering of the protobuf enum; 0 is unspecified
+        reward_type: match reward.reward_type {
+            1 => Some(RewardType::Fee),
+            2 => Some(RewardType::Rent),
+            3 => Some(RewardType::Staking),
+            4 => Some(RewardType::Voting),
+            _ => None,
+        },
+        commission: reward.commission.parse().ok(),
+    }
+}
+
+fn pubkey(bytes: &[u8]) -> anyhow::Result<Pubkey> {
+    match <[u8; 32]>::try_from(bytes) {
+        Ok(array) => Ok(Pubkey::new_from_array(array)),
+        Err(_) => bail!("invalid pubkey of length {}", bytes.len()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transaction_info(versioned: bool) -> SubscribeUpdateTransactionInfo {
+        SubscribeUpdateTransactionInfo {
+            signature: vec![1; 64],
+            is_vote: false,
+            transaction: Some(proto::Transaction {
+                signatures: vec![vec![1; 64]],
+                message: Some(proto::Message {
+                    header: Some(proto::MessageHeader {
+                        num_required_signatures: 1,
+                        num_readonly_signed_accounts: 0,
+                        num_readonly_unsigned_accounts: 1,
+                    }),
+                    account_keys: vec![vec![2; 32], vec![3; 32]],
+                    recent_blockhash: vec![4; 32],
+                    instructions: vec![proto::CompiledInstruction {
+                        program_id_index: 1,
+                        accounts: vec![0],
+                        data: vec![9, 9],
+                    }],
+                    versioned,
+                    address_table_lookups: vec![],
+                }),
+            }),
+            meta: Some(proto::TransactionStatusMeta {
+                fee: 5000,
+                pre_balances: vec![10_000, 1],
+                post_balances: vec![5000, 1],
+                log_messages_none: true,
+                return_data_none: true,
+                compute_units_consumed: Some(150),
+                ..Default::default()
+            }),
+            index: 0,
+        }
+    }
+
+    #[test]
+    fn test_convert_block() {
+        let block = SubscribeUpdateBlock {
+            slot: 42,
+            blockhash: "hash".to_string(),
+            parent_slot: 41,
+            parent_blockhash: "parent-hash".to_string(),
+            executed_transaction_count: 2,
+            transactions: vec![transaction_info(false), transaction_info(true)],
+            rewards: Some(proto::Rewards {
+                rewards: vec![proto::Reward {
+                    pubkey: "leader".to_string(),
+                    lamports: 2500,
+                    post_balance: 100_000,
+                    reward_type: 1,
+                    commission: "".to_string(),
+                }],
+            }),
+            ..Default::default()
+        };
+
+        let confirmed_block = VersionedConfirmedBlock::try_from(GeyserBlock(block)).unwrap();
+        assert_eq!(confirmed_block.parent_slot, 41);
+        assert_eq!(confirmed_block.previous_blockhash, "parent-hash");
+        assert_eq!(
+            confirmed_block.rewards[0].reward_type,
+            Some(RewardType::Fee)
+        );
+        assert_eq!(confirmed_block.rewards[0].commission, None);
+
+        let legacy = &confirmed_block.transactions[0];
+        assert!(matches!(
+            legacy.transaction.message,
+            VersionedMessage::Legacy(_)
+        ));
+        assert_eq!(legacy.meta.fee, 5000);
+        assert!(legacy.meta.status.is_ok());
+        assert_eq!(legacy.meta.log_messages, None);
+        assert_eq!(legacy.meta.compute_units_consumed, Some(150));
+        assert!(matches!(
+            confirmed_block.transactions[1].transaction.message,
+            VersionedMessage::V0(_)
+        ));
+    }
+
+    #[test]
+    fn test_encode_ui_block_json() {
+        let block = || SubscribeUpdateBlock {
+            slot: 42,
+            blockhash: "hash".to_string(),
+            parent_slot: 41,
+            transactions: vec![transaction_info(false)],
+            ..Default::default()
+        };
+
+        let json =
+            encode_ui_block_json(GeyserBlock(block()), &UiBlockEncodingConfig::default()).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["blockhash"], "hash");
+        assert_eq!(value["parentSlot"], 41);
+        assert_eq!(value["transactions"][0]["transaction"][1], "base64");
+        assert_eq!(value["transactions"][0]["meta"]["fee"], 5000);
+
+        let json_parsed = UiBlockEncodingConfig {
+            encoding: UiTransactionEncoding::JsonParsed,
+            ..Default::default()
+        };
+        let value: serde_json::Value = serde_json::from_str(
+            &encode_ui_block_json(GeyserBlock(block()), &json_parsed).unwrap(),
+        )
+        .unwrap();
+        assert!(value["transactions"][0]["transaction"]["message"]["accountKeys"].is_array());
+    }
+
+    #[test]
+    fn test_invalid_pubkey() {
+        let mut info = transaction_info(false);
+        info.transaction
+            .as_mut()
+            .unwrap()
+            .message
+            .as_mut()
+            .unwrap()
+            .account_keys = vec![vec![2; 31]];
+        assert!(versioned_transaction_with_meta(info).is_err());
+    }
+}
@@ -0,0 +1,133 @@
+use crate::Slot;
+use async_stream::stream;
+use futures::Stream;
+use log::warn;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use yellowstone_grpc_proto::tonic::{Code, Status};
+
+/// events of a single source besides the payload, see ``create_geyser_reconnecting_stream_with_events``
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SourceEvent {
+    // an update exceeded max_decoding_message_size and got dropped; the subscription is restarted
+    // after ``OVERSIZED_RESUBSCRIBE_DELAY``, repeated ones fall back to the reconnect policy
+    OversizedUpdate {
+        source: String,
+        // last slot received before; the dropped update is most likely the next one
+        slot: Option<Slot>,
+        size: Option<usize>,
+        limit: Option<usize>,
+    },
+    // the source failed and gets reconnected according to the reconnect policy
+    Error(SourceError),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorPhase {
+    // connect or subscribe call failed
+    Subscribe,
+    // established subscription returned an error
+    Stream,
+}
+
+/// failure of a source with the grpc status details
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SourceError {
+    pub source: String,
+    pub phase: ErrorPhase,
+    pub code: Code,
+    pub message: String,
+}
+
+impl SourceError {
+    pub fn from_status(source: String, phase: ErrorPhase, status: &Status) -> Self {
+        SourceError {
+            source,
+            phase,
+            code: status.code(),
+            message: status.message().to_string(),
+        }
+    }
+
+    /// provider rejects because of rate or connection limits
+    pub fn is_throttling(&self) -> bool {
+        self.code == Code::ResourceExhausted
+    }
+
+    /// token missing, invalid or expired
+    pub fn is_auth_failure(&self) -> bool {
+        matches!(self.code, Code::Unauthenticated | Code::PermissionDenied)
+    }
+}
+
+/// only the errors from a channel returned by ``create_geyser_reconnecting_stream_with_events``
+pub fn source_errors(
+    mut events: broadcast::Receiver<SourceEvent>,
+) -> impl Stream<Item = SourceError> {
+    stream! {
+        loop {
+            match events.recv().await {
+                Ok(SourceEvent::Error(source_error)) => yield source_error,
+                Ok(_) => {}
+                Err(RecvError::Lagged(n_missed)) => {
+                    warn!("source error stream lagged {} events", n_missed);
+                }
+                Err(RecvError::Closed) => return,
+            }
+        }
+    }
+}
+
+/// wait before resubscribing after an oversized update
+pub(crate) const OVERSIZED_RESUBSCRIBE_DELAY: Duration = Duration::from_millis(200);
+/// consecutive oversized updates after which the reconnect policy takes over, e.g. a limit
+/// too low for every block
+pub(crate) const MAX_CONSECUTIVE_OVERSIZED: u32 = 3;
+
+/// size and limit of a tonic "message length too large" error
+pub(crate) fn parse_oversized_status(status: &Status) -> Option<(Option<usize>, Option<usize>)> {
+    // Error, message length too large: found 5000000 bytes, the limit is: 4194304 bytes
+    if status.code() != Code::OutOfRange || !status.message().contains("message length too large") {
+        return None;
+    }
+    let mut numbers = status
+        .message()
+        .split(|c: char| !c.is_ascii_digit())
+        .filter(|part| !part.is_empty())
+        .map(|part| part.parse::<usize>().ok());
+    Some((numbers.next().flatten(), numbers.next().flatten()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_source_error_from_status() {
+        let error = SourceError::from_status(
+            "green".to_string(),
+            ErrorPhase::Stream,
+            &Status::resource_exhausted("too many connections"),
+        );
+        assert!(error.is_throttling());
+        assert!(!error.is_auth_failure());
+        assert_eq!(error.message, "too many connections");
+    }
+
+    #[test]
+    fn test_parse_oversized_status() {
+        let status = Status::new(
+            Code::OutOfRange,
+            "Error, message length too large: found 5000000 bytes, the limit is: 4194304 bytes",
+        );
+        assert_eq!(
+            parse_oversized_status(&status),
+            Some((Some(5000000), Some(4194304)))
+        );
+        assert_eq!(
+            parse_oversized_status(&Status::unavailable("connection reset")),
+            None
+        );
+    }
+}
@@ -0,0 +1,100 @@
+//! ``/slots`` and ``/blocks`` as server-sent events with json data, see ``JsonFrame``
+
+use crate::grpc_stream_utils::AbortOnDrop;
+use crate::json_frames::encode_frames;
+use crate::Message;
+use async_stream::stream;
+use axum::extract::State;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::routing::get;
+use axum::Router;
+use futures::Stream;
+use log::{info, warn};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::JoinHandle;
+
+const FRAMES_CAPACITY: usize = 1000;
+
+#[derive(Clone)]
+struct SseState {
+    slots: broadcast::Sender<Arc<str>>,
+    blocks: broadcast::Sender<Arc<str>>,
+    // encoders stop once the router and all its clones are dropped
+    _encoders: Arc<[AbortOnDrop<()>; 2]>,
+}
+
+/// router to embed into an existing axum app; ``slots`` and ``blocks`` are e.g. receivers from
+/// ``channelize_stream`` of slot and blocks (meta) streams; the encoding tasks live as long as
+/// the router
+pub fn sse_router(
+    slots: broadcast::Receiver<Message>,
+    blocks: broadcast::Receiver<Message>,
+) -> Router {
+    let slots_tx = broadcast::channel(FRAMES_CAPACITY).0;
+    let blocks_tx = broadcast::channel(FRAMES_CAPACITY).0;
+    let encoders = [
+        AbortOnDrop(tokio::spawn(encode_frames(slots, slots_tx.clone()))),
+        AbortOnDrop(tokio::spawn(encode_frames(blocks, blocks_tx.clone()))),
+    ];
+    let state = SseState {
+        slots: slots_tx,
+        blocks: blocks_tx,
+        _encoders: Arc::new(encoders),
+    };
+
+    Router::new()
+        .route("/slots", get(slot_events))
+        .route("/blocks", get(block_events))
+        .with_state(state)
+}
+
+/// serve ``sse_router`` on the given address; aborting the returned handle stops the encoding too
+pub fn spawn_sse_server(
+    addr: SocketAddr,
+    slots: broadcast::Receiver<Message>,
+    blocks: broadcast::Receiver<Message>,
+) -> JoinHandle<anyhow::Result<()>> {
+    let router = sse_router(slots, blocks);
+    tokio::spawn(async move {
+        info!("serving server-sent events on {}", addr);
+        axum::Server::try_bind(&addr)?
+            .serve(router.into_make_service())
+            .await?;
+        Ok(())
+    })
+}
+
+async fn slot_events(
+    State(state): State<SseState>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    events("slot", state.slots.subscribe())
+}
+
+async fn block_events(
+    State(state): State<SseState>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    events("block", state.blocks.subscribe())
+}
+
+fn events(
+    event_name: &'static str,
+    mut frames: broadcast::Receiver<Arc<str>>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let event_stream = stream! {
+        loop {
+            match frames.recv().await {
+                Ok(frame) => yield Ok(Event::default().event(event_name).data(frame.as_ref())),
+                // sse clients reconnect on their own; skip what was missed
+                Err(RecvError::Lagged(n_missed)) => {
+                    warn!("sse client lagged {} {} events", n_missed, event_name);
+                }
+                Err(RecvError::Closed) => return,
+            }
+        }
+    };
+    Sse::new(event_stream).keep_alive(KeepAlive::default())
+}
@@ -0,0 +1,328 @@
+use crate::grpc_subscription_autoreconnect_streams::create_geyser_reconnecting_stream;
+use crate::shared_connection::{matches_filter, merge_subscribe_requests, receiver_stream};
+use crate::{GrpcSourceConfig, Message};
+use anyhow::bail;
+use futures::future::{self, Either};
+use futures::{Stream, StreamExt};
+use log::{debug, info};
+use std::collections::BTreeMap;
+use std::pin::pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinHandle;
+use yellowstone_grpc_proto::geyser::SubscribeRequest;
+
+const CONSUMER_CAPACITY: usize = 1000;
+
+struct Consumer {
+    request: SubscribeRequest,
+    // one sender per source
+    senders: Vec<mpsc::Sender<Message>>,
+    // messages dropped because the consumer did not keep up
+    dropped: Arc<AtomicU64>,
+}
+
+// where one source sends the messages of one consumer
+struct Route {
+    name: String,
+    sender: mpsc::Sender<Message>,
+    dropped: Arc<AtomicU64>,
+}
+
+struct Shared {
+    consumers: Mutex<BTreeMap<String, Consumer>>,
+    // bumped whenever a consumer is added or removed
+    generation: watch::Sender<u64>,
+}
+
+impl Shared {
+    fn remove(&self, name: &str) -> bool {
+        let removed = self.consumers.lock().unwrap().remove(name).is_some();
+        if removed {
+            self.generation.send_modify(|generation| *generation += 1);
+        }
+        removed
+    }
+
+    // merged request and the routes of one source; None if there are no consumers
+    fn snapshot(&self, source_idx: usize) -> Option<(SubscribeRequest, Vec<Route>)> {
+        let consumers = self.consumers.lock().unwrap();
+        if consumers.is_empty() {
+            return None;
+        }
+        let named_filters = consumers
+            .iter()
+            .map(|(name, consumer)| (name.clone(), consumer.request.clone()))
+            .collect();
+        // compatibility was checked on register
+        let request = merge_subscribe_requests(named_filters).ok()?;
+        let routes = consumers
+            .iter()
+            .map(|(name, consumer)| Route {
+                name: name.clone(),
+                sender: consumer.senders[source_idx].clone(),
+                dropped: consumer.dropped.clone(),
+            })
+            .collect();
+        Some((request, routes))
+    }
+}
+
+/// one connection per source shared by many in-process consumers with their own filters
+///
+/// the filters of all consumers are merged into one upstream request per source and updates are
+/// routed back by filter name; note: registering or removing a consumer resubscribes all sources
+///
+/// a consumer which does not keep up misses messages instead of stalling the others, see ``dropped``
+pub struct SubscriptionManager {
+    shared: Arc<Shared>,
+    jh_sources: Vec<JoinHandle<()>>,
+}
+
+impl SubscriptionManager {
+    pub fn new(grpc_sources: Vec<GrpcSourceConfig>) -> Self {
+        let shared = Arc::new(Shared {
+            consumers: Mutex::new(BTreeMap::new()),
+            generation: watch::channel(0).0,
+        });
+        let jh_sources = grpc_sources
+            .into_iter()
+            .enumerate()
+            .map(|(source_idx, grpc_source)| {
+                tokio::spawn(run_source(source_idx, grpc_source, shared.clone()))
+            })
+            .collect();
+        SubscriptionManager { shared, jh_sources }
+    }
+
+    /// returns one stream per source, e.g. to feed ``create_multiplexed_stream``; the consumer is
+    /// removed once all its streams are dropped or with ``unregister``
+    pub fn register(
+        &self,
+        name: impl Into<String>,
+        request: SubscribeRequest,
+    ) -> anyhow::Result<Vec<impl Stream<Item = Message>>> {
+        let name = name.into();
+        if name.contains(':') {
+            bail!("consumer name {} must not contain ':'", name);
+        }
+        let mut consumers = self.shared.consumers.lock().unwrap();
+        if consumers.contains_key(&name) {
+            bail!("consumer {} is already registered", name);
+        }
+        let mut named_filters: Vec<(String, SubscribeRequest)> = consumers
+            .iter()
+            .map(|(name, consumer)| (name.clone(), consumer.request.clone()))
+            .collect();
+        named_filters.push((name.clone(), request.clone()));
+        merge_subscribe_requests(named_filters)?;
+
+        let (senders, receivers): (Vec<_>, Vec<_>) = self
+            .jh_sources
+            .iter()
+            .map(|_| mpsc::channel(CONSUMER_CAPACITY))
+            .unzip();
+        consumers.insert(
+            name,
+            Consumer {
+                request,
+                senders,
+                dropped: Arc::new(AtomicU64::new(0)),
+            },
+        );
+        drop(consumers);
+        self.shared
+            .generation
+            .send_modify(|generation| *generation += 1);
+
+        Ok(receivers.into_iter().map(receiver_stream).collect())
+    }
+
+    /// returns false if no consumer with that name is registered
+    pub fn unregister(&self, name: &str) -> bool {
+        self.shared.remove(name)
+    }
+
+    /// messages the consumer missed because its buffer was full
+    pub fn dropped(&self, name: &str) -> Option<u64> {
+        self.shared
+            .consumers
+            .lock()
+            .unwrap()
+            .get(name)
+            .map(|consumer| consumer.dropped.load(Ordering::Relaxed))
+    }
+
+    pub fn consumers(&self) -> Vec<String> {
+        self.shared
+            .consumers
+            .lock()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect()
+    }
+}
+
+impl Drop for SubscriptionManager {
+    fn drop(&mut self) {
+        for jh_source in &self.jh_sources {
+            jh_source.abort();
+        }
+    }
+}
+
+async fn run_source(source_idx: usize, grpc_source: GrpcSourceConfig, shared: Arc<Shared>) {
+    let mut generation = shared.generation.subscribe();
+    loop {
+        generation.borrow_and_update();
+        let Some((request, routes)) = shared.snapshot(source_idx) else {
+            debug!("no consumers for {} - waiting", grpc_source);
+            if generation.changed().await.is_err() {
+                return;
+            }
+            continue;
+        };
+        info!("subscribing {} consumers on {}", routes.len(), grpc_source);
+
+        let mut geyser_stream = pin!(create_geyser_reconnecting_stream(
+            grpc_source.clone(),
+            request
+        ));
+        loop {
+            let next_event = {
+                let next_message = geyser_stream.next();
+                let consumers_changed = generation.changed();
+                match future::select(pin!(next_message), pin!(consumers_changed)).await {
+                    Either::Left((message, _)) => Either::Left(message),
+                    Either::Right((changed, _)) => Either::Right(changed.is_ok()),
+                }
+            };
+            let message = match next_event {
+                Either::Left(Some(message)) => message,
+                Either::Left(None) | Either::Right(false) => return,
+                // resubscribe with the new set of filters
+                Either::Right(true) => break,
+            };
+            route_message(&shared, &routes, &message);
+        }
+    }
+}
+
+// never waits for a consumer, a full buffer drops the message for that consumer only
+fn route_message(shared: &Shared, routes: &[Route], message: &Message) {
+    for route in routes {
+        let routed = match message {
+            Message::GeyserSubscribeUpdate(update) => matches_filter(&update.filters, &route.name),
+            Message::Connecting(_) => true,
+        };
+        if !routed {
+            continue;
+        }
+        match route.sender.try_send(message.clone()) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                let dropped = route.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+                debug!(
+                    "consumer {} is lagging - dropped {} messages so far",
+                    route.name, dropped
+                );
+            }
+            Err(TrySendError::Closed(_)) => {
+                if routes_closed(shared, &route.name) {
+                    info!("all streams of consumer {} dropped - removing", route.name);
+                    shared.remove(&route.name);
+                }
+            }
+        }
+    }
+}
+
+fn routes_closed(shared: &Shared, name: &str) -> bool {
+    shared
+        .consumers
+        .lock()
+        .unwrap()
+        .get(name)
+        .map_or(false, |consumer| {
+            consumer.senders.iter().all(|sender| sender.is_closed())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commitment::CommitmentConfig;
+    use crate::simulation::slot_update;
+    use crate::GeyserFilter;
+
+    #[test]
+    fn test_stalled_consumer_does_not_block_others() {
+        let shared = Shared {
+            consumers: Mutex::new(BTreeMap::new()),
+            generation: watch::channel(0).0,
+        };
+        let (stalled_tx, _stalled_rx) = mpsc::channel(1);
+        let (healthy_tx, mut healthy_rx) = mpsc::channel(10);
+        let routes: Vec<Route> = [("stalled", stalled_tx), ("healthy", healthy_tx)]
+            .into_iter()
+            .map(|(name, sender)| Route {
+                name: name.to_string(),
+                sender,
+                dropped: Arc::new(AtomicU64::new(0)),
+            })
+            .collect();
+
+        for slot in 0..5 {
+            let mut update = slot_update(slot);
+            update.filters = vec!["stalled:slots".to_string(), "healthy:slots".to_string()];
+            route_message(
+                &shared,
+                &routes,
+                &Message::GeyserSubscribeUpdate(Box::new(update)),
+            );
+        }
+
+        assert_eq!(routes[0].dropped.load(Ordering::Relaxed), 4);
+        assert_eq!(routes[1].dropped.load(Ordering::Relaxed), 0);
+        let mut received = 0;
+        while healthy_rx.try_recv().is_ok() {
+            received += 1;
+        }
+        assert_eq!(received, 5);
+    }
+
+    #[tokio::test]
+    async fn test_register_validation() {
+        let manager = SubscriptionManager::new(vec![]);
+        manager
+            .register(
+                "blocks",
+                GeyserFilter(CommitmentConfig::confirmed()).blocks_meta(),
+            )
+            .unwrap();
+
+        assert!(manager
+            .register(
+                "blocks",
+                GeyserFilter(CommitmentConfig::confirmed()).slots()
+            )
+            .is_err());
+        assert!(manager
+            .register("slots", GeyserFilter(CommitmentConfig::finalized()).slots())
+            .is_err());
+        assert!(manager
+            .register("a:b", GeyserFilter(CommitmentConfig::confirmed()).slots())
+            .is_err());
+        manager
+            .register("slots", GeyserFilter(CommitmentConfig::confirmed()).slots())
+            .unwrap();
+        assert_eq!(manager.consumers(), vec!["blocks", "slots"]);
+
+        assert!(manager.unregister("blocks"));
+        assert!(!manager.unregister("blocks"));
+        assert_eq!(manager.consumers(), vec!["slots"]);
+    }
+}
@@ -0,0 +1,273 @@
+use crate::grpc_stream_utils::{forward_to_broadcast, AbortOnDrop};
+use crate::reconnect_policy::{ReconnectDecision, ReconnectPolicy};
+use crate::Attempt;
+use futures::Stream;
+use log::{error, warn};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{broadcast, watch};
+use tokio::task::JoinHandle;
+use tokio::time::sleep;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TaskState {
+    Running,
+    // panicked, restart is pending
+    Restarting { panics: Attempt },
+    // the task returned normally
+    Finished,
+    // the handle returned by ``spawn`` was aborted
+    Cancelled,
+    // reconnect policy gave up after the last panic
+    Failed { panics: Attempt },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TaskHealth {
+    pub name: String,
+    pub state: TaskState,
+}
+
+/// owns tasks spawned on behalf of the application (e.g. channelizers); a panicking task is
+/// restarted according to the reconnect policy instead of dying silently
+///
+/// cheap to clone
+#[derive(Clone)]
+pub struct TaskSupervisor {
+    tasks: Arc<Mutex<Vec<TaskHealth>>>,
+    // bumped on every state change; watch::Sender is not Clone before tokio 1.37
+    changed: Arc<watch::Sender<u64>>,
+    restart_policy: Arc<dyn ReconnectPolicy>,
+}
+
+impl TaskSupervisor {
+    pub fn new(restart_policy: impl ReconnectPolicy) -> Self {
+        TaskSupervisor {
+            tasks: Arc::new(Mutex::new(vec![])),
+            changed: Arc::new(watch::channel(0).0),
+            restart_policy: Arc::new(restart_policy),
+        }
+    }
+
+    /// run the future created by ``task_factory``; creates a new one after a panic
+    /// aborting the returned handle also aborts the running future
+    pub fn spawn<F, Fut>(&self, name: impl Into<String>, mut task_factory: F) -> JoinHandle<()>
+    where
+        F: FnMut() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let name = name.into();
+        let task_idx = {
+            let mut tasks = self.tasks.lock().unwrap();
+            tasks.push(TaskHealth {
+                name: name.clone(),
+                state: TaskState::Running,
+            });
+            tasks.len() - 1
+        };
+        self.notify();
+
+        let supervisor = self.clone();
+        tokio::spawn(async move {
+            // marks the task cancelled if this future is dropped, i.e. the handle was aborted
+            let _cancel_guard = CancelGuard {
+                supervisor: supervisor.clone(),
+                task_idx,
+            };
+            let mut panics = 0;
+            loop {
+                supervisor.set_state(task_idx, TaskState::Running);
+                let join_error = match AbortOnDrop(tokio::spawn(task_factory())).await {
+                    Ok(()) => {
+                        supervisor.set_state(task_idx, TaskState::Finished);
+                        return;
+                    }
+                    Err(join_error) => join_error,
+                };
+                if !join_error.is_panic() {
+                    // cancelled from outside - nothing to restart
+                    supervisor.set_state(task_idx, TaskState::Cancelled);
+                    return;
+                }
+                panics += 1;
+                match supervisor.restart_policy.on_disconnect(panics) {
+                    ReconnectDecision::Retry { wait, .. } => {
+                        warn!(
+                            "task {} panicked ({}) - restart #{} in {:?}",
+                            name, join_error, panics, wait
+                        );
+                        supervisor.set_state(task_idx, TaskState::Restarting { panics });
+                        sleep(wait).await;
+                    }
+                    ReconnectDecision::GiveUp => {
+                        error!("task {} panicked ({}) - giving up", name, join_error);
+                        supervisor.set_state(task_idx, TaskState::Failed { panics });
+                        return;
+                    }
+                }
+            }
+        })
+    }
+
+    /// like ``channelize_stream`` but the forwarding task is supervised; after a panic the
+    /// stream is recreated by the factory while receivers stay attached
+    pub fn channelize_stream<T, S, F>(
+        &self,
+        name: impl Into<String>,
+        mut stream_factory: F,
+        capacity: usize,
+    ) -> broadcast::Receiver<T>
+    where
+        T: Clone + Send + 'static,
+        S: Stream<Item = T> + Send + 'static,
+        F: FnMut() -> S + Send + 'static,
+    {
+        let (tx, rx) = broadcast::channel(capacity);
+        self.spawn(name, move || {
+            let source_stream = stream_factory();
+            let tx = tx.clone();
+            async move {
+                forward_to_broadcast(source_stream, &tx).await;
+                warn!("source stream ended - shutting down channelizer");
+            }
+        });
+        rx
+    }
+
+    pub fn health(&self) -> Vec<TaskHealth> {
+        self.tasks.lock().unwrap().clone()
+    }
+
+    /// resolves with the first task the supervisor gave up on
+    pub fn failed(&self) -> impl Future<Output = TaskHealth> + Send + 'static {
+        let tasks = self.tasks.clone();
+        let mut changed = self.changed.subscribe();
+        async move {
+            loop {
+                let failed = tasks
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .find(|task| matches!(task.state, TaskState::Failed { .. }))
+                    .cloned();
+                if let Some(failed) = failed {
+                    return failed;
+                }
+                if changed.changed().await.is_err() {
+                    // supervisor dropped: no task can fail anymore
+                    futures::future::pending::<()>().await;
+                }
+            }
+        }
+    }
+
+    fn set_state(&self, task_idx: usize, state: TaskState) {
+        self.tasks.lock().unwrap()[task_idx].state = state;
+        self.notify();
+    }
+
+    fn notify(&self) {
+        self.changed.send_modify(|generation| *generation += 1);
+    }
+}
+
+struct CancelGuard {
+    supervisor: TaskSupervisor,
+    task_idx: usize,
+}
+
+impl Drop for CancelGuard {
+    fn drop(&mut self) {
+        let mut tasks = self.supervisor.tasks.lock().unwrap();
+        let state = &mut tasks[self.task_idx].state;
+        if matches!(state, TaskState::Running | TaskState::Restarting { .. }) {
+            *state = TaskState::Cancelled;
+            drop(tasks);
+            self.supervisor.notify();
+        }
+    }
+}
+
+impl Default for TaskSupervisor {
+    fn default() -> Self {
+        Self::new(crate::reconnect_policy::ExponentialBackoff::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reconnect_policy::FixedDelay;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_restart_after_panic() {
+        let supervisor = TaskSupervisor::new(FixedDelay {
+            wait: Duration::from_millis(1),
+            max_attempts: None,
+            rotate_endpoint: false,
+        });
+        let runs = Arc::new(AtomicU32::new(0));
+        let task_runs = runs.clone();
+        let jh_task = supervisor.spawn("flaky", move || {
+            let run = task_runs.fetch_add(1, Ordering::Relaxed) + 1;
+            async move {
+                if run < 3 {
+                    panic!("run {} failed", run);
+                }
+            }
+        });
+        jh_task.await.unwrap();
+
+        assert_eq!(runs.load(Ordering::Relaxed), 3);
+        assert_eq!(
+            supervisor.health(),
+            vec![TaskHealth {
+                name: "flaky".to_string(),
+                state: TaskState::Finished,
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_abort_cancels_inner_task() {
+        let supervisor = TaskSupervisor::default();
+        let (dropped_tx, dropped_rx) = tokio::sync::oneshot::channel::<()>();
+        let mut dropped_tx = Some(dropped_tx);
+        let jh_task = supervisor.spawn("endless", move || {
+            // dropping the future drops the sender
+            let dropped_tx = dropped_tx.take();
+            async move {
+                let _dropped_tx = dropped_tx;
+                futures::future::pending::<()>().await;
+            }
+        });
+        tokio::task::yield_now().await;
+        jh_task.abort();
+
+        tokio::time::timeout(Duration::from_secs(1), dropped_rx)
+            .await
+            .expect("inner task aborted")
+            .unwrap_err();
+        assert_eq!(supervisor.health()[0].state, TaskState::Cancelled);
+    }
+
+    #[tokio::test]
+    async fn test_failed_resolves_on_give_up() {
+        let supervisor = TaskSupervisor::new(FixedDelay {
+            wait: Duration::from_millis(1),
+            max_attempts: Some(2),
+            rotate_endpoint: false,
+        });
+        let failed = supervisor.failed();
+        supervisor.spawn("healthy", futures::future::pending);
+        supervisor.spawn("broken", || async { panic!("always") });
+
+        let failed = tokio::time::timeout(Duration::from_secs(1), failed)
+            .await
+            .expect("failure reported");
+        assert_eq!(failed.name, "broken");
+        assert_eq!(failed.state, TaskState::Failed { panics: 2 });
+    }
+}
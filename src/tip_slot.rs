@@ -0,0 +1,91 @@
+use crate::commitment::CommitmentConfig;
+use crate::grpc_subscription_autoreconnect_streams::create_geyser_reconnecting_stream;
+use crate::grpcmultiplex_fastestwins::{create_multiplexed_stream, FromYellowstoneExtractor};
+use crate::{GeyserFilter, GrpcSourceConfig, Message, Slot};
+use futures::{Stream, StreamExt};
+use log::warn;
+use std::pin::pin;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use yellowstone_grpc_proto::geyser::subscribe_update::UpdateOneof;
+use yellowstone_grpc_proto::geyser::SubscribeUpdate;
+
+/// extracts the slot number of slot updates
+pub struct SlotExtractor;
+
+impl FromYellowstoneExtractor for SlotExtractor {
+    type Target = Slot;
+    fn map_yellowstone_update(&self, update: SubscribeUpdate) -> Option<(Slot, Self::Target)> {
+        self.try_map_yellowstone_update(update).ok()
+    }
+
+    fn try_map_yellowstone_update(
+        &self,
+        update: SubscribeUpdate,
+    ) -> Result<(Slot, Self::Target), SubscribeUpdate> {
+        match update.update_oneof {
+            Some(UpdateOneof::Slot(update_slot)) => Ok((update_slot.slot, update_slot.slot)),
+            update_oneof => Err(SubscribeUpdate {
+                update_oneof,
+                ..update
+            }),
+        }
+    }
+}
+
+/// highest slot of the commitment level seen on any source; starts with 0 until the first slot arrives
+pub fn spawn_tip_slot_watch(
+    grpc_sources: Vec<GrpcSourceConfig>,
+    commitment: CommitmentConfig,
+) -> (watch::Receiver<Slot>, JoinHandle<()>) {
+    let streams = grpc_sources
+        .into_iter()
+        .map(|grpc_source| {
+            create_geyser_reconnecting_stream(grpc_source, GeyserFilter(commitment).slots())
+        })
+        .collect();
+    spawn_tip_slot_watch_from_streams(streams)
+}
+
+/// like ``spawn_tip_slot_watch`` for streams subscribed with ``GeyserFilter::slots``
+pub fn spawn_tip_slot_watch_from_streams(
+    slot_streams: Vec<impl Stream<Item = Message> + Send + 'static>,
+) -> (watch::Receiver<Slot>, JoinHandle<()>) {
+    let (tip_tx, tip_rx) = watch::channel(0);
+    let jh_tip = tokio::spawn(async move {
+        // multiplexer yields strictly increasing slots
+        let mut slots = pin!(create_multiplexed_stream(slot_streams, SlotExtractor));
+        while let Some(slot) = slots.next().await {
+            if tip_tx.send(slot).is_err() {
+                // all receivers dropped
+                return;
+            }
+        }
+        warn!("slot streams ended - tip slot watch stops updating");
+    });
+    (tip_rx, jh_tip)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::slot_message;
+    use futures::stream;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_tip_slot_watch() {
+        let fast = stream::iter(vec![slot_message(10), slot_message(12)]).boxed();
+        let slow = stream::iter(vec![slot_message(11), slot_message(13)])
+            .then(|msg| async move {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                msg
+            })
+            .boxed();
+
+        let (mut tip, jh_tip) = spawn_tip_slot_watch_from_streams(vec![fast, slow]);
+        jh_tip.await.unwrap();
+
+        assert_eq!(*tip.borrow_and_update(), 13);
+    }
+}
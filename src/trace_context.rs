@@ -0,0 +1,60 @@
+//! w3c trace context (``traceparent``) in the grpc metadata of a source, see
+//! ``GrpcSourceConfig::with_trace_context``
+
+use opentelemetry::propagation::Injector;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use yellowstone_grpc_proto::tonic::metadata::{AsciiMetadataKey, AsciiMetadataValue, MetadataMap};
+use yellowstone_grpc_proto::tonic::{Request, Status};
+
+struct MetadataInjector<'a>(&'a mut MetadataMap);
+
+impl Injector for MetadataInjector<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        // header names and values of the w3c propagator are valid ascii metadata
+        if let (Ok(key), Ok(value)) = (
+            AsciiMetadataKey::from_bytes(key.as_bytes()),
+            AsciiMetadataValue::try_from(value),
+        ) {
+            self.0.insert(key, value);
+        }
+    }
+}
+
+/// inject the context of the current span with the global text map propagator; the application
+/// installs ``opentelemetry_sdk::propagation::TraceContextPropagator`` to send ``traceparent``
+pub fn inject_trace_context(mut request: Request<()>) -> Result<Request<()>, Status> {
+    let context = tracing::Span::current().context();
+    opentelemetry::global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&context, &mut MetadataInjector(request.metadata_mut()))
+    });
+    Ok(request)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry_sdk::propagation::TraceContextPropagator;
+    use opentelemetry_sdk::trace::TracerProvider;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_inject_traceparent() {
+        opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+        // the tracer only holds a weak reference: dropping the provider yields no-op spans
+        let provider = TracerProvider::builder().build();
+        let tracer = provider.tracer("test");
+        let subscriber =
+            tracing_subscriber::registry().with(tracing_opentelemetry::layer().with_tracer(tracer));
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("geyser_connection");
+            let _entered = span.enter();
+            let request = inject_trace_context(Request::new(())).unwrap();
+            let traceparent = request.metadata().get("traceparent").unwrap();
+            // version 00, sampled
+            assert!(traceparent.to_str().unwrap().starts_with("00-"));
+            assert!(traceparent.to_str().unwrap().ends_with("-01"));
+        });
+    }
+}
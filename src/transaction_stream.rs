@@ -0,0 +1,189 @@
+use crate::grpc_stream_utils::SlotWindowDedup;
+use crate::grpc_subscription_autoreconnect_streams::create_geyser_reconnecting_stream;
+use crate::{GeyserFilter, GrpcSourceConfig, Message, Slot};
+use async_stream::stream;
+use futures::{Stream, StreamExt};
+use merge_streams::MergeStreams;
+use std::pin::pin;
+use yellowstone_grpc_proto::geyser::subscribe_update::UpdateOneof;
+use yellowstone_grpc_proto::geyser::{
+    SubscribeRequestFilterTransactions, SubscribeUpdateTransactionInfo,
+};
+
+// signatures are remembered for that many slots behind the highest slot seen
+const DEDUP_SLOTS: u64 = 150;
+
+/// builder for ``SubscribeRequestFilterTransactions``
+///
+/// ```
+/// use geyser_grpc_connector::transaction_stream::TransactionFilter;
+/// let filter = TransactionFilter::new()
+///     .account_include(["TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA"])
+///     .vote(false)
+///     .failed(false);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct TransactionFilter {
+    filter: SubscribeRequestFilterTransactions,
+}
+
+impl TransactionFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// transaction must mention any of these accounts
+    pub fn account_include<S: Into<String>>(
+        mut self,
+        accounts: impl IntoIterator<Item = S>,
+    ) -> Self {
+        self.filter
+            .account_include
+            .extend(accounts.into_iter().map(Into::into));
+        self
+    }
+
+    /// transaction must not mention any of these accounts
+    pub fn account_exclude<S: Into<String>>(
+        mut self,
+        accounts: impl IntoIterator<Item = S>,
+    ) -> Self {
+        self.filter
+            .account_exclude
+            .extend(accounts.into_iter().map(Into::into));
+        self
+    }
+
+    /// transaction must mention all of these accounts
+    pub fn account_required<S: Into<String>>(
+        mut self,
+        accounts: impl IntoIterator<Item = S>,
+    ) -> Self {
+        self.filter
+            .account_required
+            .extend(accounts.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn vote(mut self, vote: bool) -> Self {
+        self.filter.vote = Some(vote);
+        self
+    }
+
+    pub fn failed(mut self, failed: bool) -> Self {
+        self.filter.failed = Some(failed);
+        self
+    }
+
+    pub fn signature(mut self, signature: impl Into<String>) -> Self {
+        self.filter.signature = Some(signature.into());
+        self
+    }
+
+    pub fn build(self) -> SubscribeRequestFilterTransactions {
+        self.filter
+    }
+}
+
+/// multiplex transactions matching the filter from all sources, each signature once
+pub fn create_transaction_multiplex(
+    grpc_sources: Vec<GrpcSourceConfig>,
+    geyser_filter: GeyserFilter,
+    transaction_filter: TransactionFilter,
+) -> impl Stream<Item = (Slot, SubscribeUpdateTransactionInfo)> {
+    let streams: Vec<_> = grpc_sources
+        .into_iter()
+        .map(|grpc_source| {
+            create_geyser_reconnecting_stream(
+                grpc_source,
+                geyser_filter.transactions_with(transaction_filter.clone()),
+            )
+        })
+        .collect();
+    dedup_transactions(streams.merge())
+}
+
+/// first transaction update per signature wins; updates more than ``DEDUP_SLOTS`` behind the highest
+/// slot are dropped as their signature could be forgotten already
+pub fn dedup_transactions(
+    geyser_stream: impl Stream<Item = Message>,
+) -> impl Stream<Item = (Slot, SubscribeUpdateTransactionInfo)> {
+    stream! {
+        let mut geyser_stream = pin!(geyser_stream);
+        let mut seen = SlotWindowDedup::new(DEDUP_SLOTS);
+        while let Some(message) = geyser_stream.next().await {
+            let Message::GeyserSubscribeUpdate(update) = message else {
+                continue;
+            };
+            let Some(UpdateOneof::Transaction(update)) = update.update_oneof else {
+                continue;
+            };
+            let Some(transaction) = update.transaction else {
+                continue;
+            };
+
+            seen.advance(update.slot);
+            if seen.insert(transaction.signature.clone(), update.slot) {
+                yield (update.slot, transaction);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commitment::CommitmentConfig;
+    use futures::stream;
+    use yellowstone_grpc_proto::geyser::{SubscribeUpdate, SubscribeUpdateTransaction};
+
+    fn transaction(slot: Slot, signature: u8) -> Message {
+        Message::GeyserSubscribeUpdate(Box::new(SubscribeUpdate {
+            filters: vec!["client".to_string()],
+            update_oneof: Some(UpdateOneof::Transaction(SubscribeUpdateTransaction {
+                slot,
+                transaction: Some(SubscribeUpdateTransactionInfo {
+                    signature: vec![signature; 64],
+                    ..Default::default()
+                }),
+            })),
+        }))
+    }
+
+    #[test]
+    fn test_transaction_filter() {
+        let filter = TransactionFilter::new()
+            .account_include(["program"])
+            .account_exclude(vec!["spam".to_string()])
+            .account_required(["signer"])
+            .vote(false);
+        let request = GeyserFilter(CommitmentConfig::confirmed()).transactions_with(filter);
+
+        let transactions = &request.transactions["client"];
+        assert_eq!(transactions.account_include, vec!["program"]);
+        assert_eq!(transactions.account_exclude, vec!["spam"]);
+        assert_eq!(transactions.account_required, vec!["signer"]);
+        assert_eq!(transactions.vote, Some(false));
+        assert_eq!(transactions.failed, None);
+    }
+
+    #[tokio::test]
+    async fn test_dedup_transactions() {
+        let source = stream::iter(vec![
+            transaction(10, 1),
+            transaction(10, 1),
+            transaction(11, 2),
+            Message::Connecting(1),
+            transaction(10, 1),
+            transaction(11 + DEDUP_SLOTS, 3),
+            // late duplicate behind the horizon
+            transaction(10, 1),
+        ]);
+
+        let transactions: Vec<_> = dedup_transactions(source).collect().await;
+
+        assert_eq!(transactions.len(), 3);
+        assert_eq!(transactions[1].0, 11);
+        assert_eq!(transactions[2].0, 11 + DEDUP_SLOTS);
+    }
+}
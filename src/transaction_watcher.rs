@@ -0,0 +1,321 @@
+use crate::grpc_stream_utils::AbortOnDrop;
+use crate::signature_stream::{create_signature_stream, SignatureStatus};
+use crate::GrpcSourceConfig;
+use futures::{Stream, StreamExt};
+use solana_sdk::clock::Slot;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::signature::Signature;
+use std::collections::{BTreeMap, HashMap};
+use std::pin::pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+// statuses seen before the signature got registered are kept for that many slots
+const RECENT_SLOTS: u64 = 150;
+// waiters whose receiver was dropped without ``wait_for`` are removed every that many ``watch`` calls
+const WAITER_PRUNE_INTERVAL: u64 = 256;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Confirmation {
+    Confirmed,
+    Finalized,
+}
+
+#[derive(Default)]
+struct WatcherState {
+    waiters: HashMap<Signature, Vec<(Confirmation, oneshot::Sender<SignatureStatus>)>>,
+    recent: HashMap<(Signature, Confirmation), SignatureStatus>,
+    // keys of ``recent`` by slot, so pruning does not scan all statuses
+    recent_by_slot: BTreeMap<Slot, Vec<(Signature, Confirmation)>>,
+    registrations: u64,
+}
+
+impl WatcherState {
+    fn on_status(&mut self, confirmation: Confirmation, status: SignatureStatus) {
+        if let Some(waiters) = self.waiters.remove(&status.signature) {
+            let (resolved, still_waiting): (Vec<_>, Vec<_>) = waiters
+                .into_iter()
+                .partition(|(target, _)| *target <= confirmation);
+            for (_, sender) in resolved {
+                // receiver might have timed out
+                let _ = sender.send(status.clone());
+            }
+            if !still_waiting.is_empty() {
+                self.waiters.insert(status.signature, still_waiting);
+            }
+        }
+
+        self.prune_recent(status.slot.saturating_sub(RECENT_SLOTS));
+        let key = (status.signature, confirmation);
+        self.recent_by_slot
+            .entry(status.slot)
+            .or_default()
+            .push(key);
+        self.recent.insert(key, status);
+    }
+
+    // only touches the slots below the horizon, i.e. nothing while it does not advance
+    fn prune_recent(&mut self, horizon: Slot) {
+        let retained = self.recent_by_slot.split_off(&horizon);
+        let pruned = std::mem::replace(&mut self.recent_by_slot, retained);
+        for key in pruned.into_values().flatten() {
+            // the key might have been updated with a newer slot
+            if self
+                .recent
+                .get(&key)
+                .map_or(false, |recent| recent.slot < horizon)
+            {
+                self.recent.remove(&key);
+            }
+        }
+    }
+
+    fn prune_closed_waiters(&mut self) {
+        self.waiters.retain(|_, waiters| {
+            waiters.retain(|(_, sender)| !sender.is_closed());
+            !waiters.is_empty()
+        });
+    }
+
+    fn recent_status(
+        &self,
+        signature: &Signature,
+        target: Confirmation,
+    ) -> Option<SignatureStatus> {
+        [Confirmation::Finalized, Confirmation::Confirmed]
+            .into_iter()
+            .filter(|confirmation| *confirmation >= target)
+            .find_map(|confirmation| self.recent.get(&(*signature, confirmation)).cloned())
+    }
+}
+
+/// resolves registered signatures from the multiplexed confirmed and finalized transaction streams
+///
+/// the subscriptions are dropped together with the last clone of the watcher
+#[derive(Clone)]
+pub struct TransactionWatcher {
+    state: Arc<Mutex<WatcherState>>,
+    _feeds: Arc<[AbortOnDrop<()>; 2]>,
+}
+
+impl TransactionWatcher {
+    /// subscribes confirmed and finalized transactions on all sources
+    pub fn spawn(grpc_sources: Vec<GrpcSourceConfig>) -> Self {
+        let confirmed =
+            create_signature_stream(grpc_sources.clone(), CommitmentConfig::confirmed());
+        let finalized = create_signature_stream(grpc_sources, CommitmentConfig::finalized());
+        Self::from_streams(confirmed, finalized)
+    }
+
+    pub fn from_streams(
+        confirmed: impl Stream<Item = SignatureStatus> + Send + 'static,
+        finalized: impl Stream<Item = SignatureStatus> + Send + 'static,
+    ) -> Self {
+        let state = Arc::new(Mutex::new(WatcherState::default()));
+        let feeds = [
+            AbortOnDrop(tokio::spawn(Self::feed(
+                state.clone(),
+                confirmed,
+                Confirmation::Confirmed,
+            ))),
+            AbortOnDrop(tokio::spawn(Self::feed(
+                state.clone(),
+                finalized,
+                Confirmation::Finalized,
+            ))),
+        ];
+        TransactionWatcher {
+            state,
+            _feeds: Arc::new(feeds),
+        }
+    }
+
+    async fn feed(
+        state: Arc<Mutex<WatcherState>>,
+        statuses: impl Stream<Item = SignatureStatus>,
+        confirmation: Confirmation,
+    ) {
+        let mut statuses = pin!(statuses);
+        while let Some(status) = statuses.next().await {
+            state.lock().unwrap().on_status(confirmation, status);
+        }
+    }
+
+    /// resolves once the signature reached at least the given confirmation
+    pub fn watch(
+        &self,
+        signature: Signature,
+        confirmation: Confirmation,
+    ) -> oneshot::Receiver<SignatureStatus> {
+        let (sender, receiver) = oneshot::channel();
+        let mut state = self.state.lock().unwrap();
+        state.registrations += 1;
+        if state.registrations % WAITER_PRUNE_INTERVAL == 0 {
+            state.prune_closed_waiters();
+        }
+        match state.recent_status(&signature, confirmation) {
+            Some(status) => {
+                let _ = sender.send(status);
+            }
+            None => {
+                state
+                    .waiters
+                    .entry(signature)
+                    .or_default()
+                    .push((confirmation, sender));
+            }
+        }
+        receiver
+    }
+
+    /// like ``watch`` but gives up after the timeout; none if the transaction did not land in time
+    pub async fn wait_for(
+        &self,
+        signature: Signature,
+        confirmation: Confirmation,
+        timeout: Duration,
+    ) -> Option<SignatureStatus> {
+        let receiver = self.watch(signature, confirmation);
+        match tokio::time::timeout(timeout, receiver).await {
+            Ok(Ok(status)) => Some(status),
+            _ => {
+                let mut state = self.state.lock().unwrap();
+                if let Some(waiters) = state.waiters.get_mut(&signature) {
+                    waiters.retain(|(_, sender)| !sender.is_closed());
+                    if waiters.is_empty() {
+                        state.waiters.remove(&signature);
+                    }
+                }
+                None
+            }
+        }
+    }
+
+    pub fn pending_count(&self) -> usize {
+        self.state.lock().unwrap().waiters.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::mpsc;
+
+    fn status(slot: Slot, signature: u8) -> SignatureStatus {
+        SignatureStatus {
+            slot,
+            signature: Signature::from([signature; 64]),
+            err: None,
+        }
+    }
+
+    fn channel_stream(
+        mut receiver: mpsc::UnboundedReceiver<SignatureStatus>,
+    ) -> impl Stream<Item = SignatureStatus> {
+        async_stream::stream! {
+            while let Some(status) = receiver.recv().await {
+                yield status;
+            }
+        }
+    }
+
+    #[test]
+    fn test_prune_recent() {
+        let mut state = WatcherState::default();
+        state.on_status(Confirmation::Confirmed, status(10, 1));
+        state.on_status(Confirmation::Confirmed, status(20, 2));
+        // same key again at a later slot
+        state.on_status(Confirmation::Confirmed, status(100, 1));
+        state.on_status(Confirmation::Confirmed, status(165, 3));
+
+        // horizon 15 pruned slot 10 but kept the updated key
+        assert_eq!(state.recent.len(), 3);
+        assert!(state.recent_by_slot.keys().all(|slot| *slot >= 15));
+        assert_eq!(
+            state
+                .recent_status(&Signature::from([1; 64]), Confirmation::Confirmed)
+                .unwrap()
+                .slot,
+            100
+        );
+
+        state.on_status(Confirmation::Confirmed, status(251, 4));
+        assert_eq!(state.recent.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_transaction_watcher() {
+        let (confirmed_tx, confirmed_rx) = mpsc::unbounded_channel();
+        let (finalized_tx, finalized_rx) = mpsc::unbounded_channel();
+        let watcher = TransactionWatcher::from_streams(
+            channel_stream(confirmed_rx),
+            channel_stream(finalized_rx),
+        );
+
+        let confirmed = watcher.watch(Signature::from([1; 64]), Confirmation::Confirmed);
+        let finalized = watcher.watch(Signature::from([1; 64]), Confirmation::Finalized);
+        confirmed_tx.send(status(10, 1)).unwrap();
+        assert_eq!(confirmed.await.unwrap().slot, 10);
+        assert_eq!(watcher.pending_count(), 1);
+        finalized_tx.send(status(10, 1)).unwrap();
+        assert_eq!(finalized.await.unwrap().slot, 10);
+
+        // landed before registration
+        confirmed_tx.send(status(11, 2)).unwrap();
+        tokio::task::yield_now().await;
+        let late = watcher
+            .wait_for(
+                Signature::from([2; 64]),
+                Confirmation::Confirmed,
+                Duration::from_secs(1),
+            )
+            .await;
+        assert_eq!(late.unwrap().slot, 11);
+
+        let missing = watcher
+            .wait_for(
+                Signature::from([3; 64]),
+                Confirmation::Confirmed,
+                Duration::from_millis(10),
+            )
+            .await;
+        assert!(missing.is_none());
+        assert_eq!(watcher.pending_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_dropped_receivers_are_pruned() {
+        let watcher = TransactionWatcher::from_streams(
+            futures::stream::pending(),
+            futures::stream::pending(),
+        );
+        for signature in 1..WAITER_PRUNE_INTERVAL {
+            drop(watcher.watch(
+                Signature::from([(signature % 200) as u8; 64]),
+                Confirmation::Confirmed,
+            ));
+        }
+        assert_eq!(watcher.pending_count(), 200);
+        // this registration prunes all dropped receivers
+        let _kept = watcher.watch(Signature::from([250; 64]), Confirmation::Confirmed);
+        assert_eq!(watcher.pending_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_drop_aborts_feeds() {
+        let (confirmed_tx, confirmed_rx) = mpsc::unbounded_channel();
+        let watcher = TransactionWatcher::from_streams(
+            channel_stream(confirmed_rx),
+            futures::stream::pending(),
+        );
+        let clone = watcher.clone();
+        drop(watcher);
+        tokio::task::yield_now().await;
+        assert!(!confirmed_tx.is_closed());
+
+        drop(clone);
+        tokio::task::yield_now().await;
+        assert!(confirmed_tx.is_closed());
+    }
+}
@@ -0,0 +1,139 @@
+use crate::transaction_stream::{create_transaction_multiplex, TransactionFilter};
+use crate::{GeyserFilter, GrpcSourceConfig};
+use futures::{Stream, StreamExt};
+use log::warn;
+use solana_sdk::clock::Slot;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_sdk::vote::instruction::VoteInstruction;
+use yellowstone_grpc_proto::geyser::SubscribeUpdateTransactionInfo;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VoteTransaction {
+    pub landed_slot: Slot,
+    pub signature: Signature,
+    // fee payer of the vote transaction
+    pub identity: Pubkey,
+    pub vote_account: Pubkey,
+    // highest slot voted on; none for vote instructions without slots (e.g. authorize)
+    pub voted_slot: Option<Slot>,
+}
+
+impl VoteTransaction {
+    pub fn from_transaction(
+        landed_slot: Slot,
+        info: &SubscribeUpdateTransactionInfo,
+    ) -> anyhow::Result<Self> {
+        let message = info
+            .transaction
+            .as_ref()
+            .and_then(|transaction| transaction.message.as_ref())
+            .ok_or_else(|| anyhow::anyhow!("transaction without message"))?;
+        let account_key = |index: usize| -> anyhow::Result<Pubkey> {
+            let key = message
+                .account_keys
+                .get(index)
+                .ok_or_else(|| anyhow::anyhow!("account index {} out of range", index))?;
+            Pubkey::try_from(key.as_slice()).map_err(|_| anyhow::anyhow!("invalid pubkey"))
+        };
+
+        let vote_program = solana_sdk::vote::program::id();
+        let instruction = message
+            .instructions
+            .iter()
+            .find(|instruction| {
+                account_key(instruction.program_id_index as usize).ok() == Some(vote_program)
+            })
+            .ok_or_else(|| anyhow::anyhow!("no vote instruction"))?;
+        let vote_account_index = *instruction
+            .accounts
+            .first()
+            .ok_or_else(|| anyhow::anyhow!("vote instruction without accounts"))?;
+        let voted_slot = bincode::deserialize::<VoteInstruction>(&instruction.data)
+            .ok()
+            .and_then(|vote_instruction| vote_instruction.last_voted_slot());
+
+        Ok(VoteTransaction {
+            landed_slot,
+            signature: Signature::try_from(info.signature.as_slice())
+                .map_err(|_| anyhow::anyhow!("invalid signature"))?,
+            identity: account_key(0)?,
+            vote_account: account_key(vote_account_index as usize)?,
+            voted_slot,
+        })
+    }
+
+    /// slots between the voted slot and the slot the vote landed in
+    pub fn latency_slots(&self) -> Option<u64> {
+        self.voted_slot
+            .map(|voted_slot| self.landed_slot.saturating_sub(voted_slot))
+    }
+}
+
+/// vote transactions of the given validators (identity or vote account), multiplexed and deduplicated
+///
+/// an empty list subscribes all votes of the cluster
+pub fn create_vote_stream(
+    grpc_sources: Vec<GrpcSourceConfig>,
+    commitment_config: CommitmentConfig,
+    validators: Vec<Pubkey>,
+) -> impl Stream<Item = VoteTransaction> {
+    let filter = TransactionFilter::new()
+        .vote(true)
+        .account_include(validators.iter().map(|validator| validator.to_string()));
+    create_transaction_multiplex(grpc_sources, GeyserFilter(commitment_config), filter).filter_map(
+        |(slot, info)| async move {
+            match VoteTransaction::from_transaction(slot, &info) {
+                Ok(vote) => Some(vote),
+                Err(err) => {
+                    warn!("skip vote transaction in slot {}: {}", slot, err);
+                    None
+                }
+            }
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::hash::Hash;
+    use solana_sdk::vote::state::Vote;
+    use yellowstone_grpc_proto::prelude::{CompiledInstruction, Message, Transaction};
+
+    #[test]
+    fn test_vote_from_transaction() {
+        let identity = Pubkey::new_unique();
+        let vote_account = Pubkey::new_unique();
+        let vote_instruction = VoteInstruction::Vote(Vote::new(vec![98, 99], Hash::default()));
+        let info = SubscribeUpdateTransactionInfo {
+            signature: vec![7; 64],
+            is_vote: true,
+            transaction: Some(Transaction {
+                signatures: vec![vec![7; 64]],
+                message: Some(Message {
+                    account_keys: vec![
+                        identity.to_bytes().to_vec(),
+                        vote_account.to_bytes().to_vec(),
+                        solana_sdk::vote::program::id().to_bytes().to_vec(),
+                    ],
+                    instructions: vec![CompiledInstruction {
+                        program_id_index: 2,
+                        accounts: vec![1, 0],
+                        data: bincode::serialize(&vote_instruction).unwrap(),
+                    }],
+                    ..Default::default()
+                }),
+            }),
+            ..Default::default()
+        };
+
+        let vote = VoteTransaction::from_transaction(100, &info).unwrap();
+
+        assert_eq!(vote.identity, identity);
+        assert_eq!(vote.vote_account, vote_account);
+        assert_eq!(vote.voted_slot, Some(99));
+        assert_eq!(vote.latency_slots(), Some(1));
+    }
+}
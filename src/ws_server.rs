@@ -0,0 +1,142 @@
+//! serve the multiplexed feed as json frames over websocket, see ``JsonFrame``
+
+use crate::grpc_stream_utils::AbortOnDrop;
+use crate::json_frames::encode_frames;
+use crate::Message;
+use futures::future;
+use futures::{SinkExt, StreamExt};
+use log::{debug, info, warn};
+use std::net::SocketAddr;
+use std::pin::pin;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite;
+
+const FRAMES_CAPACITY: usize = 1000;
+// e.g. out of file descriptors; retrying right away would spin
+const ACCEPT_ERROR_BACKOFF: Duration = Duration::from_millis(100);
+
+/// accept websocket clients on the listener and send every frame to all of them; ``updates`` is
+/// e.g. a receiver from ``channelize_stream``
+///
+/// each update is serialized once; a client lagging behind the buffer gets disconnected; aborting
+/// the returned handle also stops the encoding and disconnects the clients
+pub fn spawn_websocket_server(
+    listener: TcpListener,
+    updates: broadcast::Receiver<Message>,
+) -> JoinHandle<()> {
+    let (frames_tx, _) = broadcast::channel::<Arc<str>>(FRAMES_CAPACITY);
+    let encoder = AbortOnDrop(tokio::spawn(encode_frames(updates, frames_tx.clone())));
+
+    tokio::spawn(async move {
+        let _encoder = encoder;
+        loop {
+            let (tcp_stream, peer) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(accept_error) => {
+                    warn!("websocket accept failed: {}", accept_error);
+                    tokio::time::sleep(ACCEPT_ERROR_BACKOFF).await;
+                    continue;
+                }
+            };
+            tokio::spawn(serve_client(tcp_stream, peer, frames_tx.subscribe()));
+        }
+    })
+}
+
+async fn serve_client(
+    tcp_stream: TcpStream,
+    peer: SocketAddr,
+    mut frames: broadcast::Receiver<Arc<str>>,
+) {
+    let websocket = match tokio_tungstenite::accept_async(tcp_stream).await {
+        Ok(websocket) => websocket,
+        Err(handshake_error) => {
+            debug!(
+                "websocket handshake with {} failed: {}",
+                peer, handshake_error
+            );
+            return;
+        }
+    };
+    info!("websocket client {} connected", peer);
+    let (mut sink, mut incoming) = websocket.split();
+
+    let send_frames = async {
+        loop {
+            match frames.recv().await {
+                Ok(frame) => {
+                    if sink
+                        .send(tungstenite::Message::Text(frame.to_string()))
+                        .await
+                        .is_err()
+                    {
+                        return;
+                    }
+                }
+                Err(RecvError::Lagged(n_missed)) => {
+                    warn!(
+                        "websocket client {} lagged {} frames - disconnecting",
+                        peer, n_missed
+                    );
+                    let _ = sink.close().await;
+                    return;
+                }
+                Err(RecvError::Closed) => {
+                    let _ = sink.close().await;
+                    return;
+                }
+            }
+        }
+    };
+    // clients only send control frames; reading is needed to answer pings and notice the close
+    let until_closed = async {
+        while let Some(Ok(frame)) = incoming.next().await {
+            if frame.is_close() {
+                return;
+            }
+        }
+    };
+    future::select(pin!(send_frames), pin!(until_closed)).await;
+    info!("websocket client {} disconnected", peer);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use yellowstone_grpc_proto::geyser::subscribe_update::UpdateOneof;
+    use yellowstone_grpc_proto::geyser::{SubscribeUpdate, SubscribeUpdateSlot};
+
+    #[tokio::test]
+    async fn test_websocket_frames() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (updates_tx, updates_rx) = broadcast::channel(10);
+        let _jh_server = spawn_websocket_server(listener, updates_rx);
+
+        let (mut client, _) = tokio_tungstenite::connect_async(format!("ws://{}", addr))
+            .await
+            .unwrap();
+        // let the server subscribe the client to the frames
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let sent = updates_tx.send(Message::GeyserSubscribeUpdate(Box::new(SubscribeUpdate {
+            filters: vec![],
+            update_oneof: Some(UpdateOneof::Slot(SubscribeUpdateSlot {
+                slot: 42,
+                parent: None,
+                status: 1,
+            })),
+        })));
+        assert!(sent.is_ok());
+
+        let frame = client.next().await.unwrap().unwrap();
+        let json: serde_json::Value = serde_json::from_str(frame.to_text().unwrap()).unwrap();
+        assert_eq!(json["type"], "slot");
+        assert_eq!(json["slot"], 42);
+    }
+}